@@ -0,0 +1,545 @@
+// The assembler: turn the editor's lines (one function, after the host imports), or a
+// module's functions and its other sections (see sections.rs), into a Module, or the
+// errors that prevent it, each with the index of the function and line it belongs to.
+// The lines must parse, nest and type-check; then every $name is resolved to a number.
+
+use crate::{
+    instruction::{ImmKind, Immediate, Index, Instruction},
+    module::{
+        Export, ExportKind, Function, HOST_MODULE, Import, Limits, Module, PRINT_FUNCS,
+        print_func_type,
+    },
+    nesting::nesting,
+    parse::{DeclKind, ParsedLine},
+    sections::ModuleSections,
+    validate::{
+        Context, Func, Global, Invalid, function_type, module_function_context,
+        validate_module_function,
+    },
+};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AssembleError {
+    pub func: usize, // among the module's functions (not counting the imports)
+    pub line: usize,
+    pub message: String,
+    pub invalid: Option<Invalid>, // if the validator found it
+}
+
+impl fmt::Display for AssembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line + 1, self.message)
+    }
+}
+
+// One of a module's functions: its name (for `call $name`), and its lines (starting
+// with its declarations).
+#[derive(Clone, Debug, PartialEq)]
+pub struct ModuleFunction {
+    pub name: Option<String>,
+    pub lines: Vec<ParsedLine>,
+}
+
+// The functions a module's functions can call (after the print functions).
+pub fn module_funcs(funcs: &[ModuleFunction]) -> Vec<Func> {
+    (funcs.iter())
+        .map(|func| Func {
+            name: func.name.clone(),
+            ty: function_type(&func.lines),
+        })
+        .collect()
+}
+
+// The first problem on each line that has one, in line order.
+fn line_errors(
+    func: usize,
+    lines: &[ParsedLine],
+    funcs: &[Func],
+    globals: &[Global],
+) -> Vec<AssembleError> {
+    let nesting = nesting(lines.iter().map(|line| line.instruction()));
+    let checks = validate_module_function(lines, funcs, globals);
+    let mut errors = Vec::new();
+    for (index, line) in lines.iter().enumerate() {
+        let check = &checks[index];
+        let message = (line.error().map(|error| (error.message.clone(), None)))
+            .or_else(|| nesting[index].error.clone().map(|error| (error, None)))
+            .or_else(|| check.error.clone().map(|error| (error, check.invalid)));
+        if let Some((message, invalid)) = message {
+            errors.push(AssembleError {
+                func,
+                line: index,
+                message,
+                invalid,
+            });
+        }
+    }
+    errors
+}
+
+// Exports of a name that an earlier line (of any function) already exported.
+fn duplicate_exports(funcs: &[ModuleFunction]) -> Vec<AssembleError> {
+    let mut errors = Vec::new();
+    let mut names: Vec<&String> = Vec::new();
+    for (func, function) in funcs.iter().enumerate() {
+        for (index, line) in function.lines.iter().enumerate() {
+            if let Some(decl) = line.declaration()
+                && decl.kind == DeclKind::Export
+                && let Some(name) = &decl.name
+            {
+                if names.contains(&name) {
+                    errors.push(AssembleError {
+                        func,
+                        line: index,
+                        message: format!("duplicate export \"{name}\""),
+                        invalid: None,
+                    });
+                }
+                names.push(name);
+            }
+        }
+    }
+    errors
+}
+
+// The lines of one function (which can call itself as function 4).
+pub fn assemble(lines: &[ParsedLine]) -> Result<Module, Vec<AssembleError>> {
+    let func = ModuleFunction {
+        name: None,
+        lines: lines.to_vec(),
+    };
+    assemble_module(&[func], &ModuleSections::default())
+}
+
+// A module's functions, in order after the print functions and the other imports (so
+// without other imports, the first is function 4), with its other sections (as
+// resolved for these functions). The module has a memory if it declares one, or if it
+// has data, or if any of its functions uses one.
+pub fn assemble_module(
+    funcs: &[ModuleFunction],
+    sections: &ModuleSections,
+) -> Result<Module, Vec<AssembleError>> {
+    let sigs = sections.funcs(funcs);
+    let globals = sections.globals();
+    let mut errors: Vec<AssembleError> = (funcs.iter().enumerate())
+        .flat_map(|(func, function)| line_errors(func, &function.lines, &sigs, &globals))
+        .chain(duplicate_exports(funcs))
+        .collect();
+    if !errors.is_empty() {
+        errors.sort_by_key(|error| (error.func, error.line));
+        return Err(errors);
+    }
+
+    let mut module = Module::default();
+    for (name, param) in PRINT_FUNCS {
+        let type_index = module.type_index(&print_func_type(param));
+        module.imports.push(Import {
+            module: HOST_MODULE.to_string(),
+            name: name.to_string(),
+            type_index,
+        });
+    }
+    for import in &sections.imports {
+        let type_index = module.type_index(&import.func.ty);
+        module.imports.push(Import {
+            module: import.module.clone(),
+            name: import.name.clone(),
+            type_index,
+        });
+    }
+    for (func, function) in funcs.iter().enumerate() {
+        let (ctx, _) = module_function_context(&function.lines, &sigs, &globals);
+        let mut resolver = Resolver {
+            ctx: &ctx,
+            labels: Vec::new(),
+        };
+        let mut body = Vec::new();
+        let mut body_lines = Vec::new();
+        for (index, line) in function.lines.iter().enumerate() {
+            if let Some(instr) = line.instruction() {
+                match resolver.instruction(instr) {
+                    Ok(instr) => {
+                        body.push(instr);
+                        body_lines.push(index);
+                    }
+                    Err(message) => errors.push(AssembleError {
+                        func,
+                        line: index,
+                        message,
+                        invalid: None,
+                    }),
+                }
+            }
+        }
+
+        let func_index = module.imports.len() + func;
+        let func_type = &sigs[sections.imports.len() + func].ty;
+        let type_index = module.type_index(func_type);
+        module.funcs.push(Function {
+            type_index,
+            locals: (ctx.locals[func_type.params.len()..].iter())
+                .map(|local| local.ty)
+                .collect(),
+            body,
+            lines: body_lines,
+        });
+        for decl in function.lines.iter().filter_map(|line| line.declaration()) {
+            if decl.kind == DeclKind::Export {
+                module.exports.push(Export {
+                    name: decl.name.clone().expect("export name"),
+                    kind: ExportKind::Func,
+                    index: func_index as u32,
+                });
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    module.table = sections.table;
+    module.memory = sections.memory;
+    if module.memory.is_none()
+        && (!sections.data.is_empty()
+            || (module.funcs.iter()).any(|func| func.body.iter().any(Instruction::uses_memory)))
+    {
+        module.memory = Some(Limits { min: 1, max: None });
+    }
+    module.globals = (sections.globals.iter())
+        .map(|(_, global)| global.clone())
+        .collect();
+    module.exports.extend(sections.exports.iter().cloned());
+    module.elems = sections.elems.clone();
+    module.data = sections.data.clone();
+    Ok(module)
+}
+
+struct Resolver<'a> {
+    ctx: &'a Context,
+    labels: Vec<Option<String>>, // the enclosing blocks' labels, innermost last
+}
+
+impl Resolver<'_> {
+    fn label(&self, index: &Index) -> Result<Index, String> {
+        match index {
+            Index::Num(_) => Ok(index.clone()),
+            Index::Id(id) => (self.labels.iter().rev())
+                .position(|label| label.as_ref() == Some(id))
+                .map(|depth| Index::Num(depth as u32))
+                .ok_or_else(|| format!("unknown label {index}")),
+        }
+    }
+
+    fn lookup<T>(
+        items: &[T],
+        name: impl Fn(&T) -> Option<&String>,
+        index: &Index,
+        what: &str,
+    ) -> Result<Index, String> {
+        match index {
+            Index::Num(_) => Ok(index.clone()),
+            Index::Id(id) => (items.iter())
+                .position(|item| name(item) == Some(id))
+                .map(|found| Index::Num(found as u32))
+                .ok_or_else(|| format!("unknown {what} {index}")),
+        }
+    }
+
+    // The instruction with its $names replaced by numbers (and its labels dropped).
+    fn instruction(&mut self, instr: &Instruction) -> Result<Instruction, String> {
+        let ctx = self.ctx;
+        let imm = match (instr.info.imm, &instr.imm) {
+            (ImmKind::BlockType, Immediate::Block { label, ty }) => {
+                self.labels.push(label.clone());
+                Immediate::Block {
+                    label: None,
+                    ty: *ty,
+                }
+            }
+            (ImmKind::OptLabel, _) => {
+                if instr.mnemonic() == "end" {
+                    self.labels.pop();
+                }
+                Immediate::Label(None)
+            }
+            (ImmKind::Label, Immediate::Index(index)) => Immediate::Index(self.label(index)?),
+            (ImmKind::BrTable, Immediate::BrTable { labels, default }) => Immediate::BrTable {
+                labels: (labels.iter())
+                    .map(|label| self.label(label))
+                    .collect::<Result<_, _>>()?,
+                default: self.label(default)?,
+            },
+            (ImmKind::Local, Immediate::Index(index)) => Immediate::Index(Self::lookup(
+                &ctx.locals,
+                |local| local.name.as_ref(),
+                index,
+                "local",
+            )?),
+            (ImmKind::Global, Immediate::Index(index)) => Immediate::Index(Self::lookup(
+                &ctx.globals,
+                |global| global.name.as_ref(),
+                index,
+                "global",
+            )?),
+            (ImmKind::Func, Immediate::Index(index)) => Immediate::Index(Self::lookup(
+                &ctx.funcs,
+                |func| func.name.as_ref(),
+                index,
+                "function",
+            )?),
+            (_, imm) => imm.clone(),
+        };
+        Ok(Instruction {
+            info: instr.info,
+            imm,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        instruction::ValType,
+        parse::parse_line,
+        sections::{Section, Sections},
+        validate::FuncType,
+        value::Value,
+    };
+
+    fn assemble_text(text: &str) -> Result<Module, Vec<AssembleError>> {
+        let parsed: Vec<_> = text.lines().map(parse_line).collect();
+        assemble(&parsed)
+    }
+
+    #[test]
+    fn names_are_resolved() {
+        let text = "(param $n i32)\n(result i32)\n(export \"count\")\n(local $i i32)\nblock $done\nloop $top\nlocal.get $i\nlocal.get $n\ni32.ge_u\nbr_if $done\nlocal.get $i\ni32.const 1\ni32.add\nlocal.set $i\nbr $top\nend $top\nend\nlocal.get $i";
+        let module = assemble_text(text).unwrap();
+        assert_eq!(module.imports.len(), 4);
+        assert_eq!(
+            module.types[4],
+            FuncType {
+                params: vec![ValType::I32],
+                results: vec![ValType::I32]
+            }
+        );
+        assert_eq!(module.export_type("count"), Some(&module.types[4]));
+        assert_eq!(module.func_type(1), Some(&print_func_type(ValType::I64)));
+        assert_eq!(module.funcs[0].locals, vec![ValType::I32]);
+        assert_eq!(module.funcs[0].lines, (4..18).collect::<Vec<_>>());
+        let body: Vec<String> = module.funcs[0].body.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            body,
+            vec![
+                "block",
+                "loop",
+                "local.get 1",
+                "local.get 0",
+                "i32.ge_u",
+                "br_if 1",
+                "local.get 1",
+                "i32.const 1",
+                "i32.add",
+                "local.set 1",
+                "br 0",
+                "end",
+                "end",
+                "local.get 1"
+            ]
+        );
+        assert_eq!(
+            module.exports,
+            vec![Export {
+                name: "count".to_string(),
+                kind: ExportKind::Func,
+                index: 4
+            }]
+        );
+        assert_eq!(module.memory, None);
+        assert_eq!(
+            assemble_text("i32.const 0\ni32.load\ndrop").unwrap().memory,
+            Some(Limits { min: 1, max: None })
+        );
+    }
+
+    #[test]
+    fn errors_name_their_lines() {
+        let errors = |text: &str| -> Vec<(usize, String)> {
+            (assemble_text(text).unwrap_err().into_iter())
+                .map(|error| (error.line, error.message))
+                .collect()
+        };
+        assert_eq!(
+            errors("i32.const x\nblock\n\ni32.const 1\ni64.const 2\ni32.add"),
+            vec![
+                (0, "expected an i32 constant".to_string()),
+                (1, "`block` is never closed by `end`".to_string()),
+                (
+                    5,
+                    "i32.add expects [i32 i32] but the stack has [i32 i64]".to_string()
+                )
+            ]
+        );
+        assert_eq!(
+            errors("(export \"f\")\n(export \"f\")"),
+            vec![(1, "duplicate export \"f\"".to_string())]
+        );
+        assert_eq!(
+            AssembleError {
+                func: 0,
+                line: 2,
+                message: "oops".to_string(),
+                invalid: None,
+            }
+            .to_string(),
+            "line 3: oops"
+        );
+    }
+
+    fn function(name: Option<&str>, text: &str) -> ModuleFunction {
+        ModuleFunction {
+            name: name.map(str::to_string),
+            lines: text.lines().map(parse_line).collect(),
+        }
+    }
+
+    #[test]
+    fn modules() {
+        let none = ModuleSections::default();
+        let module = assemble_module(&[
+            function(
+                Some("main"),
+                "(result i32)\n(export \"main\")\ni32.const 20\ncall $double\ncall 6",
+            ),
+            function(
+                Some("double"),
+                "(param $x i32)\n(result i32)\nlocal.get $x\nlocal.get $x\ni32.add",
+            ),
+            function(
+                None,
+                "(param i32)\n(result i32)\n(export \"inc\")\nlocal.get 0\ni32.const 1\ni32.add",
+            ),
+        ], &none)
+        .unwrap();
+        assert_eq!(module.funcs.len(), 3);
+        let body: Vec<String> = module.funcs[0].body.iter().map(|i| i.to_string()).collect();
+        assert_eq!(body, vec!["i32.const 20", "call 5", "call 6"]);
+        assert_eq!(module.funcs[1].lines, vec![2, 3, 4]);
+        let exports: Vec<(&str, u32)> = (module.exports.iter())
+            .map(|export| (export.name.as_str(), export.index))
+            .collect();
+        assert_eq!(exports, vec![("main", 4), ("inc", 6)]);
+        assert_eq!(module.func_type(5), module.func_type(6));
+        assert_eq!(module.memory, None);
+
+        // errors name their function; exports are shared by the whole module, and
+        // calls are checked against the callee's type
+        let errors: Vec<(usize, usize, String)> = assemble_module(
+            &[
+                function(Some("a"), "(export \"f\")\ni64.const 1\ncall $b"),
+                function(
+                    Some("b"),
+                    "(param i32)\n(export \"f\")\ni32.const 0\ni32.load\ndrop",
+                ),
+            ],
+            &none,
+        )
+        .unwrap_err()
+        .into_iter()
+        .map(|error| (error.func, error.line, error.message))
+        .collect();
+        assert_eq!(
+            errors,
+            vec![
+                (
+                    0,
+                    2,
+                    "call $b expects [i32] but the stack has [i64]".to_string()
+                ),
+                (1, 1, "duplicate export \"f\"".to_string())
+            ]
+        );
+        let module = assemble_module(
+            &[
+                function(None, "nop"),
+                function(None, "i32.const 0\ni32.load\ndrop"),
+            ],
+            &none,
+        )
+        .unwrap();
+        assert_eq!(module.memory, Some(Limits { min: 1, max: None }));
+    }
+
+    #[test]
+    fn sections() {
+        let mut sections = Sections::default();
+        sections.push(Section::Imports, &["env", "twice", "$twice", "i64", "i64"]);
+        sections.push(Section::Table, &["2", "2"]);
+        sections.push(Section::Globals, &["$total", "mut i64", "0"]);
+        sections.push(Section::Exports, &["total", "global", "$total"]);
+        sections.push(Section::Elements, &["0", "$add $twice"]);
+        sections.push(Section::Data, &["16", "\"ok\""]);
+        let funcs = [function(
+            Some("add"),
+            "(param $n i64)\n(export \"add\")\nglobal.get $total\nlocal.get $n\ncall $twice\ni64.add\nglobal.set $total",
+        )];
+        let (resolved, errors) = sections.resolve(&funcs);
+        assert_eq!(errors, []);
+        let module = assemble_module(&funcs, &resolved).unwrap();
+
+        // the import comes after the print functions, and the function after it
+        assert_eq!(module.imports.len(), 5);
+        assert_eq!(module.imports[4].name, "twice");
+        assert_eq!(module.func_type(4).unwrap().results, [ValType::I64]);
+        assert_eq!(module.func_type(5).unwrap().results, []);
+        let body: Vec<String> = module.funcs[0].body.iter().map(|i| i.to_string()).collect();
+        assert_eq!(
+            body,
+            [
+                "global.get 0",
+                "local.get 0",
+                "call 4",
+                "i64.add",
+                "global.set 0"
+            ]
+        );
+        let exports: Vec<(&str, ExportKind, u32)> = (module.exports.iter())
+            .map(|export| (export.name.as_str(), export.kind, export.index))
+            .collect();
+        assert_eq!(
+            exports,
+            [
+                ("add", ExportKind::Func, 5),
+                ("total", ExportKind::Global, 0)
+            ]
+        );
+        assert_eq!(
+            module.table,
+            Some(Limits {
+                min: 2,
+                max: Some(2)
+            })
+        );
+        assert_eq!(module.elems[0].funcs, [5, 4]);
+        assert_eq!(module.globals[0].init, Value::I64(0));
+
+        // data needs a memory, even without memory instructions
+        assert_eq!(module.memory, Some(Limits { min: 1, max: None }));
+        assert_eq!(module.data[0].bytes, b"ok");
+
+        // the functions' lines are checked against the sections
+        let (resolved, _) = Sections::default().resolve(&funcs);
+        let errors: Vec<String> = (assemble_module(&funcs, &resolved).unwrap_err().iter())
+            .map(|error| error.to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                "line 3: unknown global $total",
+                "line 5: unknown function $twice",
+                "line 7: unknown global $total"
+            ]
+        );
+    }
+}
@@ -0,0 +1,343 @@
+// Completions for the word at the caret on a line of a function body. The first word
+// of a line completes to an instruction's mnemonic: those that start with what has
+// been typed, ranked by whether they type-check with the operand stack before the line
+// (those that take their operands from the top of the stack first, then those whose
+// types depend on their immediates, then the rest). The word after a mnemonic
+// completes to what its immediate refers to: the function's locals, the module's
+// globals or functions, or the labels of the enclosing blocks (innermost first).
+
+use crate::{
+    instruction::{INSTRUCTIONS, ImmKind, InstrInfo, ValType, format_signature, lookup},
+    validate::{Context, StackType},
+};
+use std::ops::Range;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Completion {
+    pub text: String,   // what replaces the word
+    pub detail: String, // shown beside it: a type or signature, or a block's kind
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Completions {
+    pub range: Range<usize>, // the chars of the line that a completion replaces
+    pub items: Vec<Completion>,
+}
+
+// What a line can refer to: the function's context, the operand stack before the line
+// (if it is known), and the blocks open at the line (with their labels), outermost
+// first.
+pub struct Scope<'a> {
+    pub ctx: &'a Context,
+    pub stack: Option<&'a [StackType]>,
+    pub blocks: &'a [(&'static str, Option<String>)],
+}
+
+// The completions for the word at char `caret` of `line` (None if there are none).
+pub fn complete(line: &str, caret: usize, scope: &Scope) -> Option<Completions> {
+    let split = line
+        .char_indices()
+        .nth(caret)
+        .map_or(line.len(), |(i, _)| i);
+    let (before, after) = line.split_at(split);
+    if before.contains(";;") || before.trim_start().starts_with('(') {
+        return None;
+    }
+    let prefix = &before[before
+        .trim_end_matches(|ch: char| !ch.is_whitespace())
+        .len()..];
+    let words: Vec<&str> = before[..before.len() - prefix.len()]
+        .split_whitespace()
+        .collect();
+    let rest = after.find(char::is_whitespace).unwrap_or(after.len());
+    let range = caret - prefix.chars().count()..caret + after[..rest].chars().count();
+
+    let items = match words[..] {
+        [] if prefix.is_empty() => return None,
+        [] => mnemonics(prefix, scope.stack),
+        [mnemonic, ..] => {
+            let info = lookup(mnemonic)?;
+            if words.len() > 1 && info.imm != ImmKind::BrTable {
+                return None;
+            }
+            (immediates(info, scope).into_iter())
+                .filter(|item| matches_prefix(&item.text, prefix))
+                .collect()
+        }
+    };
+    // nothing more to offer once the word is complete
+    match &items[..] {
+        [] => None,
+        [only] if only.text == prefix => None,
+        _ => Some(Completions { range, items }),
+    }
+}
+
+// A name matches with or without its `$`.
+fn matches_prefix(text: &str, prefix: &str) -> bool {
+    text.starts_with(prefix)
+        || text
+            .strip_prefix('$')
+            .is_some_and(|id| id.starts_with(prefix))
+}
+
+// Whether the top of the stack holds operands of these types.
+fn fits(params: &[ValType], stack: &[StackType]) -> bool {
+    params.len() <= stack.len()
+        && (params.iter().rev().zip(stack.iter().rev()))
+            .all(|(param, ty)| ty.is_none_or(|ty| ty == *param))
+}
+
+fn mnemonics(prefix: &str, stack: Option<&[StackType]>) -> Vec<Completion> {
+    let rank = |info: &InstrInfo| match (&info.sig, stack) {
+        _ if info.mnemonic == prefix => 0,
+        (Some(sig), Some(stack)) if fits(sig.params, stack) => 1,
+        (None, _) | (_, None) => 2,
+        _ => 3,
+    };
+    let mut found: Vec<&InstrInfo> = (INSTRUCTIONS.iter())
+        .filter(|info| info.mnemonic.starts_with(prefix))
+        .collect();
+    found.sort_by_key(|info| rank(info));
+    (found.into_iter())
+        .map(|info| Completion {
+            text: info.mnemonic.to_string(),
+            detail: info
+                .sig
+                .as_ref()
+                .map_or(String::new(), |sig| sig.to_string()),
+        })
+        .collect()
+}
+
+// A local, global or function is referred to by its name if it has one, and otherwise
+// by its number.
+fn reference(name: &Option<String>, index: usize) -> String {
+    name.as_ref()
+        .map_or(index.to_string(), |name| format!("${name}"))
+}
+
+// Everything the instruction's immediate could refer to, the likeliest first.
+fn immediates(info: &InstrInfo, scope: &Scope) -> Vec<Completion> {
+    let top = scope.stack.and_then(|stack| stack.last().copied());
+    // (an operand of an unknown type fits anything)
+    let takes_top = |ty: ValType| top.is_none_or(|top| top.is_none_or(|top| top == ty));
+    let mut ranked: Vec<(bool, Completion)> = match info.imm {
+        ImmKind::Local => (scope.ctx.locals.iter().enumerate())
+            .map(|(index, local)| {
+                let item = Completion {
+                    text: reference(&local.name, index),
+                    detail: local.ty.to_string(),
+                };
+                (info.mnemonic == "local.get" || takes_top(local.ty), item)
+            })
+            .collect(),
+        ImmKind::Global => (scope.ctx.globals.iter().enumerate())
+            .map(|(index, global)| {
+                let item = Completion {
+                    text: reference(&global.name, index),
+                    detail: match global.mutable {
+                        true => format!("mut {}", global.ty),
+                        false => global.ty.to_string(),
+                    },
+                };
+                let settable = global.mutable && takes_top(global.ty);
+                (info.mnemonic == "global.get" || settable, item)
+            })
+            .collect(),
+        ImmKind::Func => (scope.ctx.funcs.iter().enumerate())
+            .map(|(index, func)| {
+                let item = Completion {
+                    text: reference(&func.name, index),
+                    detail: format_signature(&func.ty.params, &func.ty.results),
+                };
+                let fits = scope.stack.is_none_or(|stack| fits(&func.ty.params, stack));
+                (fits, item)
+            })
+            .collect(),
+        ImmKind::Label | ImmKind::BrTable => {
+            let blocks = scope
+                .blocks
+                .iter()
+                .rev()
+                .map(|(kind, label)| (*kind, label));
+            (blocks.chain([("func", &None)]).enumerate())
+                .map(|(depth, (kind, label))| {
+                    let item = Completion {
+                        text: reference(label, depth),
+                        detail: kind.to_string(),
+                    };
+                    (true, item)
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    };
+    ranked.sort_by_key(|(likely, _)| !likely);
+    ranked.into_iter().map(|(_, item)| item).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::validate::{Func, FuncType, Global, Local};
+
+    fn texts(completions: Option<Completions>) -> Vec<String> {
+        (completions
+            .map_or(Vec::new(), |completions| completions.items)
+            .into_iter())
+        .map(|item| item.text)
+        .collect()
+    }
+
+    fn context() -> Context {
+        Context {
+            locals: vec![
+                Local {
+                    name: Some("x".to_string()),
+                    ty: ValType::I32,
+                },
+                Local {
+                    name: None,
+                    ty: ValType::F64,
+                },
+            ],
+            funcs: vec![
+                Func {
+                    name: Some("print_f64".to_string()),
+                    ty: FuncType {
+                        params: vec![ValType::F64],
+                        results: Vec::new(),
+                    },
+                },
+                Func {
+                    name: None,
+                    ty: FuncType {
+                        params: vec![ValType::I32],
+                        results: vec![ValType::I32],
+                    },
+                },
+            ],
+            globals: vec![
+                Global {
+                    name: Some("limit".to_string()),
+                    ty: ValType::I32,
+                    mutable: false,
+                },
+                Global {
+                    name: Some("count".to_string()),
+                    ty: ValType::I32,
+                    mutable: true,
+                },
+            ],
+            ..Context::default()
+        }
+    }
+
+    #[test]
+    fn mnemonics_are_ranked_by_the_stack() {
+        let ctx = context();
+        let stack = [Some(ValType::F32), Some(ValType::I32)];
+        let scope = Scope {
+            ctx: &ctx,
+            stack: Some(&stack),
+            blocks: &[],
+        };
+        let completions = complete("  i32.e", 7, &scope).unwrap();
+        assert_eq!(completions.range, 2..7);
+        assert_eq!(
+            completions.items[0],
+            Completion {
+                text: "i32.eqz".to_string(),
+                detail: "[i32] -> [i32]".to_string()
+            }
+        );
+        assert_eq!(
+            texts(Some(completions)),
+            ["i32.eqz", "i32.extend8_s", "i32.extend16_s", "i32.eq"]
+        );
+        // an exact match comes first
+        let items = texts(complete("i32.load", 8, &scope));
+        assert_eq!(items[..2], ["i32.load", "i32.load8_s"]);
+        // with an f32 on top, the f32 operations that take one come first
+        let stack = [Some(ValType::F32)];
+        let scope = Scope {
+            stack: Some(&stack),
+            ..scope
+        };
+        let items = texts(complete("f32.c", 5, &scope));
+        assert_eq!(
+            items,
+            [
+                "f32.const",
+                "f32.ceil",
+                "f32.copysign",
+                "f32.convert_i32_s",
+                "f32.convert_i32_u",
+                "f32.convert_i64_s",
+                "f32.convert_i64_u",
+            ]
+        );
+        // the word runs to the end of the word the caret is in
+        assert_eq!(complete("i32.ad ;; x", 3, &scope).unwrap().range, 0..6);
+    }
+
+    #[test]
+    fn nothing_to_complete() {
+        let ctx = context();
+        let scope = Scope {
+            ctx: &ctx,
+            stack: None,
+            blocks: &[],
+        };
+        assert_eq!(complete("", 0, &scope), None);
+        assert_eq!(complete("  ", 2, &scope), None);
+        assert_eq!(complete("i32.xyz", 7, &scope), None);
+        assert_eq!(complete("drop", 4, &scope), None);
+        assert_eq!(complete(";; i32.", 7, &scope), None);
+        assert_eq!(complete("(param i", 8, &scope), None);
+        assert_eq!(complete("i32.add ", 8, &scope), None);
+        assert_eq!(complete("local.get 0 ", 12, &scope), None);
+    }
+
+    #[test]
+    fn immediates() {
+        let ctx = context();
+        let stack = [Some(ValType::F64)];
+        let blocks = [("block", Some("outer".to_string())), ("loop", None)];
+        let scope = Scope {
+            ctx: &ctx,
+            stack: Some(&stack),
+            blocks: &blocks,
+        };
+        assert_eq!(texts(complete("local.get ", 10, &scope)), ["$x", "1"]);
+        // locals of the type on top of the stack come first
+        assert_eq!(texts(complete("local.set ", 10, &scope)), ["1", "$x"]);
+        assert_eq!(texts(complete("local.tee $", 11, &scope)), ["$x"]);
+        let completions = complete("global.get ", 11, &scope).unwrap();
+        assert_eq!(completions.range, 11..11);
+        assert_eq!(
+            completions.items[1],
+            Completion {
+                text: "$count".to_string(),
+                detail: "mut i32".to_string()
+            }
+        );
+        assert_eq!(texts(complete("call ", 5, &scope)), ["$print_f64", "1"]);
+        assert_eq!(
+            complete("call 1", 6, &scope),
+            None,
+            "an exact match is complete"
+        );
+        assert_eq!(texts(complete("call p", 6, &scope)), ["$print_f64"]);
+        // labels, innermost first, and then the function's
+        let completions = complete("br ", 3, &scope).unwrap();
+        assert_eq!(
+            (completions.items.iter())
+                .map(|item| (item.text.as_str(), item.detail.as_str()))
+                .collect::<Vec<_>>(),
+            [("0", "loop"), ("$outer", "block"), ("2", "func")]
+        );
+        assert_eq!(texts(complete("br_table 0 $o", 13, &scope)), ["$outer"]);
+    }
+}
@@ -0,0 +1,195 @@
+// The completion popup: an overlay (see dom_overlay.rs) listing completions (see
+// completion.rs), each with its detail, one of them selected. The editor shows it at
+// the caret, and moves the selection with the keyboard; pressing the mouse on a
+// completion selects it and accepts it (without taking the focus from the editor).
+
+use crate::{
+    completion::Completion,
+    dom_overlay::DomOverlay,
+    dom_struct::DomStruct,
+    dom_text::DomText,
+    dom_vec::DomVec,
+    web_support::{AccessToken, Component, ElementFactory, WithElement, WithNode, audit_eq},
+};
+use std::{cell::RefCell, rc::Rc};
+use web_sys::{HtmlDivElement, HtmlSpanElement};
+
+// Every completion is this tall (see the stylesheet).
+const ITEM_HEIGHT_PX: f64 = 24.0;
+
+type Text = DomStruct<(DomText, ()), HtmlSpanElement>;
+type Item = DomStruct<(Text, (Text, ())), HtmlDivElement>;
+type Items = DomVec<Item, HtmlDivElement>;
+
+struct _CompletionPopup {
+    factory: ElementFactory,
+    component: DomOverlay<(Items, ())>,
+    items: Vec<Completion>, // as shown (empty while hidden)
+    selected: usize,
+    onaccept: Option<Rc<dyn Fn()>>,
+}
+
+// A handle to the popup (clones refer to the same popup).
+#[derive(Clone)]
+pub struct CompletionPopup(Rc<RefCell<_CompletionPopup>>);
+
+impl CompletionPopup {
+    pub fn new(factory: &ElementFactory) -> Self {
+        let mut items = Items::new(factory.div());
+        items.set_attribute("class", "items");
+        CompletionPopup(Rc::new(RefCell::new(_CompletionPopup {
+            factory: factory.clone(),
+            component: DomOverlay::new((items, ()), factory.div(), "completion"),
+            items: Vec::new(),
+            selected: 0,
+            onaccept: None,
+        })))
+    }
+
+    // Show these completions, the first one selected, with the popup's top left corner
+    // at (`left`, `top`) in the viewport.
+    pub fn show(&mut self, items: Vec<Completion>, left: f64, top: f64) {
+        let mut inner = self.0.borrow_mut();
+        if items != inner.items {
+            let factory = inner.factory.clone();
+            let list = &mut inner.component.get_mut().0;
+            list.clear();
+            for (index, item) in items.iter().enumerate() {
+                let text = |text: &str, class: &str| {
+                    let mut span = Text::new((DomText::new(text), ()), factory.span());
+                    span.set_attribute("class", class);
+                    span
+                };
+                let mut div = Item::new(
+                    (text(&item.text, "text"), (text(&item.detail, "detail"), ())),
+                    factory.div(),
+                );
+                let popup_ref = Rc::downgrade(&self.0);
+                div.set_onmousedown(move |ev| {
+                    ev.prevent_default();
+                    if let Some(popup_ref) = popup_ref.upgrade() {
+                        let mut popup = CompletionPopup(popup_ref);
+                        popup.select(index);
+                        // (accepting replaces the list, so not while its handler runs)
+                        wasm_bindgen_futures::spawn_local(async move { popup.accept() });
+                    }
+                });
+                list.push(div);
+            }
+            inner.items = items;
+            inner.selected = usize::MAX;
+        }
+        inner.component.show_at(left, top);
+        if inner.selected == usize::MAX {
+            inner.select(0);
+        }
+    }
+
+    pub fn hide(&mut self) {
+        let mut inner = self.0.borrow_mut();
+        inner.component.hide();
+        inner.component.get_mut().0.clear();
+        inner.items.clear();
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.0.borrow().component.is_shown()
+    }
+
+    // Where the popup is shown (if it is).
+    pub fn position(&self) -> Option<(f64, f64)> {
+        self.0.borrow().component.position()
+    }
+
+    pub fn items(&self) -> Vec<Completion> {
+        self.0.borrow().items.clone()
+    }
+
+    // The selected completion (None while the popup is hidden).
+    pub fn selected(&self) -> Option<Completion> {
+        let inner = self.0.borrow();
+        inner.items.get(inner.selected).cloned()
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.0.borrow_mut().select(index);
+    }
+
+    // Select the next (or previous) completion, wrapping around.
+    pub fn select_next(&mut self, forward: bool) {
+        let mut inner = self.0.borrow_mut();
+        let len = inner.items.len();
+        if len > 0 {
+            let index = match forward {
+                true => (inner.selected + 1) % len,
+                false => (inner.selected + len - 1) % len,
+            };
+            inner.select(index);
+        }
+    }
+
+    // Call `handler` when a completion is accepted with the mouse (when the popup is no
+    // longer borrowed).
+    pub fn set_onaccept(&mut self, handler: impl Fn() + 'static) {
+        self.0.borrow_mut().onaccept = Some(Rc::new(handler));
+    }
+
+    fn accept(&self) {
+        let onaccept = self.0.borrow().onaccept.clone();
+        if let Some(onaccept) = onaccept {
+            onaccept();
+        }
+    }
+}
+
+impl _CompletionPopup {
+    // Select a completion, scrolling the list so that it can be seen.
+    fn select(&mut self, index: usize) {
+        if index == self.selected || index >= self.items.len() {
+            return;
+        }
+        let list = &mut self.component.get_mut().0;
+        if let Some(item) = list.get_mut(self.selected) {
+            item.remove_attribute("class");
+        }
+        (list.get_mut(index).expect("item index")).set_attribute("class", "selected");
+        self.selected = index;
+
+        let top = index as f64 * ITEM_HEIGHT_PX;
+        let (scroll_top, height) = (self.component.scroll_top(), self.component.client_height());
+        if top < scroll_top {
+            self.component.set_scroll_top(top);
+        } else if top + ITEM_HEIGHT_PX > scroll_top + height && height > 0.0 {
+            self.component.set_scroll_top(top + ITEM_HEIGHT_PX - height);
+        }
+    }
+}
+
+impl WithNode for CompletionPopup {
+    fn with_node(&self, f: impl FnMut(&web_sys::Node), g: AccessToken) {
+        self.0.borrow().component.with_node(f, g);
+    }
+}
+
+impl WithElement<HtmlDivElement> for CompletionPopup {
+    fn with_element(&self, f: impl FnMut(&HtmlDivElement), g: AccessToken) {
+        self.0.borrow().component.with_element(f, g);
+    }
+}
+
+// To audit, audit the overlay, then check that it is shown exactly when there are
+// completions, and that it shows them.
+impl Component for CompletionPopup {
+    fn try_audit(&self) -> Result<(), String> {
+        let inner = self.0.borrow();
+        inner.component.try_audit()?;
+        audit_eq!(inner.component.is_shown(), !inner.items.is_empty());
+        let list = &inner.component.get().0;
+        audit_eq!(list.len(), inner.items.len());
+        for (div, item) in list.iter().zip(&inner.items) {
+            audit_eq!(div.get().0.get().0.get(), item.text);
+            audit_eq!(div.get().1.0.get().0.get(), item.detail);
+        }
+        Ok(())
+    }
+}
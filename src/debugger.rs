@@ -0,0 +1,644 @@
+// The Debugger panel: runs one of the module's functions in the interpreter (see
+// interpreter.rs), one step at a time. Step executes one instruction, Step Over runs
+// a call to completion, and Continue runs until a line with a breakpoint (set by
+// clicking a function's gutter). While paused, the module editor highlights the next
+// line to execute, and the panel shows the operand stack (top first), the locals and
+// labels of the innermost call (innermost label first) and the values printed so far.
+// Editing the program ends the session.
+//
+// Step Back undoes a step, and the timeline scrubber goes to any step of the run so
+// far (as far back as the interpreter remembers), showing the machine as it was then.
+// Going forwards again executes again, so it retraces the same run.
+//
+// Below the views, the memory inspector (see memory_inspector.rs) shows the machine's
+// memory, highlighting what the last step changed.
+
+use crate::{
+    assemble::ModuleFunction,
+    dom_struct::DomStruct,
+    dom_text::DomText,
+    dom_vec::DomVec,
+    instruction::Immediate,
+    interpreter::Machine,
+    memory_inspector::{MemoryEvent, MemoryInspector},
+    module_editor::ModuleEditor,
+    sections,
+    validate::module_function_context,
+    value::{Value, format_results, parse_args},
+    web_support::{
+        AccessToken, Component, ElementFactory, WithElement, WithNode, audit_eq, dom_op_scope,
+    },
+};
+use std::{
+    cell::RefCell,
+    ops::Range,
+    rc::{Rc, Weak},
+};
+use web_sys::{HtmlButtonElement, HtmlDivElement, HtmlInputElement};
+
+type Input = DomStruct<(), HtmlInputElement>;
+type Button = DomStruct<(DomText, ()), HtmlButtonElement>;
+type Buttons = (Button, (Button, (Button, (Button, (Button, ())))));
+type Controls = DomStruct<(Input, (Input, Buttons)), HtmlDivElement>;
+type TextDiv = DomStruct<(DomText, ()), HtmlDivElement>;
+type Lines = DomVec<TextDiv, HtmlDivElement>;
+type Timeline = DomStruct<(Input, (TextDiv, ())), HtmlDivElement>;
+type Section = DomStruct<(TextDiv, (Lines, ())), HtmlDivElement>;
+type Sections = (
+    Section,
+    (Section, (Section, (Section, (MemoryInspector, ())))),
+);
+type PanelContents = (Controls, (Timeline, (TextDiv, Sections)));
+
+// How many instructions Continue (or Step Over) runs before pausing anyway.
+const MAX_STEPS: usize = 1_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum View {
+    Stack,
+    Locals,
+    Labels,
+    Printed,
+}
+
+impl View {
+    const ALL: [View; 4] = [View::Stack, View::Locals, View::Labels, View::Printed];
+
+    fn heading(&self) -> &'static str {
+        match self {
+            View::Stack => "Stack",
+            View::Locals => "Locals",
+            View::Labels => "Labels",
+            View::Printed => "Printed",
+        }
+    }
+}
+
+// A call being debugged, and the functions it was assembled from.
+struct Session {
+    machine: Machine,
+    functions: Vec<ModuleFunction>,
+    sections: sections::Sections, // the module's other sections, as typed
+    header_lens: Vec<usize>,      // how many of each function's lines are its header's
+    name: String,
+    args: Vec<Value>,
+    local_names: Vec<Vec<Option<String>>>, // for each function
+    furthest: usize,                       // the most steps taken
+}
+
+impl Session {
+    // The module editor's function for a function of the machine's module (which
+    // counts the imports first).
+    fn function(&self, func: u32) -> usize {
+        func as usize - self.machine.module().imports.len()
+    }
+
+    // The module editor's function and line for a function of the machine's module
+    // and one of its lines.
+    fn location(&self, func: u32, line: usize) -> (usize, usize) {
+        let func = self.function(func);
+        (func, line - self.header_lens[func])
+    }
+
+    // Where the next instruction is (if it's on a line).
+    fn current_location(&self) -> Option<(usize, usize)> {
+        let machine = &self.machine;
+        let (func, line) = machine.current_func().zip(machine.current_line())?;
+        Some(self.location(func, line))
+    }
+}
+
+struct _Debugger {
+    factory: ElementFactory,
+    module: ModuleEditor,
+    component: DomStruct<PanelContents, HtmlDivElement>,
+    session: Option<Session>,
+    message: String,                 // why there's no session
+    status: String,                  // as shown
+    timeline: (usize, usize, usize), // as shown: the earliest step, current and furthest
+    views: [Vec<String>; 4],         // as shown, in the order of View::ALL
+}
+
+pub struct Debugger(Rc<RefCell<_Debugger>>);
+
+impl Debugger {
+    pub fn new(factory: &ElementFactory, module: &ModuleEditor) -> Self {
+        let input = |placeholder: &str| {
+            let mut input = Input::new((), factory.input());
+            input.set_attribute("placeholder", placeholder);
+            input.set_attribute("spellcheck", "false");
+            input
+        };
+        let button = |label: &str| Button::new((DomText::new(label), ()), factory.button());
+        let text_div = |text: &str, class: &str| {
+            let mut div = TextDiv::new((DomText::new(text), ()), factory.div());
+            div.set_attribute("class", class);
+            div
+        };
+        let section = |view: View| {
+            let mut section = Section::new(
+                (
+                    text_div(view.heading(), "heading"),
+                    (Lines::new(factory.div()), ()),
+                ),
+                factory.div(),
+            );
+            section.set_attribute("class", "section");
+            section
+        };
+        let mut controls = Controls::new(
+            (
+                input("export"),
+                (
+                    input("arguments"),
+                    (
+                        button("Start"),
+                        (
+                            button("Step Back"),
+                            (
+                                button("Step"),
+                                (button("Step Over"), (button("Continue"), ())),
+                            ),
+                        ),
+                    ),
+                ),
+            ),
+            factory.div(),
+        );
+        controls.set_attribute("class", "controls");
+        let mut scrubber = Input::new((), factory.input());
+        scrubber.set_attribute("type", "range");
+        scrubber.set_attribute("min", "0");
+        scrubber.set_attribute("max", "0");
+        scrubber.set_value("0");
+        let mut timeline = Timeline::new(
+            (scrubber, (text_div("step 0 of 0", "position"), ())),
+            factory.div(),
+        );
+        timeline.set_attribute("class", "timeline");
+        let status = text_div("", "status");
+
+        let ret = Debugger(Rc::new_cyclic(|this: &Weak<RefCell<_Debugger>>| {
+            let debugger_ref = this.clone();
+            let inspector = MemoryInspector::new(factory, move |event| {
+                if let Some(debugger_ref) = debugger_ref.upgrade() {
+                    Debugger(debugger_ref).handle_memory_event(event);
+                }
+            });
+            let sections = (
+                section(View::Stack),
+                (
+                    section(View::Locals),
+                    (
+                        section(View::Labels),
+                        (section(View::Printed), (inspector, ())),
+                    ),
+                ),
+            );
+            let mut component =
+                DomStruct::new((controls, (timeline, (status, sections))), factory.div());
+            component.set_attribute("class", "debugger");
+            RefCell::new(_Debugger {
+                factory: factory.clone(),
+                module: module.clone(),
+                component,
+                session: None,
+                message: String::new(),
+                status: String::new(),
+                timeline: (0, 0, 0),
+                views: Default::default(),
+            })
+        }));
+
+        let actions: [fn(&mut Debugger); 5] = [
+            Debugger::start,
+            Debugger::step_back,
+            Debugger::step,
+            Debugger::step_over,
+            Debugger::resume,
+        ];
+        for (index, action) in actions.into_iter().enumerate() {
+            let debugger_ref = Rc::clone(&ret.0);
+            (ret.0.borrow_mut().button_mut(index))
+                .set_onclick(move |_| action(&mut Debugger(debugger_ref.clone())));
+        }
+
+        let debugger_ref = Rc::clone(&ret.0);
+        ret.0.borrow_mut().scrubber_mut().set_oninput(move |_| {
+            let step = debugger_ref.borrow().scrubber().value().parse();
+            if let Ok(step) = step {
+                Debugger(debugger_ref.clone()).seek(step);
+            }
+        });
+
+        ret
+    }
+
+    pub fn set_export(&mut self, name: &str) {
+        self.0.borrow_mut().export_input_mut().set_value(name);
+    }
+
+    pub fn set_args(&mut self, args: &str) {
+        self.0.borrow_mut().args_input_mut().set_value(args);
+    }
+
+    pub fn status(&self) -> String {
+        self.0.borrow().status.clone()
+    }
+
+    // The earliest step the timeline can go back to, the current step, and the
+    // furthest step taken.
+    pub fn timeline(&self) -> (usize, usize, usize) {
+        self.0.borrow().timeline
+    }
+
+    // The lines of one of the panel's views.
+    pub fn view(&self, view: View) -> Vec<String> {
+        self.0.borrow().views[view as usize].clone()
+    }
+
+    // The memory inspector's rows with elements (see MemoryInspector::rows).
+    pub fn memory_rows(&self) -> (Range<usize>, Vec<String>) {
+        self.0.borrow().inspector().rows()
+    }
+
+    // The value of each type at the memory inspector's address.
+    pub fn memory_values(&self) -> Vec<String> {
+        self.0.borrow().inspector().values().to_vec()
+    }
+
+    // The addresses of the bytes the last step changed, as highlighted.
+    pub fn changed_bytes(&self) -> Vec<usize> {
+        self.0.borrow().inspector().changed().to_vec()
+    }
+
+    pub fn memory_address(&self) -> String {
+        self.0.borrow().inspector().address()
+    }
+
+    // Type an address into the memory inspector (which stops it following loads and
+    // stores).
+    pub fn set_memory_address(&mut self, text: &str) {
+        let mut inner = self.0.borrow_mut();
+        let _scope = dom_op_scope("debugger");
+        inner.inspector_mut().set_address(text);
+        inner.refresh_memory();
+    }
+
+    pub fn set_memory_follow(&mut self, follow: bool) {
+        let mut inner = self.0.borrow_mut();
+        let _scope = dom_op_scope("debugger");
+        inner.inspector_mut().set_follow(follow);
+        inner.refresh_memory();
+    }
+
+    fn handle_memory_event(&mut self, event: MemoryEvent) {
+        let mut inner = self.0.borrow_mut();
+        let _scope = dom_op_scope("debugger");
+        inner.inspector_mut().handle(event);
+        inner.refresh_memory();
+    }
+
+    // Assemble the module and call the export (or, if none is given, the first
+    // exported function), pausing before its first instruction.
+    pub fn start(&mut self) {
+        let mut inner = self.0.borrow_mut();
+        inner.session = None;
+        match inner.prepare() {
+            Ok(session) => inner.session = Some(session),
+            Err(message) => inner.message = message,
+        }
+        inner.refresh();
+    }
+
+    // Execute one instruction (starting a session if there isn't one).
+    pub fn step(&mut self) {
+        self.act(|machine, _| {
+            let _ = machine.step();
+        });
+    }
+
+    pub fn step_back(&mut self) {
+        self.act(|machine, _| {
+            machine.step_back();
+        });
+    }
+
+    // Go to the state after `step` steps of the run.
+    pub fn seek(&mut self, step: usize) {
+        self.act(|machine, _| {
+            let _ = machine.seek(step);
+        });
+    }
+
+    pub fn step_over(&mut self) {
+        self.act(|machine, breakpoints| {
+            let _ = machine.step_over(breakpoints, MAX_STEPS);
+        });
+    }
+
+    // Run to the next breakpoint, or to the end of the call.
+    pub fn resume(&mut self) {
+        self.act(|machine, breakpoints| {
+            let _ = machine.run_to_breakpoint(breakpoints, MAX_STEPS);
+        });
+    }
+
+    // Advance the session (whether it traps or finishes, the outcome is shown from
+    // the machine's state).
+    fn act(&mut self, advance: impl FnOnce(&mut Machine, &[(u32, usize)])) {
+        if self.0.borrow().session.is_none() {
+            return self.start();
+        }
+        let mut inner = self.0.borrow_mut();
+        let inner = &mut *inner;
+        let session = inner.session.as_mut().expect("session");
+        if session.functions == inner.module.module_functions()
+            && session.sections == inner.module.sections()
+        {
+            let imports = session.machine.module().imports.len();
+            let breakpoints: Vec<(u32, usize)> = (inner.module.breakpoints().into_iter())
+                .map(|(func, line)| ((imports + func) as u32, session.header_lens[func] + line))
+                .collect();
+            advance(&mut session.machine, &breakpoints);
+            session.furthest = session.furthest.max(session.machine.steps());
+        } else {
+            inner.session = None;
+            inner.message = "the program has changed: Start again".to_string();
+        }
+        inner.refresh();
+    }
+}
+
+impl _Debugger {
+    fn controls_mut(&mut self) -> &mut Controls {
+        &mut self.component.get_mut().0
+    }
+
+    fn export_input(&self) -> &Input {
+        &self.component.get().0.get().0
+    }
+
+    fn export_input_mut(&mut self) -> &mut Input {
+        &mut self.controls_mut().get_mut().0
+    }
+
+    fn args_input(&self) -> &Input {
+        &self.component.get().0.get().1.0
+    }
+
+    fn args_input_mut(&mut self) -> &mut Input {
+        &mut self.controls_mut().get_mut().1.0
+    }
+
+    fn button_mut(&mut self, index: usize) -> &mut Button {
+        let buttons = &mut self.controls_mut().get_mut().1.1;
+        match index {
+            0 => &mut buttons.0,
+            1 => &mut buttons.1.0,
+            2 => &mut buttons.1.1.0,
+            3 => &mut buttons.1.1.1.0,
+            _ => &mut buttons.1.1.1.1.0,
+        }
+    }
+
+    fn timeline_mut(&mut self) -> &mut Timeline {
+        &mut self.component.get_mut().1.0
+    }
+
+    fn scrubber(&self) -> &Input {
+        &self.component.get().1.0.get().0
+    }
+
+    fn scrubber_mut(&mut self) -> &mut Input {
+        &mut self.timeline_mut().get_mut().0
+    }
+
+    fn status_div_mut(&mut self) -> &mut TextDiv {
+        &mut self.component.get_mut().1.1.0
+    }
+
+    fn section(&self, view: View) -> &Section {
+        let sections = &self.component.get().1.1.1;
+        match view {
+            View::Stack => &sections.0,
+            View::Locals => &sections.1.0,
+            View::Labels => &sections.1.1.0,
+            View::Printed => &sections.1.1.1.0,
+        }
+    }
+
+    fn inspector(&self) -> &MemoryInspector {
+        &self.component.get().1.1.1.1.1.1.1.0
+    }
+
+    fn inspector_mut(&mut self) -> &mut MemoryInspector {
+        &mut self.component.get_mut().1.1.1.1.1.1.1.0
+    }
+
+    fn lines_mut(&mut self, view: View) -> &mut Lines {
+        let sections = &mut self.component.get_mut().1.1.1;
+        let section = match view {
+            View::Stack => &mut sections.0,
+            View::Locals => &mut sections.1.0,
+            View::Labels => &mut sections.1.1.0,
+            View::Printed => &mut sections.1.1.1.0,
+        };
+        &mut section.get_mut().1.0
+    }
+
+    // A session for the export and arguments, or what's wrong with them.
+    fn prepare(&self) -> Result<Session, String> {
+        let functions = self.module.module_functions();
+        let sections = self.module.sections();
+        let module = (self.module.assemble()).map_err(|errors| {
+            errors
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })?;
+        let export = module.func_export(self.export_input().value().trim())?;
+        let (name, func) = (export.name.clone(), export.index);
+        let ty = module.func_type(func).expect("exported function");
+        let args = parse_args(&self.args_input().value(), &ty.params)?;
+        let (resolved, _) = sections.resolve(&functions);
+        let (funcs, globals) = (resolved.funcs(&functions), resolved.globals());
+        let local_names = (functions.iter())
+            .map(|function| {
+                let (ctx, _) = module_function_context(&function.lines, &funcs, &globals);
+                ctx.locals.into_iter().map(|local| local.name).collect()
+            })
+            .collect();
+        let header_lens = (0..functions.len())
+            .map(|index| self.module.header_len(index))
+            .collect();
+        let mut machine = Machine::new(module).map_err(|error| error.to_string())?;
+        machine
+            .invoke(func, &args)
+            .map_err(|error| error.to_string())?;
+        Ok(Session {
+            machine,
+            functions,
+            sections,
+            header_lens,
+            name,
+            args,
+            local_names,
+            furthest: 0,
+        })
+    }
+
+    // What the session's machine is doing, and what it holds.
+    fn describe(&self, session: &Session) -> (String, [Vec<String>; 4]) {
+        let machine = &session.machine;
+        let place =
+            (session.current_location()).map(|(func, line)| self.module.place(func, Some(line)));
+        let status = match (machine.finished(), place) {
+            (None, Some(place)) => format!("paused at {place}"),
+            (None, None) => "paused at the end of the function".to_string(),
+            (Some(Ok(results)), _) => {
+                let args: Vec<String> = session.args.iter().map(|arg| arg.to_string()).collect();
+                format!(
+                    "{}({}) returned {}",
+                    session.name,
+                    args.join(", "),
+                    format_results(results)
+                )
+            }
+            (Some(Err(trap)), Some(place)) => format!("trap at {place}: {trap}"),
+            (Some(Err(trap)), None) => format!("trap: {trap}"),
+        };
+
+        let typed = |value: &Value| format!("{value} : {}", value.ty());
+        let stack = machine.stack().iter().rev().map(typed).collect();
+        let local_names =
+            (machine.current_func()).map(|func| &session.local_names[session.function(func)]);
+        let locals = (machine.locals().iter().enumerate())
+            .map(
+                |(index, value)| match local_names.and_then(|names| names.get(index)) {
+                    Some(Some(name)) => format!("${name} = {}", typed(value)),
+                    _ => format!("{index} = {}", typed(value)),
+                },
+            )
+            .collect();
+        let module = machine.module();
+        let labels = match machine.frames().last() {
+            Some(frame) => {
+                let index = session.function(frame.func);
+                let lines = &session.functions[index].lines;
+                (frame.labels.iter().rev())
+                    .map(|label| {
+                        // (the assembled blocks have lost their names)
+                        let line = module.funcs[index].lines[label.start];
+                        let name = match lines[line].instruction().map(|i| &i.imm) {
+                            Some(Immediate::Block {
+                                label: Some(name), ..
+                            }) => format!(" ${name}"),
+                            _ => String::new(),
+                        };
+                        let (_, line) = session.location(frame.func, line);
+                        format!("{}{name} (line {})", label.mnemonic, line + 1)
+                    })
+                    .collect()
+            }
+            None => Vec::new(),
+        };
+        let printed = machine.printed().iter().map(Value::to_string).collect();
+        (status, [stack, locals, labels, printed])
+    }
+
+    // Bring the status, the views, the memory inspector and the module editor's
+    // current line up to date.
+    fn refresh(&mut self) {
+        let (status, views, location, timeline) = match &self.session {
+            Some(session) => {
+                let (status, views) = self.describe(session);
+                let machine = &session.machine;
+                let timeline = (machine.earliest_step(), machine.steps(), session.furthest);
+                (status, views, session.current_location(), timeline)
+            }
+            None => (self.message.clone(), Default::default(), None, (0, 0, 0)),
+        };
+        (self.module.set_current_line(location)).expect("function index");
+
+        let _scope = dom_op_scope("debugger");
+        if status != self.status {
+            self.status_div_mut().get_mut().0.set_data(&status);
+            self.status = status;
+        }
+        if timeline != self.timeline {
+            let (earliest, step, furthest) = timeline;
+            let scrubber = self.scrubber_mut();
+            scrubber.set_attribute("min", &earliest.to_string());
+            scrubber.set_attribute("max", &furthest.to_string());
+            scrubber.set_value(&step.to_string());
+            let position = &mut self.timeline_mut().get_mut().1.0;
+            (position.get_mut().0).set_data(&format!("step {step} of {furthest}"));
+            self.timeline = timeline;
+        }
+        for (view, lines) in View::ALL.into_iter().zip(views) {
+            if lines == self.views[view as usize] {
+                continue;
+            }
+            let factory = self.factory.clone();
+            let dom_lines = self.lines_mut(view);
+            dom_lines.clear();
+            for text in &lines {
+                dom_lines.push(TextDiv::new((DomText::new(text), ()), factory.div()));
+            }
+            self.views[view as usize] = lines;
+        }
+        self.refresh_memory();
+    }
+
+    // Show the session's memory in the memory inspector (or none, without a session).
+    fn refresh_memory(&mut self) {
+        let (memory, changed, access) = match &self.session {
+            Some(session) => {
+                let machine = &session.machine;
+                let changed = machine.changed_bytes();
+                (machine.memory(), changed, machine.last_access())
+            }
+            None => (&[][..], Vec::new(), None),
+        };
+        let inspector = &mut self.component.get_mut().1.1.1.1.1.1.1.0;
+        inspector.show(memory, &changed, access);
+    }
+}
+
+impl WithNode for Debugger {
+    fn with_node(&self, f: impl FnMut(&web_sys::Node), g: AccessToken) {
+        self.0.borrow().component.with_node(f, g);
+    }
+}
+
+impl WithElement<HtmlDivElement> for Debugger {
+    fn with_element(&self, f: impl FnMut(&HtmlDivElement), g: AccessToken) {
+        self.0.borrow().component.with_element(f, g);
+    }
+}
+
+// To audit, audit the panel's elements, then check that the status and the views
+// show what was recorded.
+impl Component for Debugger {
+    fn try_audit(&self) -> Result<(), String> {
+        let inner = self.0.borrow();
+        inner.component.try_audit()?;
+        let (_, step, furthest) = inner.timeline;
+        let timeline = inner.component.get().1.0.get();
+        audit_eq!(timeline.0.value(), step.to_string());
+        let position = format!("step {step} of {furthest}");
+        audit_eq!(timeline.1.0.get().0.get(), &position);
+        audit_eq!(inner.component.get().1.1.0.get().0.get(), &inner.status);
+        for view in View::ALL {
+            let lines = &inner.section(view).get().1.0;
+            let expected = &inner.views[view as usize];
+            audit_eq!(lines.len(), expected.len());
+            for (line, text) in lines.iter().zip(expected) {
+                audit_eq!(line.get().0.get(), text);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,658 @@
+// The binary decoder: read a module in the WebAssembly binary format back into a
+// Module, with the names from its custom "name" section. It reads the sections the
+// encoder writes (types, imports, functions, the table, memory, globals, exports,
+// elements, code and data), and skips the ones this editor has no model for (the
+// start function, the data count and other custom sections).
+
+use crate::{
+    encode::{MAGIC, VERSION},
+    instruction::{
+        BlockType, ImmKind, Immediate, Index, Instruction, MemArg, ValType, lookup_opcode,
+    },
+    module::{Data, Elem, Export, ExportKind, Function, Global, Import, Limits, Module},
+    validate::FuncType,
+    value::Value,
+};
+use anyhow::{Context, Result, anyhow, bail};
+use std::collections::HashMap;
+
+// A function body may declare at most this many locals (as wasmparser does).
+const MAX_LOCALS: u64 = 50_000;
+
+// The names from the "name" section: of the module, of functions (by function index),
+// and of each function's locals and labels (by function index, then local index or
+// the label's position among the function's blocks).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Names {
+    pub module: Option<String>,
+    pub funcs: HashMap<u32, String>,
+    pub locals: HashMap<u32, HashMap<u32, String>>,
+    pub labels: HashMap<u32, HashMap<u32, String>>,
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    base: usize, // where `bytes` start in the module, for error messages
+}
+
+impl<'a> Reader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self {
+            bytes,
+            pos: 0,
+            base: 0,
+        }
+    }
+
+    // The position in the module.
+    fn offset(&self) -> usize {
+        self.base + self.pos
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8]> {
+        let Some(bytes) = self.bytes.get(self.pos..self.pos.saturating_add(len)) else {
+            bail!(
+                "unexpected end of the module at byte {}",
+                self.base + self.bytes.len()
+            );
+        };
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    // An unsigned LEB128 number of at most `bits` bits.
+    fn unsigned(&mut self, bits: u32) -> Result<u64> {
+        let start = self.offset();
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+            if shift >= bits {
+                bail!("integer too long at byte {start}");
+            }
+        }
+        if shift > bits && value >> bits != 0 {
+            bail!("integer too large at byte {start}");
+        }
+        Ok(value)
+    }
+
+    // A signed LEB128 number of at most `bits` bits.
+    fn signed(&mut self, bits: u32) -> Result<i64> {
+        let start = self.offset();
+        let mut value = 0i64;
+        let mut shift = 0;
+        let byte = loop {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as i64) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break byte;
+            }
+            if shift >= bits {
+                bail!("integer too long at byte {start}");
+            }
+        };
+        if shift > bits {
+            // the last byte's bits past the value's must all copy its sign bit
+            let used = bits + 7 - shift;
+            let extra = (byte & 0x7f) >> (used - 1);
+            if extra != 0 && extra != 0x7f >> (used - 1) {
+                bail!("integer too large at byte {start}");
+            }
+        }
+        if shift < 64 && byte & 0x40 != 0 {
+            value |= -1 << shift; // sign-extend
+        }
+        Ok(value)
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(self.unsigned(32)? as u32)
+    }
+
+    fn len(&mut self) -> Result<usize> {
+        Ok(self.u32()? as usize)
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let start = self.offset();
+        let len = self.len()?;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| anyhow!("invalid UTF-8 name at byte {start}"))
+    }
+
+    fn valtype(&mut self) -> Result<ValType> {
+        let byte = self.byte()?;
+        valtype(byte).ok_or_else(|| {
+            anyhow!(
+                "unsupported value type {byte:#04x} at byte {}",
+                self.offset() - 1
+            )
+        })
+    }
+
+    fn valtypes(&mut self) -> Result<Vec<ValType>> {
+        (0..self.u32()?).map(|_| self.valtype()).collect()
+    }
+
+    // A vector of items, prefixed by their count.
+    fn vec_of<T>(&mut self, mut read: impl FnMut(&mut Self) -> Result<T>) -> Result<Vec<T>> {
+        (0..self.u32()?).map(|_| read(self)).collect()
+    }
+
+    // A section's (or subsection's) contents, prefixed by their size.
+    fn sized(&mut self) -> Result<Reader<'a>> {
+        let len = self.len()?;
+        let base = self.offset();
+        Ok(Reader {
+            base,
+            ..Reader::new(self.bytes(len)?)
+        })
+    }
+
+    fn expect_end(&self, what: &str) -> Result<()> {
+        if !self.at_end() {
+            bail!("{what} has {} bytes left over", self.bytes.len() - self.pos);
+        }
+        Ok(())
+    }
+}
+
+fn valtype(byte: u8) -> Option<ValType> {
+    match byte {
+        0x7f => Some(ValType::I32),
+        0x7e => Some(ValType::I64),
+        0x7d => Some(ValType::F32),
+        0x7c => Some(ValType::F64),
+        _ => None,
+    }
+}
+
+fn read_func_type(reader: &mut Reader) -> Result<FuncType> {
+    let form = reader.byte()?;
+    if form != 0x60 {
+        bail!("expected a function type, got {form:#04x}");
+    }
+    Ok(FuncType {
+        params: reader.valtypes()?,
+        results: reader.valtypes()?,
+    })
+}
+
+fn read_limits(reader: &mut Reader) -> Result<Limits> {
+    let flags = reader.byte()?;
+    let min = reader.u32()?;
+    let max = if flags & 1 != 0 {
+        Some(reader.u32()?)
+    } else {
+        None
+    };
+    Ok(Limits { min, max })
+}
+
+// A constant expression: one `const` instruction, then `end`.
+fn read_const(reader: &mut Reader) -> Result<Value> {
+    let start = reader.offset();
+    let value = match reader.byte()? {
+        0x41 => Value::I32(reader.signed(32)? as i32),
+        0x42 => Value::I64(reader.signed(64)?),
+        0x43 => Value::F32(f32::from_bits(u32::from_le_bytes(
+            reader.bytes(4)?.try_into()?,
+        ))),
+        0x44 => Value::F64(f64::from_bits(u64::from_le_bytes(
+            reader.bytes(8)?.try_into()?,
+        ))),
+        _ => bail!("unsupported constant expression at byte {start}"),
+    };
+    if reader.byte()? != 0x0b {
+        bail!("unsupported constant expression at byte {start}");
+    }
+    Ok(value)
+}
+
+// An active segment's flags (which must be 0: for table or memory 0) and offset.
+fn read_segment_offset(reader: &mut Reader, what: &str) -> Result<u32> {
+    let start = reader.offset();
+    let flags = reader.u32()?;
+    if flags != 0 {
+        bail!("unsupported {what} (kind {flags}) at byte {start}");
+    }
+    match read_const(reader)? {
+        Value::I32(offset) => Ok(offset as u32),
+        _ => bail!("the offset of the {what} at byte {start} is not an i32"),
+    }
+}
+
+fn read_index(reader: &mut Reader) -> Result<Index> {
+    Ok(Index::Num(reader.u32()?))
+}
+
+fn read_instruction(reader: &mut Reader) -> Result<Instruction> {
+    let start = reader.offset();
+    let opcode = reader.byte()?;
+    let info = lookup_opcode(opcode)
+        .ok_or_else(|| anyhow!("unsupported instruction {opcode:#04x} at byte {start}"))?;
+    let imm = match info.imm {
+        ImmKind::None => Immediate::None,
+        ImmKind::Memory => match reader.byte()? {
+            0x00 => Immediate::None,
+            _ => bail!(
+                "{} of a memory other than 0, at byte {start}",
+                info.mnemonic
+            ),
+        },
+        ImmKind::OptLabel => Immediate::Label(None),
+        ImmKind::I32 => Immediate::I32(reader.signed(32)? as i32),
+        ImmKind::I64 => Immediate::I64(reader.signed(64)?),
+        ImmKind::F32 => Immediate::F32(u32::from_le_bytes(reader.bytes(4)?.try_into()?)),
+        ImmKind::F64 => Immediate::F64(u64::from_le_bytes(reader.bytes(8)?.try_into()?)),
+        ImmKind::Local | ImmKind::Global | ImmKind::Func | ImmKind::Label => {
+            Immediate::Index(read_index(reader)?)
+        }
+        ImmKind::BlockType => {
+            let ty = match reader.byte()? {
+                0x40 => BlockType::Empty,
+                byte => BlockType::Value(valtype(byte).ok_or_else(|| {
+                    anyhow!("unsupported block type {byte:#04x} at byte {}", start + 1)
+                })?),
+            };
+            Immediate::Block { label: None, ty }
+        }
+        ImmKind::BrTable => Immediate::BrTable {
+            labels: reader.vec_of(read_index)?,
+            default: read_index(reader)?,
+        },
+        ImmKind::MemArg(natural) => {
+            let align = reader.u32()?;
+            if align > natural {
+                bail!(
+                    "alignment 2^{align} of {} at byte {start} is larger than the natural alignment ({})",
+                    info.mnemonic,
+                    1 << natural
+                );
+            }
+            let offset = reader.u32()?;
+            Immediate::MemArg(MemArg { offset, align })
+        }
+    };
+    Ok(Instruction { info, imm })
+}
+
+// A function's code: its locals (as runs of the same type), then its body up to the
+// function's final `end`.
+fn read_code(reader: &mut Reader, type_index: u32) -> Result<Function> {
+    let mut code = reader.sized()?;
+    let runs = code.vec_of(|code| Ok((code.u32()?, code.valtype()?)))?;
+    if runs.iter().map(|(count, _)| *count as u64).sum::<u64>() > MAX_LOCALS {
+        bail!("too many locals");
+    }
+    let locals = (runs.into_iter())
+        .flat_map(|(count, ty)| std::iter::repeat_n(ty, count as usize))
+        .collect();
+    let mut body = Vec::new();
+    let mut depth = 0usize;
+    loop {
+        let instr = read_instruction(&mut code)?;
+        match instr.mnemonic() {
+            "block" | "loop" | "if" => depth += 1,
+            "end" if depth == 0 => break,
+            "end" => depth -= 1,
+            _ => {}
+        }
+        body.push(instr);
+    }
+    code.expect_end("a function body")?;
+    Ok(Function {
+        type_index,
+        locals,
+        body,
+        lines: Vec::new(),
+    })
+}
+
+// A name map: pairs of an index and a name.
+fn read_name_map(reader: &mut Reader) -> Result<HashMap<u32, String>> {
+    Ok(reader
+        .vec_of(|reader| Ok((reader.u32()?, reader.name()?)))?
+        .into_iter()
+        .collect())
+}
+
+// An indirect name map: a name map for each function.
+fn read_indirect_name_map(reader: &mut Reader) -> Result<HashMap<u32, HashMap<u32, String>>> {
+    Ok(
+        (reader.vec_of(|reader| Ok((reader.u32()?, read_name_map(reader)?)))?)
+            .into_iter()
+            .collect(),
+    )
+}
+
+// The "name" section's subsections: the module name (0), function names (1), local
+// names (2) and label names (3, from the extended name section proposal).
+fn read_names(reader: &mut Reader, names: &mut Names) -> Result<()> {
+    while !reader.at_end() {
+        let id = reader.byte()?;
+        let mut contents = reader.sized()?;
+        match id {
+            0 => names.module = Some(contents.name()?),
+            1 => names.funcs = read_name_map(&mut contents)?,
+            2 => names.locals = read_indirect_name_map(&mut contents)?,
+            3 => names.labels = read_indirect_name_map(&mut contents)?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+pub fn decode(bytes: &[u8]) -> Result<(Module, Names)> {
+    let mut reader = Reader::new(bytes);
+    if reader.bytes(4)? != MAGIC.as_slice() {
+        bail!("not a WebAssembly module");
+    }
+    let version = u32::from_le_bytes(reader.bytes(4)?.try_into()?);
+    if version != VERSION {
+        bail!("unsupported binary format version {version}");
+    }
+
+    let mut module = Module::default();
+    let mut names = Names::default();
+    let mut type_indices = Vec::new();
+    while !reader.at_end() {
+        let id = reader.byte()?;
+        let mut section = reader.sized()?;
+        let what = match id {
+            0 => {
+                // a malformed "name" section is ignored, as the spec allows
+                if section.name().ok().as_deref() == Some("name") {
+                    let mut found = Names::default();
+                    if read_names(&mut section, &mut found).is_ok() {
+                        names = found;
+                    }
+                }
+                continue;
+            }
+            1 => {
+                module.types = section.vec_of(read_func_type)?;
+                "the type section"
+            }
+            2 => {
+                for _ in 0..section.u32()? {
+                    let module_name = section.name()?;
+                    let name = section.name()?;
+                    match section.byte()? {
+                        0x00 => module.imports.push(Import {
+                            module: module_name,
+                            name,
+                            type_index: section.u32()?,
+                        }),
+                        kind => bail!("unsupported import of {module_name}.{name} (kind {kind})"),
+                    }
+                }
+                "the import section"
+            }
+            3 => {
+                type_indices = section.vec_of(Reader::u32)?;
+                "the function section"
+            }
+            4 => {
+                let tables = section.vec_of(|section| {
+                    let start = section.offset();
+                    match section.byte()? {
+                        0x70 => read_limits(section),
+                        ty => bail!("unsupported table type {ty:#04x} at byte {start}"),
+                    }
+                })?;
+                module.table = tables.first().copied();
+                "the table section"
+            }
+            5 => {
+                let limits = section.vec_of(read_limits)?;
+                module.memory = limits.first().copied();
+                "the memory section"
+            }
+            6 => {
+                module.globals = section.vec_of(|section| {
+                    let ty = section.valtype()?;
+                    let mutable = section.byte()? == 0x01;
+                    let init = read_const(section)?;
+                    if init.ty() != ty {
+                        bail!("a global of type {ty} starts as a {}", init.ty());
+                    }
+                    Ok(Global { ty, mutable, init })
+                })?;
+                "the global section"
+            }
+            7 => {
+                for _ in 0..section.u32()? {
+                    let name = section.name()?;
+                    let kind = section.byte()?;
+                    let index = section.u32()?;
+                    let kind = match kind {
+                        0x00 => ExportKind::Func,
+                        0x01 => ExportKind::Table,
+                        0x02 => ExportKind::Memory,
+                        0x03 => ExportKind::Global,
+                        _ => bail!("unsupported export of \"{name}\" (kind {kind})"),
+                    };
+                    module.exports.push(Export { name, kind, index });
+                }
+                "the export section"
+            }
+            9 => {
+                module.elems = section.vec_of(|section| {
+                    Ok(Elem {
+                        offset: read_segment_offset(section, "element segment")?,
+                        funcs: section.vec_of(Reader::u32)?,
+                    })
+                })?;
+                "the element section"
+            }
+            10 => {
+                let count = section.u32()? as usize;
+                if count != type_indices.len() {
+                    bail!(
+                        "{} functions, but {count} function bodies",
+                        type_indices.len()
+                    );
+                }
+                module.funcs = (type_indices.iter())
+                    .enumerate()
+                    .map(|(i, &type_index)| {
+                        read_code(&mut section, type_index)
+                            .with_context(|| format!("in function {i}"))
+                    })
+                    .collect::<Result<_>>()?;
+                "the code section"
+            }
+            11 => {
+                module.data = section.vec_of(|section| {
+                    let offset = read_segment_offset(section, "data segment")?;
+                    let len = section.len()?;
+                    Ok(Data {
+                        offset,
+                        bytes: section.bytes(len)?.to_vec(),
+                    })
+                })?;
+                "the data section"
+            }
+            8 | 12 => continue,
+            _ => bail!("unknown section {id}"),
+        };
+        section.expect_end(what)?;
+    }
+    if module.funcs.len() != type_indices.len() {
+        bail!("{} functions, but no function bodies", type_indices.len());
+    }
+    Ok((module, names))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble::assemble, encode::write_u32, parse::parse_line};
+
+    fn assembled(text: &str) -> Module {
+        let parsed: Vec<_> = text.lines().map(parse_line).collect();
+        assemble(&parsed).unwrap()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut module = assembled(
+            "(param $n i64)\n(result f64)\n(export \"f\")\n(local i32 i32 f32)\nblock $b (result f64)\nloop\nf64.const 1.5\nlocal.get $n\ni64.const -9000000000\ni64.lt_s\nbr_if 1\ndrop\ni32.const 7\ni32.load8_u offset=3\nbr_table 0 0 0\nend\nf64.const nan:0x123\nend\nmemory.size\ncall $print_i32\nf32.const -0.5\ndrop",
+        );
+        let (decoded, names) = decode(&module.encode()).unwrap();
+        for func in &mut module.funcs {
+            func.lines.clear();
+        }
+        assert_eq!(decoded, module);
+        assert_eq!(names, Names::default());
+    }
+
+    // A "name" section naming the module, function 4, its local 1 and its first label.
+    fn name_section() -> Vec<u8> {
+        let mut contents = Vec::new();
+        let mut subsection = |id: u8, bytes: &[u8]| {
+            contents.push(id);
+            write_u32(&mut contents, bytes.len() as u32);
+            contents.extend_from_slice(bytes);
+        };
+        subsection(0, b"\x03mod");
+        subsection(1, b"\x01\x04\x03add");
+        subsection(2, b"\x01\x04\x01\x01\x03sum");
+        subsection(3, b"\x01\x04\x01\x00\x04done");
+        let mut section = vec![0x00];
+        write_u32(&mut section, (contents.len() + 5) as u32);
+        section.extend_from_slice(b"\x04name");
+        section.extend_from_slice(&contents);
+        section
+    }
+
+    #[test]
+    fn names() {
+        let mut bytes = assembled("(param i32)\n(local i32)\nblock\nend").encode();
+        bytes.extend(name_section());
+        let (module, names) = decode(&bytes).unwrap();
+        assert_eq!(module.funcs.len(), 1);
+        assert_eq!(names.module.as_deref(), Some("mod"));
+        assert_eq!(names.funcs[&4], "add");
+        assert_eq!(names.locals[&4][&1], "sum");
+        assert_eq!(names.labels[&4][&0], "done");
+
+        // a broken name section is ignored
+        let mut bytes = assembled("nop").encode();
+        bytes.extend_from_slice(b"\x00\x07\x04name\x01\x05");
+        assert_eq!(decode(&bytes).unwrap().1, Names::default());
+    }
+
+    #[test]
+    fn malformed() {
+        let error = |bytes: &[u8]| decode(bytes).unwrap_err().to_string();
+        assert_eq!(error(b"\0asn\x01\0\0\0"), "not a WebAssembly module");
+        assert_eq!(
+            error(b"\0asm\x02\0\0\0"),
+            "unsupported binary format version 2"
+        );
+        assert_eq!(
+            error(b"\0asm\x01\0\0\0\x01\x05"),
+            "unexpected end of the module at byte 10"
+        );
+        assert_eq!(error(b"\0asm\x01\0\0\0\x0e\x00"), "unknown section 14");
+        assert_eq!(
+            error(b"\0asm\x01\0\0\0\x01\x05\x01\x60\x00\x00\x00"),
+            "the type section has 1 bytes left over"
+        );
+        assert_eq!(
+            error(b"\0asm\x01\0\0\0\x01\x04\x01\x60\x00\x00\x03\x02\x01\x00"),
+            "1 functions, but no function bodies"
+        );
+
+        // an instruction this editor doesn't know (a saturating truncation)
+        let bytes =
+            b"\0asm\x01\0\0\0\x01\x04\x01\x60\x00\x00\x03\x02\x01\x00\x0a\x05\x01\x03\x00\xfc\x00";
+        assert_eq!(
+            format!("{:#}", decode(bytes).unwrap_err()),
+            "in function 0: unsupported instruction 0xfc at byte 23"
+        );
+
+        // an i32.load with an alignment of 2^64 (larger than its natural 4 bytes)
+        let bytes = b"\0asm\x01\0\0\0\x01\x04\x01\x60\x00\x00\x03\x02\x01\x00\x05\x03\x01\x00\x01\x0a\x09\x01\x07\x00\x41\x00\x28\x40\x00\x1a\x0b";
+        assert_eq!(
+            format!("{:#}", decode(bytes).unwrap_err()),
+            "in function 0: alignment 2^64 of i32.load at byte 30 is larger than the natural alignment (4)"
+        );
+
+        // a table and a global (after the code section, where they don't belong, but
+        // the decoder doesn't check the order)
+        let mut bytes = assembled("nop").encode();
+        bytes.extend_from_slice(b"\x04\x04\x01\x70\x00\x01\x06\x06\x01\x7f\x00\x41\x00\x0b");
+        let module = decode(&bytes).unwrap().0;
+        assert_eq!(module.table, Some(Limits { min: 1, max: None }));
+        assert_eq!(
+            module.globals,
+            vec![Global {
+                ty: ValType::I32,
+                mutable: false,
+                init: Value::I32(0)
+            }]
+        );
+
+        // segments other than active ones, and other constant expressions
+        let mut bytes = assembled("nop").encode();
+        bytes.extend_from_slice(b"\x0b\x04\x01\x01\x00\x00");
+        assert_eq!(
+            error(&bytes),
+            "unsupported data segment (kind 1) at byte 115"
+        );
+        let mut bytes = assembled("nop").encode();
+        bytes.extend_from_slice(b"\x06\x06\x01\x7f\x00\x23\x00\x0b");
+        assert_eq!(error(&bytes), "unsupported constant expression at byte 117");
+    }
+
+    #[test]
+    fn leb128() {
+        let read = |bytes: &[u8], bits| Reader::new(bytes).signed(bits).map_err(|e| e.to_string());
+        assert_eq!(read(&[0x7f], 32), Ok(-1));
+        assert_eq!(read(&[0xc0, 0xbb, 0x78], 32), Ok(-123456));
+        assert_eq!(
+            read(&[0xff, 0xff, 0xff, 0xff, 0x07], 32),
+            Ok(i32::MAX as i64)
+        );
+        assert_eq!(
+            read(&[0x80, 0x80, 0x80, 0x80, 0x78], 32),
+            Ok(i32::MIN as i64)
+        );
+        assert_eq!(
+            read(&[0xff, 0xff, 0xff, 0xff, 0x0f], 32),
+            Err("integer too large at byte 0".to_string())
+        );
+        assert_eq!(
+            read(&[0x80, 0x80, 0x80, 0x80, 0x80, 0x00], 32),
+            Err("integer too long at byte 0".to_string())
+        );
+        let mut min = Vec::new();
+        crate::encode::write_i64(&mut min, i64::MIN);
+        assert_eq!(read(&min, 64), Ok(i64::MIN));
+        let read = |bytes: &[u8]| Reader::new(bytes).u32().map_err(|e| e.to_string());
+        assert_eq!(read(&[0xff, 0xff, 0xff, 0xff, 0x0f]), Ok(u32::MAX));
+        assert_eq!(
+            read(&[0xff, 0xff, 0xff, 0xff, 0x1f]),
+            Err("integer too large at byte 0".to_string())
+        );
+    }
+}
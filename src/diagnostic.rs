@@ -0,0 +1,142 @@
+// Diagnostics: errors, warnings and notes attached to a char range of one line. The
+// editor renders them as a squiggle under the range (in an overlay that mirrors the
+// line's text) and a badge in the gutter, whose title shows the messages on hover.
+
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    Note,
+    Warning,
+    Error,
+}
+
+impl Severity {
+    // The CSS class of a squiggle or badge.
+    pub fn class(&self) -> &'static str {
+        match self {
+            Severity::Note => "diag-note",
+            Severity::Warning => "diag-warning",
+            Severity::Error => "diag-error",
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Severity::Note => "note",
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub range: Range<usize>, // in chars
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, range: Range<usize>, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            range,
+            message: message.into(),
+        }
+    }
+
+    // Move the range to account for an edit to the line: the chars in `removed`
+    // were replaced by `inserted` chars. Text inserted at the start of the range is
+    // outside it; text inserted within it is inside. Returns false if the edit
+    // deleted all of the range's text.
+    pub fn adjust(&mut self, removed: Range<usize>, inserted: usize) -> bool {
+        let map = |pos: usize, is_end: bool| {
+            if pos < removed.start || (is_end && pos == removed.start) {
+                pos
+            } else if pos >= removed.end {
+                pos - removed.len() + inserted
+            } else if is_end {
+                removed.start + inserted
+            } else {
+                removed.start
+            }
+        };
+        let was_empty = self.range.is_empty();
+        self.range = map(self.range.start, false)..map(self.range.end, true);
+        was_empty || !self.range.is_empty()
+    }
+}
+
+// Split a line of `len` chars into segments, each with the highest severity of the
+// diagnostics that cover it (or None), merging neighbors of the same severity.
+pub fn segments(len: usize, diagnostics: &[Diagnostic]) -> Vec<(Range<usize>, Option<Severity>)> {
+    let mut bounds = vec![0, len];
+    for diagnostic in diagnostics {
+        bounds.push(diagnostic.range.start.min(len));
+        bounds.push(diagnostic.range.end.min(len));
+    }
+    bounds.sort_unstable();
+    bounds.dedup();
+
+    let mut ret: Vec<(Range<usize>, Option<Severity>)> = Vec::new();
+    for pair in bounds.windows(2) {
+        let range = pair[0]..pair[1];
+        let severity = diagnostics
+            .iter()
+            .filter(|diagnostic| {
+                diagnostic.range.start <= range.start && range.end <= diagnostic.range.end
+            })
+            .map(|diagnostic| diagnostic.severity)
+            .max();
+        match ret.last_mut() {
+            Some((last, last_severity)) if *last_severity == severity => last.end = range.end,
+            _ => ret.push((range, severity)),
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn segments_take_the_highest_severity() {
+        let diagnostics = [
+            Diagnostic::new(Severity::Warning, 2..8, "w"),
+            Diagnostic::new(Severity::Error, 4..6, "e"),
+            Diagnostic::new(Severity::Note, 7..12, "n"),
+        ];
+        assert_eq!(
+            segments(10, &diagnostics),
+            vec![
+                (0..2, None),
+                (2..4, Some(Severity::Warning)),
+                (4..6, Some(Severity::Error)),
+                (6..8, Some(Severity::Warning)),
+                (8..10, Some(Severity::Note)),
+            ]
+        );
+        assert_eq!(segments(3, &[]), vec![(0..3, None)]);
+        assert_eq!(segments(0, &[]), vec![]);
+    }
+
+    #[test]
+    fn ranges_move_with_edits() {
+        let adjusted = |removed: Range<usize>, inserted| {
+            let mut diagnostic = Diagnostic::new(Severity::Error, 4..8, "e");
+            diagnostic
+                .adjust(removed, inserted)
+                .then_some(diagnostic.range)
+        };
+        assert_eq!(adjusted(0..0, 3), Some(7..11)); // insert before
+        assert_eq!(adjusted(4..4, 3), Some(7..11)); // insert at the start
+        assert_eq!(adjusted(6..6, 3), Some(4..11)); // insert within
+        assert_eq!(adjusted(8..8, 3), Some(4..8)); // insert at the end
+        assert_eq!(adjusted(9..10, 0), Some(4..8)); // delete after
+        assert_eq!(adjusted(2..5, 0), Some(2..5)); // delete over the start
+        assert_eq!(adjusted(6..10, 1), Some(4..7)); // replace over the end
+        assert_eq!(adjusted(3..9, 0), None); // delete all of it
+    }
+}
@@ -0,0 +1,456 @@
+// The disassembler: turn a decoded module's functions (see decode.rs) back into
+// function headers (see header.rs) and editor lines, in the syntax that parse.rs
+// accepts, and its other sections into the module editor's entries (see sections.rs).
+// Names from the module's "name" section come back as $names wherever the text format
+// can use them: on functions, params and locals, blocks and branches. The editor
+// indents the lines by their nesting.
+
+use crate::{
+    decode::{Names, decode},
+    header::{FunctionText, Header, HeaderField},
+    instruction::{Immediate, Index, Instruction, ValType},
+    module::{ExportKind, HOST_MODULE, Import, Limits, Module, PRINT_FUNCS},
+    sections::{ModuleText, Section, Sections},
+    token::{is_id, quote, quote_bytes},
+};
+use anyhow::{Result, bail};
+use std::collections::HashMap;
+
+// The usable names in a name map: those that are identifiers, and not shared.
+fn usable(names: Option<&HashMap<u32, String>>) -> HashMap<u32, &str> {
+    let Some(names) = names else {
+        return HashMap::new();
+    };
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for name in names.values() {
+        *counts.entry(name).or_default() += 1;
+    }
+    (names.iter())
+        .filter(|(_, name)| is_id(name) && counts[name.as_str()] == 1)
+        .map(|(&index, name)| (index, name.as_str()))
+        .collect()
+}
+
+// A header field for params or locals: a part for each named one, and one for each
+// run of unnamed ones.
+fn declarations(types: &[ValType], names: &[Option<&str>]) -> String {
+    let mut parts = Vec::new();
+    let mut unnamed = Vec::new();
+    for (ty, name) in types.iter().zip(names) {
+        if let Some(name) = name {
+            if !unnamed.is_empty() {
+                parts.push(unnamed.join(" "));
+                unnamed.clear();
+            }
+            parts.push(format!("${name} {ty}"));
+        } else {
+            unnamed.push(ty.to_string());
+        }
+    }
+    if !unnamed.is_empty() {
+        parts.push(unnamed.join(" "));
+    }
+    parts.join(", ")
+}
+
+fn is_print(import: &Import) -> bool {
+    import.module == HOST_MODULE && PRINT_FUNCS.iter().any(|(name, _)| *name == import.name)
+}
+
+// How the editor refers to one of the module's functions: the host's print functions
+// by name, and the others by name if they have one, or else by their number in the
+// editor's module (where the print functions come first, then the other imports, then
+// the functions the module defines).
+fn func_ref(module: &Module, func_names: &HashMap<u32, &str>, index: u32) -> Index {
+    match module.imports.get(index as usize) {
+        Some(import) if is_print(import) => Index::Id(import.name.clone()),
+        _ => match func_names.get(&index) {
+            Some(name) => Index::Id(name.to_string()),
+            None => {
+                let before = &module.imports[..module.imports.len().min(index as usize)];
+                let prints = before.iter().filter(|import| is_print(import)).count();
+                Index::Num(index - prints as u32 + PRINT_FUNCS.len() as u32)
+            }
+        },
+    }
+}
+
+// Function `func` (an index counting the imports first).
+pub fn disassemble(module: &Module, names: &Names, func: u32) -> Result<FunctionText> {
+    let imports = module.imports.len() as u32;
+    let Some(function) = func
+        .checked_sub(imports)
+        .and_then(|i| module.funcs.get(i as usize))
+    else {
+        bail!("function {func} is not defined in the module");
+    };
+    let Some(ty) = module.types.get(function.type_index as usize) else {
+        bail!(
+            "function {func} has an unknown type {}",
+            function.type_index
+        );
+    };
+
+    let local_names = usable(names.locals.get(&func));
+    let label_names = usable(names.labels.get(&func));
+    let func_names = usable(Some(&names.funcs));
+    let names_of = |range: std::ops::Range<usize>| -> Vec<Option<&str>> {
+        range
+            .map(|i| local_names.get(&(i as u32)).copied())
+            .collect()
+    };
+
+    let mut header = Header::default();
+    if let Some(name) = func_names.get(&func) {
+        header.set_field(HeaderField::Name, &format!("${name}"));
+    }
+    let params = declarations(&ty.params, &names_of(0..ty.params.len()));
+    header.set_field(HeaderField::Params, &params);
+    let results: Vec<String> = ty.results.iter().map(ValType::to_string).collect();
+    header.set_field(HeaderField::Results, &results.join(" "));
+    let locals = declarations(
+        &function.locals,
+        &names_of(ty.params.len()..ty.params.len() + function.locals.len()),
+    );
+    header.set_field(HeaderField::Locals, &locals);
+    let mut lines = Vec::new();
+    for export in &module.exports {
+        if export.kind == ExportKind::Func && export.index == func {
+            lines.push(format!("(export {})", quote(&export.name)));
+        }
+    }
+
+    let mut labels: Vec<Option<&str>> = Vec::new(); // the enclosing blocks', innermost last
+    let mut blocks = 0;
+    let label = |labels: &[Option<&str>], index: &Index| -> Index {
+        match index {
+            Index::Num(depth) => (labels.len().checked_sub(*depth as usize + 1))
+                .and_then(|i| labels[i])
+                .map_or(index.clone(), |name| Index::Id(name.to_string())),
+            Index::Id(_) => index.clone(),
+        }
+    };
+    for instr in &function.body {
+        let mut instr: Instruction = instr.clone();
+        match (instr.mnemonic(), &mut instr.imm) {
+            ("block" | "loop" | "if", Immediate::Block { label, .. }) => {
+                *label = label_names.get(&blocks).map(|name| name.to_string());
+                labels.push(label_names.get(&blocks).copied());
+                blocks += 1;
+            }
+            ("end", _) => {
+                labels.pop();
+            }
+            ("local.get" | "local.set" | "local.tee", Immediate::Index(Index::Num(index))) => {
+                if let Some(name) = local_names.get(index) {
+                    instr.imm = Immediate::Index(Index::Id(name.to_string()));
+                }
+            }
+            ("call", Immediate::Index(Index::Num(index))) => {
+                instr.imm = Immediate::Index(func_ref(module, &func_names, *index));
+            }
+            ("br" | "br_if", Immediate::Index(index)) => *index = label(&labels, index),
+            (
+                _,
+                Immediate::BrTable {
+                    labels: targets,
+                    default,
+                },
+            ) => {
+                for target in targets {
+                    *target = label(&labels, target);
+                }
+                *default = label(&labels, default);
+            }
+            _ => {}
+        }
+        lines.push(instr.to_string());
+    }
+    Ok(FunctionText { header, lines })
+}
+
+fn limits(limits: &Limits) -> [String; 2] {
+    let max = limits.max.map_or(String::new(), |max| max.to_string());
+    [limits.min.to_string(), max]
+}
+
+// The entries of the module's other sections: its imports (but the print functions,
+// which every module has), table, memory, globals, the exports that aren't `export`
+// lines of its functions, and its segments.
+pub fn sections(module: &Module, names: &Names) -> Sections {
+    let func_names = usable(Some(&names.funcs));
+    let mut sections = Sections::default();
+    for (index, import) in module.imports.iter().enumerate() {
+        if is_print(import) {
+            continue;
+        }
+        let ty = (module.types.get(import.type_index as usize))
+            .cloned()
+            .unwrap_or_default();
+        let id = (func_names.get(&(index as u32))).map_or(String::new(), |name| format!("${name}"));
+        let params = declarations(&ty.params, &vec![None; ty.params.len()]);
+        let results: Vec<String> = ty.results.iter().map(ValType::to_string).collect();
+        sections.push(
+            Section::Imports,
+            &[
+                &import.module,
+                &import.name,
+                &id,
+                &params,
+                &results.join(" "),
+            ],
+        );
+    }
+    if let Some(table) = &module.table {
+        let [min, max] = limits(table);
+        sections.push(Section::Table, &[&min, &max]);
+    }
+    if let Some(memory) = &module.memory {
+        let [min, max] = limits(memory);
+        sections.push(Section::Memory, &[&min, &max]);
+    }
+    for global in &module.globals {
+        let ty = match global.mutable {
+            true => format!("mut {}", global.ty),
+            false => global.ty.to_string(),
+        };
+        sections.push(Section::Globals, &["", &ty, &global.init.to_string()]);
+    }
+    let imports = module.imports.len() as u32;
+    for export in &module.exports {
+        let index = match export.kind {
+            ExportKind::Func if export.index >= imports => continue, // an `export` line
+            ExportKind::Func => func_ref(module, &func_names, export.index).to_string(),
+            _ => export.index.to_string(),
+        };
+        sections.push(
+            Section::Exports,
+            &[&export.name, export.kind.keyword(), &index],
+        );
+    }
+    for elem in &module.elems {
+        let funcs: Vec<String> = (elem.funcs.iter())
+            .map(|&func| func_ref(module, &func_names, func).to_string())
+            .collect();
+        sections.push(
+            Section::Elements,
+            &[&elem.offset.to_string(), &funcs.join(" ")],
+        );
+    }
+    for data in &module.data {
+        sections.push(
+            Section::Data,
+            &[&data.offset.to_string(), &quote_bytes(&data.bytes)],
+        );
+    }
+    sections
+}
+
+// The functions a binary module defines (not the ones it imports), and its other
+// sections.
+pub fn disassemble_wasm(bytes: &[u8]) -> Result<ModuleText> {
+    let (module, names) = decode(bytes)?;
+    if module.funcs.is_empty() {
+        bail!("the module has no functions");
+    }
+    let imports = module.imports.len() as u32;
+    let funcs = (0..module.funcs.len() as u32)
+        .map(|func| disassemble(&module, &names, imports + func))
+        .collect::<Result<_>>()?;
+    Ok(ModuleText {
+        sections: sections(&module, &names),
+        funcs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assemble::{ModuleFunction, assemble_module},
+        encode::write_u32,
+        parse::parse_line,
+        sections::ModuleSections,
+    };
+
+    fn function(text: &str) -> ModuleFunction {
+        ModuleFunction {
+            name: None,
+            lines: text.lines().map(parse_line).collect(),
+        }
+    }
+
+    fn wasm(texts: &[&str]) -> Vec<u8> {
+        let funcs: Vec<_> = texts.iter().map(|text| function(text)).collect();
+        assemble_module(&funcs, &ModuleSections::default())
+            .unwrap()
+            .encode()
+    }
+
+    fn header(fields: [&str; 4]) -> Header {
+        Header {
+            fields: fields.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let text = "(param i32 i64)\n(result i32)\n(export \"a\\\"b\")\n(export \"f\")\n(local f32 f32)\nblock (result i32)\nloop\ni32.const 3\nlocal.get 0\nbr_if 1\ndrop\nlocal.get 1\ni64.eqz\nbr_table 0 0 0\nend\ni32.const 2\nend\ni32.const -5\ni32.store16 offset=4 align=1\nmemory.size\ncall $print_i32\nf32.const nan:0x7\nlocal.set 3\ni32.const 1\ni64.const 2\ncall 4\ndrop\ni32.const 9";
+        // (the memory is the one the assembler adds for the memory instructions)
+        let module = disassemble_wasm(&wasm(&[text])).unwrap();
+        let mut sections = Sections::default();
+        sections.push(Section::Memory, &["1", ""]);
+        assert_eq!(module.sections, sections);
+        let funcs = module.funcs;
+        assert_eq!(funcs.len(), 1);
+        assert_eq!(funcs[0].header, header(["", "i32 i64", "i32", "f32 f32"]));
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(funcs[0].lines[..2], lines[2..4]);
+        assert_eq!(funcs[0].lines[2..], lines[5..]);
+
+        // and the function assembles to the same module
+        let module =
+            assemble_module(&[funcs[0].module_function()], &ModuleSections::default()).unwrap();
+        assert_eq!(module.encode(), wasm(&[text]));
+    }
+
+    #[test]
+    fn sections() {
+        // (without a "name" section, functions other than the print functions come back
+        // as numbers)
+        let mut sections = Sections::default();
+        sections.push(Section::Imports, &["env", "log", "", "i32 f64", ""]);
+        sections.push(Section::Imports, &["env", "now", "", "", "i64"]);
+        sections.push(Section::Table, &["4", "8"]);
+        sections.push(Section::Memory, &["2", ""]);
+        sections.push(Section::Globals, &["", "mut i32", "-1"]);
+        sections.push(Section::Globals, &["", "f64", "1.5"]);
+        sections.push(Section::Exports, &["mem", "memory", "0"]);
+        sections.push(Section::Exports, &["count", "global", "0"]);
+        sections.push(Section::Exports, &["now", "func", "5"]);
+        sections.push(Section::Elements, &["0", "6 5 $print_i32 4"]);
+        sections.push(Section::Data, &["8", "\"hi\\00\""]);
+        let funcs = [ModuleFunction {
+            name: Some("run".to_string()),
+            lines: vec![parse_line("nop")],
+        }];
+        let (resolved, errors) = sections.resolve(&funcs);
+        assert_eq!(errors, []);
+        let bytes = assemble_module(&funcs, &resolved).unwrap().encode();
+        let module = disassemble_wasm(&bytes).unwrap();
+        assert_eq!(module.sections, sections);
+        assert_eq!(module.funcs.len(), 1);
+
+        // the import's name comes back as its $id, and in the segment
+        let bytes = with_names(bytes, &[(4, "log")], &[], &[]);
+        let module = disassemble_wasm(&bytes).unwrap();
+        assert_eq!(module.sections.entries(Section::Imports)[0][2], "$log");
+        assert_eq!(
+            module.sections.entries(Section::Elements)[0][1],
+            "6 5 $print_i32 $log"
+        );
+    }
+
+    // A name map from (index, name) pairs.
+    fn name_map(out: &mut Vec<u8>, names: &[(u32, &str)]) {
+        write_u32(out, names.len() as u32);
+        for (index, name) in names {
+            write_u32(out, *index);
+            write_u32(out, name.len() as u32);
+            out.extend_from_slice(name.as_bytes());
+        }
+    }
+
+    // Append a "name" section with function names, and local and label names for
+    // function 4.
+    fn with_names(
+        mut bytes: Vec<u8>,
+        funcs: &[(u32, &str)],
+        locals: &[(u32, &str)],
+        labels: &[(u32, &str)],
+    ) -> Vec<u8> {
+        let mut contents = Vec::new();
+        let mut subsections = vec![(1, Vec::new()), (2, vec![1, 4]), (3, vec![1, 4])];
+        name_map(&mut subsections[0].1, funcs);
+        name_map(&mut subsections[1].1, locals);
+        name_map(&mut subsections[2].1, labels);
+        for (id, subsection) in subsections {
+            contents.push(id);
+            write_u32(&mut contents, subsection.len() as u32);
+            contents.extend(subsection);
+        }
+        bytes.push(0x00);
+        write_u32(&mut bytes, contents.len() as u32 + 5);
+        bytes.extend_from_slice(b"\x04name");
+        bytes.extend(contents);
+        bytes
+    }
+
+    #[test]
+    fn names() {
+        let bytes = wasm(&[
+            "(param i32 i32)\n(local i32 i64)\nblock\nloop\nlocal.get 1\nlocal.set 2\nbr 1\nend\nblock\nbr 1\nend\nend\ni32.const 0\ni32.const 0\ncall 4\ncall 5",
+            "nop",
+        ]);
+        let bytes = with_names(
+            bytes,
+            &[(4, "self")],
+            &[
+                (0, "x"),
+                (2, "t"),
+                (1, "not a name"),
+                (3, "dup"),
+                (4, "dup"),
+            ],
+            &[(0, "outer"), (1, "top")],
+        );
+        let funcs = disassemble_wasm(&bytes).unwrap().funcs;
+        assert_eq!(
+            funcs[0].header,
+            header(["$self", "$x i32, i32", "", "$t i32, i64"])
+        );
+        assert_eq!(
+            funcs[0].lines,
+            vec![
+                "block $outer",
+                "loop $top",
+                "local.get 1",
+                "local.set $t",
+                "br $outer",
+                "end",
+                "block",
+                "br $outer",
+                "end",
+                "end",
+                "i32.const 0",
+                "i32.const 0",
+                "call $self",
+                "call 5",
+            ]
+        );
+        assert_eq!(funcs[1].header, Header::default());
+    }
+
+    #[test]
+    fn functions() {
+        assert_eq!(
+            disassemble_wasm(b"\0asm\x01\0\0\0")
+                .unwrap_err()
+                .to_string(),
+            "the module has no functions"
+        );
+        let funcs = disassemble_wasm(&wasm(&[
+            "nop",
+            "(param i64)\n(result i64)\n(export \"g\")\nlocal.get 0\ncall 4",
+        ]))
+        .unwrap()
+        .funcs;
+        assert_eq!(funcs.len(), 2);
+        assert_eq!(funcs[0].lines, vec!["nop"]);
+        assert_eq!(funcs[1].header, header(["", "i64", "i64", ""]));
+        assert_eq!(
+            funcs[1].lines,
+            vec!["(export \"g\")", "local.get 0", "call 4"]
+        );
+    }
+}
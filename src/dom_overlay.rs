@@ -0,0 +1,87 @@
+// A Codillon DOM "overlay": a DomStruct in a <div> that floats above the page, at a
+// position given in the viewport's coordinates (e.g. at the caret), and can be hidden.
+// The overlay's <div> has the class `overlay` (see the stylesheet), plus any class
+// given to `new`.
+
+use crate::{
+    dom_struct::{DomStruct, Structure},
+    web_support::{AccessToken, Component, ElementHandle, WithElement, WithNode},
+};
+use delegate::delegate;
+use web_sys::HtmlDivElement;
+
+pub struct DomOverlay<Child: Structure> {
+    inner: DomStruct<Child, HtmlDivElement>,
+    position: Option<(f64, f64)>, // as shown (None while hidden)
+}
+
+impl<Child: Structure> DomOverlay<Child> {
+    // Create the overlay, hidden.
+    pub fn new(contents: Child, elem: ElementHandle<HtmlDivElement>, class: &str) -> Self {
+        let mut inner = DomStruct::new(contents, elem);
+        inner.set_attribute("class", format!("overlay {class}").trim_end());
+        inner.set_attribute("hidden", "");
+        Self {
+            inner,
+            position: None,
+        }
+    }
+
+    // Show the overlay with its top left corner at (`left`, `top`).
+    pub fn show_at(&mut self, left: f64, top: f64) {
+        if self.position == Some((left, top)) {
+            return;
+        }
+        if self.position.is_none() {
+            self.inner.remove_attribute("hidden");
+        }
+        (self.inner).set_attribute("style", &format!("left: {left}px; top: {top}px"));
+        self.position = Some((left, top));
+    }
+
+    pub fn hide(&mut self) {
+        if self.position.take().is_some() {
+            self.inner.set_attribute("hidden", "");
+        }
+    }
+
+    pub fn position(&self) -> Option<(f64, f64)> {
+        self.position
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.position.is_some()
+    }
+
+    delegate! {
+        to self.inner {
+            pub fn get(&self) -> &Child;
+            pub fn get_mut(&mut self) -> &mut Child;
+            pub fn set_onmousedown<F: Fn(web_sys::MouseEvent) + 'static>(&mut self, handler: F);
+            pub fn scroll_top(&self) -> f64;
+            pub fn set_scroll_top(&mut self, top: f64);
+            pub fn client_height(&self) -> f64;
+        }
+    }
+}
+
+// To audit, audit the DomStruct (whose audit includes the `hidden` and `style`
+// attributes).
+impl<Child: Structure> Component for DomOverlay<Child> {
+    fn try_audit(&self) -> Result<(), String> {
+        self.inner.try_audit()
+    }
+}
+
+// Accessors for the overlay's element (only usable by the web_support module).
+impl<Child: Structure> WithNode for DomOverlay<Child> {
+    fn with_node(&self, f: impl FnMut(&web_sys::Node), g: AccessToken) {
+        self.inner.with_node(f, g);
+    }
+}
+
+impl<Child: Structure> WithElement<HtmlDivElement> for DomOverlay<Child> {
+    fn with_element(&self, f: impl FnMut(&HtmlDivElement), g: AccessToken) {
+        self.inner.with_element(f, g);
+    }
+}
@@ -2,10 +2,11 @@
 // of heterogeneous Components of (possibly) different types.
 
 use crate::web_support::{
-    AccessToken, AnyElement, ArrayHandle, Component, ElementHandle, NodeListHandle, WithElement,
-    WithNode,
+    AccessToken, AnyElement, ArrayHandle, Component, ElementHandle, FileHandle, NodeListHandle,
+    WithElement, WithNode, audit_eq,
 };
 use delegate::delegate;
+use std::ops::Range;
 
 pub struct DomStruct<Child: Structure, Element: AnyElement> {
     contents: Child,
@@ -18,7 +19,7 @@ pub struct DomStruct<Child: Structure, Element: AnyElement> {
 pub trait Structure {
     const LEN: usize;
     fn install(&self, nodes: &mut ArrayHandle, index: usize);
-    fn audit(&self, node_list: &NodeListHandle, index: usize);
+    fn audit(&self, node_list: &NodeListHandle, index: usize) -> Result<(), String>;
 }
 
 // Base case: an empty structure (aka the end of every structure).
@@ -27,8 +28,9 @@ impl Structure for () {
     fn install(&self, nodes: &mut ArrayHandle, index: usize) {
         assert_eq!(index, nodes.length());
     }
-    fn audit(&self, node_list: &NodeListHandle, index: usize) {
-        assert_eq!(index, node_list.length());
+    fn audit(&self, node_list: &NodeListHandle, index: usize) -> Result<(), String> {
+        audit_eq!(index, node_list.length());
+        Ok(())
     }
 }
 
@@ -40,11 +42,11 @@ impl<First: Component, Rest: Structure> Structure for (First, Rest) {
         nodes.set(index, &self.0);
         self.1.install(nodes, index + 1);
     }
-    fn audit(&self, node_list: &NodeListHandle, index: usize) {
-        assert_eq!(index + Self::LEN, node_list.length());
-        node_list.audit_node(index, &self.0);
-        self.0.audit();
-        self.1.audit(node_list, index + 1);
+    fn audit(&self, node_list: &NodeListHandle, index: usize) -> Result<(), String> {
+        audit_eq!(index + Self::LEN, node_list.length());
+        node_list.audit_node(index, &self.0)?;
+        self.0.try_audit()?;
+        self.1.audit(node_list, index + 1)
     }
 }
 
@@ -74,18 +76,48 @@ impl<Child: Structure, Element: AnyElement> DomStruct<Child, Element> {
     delegate! {
     to self.elem {
     pub fn set_attribute(&mut self, name: &str, value: &str);
+    pub fn remove_attribute(&mut self, name: &str);
         pub fn set_onbeforeinput<F: Fn(web_sys::InputEvent) + 'static>(&mut self, handler: F);
+        pub fn set_onclick<F: Fn(web_sys::MouseEvent) + 'static>(&mut self, handler: F);
+        pub fn set_onmousedown<F: Fn(web_sys::MouseEvent) + 'static>(&mut self, handler: F);
+        pub fn set_oninput<F: Fn(web_sys::Event) + 'static>(&mut self, handler: F);
+        pub fn set_onchange<F: Fn(web_sys::Event) + 'static>(&mut self, handler: F);
+        pub fn set_onscroll<F: Fn(web_sys::Event) + 'static>(&mut self, handler: F);
+        pub fn scroll_top(&self) -> f64;
+        pub fn set_scroll_top(&mut self, top: f64);
+        pub fn client_height(&self) -> f64;
     }
     }
 }
 
+impl<Child: Structure> DomStruct<Child, web_sys::HtmlInputElement> {
+    delegate! {
+        to self.elem {
+            pub fn value(&self) -> String;
+            pub fn set_value(&mut self, value: &str);
+            pub fn files(&self) -> Vec<FileHandle>;
+        }
+    }
+}
+
+impl<Child: Structure> DomStruct<Child, web_sys::HtmlTextAreaElement> {
+    delegate! {
+        to self.elem {
+            pub fn value(&self) -> String;
+            pub fn set_value(&mut self, value: &str);
+            pub fn selection_start(&self) -> usize;
+            pub fn select_range(&mut self, range: Range<usize>);
+        }
+    }
+}
+
 // To audit, audit the parent element itself, then audit the structure members.
 impl<Child: Structure, Element: AnyElement> Component for DomStruct<Child, Element> {
-    fn audit(&self) {
-        self.elem.audit();
+    fn try_audit(&self) -> Result<(), String> {
+        self.elem.try_audit()?;
         let dom_children = self.elem.get_child_node_list();
-        assert_eq!(dom_children.length(), Child::LEN);
-        self.contents.audit(&dom_children, 0);
+        audit_eq!(dom_children.length(), Child::LEN);
+        self.contents.audit(&dom_children, 0)
     }
 }
 
@@ -1,13 +1,20 @@
 // A Codillon Text Component. This represents a string;
-// the interface allows assignment, appending, and inserting into the
+// the interface allows assignment, appending, inserting into, and deleting from the
 // string, and enforces that the DOM contents will match the Rust contents.
+// The string is kept with an index (see text_index.rs), so conversions between
+// char, byte, UTF-16 and grapheme offsets don't rescan the whole string.
 
-use crate::web_support::{AccessToken, Component, TextHandle, WithNode};
+use crate::{
+    text_index::{IndexedText, Unit},
+    web_support::{AccessToken, Component, TextHandle, WithNode, audit_eq},
+};
 use anyhow::Result;
+use delegate::delegate;
+use std::ops::Range;
 
 #[derive(Default)]
 pub struct DomText {
-    contents: String,
+    contents: IndexedText,
     text_node: TextHandle,
 }
 
@@ -18,23 +25,60 @@ impl DomText {
         ret
     }
 
+    pub fn get(&self) -> &str {
+        self.contents.as_str()
+    }
+
     pub fn push_str(&mut self, string: &str) {
         self.contents.push_str(string);
         self.text_node.append_data(string);
     }
 
     pub fn set_data(&mut self, string: &str) {
-        self.contents = string.to_string();
+        self.contents.set(string);
         self.text_node.set_data(string);
     }
 
     pub fn insert_at_char(&mut self, char_idx: usize, string: &str) -> Result<()> {
-        let byte_idx = str_indices::chars::to_byte_idx(&self.contents, char_idx);
-        let utf16_idx = str_indices::utf16::from_byte_idx(&self.contents, byte_idx);
-        self.contents.insert_str(byte_idx, string);
+        let byte_idx = self.contents.char_to_byte(char_idx);
+        let utf16_idx = self.contents.char_to_utf16(char_idx);
+        self.contents.replace_bytes(byte_idx..byte_idx, string);
         self.text_node.insert_data(utf16_idx.try_into()?, string);
         Ok(())
     }
+
+    pub fn delete_chars(&mut self, char_range: Range<usize>) -> Result<()> {
+        let start_byte = self.contents.char_to_byte(char_range.start);
+        let end_byte = self.contents.char_to_byte(char_range.end);
+        let start_utf16 = self.contents.char_to_utf16(char_range.start);
+        let end_utf16 = self.contents.char_to_utf16(char_range.end);
+        self.contents.replace_bytes(start_byte..end_byte, "");
+        self.text_node.delete_data(
+            start_utf16.try_into()?,
+            (end_utf16 - start_utf16).try_into()?,
+        );
+        Ok(())
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.contents.len(Unit::Char)
+    }
+
+    // Offset conversions (e.g. a DOM Selection offset is in UTF-16 code units),
+    // and grapheme-cluster boundaries (in char offsets).
+    delegate! {
+        to self.contents {
+            pub fn char_to_byte(&self, char_idx: usize) -> usize;
+            pub fn byte_to_char(&self, byte_idx: usize) -> usize;
+            pub fn char_to_utf16(&self, char_idx: usize) -> usize;
+            pub fn utf16_to_char(&self, utf16_idx: usize) -> usize;
+            pub fn char_to_grapheme(&self, char_idx: usize) -> usize;
+            pub fn grapheme_to_char(&self, grapheme_idx: usize) -> usize;
+            pub fn is_grapheme_boundary(&self, char_idx: usize) -> bool;
+            pub fn prev_grapheme_boundary(&self, char_idx: usize) -> usize;
+            pub fn next_grapheme_boundary(&self, char_idx: usize) -> usize;
+        }
+    }
 }
 
 impl WithNode for DomText {
@@ -44,7 +88,8 @@ impl WithNode for DomText {
 }
 
 impl Component for DomText {
-    fn audit(&self) {
-        assert_eq!(self.contents, self.text_node.data());
+    fn try_audit(&self) -> Result<(), String> {
+        audit_eq!(self.contents.as_str(), self.text_node.data());
+        Ok(())
     }
 }
@@ -0,0 +1,104 @@
+// A Codillon tooltip: an overlay (see dom_overlay.rs) showing a few lines of text, the
+// first of them as a title (e.g. the reference of the instruction under the mouse).
+// A tooltip is a handle (clones refer to the same tooltip), so the component that
+// shows it and the component that puts it in the document can both hold it.
+
+use crate::{
+    dom_overlay::DomOverlay,
+    dom_struct::DomStruct,
+    dom_text::DomText,
+    dom_vec::DomVec,
+    web_support::{AccessToken, Component, ElementFactory, WithElement, WithNode, audit_eq},
+};
+use std::{cell::RefCell, rc::Rc};
+use web_sys::HtmlDivElement;
+
+type Line = DomStruct<(DomText, ()), HtmlDivElement>;
+type Lines = DomVec<Line, HtmlDivElement>;
+
+struct _DomTooltip {
+    factory: ElementFactory,
+    component: DomOverlay<(Lines, ())>,
+    lines: Vec<String>, // as shown (kept while hidden)
+}
+
+#[derive(Clone)]
+pub struct DomTooltip(Rc<RefCell<_DomTooltip>>);
+
+impl DomTooltip {
+    pub fn new(factory: &ElementFactory) -> Self {
+        DomTooltip(Rc::new(RefCell::new(_DomTooltip {
+            factory: factory.clone(),
+            component: DomOverlay::new((Lines::new(factory.div()), ()), factory.div(), "tooltip"),
+            lines: Vec::new(),
+        })))
+    }
+
+    // Show these lines, with the tooltip's top left corner at (`left`, `top`) in the
+    // viewport.
+    pub fn show(&mut self, lines: &[String], left: f64, top: f64) {
+        let mut inner = self.0.borrow_mut();
+        if lines != inner.lines {
+            let factory = inner.factory.clone();
+            let dom_lines = &mut inner.component.get_mut().0;
+            dom_lines.clear();
+            for (index, text) in lines.iter().enumerate() {
+                let mut div = Line::new((DomText::new(text), ()), factory.div());
+                if index == 0 {
+                    div.set_attribute("class", "title");
+                }
+                dom_lines.push(div);
+            }
+            inner.lines = lines.to_vec();
+        }
+        inner.component.show_at(left, top);
+    }
+
+    pub fn hide(&mut self) {
+        self.0.borrow_mut().component.hide();
+    }
+
+    pub fn is_shown(&self) -> bool {
+        self.0.borrow().component.is_shown()
+    }
+
+    // The lines shown (none while the tooltip is hidden).
+    pub fn lines(&self) -> Vec<String> {
+        let inner = self.0.borrow();
+        match inner.component.is_shown() {
+            true => inner.lines.clone(),
+            false => Vec::new(),
+        }
+    }
+
+    // Where the tooltip is shown (if it is).
+    pub fn position(&self) -> Option<(f64, f64)> {
+        self.0.borrow().component.position()
+    }
+}
+
+impl WithNode for DomTooltip {
+    fn with_node(&self, f: impl FnMut(&web_sys::Node), g: AccessToken) {
+        self.0.borrow().component.with_node(f, g);
+    }
+}
+
+impl WithElement<HtmlDivElement> for DomTooltip {
+    fn with_element(&self, f: impl FnMut(&HtmlDivElement), g: AccessToken) {
+        self.0.borrow().component.with_element(f, g);
+    }
+}
+
+// To audit, audit the overlay, then check that it shows the lines.
+impl Component for DomTooltip {
+    fn try_audit(&self) -> Result<(), String> {
+        let inner = self.0.borrow();
+        inner.component.try_audit()?;
+        let dom_lines = &inner.component.get().0;
+        audit_eq!(dom_lines.len(), inner.lines.len());
+        for (div, text) in dom_lines.iter().zip(&inner.lines) {
+            audit_eq!(div.get().0.get(), text);
+        }
+        Ok(())
+    }
+}
@@ -1,7 +1,7 @@
 // A Codillon DOM "vector": a variable-length collection of Components of the same type
 
 use crate::web_support::{
-    AccessToken, AnyElement, Component, ElementHandle, WithElement, WithNode,
+    AccessToken, AnyElement, ArrayHandle, Component, ElementHandle, WithElement, WithNode, audit_eq,
 };
 use delegate::delegate;
 
@@ -23,8 +23,22 @@ impl<Child: Component, Element: AnyElement> DomVec<Child, Element> {
         self.elem.append_node(self.contents.last().unwrap());
     }
 
+    pub fn insert(&mut self, index: usize, elem: Child) {
+        self.elem.insert_node(index, &elem);
+        self.contents.insert(index, elem);
+    }
+
+    // Remove a child, detaching its node from the DOM (the Child is returned intact).
     pub fn remove(&mut self, index: usize) -> Child {
-        self.contents.remove(index)
+        let ret = self.contents.remove(index);
+        self.elem.remove_node(&ret);
+        ret
+    }
+
+    // Remove every child (with one DOM operation).
+    pub fn clear(&mut self) {
+        self.elem.attach_nodes(ArrayHandle::new_with_length(0));
+        self.contents.clear();
     }
 
     pub fn set_contents(&mut self, elem: Child) {
@@ -36,9 +50,14 @@ impl<Child: Component, Element: AnyElement> DomVec<Child, Element> {
     to self.contents {
         pub fn get(&self, index: usize) -> Option<&Child>;
         pub fn get_mut(&mut self, index: usize) -> Option<&mut Child>;
+        pub fn len(&self) -> usize;
+        pub fn is_empty(&self) -> bool;
+        pub fn iter(&self) -> std::slice::Iter<'_, Child>;
+        pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Child>;
     }
     to self.elem {
         pub fn set_attribute(&mut self, name: &str, value: &str);
+        pub fn remove_attribute(&mut self, name: &str);
     pub fn set_onbeforeinput<F: Fn(web_sys::InputEvent) + 'static>(&mut self, handler: F);
     }
     }
@@ -48,14 +67,15 @@ impl<Child: Component, Element: AnyElement> DomVec<Child, Element> {
 // audit it, and also verify that the child's opinion of its node matches the
 // actual child node of the DomVec's parent element.
 impl<Child: Component, Element: AnyElement> Component for DomVec<Child, Element> {
-    fn audit(&self) {
-        self.elem.audit();
+    fn try_audit(&self) -> Result<(), String> {
+        self.elem.try_audit()?;
         let dom_children = self.elem.get_child_node_list();
-        assert_eq!(dom_children.length(), self.contents.len());
+        audit_eq!(dom_children.length(), self.contents.len());
         for (index, elem) in self.contents.iter().enumerate() {
-            elem.audit();
-            dom_children.audit_node(index, elem);
+            elem.try_audit()?;
+            dom_children.audit_node(index, elem)?;
         }
+        Ok(())
     }
 }
 
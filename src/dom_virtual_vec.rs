@@ -0,0 +1,328 @@
+// A Codillon virtualized DOM "vector": like a DomVec, a variable-length collection of
+// Components of the same type, but only a contiguous "window" of the children is
+// attached to the DOM. Spacer elements before and after the window stand in for the
+// detached children (each assumed to be `child_height` pixels tall), so the parent's
+// scroll height is the same as if every child were attached.
+//
+// A contiguous range of the children can also be hidden (e.g. a folded block of
+// lines): hidden children are never attached, even within the window, and take no
+// height in the spacers.
+
+use crate::web_support::{
+    AccessToken, AnyElement, ArrayHandle, Component, ElementFactory, ElementHandle, WithElement,
+    WithNode, audit_assert, audit_detached, audit_eq,
+};
+use delegate::delegate;
+use std::ops::Range;
+use web_sys::HtmlDivElement;
+
+pub struct DomVirtualVec<Child: Component, Element: AnyElement> {
+    contents: Vec<Child>,
+    window: Range<usize>,
+    hidden: Range<usize>,
+    child_height: f64,
+    top_spacer: Spacer,
+    bottom_spacer: Spacer,
+    elem: ElementHandle<Element>,
+}
+
+// A non-editable block element whose height stands in for some number of children.
+struct Spacer {
+    count: usize,
+    elem: ElementHandle<HtmlDivElement>,
+}
+
+impl Spacer {
+    fn new(factory: &ElementFactory) -> Self {
+        let mut elem = factory.div();
+        elem.set_attribute("class", "spacer");
+        elem.set_attribute("contenteditable", "false");
+        elem.set_attribute("style", "height: 0px");
+        Self { count: 0, elem }
+    }
+
+    fn set_count(&mut self, count: usize, child_height: f64) {
+        if count != self.count {
+            self.count = count;
+            self.elem.set_attribute(
+                "style",
+                &format!("height: {}px", count as f64 * child_height),
+            );
+        }
+    }
+}
+
+impl<Child: Component, Element: AnyElement> DomVirtualVec<Child, Element> {
+    pub fn new(elem: ElementHandle<Element>, factory: &ElementFactory, child_height: f64) -> Self {
+        let top_spacer = Spacer::new(factory);
+        let bottom_spacer = Spacer::new(factory);
+        let mut nodes = ArrayHandle::new_with_length(2);
+        nodes.set(0, &top_spacer.elem);
+        nodes.set(1, &bottom_spacer.elem);
+        elem.attach_nodes(nodes);
+        Self {
+            contents: Vec::new(),
+            window: 0..0,
+            hidden: 0..0,
+            child_height,
+            top_spacer,
+            bottom_spacer,
+            elem,
+        }
+    }
+
+    // The range of children currently attached to the DOM.
+    pub fn window(&self) -> Range<usize> {
+        self.window.clone()
+    }
+
+    pub fn is_attached(&self, index: usize) -> bool {
+        self.window.contains(&index) && !self.hidden.contains(&index)
+    }
+
+    // The range of hidden children (empty if none are).
+    pub fn hidden(&self) -> Range<usize> {
+        self.hidden.clone()
+    }
+
+    // How many of the children in `range` are not hidden.
+    fn visible_count(&self, range: Range<usize>) -> usize {
+        let hidden = range.start.max(self.hidden.start)..range.end.min(self.hidden.end);
+        range.len() - hidden.len()
+    }
+
+    // The child index of the `position`th child that is not hidden (counting from
+    // `start`).
+    fn visible_index(&self, start: usize, position: usize) -> usize {
+        let hidden = start.max(self.hidden.start)..self.hidden.end;
+        match start + position >= hidden.start && !hidden.is_empty() {
+            true => start + position + hidden.len(),
+            false => start + position,
+        }
+    }
+
+    // The DOM index (among the parent element's child nodes) of an attached child.
+    fn dom_index(&self, index: usize) -> usize {
+        1 + self.visible_count(self.window.start..index)
+    }
+
+    // The child index corresponding to a position between the parent's child nodes
+    // (e.g. a Selection offset within the parent element).
+    pub fn index_at_dom_offset(&self, offset: usize) -> usize {
+        (self.visible_index(self.window.start, offset.saturating_sub(1))).min(self.window.end)
+    }
+
+    fn update_spacers(&mut self) {
+        self.top_spacer
+            .set_count(self.visible_count(0..self.window.start), self.child_height);
+        self.bottom_spacer.set_count(
+            self.visible_count(self.window.end..self.contents.len()),
+            self.child_height,
+        );
+    }
+
+    pub fn push(&mut self, elem: Child) {
+        self.insert(self.contents.len(), elem);
+    }
+
+    // Insert a child; it is attached if it lands within (or at the edge of) the window,
+    // unless it lands inside the hidden range (which then grows to include it).
+    pub fn insert(&mut self, index: usize, elem: Child) {
+        if index <= self.hidden.start && !self.hidden.is_empty() {
+            self.hidden = self.hidden.start + 1..self.hidden.end + 1;
+        } else if index < self.hidden.end {
+            self.hidden.end += 1;
+        }
+        if index < self.window.start {
+            self.window = self.window.start + 1..self.window.end + 1;
+        } else if index <= self.window.end {
+            if !self.hidden.contains(&index) {
+                self.elem.insert_node(self.dom_index(index), &elem);
+            }
+            self.window.end += 1;
+        }
+        self.contents.insert(index, elem);
+        self.update_spacers();
+    }
+
+    // Remove a child, detaching its node from the DOM (the Child is returned intact).
+    pub fn remove(&mut self, index: usize) -> Child {
+        if self.is_attached(index) {
+            self.elem.remove_node(&self.contents[index]);
+        }
+        if index < self.window.start {
+            self.window = self.window.start - 1..self.window.end - 1;
+        } else if index < self.window.end {
+            self.window.end -= 1;
+        }
+        if index < self.hidden.start {
+            self.hidden = self.hidden.start - 1..self.hidden.end - 1;
+        } else if index < self.hidden.end {
+            self.hidden.end -= 1;
+        }
+        // (an empty hidden range is always 0..0, so that `insert` doesn't grow it)
+        if self.hidden.is_empty() {
+            self.hidden = 0..0;
+        }
+        let ret = self.contents.remove(index);
+        self.update_spacers();
+        ret
+    }
+
+    // Attach exactly the children in `window` (clamped to the length of the vector),
+    // detaching the others. Children that stay attached are not touched.
+    pub fn set_window(&mut self, window: Range<usize>) {
+        let end = window.end.min(self.contents.len());
+        let window = window.start.min(end)..end;
+
+        let kept = self.window.start.max(window.start)..self.window.end.min(window.end);
+        let kept = if kept.is_empty() {
+            window.start..window.start
+        } else {
+            kept
+        };
+        for index in self.window.clone() {
+            if !kept.contains(&index) && self.is_attached(index) {
+                self.elem.remove_node(&self.contents[index]);
+            }
+        }
+
+        // Attach the new children before the kept ones (from the nearest outward),
+        // then the ones after.
+        self.window = kept.clone();
+        for index in (window.start..kept.start).rev() {
+            if !self.hidden.contains(&index) {
+                self.elem.insert_node(1, &self.contents[index]);
+            }
+            self.window.start = index;
+        }
+        for index in kept.end..window.end {
+            if !self.hidden.contains(&index) {
+                self.elem
+                    .insert_node(self.dom_index(index), &self.contents[index]);
+            }
+            self.window.end = index + 1;
+        }
+        self.update_spacers();
+    }
+
+    // Hide exactly the children in `hidden` (clamped to the length of the vector),
+    // detaching them, and attach the previously hidden children within the window.
+    pub fn set_hidden(&mut self, hidden: Range<usize>) {
+        let end = hidden.end.min(self.contents.len());
+        let hidden = match hidden.start < end {
+            true => hidden.start..end,
+            false => 0..0,
+        };
+        for index in self.window.clone() {
+            if hidden.contains(&index) && self.is_attached(index) {
+                self.elem.remove_node(&self.contents[index]);
+            }
+        }
+        let shown = self.hidden.clone();
+        self.hidden = hidden;
+        // (in order, so the children before each one are already attached)
+        for index in self.window.clone() {
+            if shown.contains(&index) && self.is_attached(index) {
+                self.elem
+                    .insert_node(self.dom_index(index), &self.contents[index]);
+            }
+        }
+        self.update_spacers();
+    }
+
+    // Make sure a child is in the window, growing the window if it is adjacent, or else
+    // moving the window to surround it. (A hidden child stays detached.)
+    pub fn ensure_attached(&mut self, index: usize, margin: usize) {
+        if self.window.contains(&index) {
+            return;
+        }
+        if index + 1 >= self.window.start && index <= self.window.end {
+            self.set_window(self.window.start.min(index)..self.window.end.max(index + 1));
+        } else {
+            self.set_window(index.saturating_sub(margin)..index + margin + 1);
+        }
+    }
+
+    // Attach the children that are visible in the parent's scroll viewport, plus
+    // `overscan` children on either side. (Hidden children take no room in the viewport.)
+    pub fn fit_window_to_viewport(&mut self, overscan: usize) {
+        let top = self.elem.scroll_top();
+        let height = self.elem.client_height();
+        let first = (top / self.child_height).floor() as usize;
+        let last = ((top + height) / self.child_height).ceil() as usize;
+        let first = self.visible_index(0, first.saturating_sub(overscan));
+        let last = self.visible_index(0, last + overscan);
+        self.set_window(first..last);
+    }
+
+    delegate! {
+        to self.contents {
+            pub fn get(&self, index: usize) -> Option<&Child>;
+            pub fn get_mut(&mut self, index: usize) -> Option<&mut Child>;
+            pub fn len(&self) -> usize;
+            pub fn is_empty(&self) -> bool;
+            pub fn iter(&self) -> std::slice::Iter<'_, Child>;
+        }
+        to self.elem {
+            pub fn set_attribute(&mut self, name: &str, value: &str);
+            pub fn set_onbeforeinput<F: Fn(web_sys::InputEvent) + 'static>(&mut self, handler: F);
+            pub fn set_onscroll<F: Fn(web_sys::Event) + 'static>(&mut self, handler: F);
+            pub fn set_onkeydown<F: Fn(web_sys::KeyboardEvent) + 'static>(&mut self, handler: F);
+            pub fn set_ondragover<F: Fn(web_sys::DragEvent) + 'static>(&mut self, handler: F);
+            pub fn set_ondrop<F: Fn(web_sys::DragEvent) + 'static>(&mut self, handler: F);
+            pub fn set_onmouseover<F: Fn(web_sys::MouseEvent) + 'static>(&mut self, handler: F);
+            pub fn set_onmouseleave<F: Fn(web_sys::MouseEvent) + 'static>(&mut self, handler: F);
+        }
+    }
+}
+
+// To audit, audit the parent element and the spacers, then audit every child. Attached
+// children (those in the window and not hidden) must match the parent's child nodes
+// (between the spacers), and detached children must have no parent at all.
+impl<Child: Component, Element: AnyElement> Component for DomVirtualVec<Child, Element> {
+    fn try_audit(&self) -> Result<(), String> {
+        self.elem.try_audit()?;
+        self.top_spacer.elem.try_audit()?;
+        self.bottom_spacer.elem.try_audit()?;
+        audit_assert!(self.window.end <= self.contents.len());
+        audit_assert!(self.hidden.end <= self.contents.len());
+        audit_assert!(!self.hidden.is_empty() || self.hidden == (0..0));
+        audit_eq!(
+            self.top_spacer.count,
+            self.visible_count(0..self.window.start)
+        );
+        audit_eq!(
+            self.bottom_spacer.count,
+            self.visible_count(self.window.end..self.contents.len())
+        );
+
+        let attached = self.visible_count(self.window.clone());
+        let dom_children = self.elem.get_child_node_list();
+        audit_eq!(dom_children.length(), attached + 2);
+        dom_children.audit_node(0, &self.top_spacer.elem)?;
+        dom_children.audit_node(attached + 1, &self.bottom_spacer.elem)?;
+        for (index, elem) in self.contents.iter().enumerate() {
+            elem.try_audit()?;
+            if self.is_attached(index) {
+                dom_children.audit_node(self.dom_index(index), elem)?;
+            } else {
+                audit_detached(elem)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Accessors for the parent element (only usable by the web_support module).
+impl<Child: Component, Element: AnyElement> WithNode for DomVirtualVec<Child, Element> {
+    fn with_node(&self, f: impl FnMut(&web_sys::Node), g: AccessToken) {
+        self.elem.with_node(f, g);
+    }
+}
+
+impl<Child: Component, Element: AnyElement> WithElement<Element> for DomVirtualVec<Child, Element> {
+    fn with_element(&self, f: impl FnMut(&Element), g: AccessToken) {
+        self.elem.with_element(f, g);
+    }
+}
@@ -0,0 +1,694 @@
+// One line of the Codillon editor. The line's text is kept in Rust (with an offset
+// index), and is rendered as a sequence of <span> tokens, each classed by its kind
+// of WebAssembly token, followed by a <br>. When the text changes, the line is
+// re-tokenized and only the tokens that changed are updated in the DOM. The line is
+// also re-parsed, and the parsed instruction (or parse error) is kept with it. The
+// line's indentation (its block nesting depth) is presentation only: it is a style on
+// the line's <span>, not part of the text. Likewise, the operand stack after the line's
+// instruction is shown (by the stylesheet) from an attribute of the tokens' <span>.
+//
+// Diagnostics are drawn by two non-editable siblings of the tokens: a badge,
+// and an overlay that mirrors the line's text (transparently) in segments, so that the
+// segments covered by a diagnostic can be underlined. The overlay is empty when the
+// line has no diagnostics.
+//
+// Before the badge, the line's gutter shows whether it has a breakpoint (clicking the
+// gutter is handled by the editor, which knows the line by its id). The line the
+// debugger has stopped at is highlighted.
+//
+// A line that opens a block has a fold toggle in its gutter. When the editor folds the
+// lines after it (see dom_virtual_vec.rs), the line stands in for them, with a
+// non-editable placeholder after its text saying how many lines are folded.
+
+use crate::{
+    diagnostic::{Diagnostic, Severity, segments},
+    dom_struct::DomStruct,
+    dom_text::DomText,
+    dom_vec::DomVec,
+    instruction::Instruction,
+    nesting::LineNesting,
+    parse::{ParsedLine, parse_line},
+    text_index::{IndexedText, Unit},
+    token::{Token, TokenKind, tokenize},
+    validate::{LineCheck, format_stack},
+    web_support::{
+        AccessToken, Component, ElementFactory, SelectionHandle, SelectionPoint, WithNode,
+        audit_eq, is_event_target,
+    },
+};
+use anyhow::Result;
+use delegate::delegate;
+use std::{ops::Range, rc::Rc};
+use web_sys::{HtmlBrElement, HtmlSpanElement};
+
+type DomBr = DomStruct<(), HtmlBrElement>;
+type FoldToggle = DomStruct<(), HtmlSpanElement>;
+type Gutter = DomStruct<(FoldToggle, ()), HtmlSpanElement>;
+type Badge = DomStruct<(), HtmlSpanElement>;
+type TextSpan = DomStruct<(DomText, ()), HtmlSpanElement>;
+type TokenVec = DomVec<TextSpan, HtmlSpanElement>;
+type Overlay = DomVec<TextSpan, HtmlSpanElement>;
+type LineContents = (
+    Gutter,
+    (Badge, (TokenVec, (Overlay, (TextSpan, (DomBr, ()))))),
+);
+
+// Indentation per level of block nesting.
+const INDENT_CH: usize = 2;
+
+pub struct EditLine {
+    id: usize,
+    text: IndexedText,
+    tokens: Vec<Token>,
+    parsed: ParsedLine,
+    nesting: LineNesting,
+    check: LineCheck,
+    external: Vec<Diagnostic>,    // added through the editor's API
+    diagnostics: Vec<Diagnostic>, // as rendered
+    segments: Vec<(String, Option<Severity>)>, // the overlay's contents
+    breakpoint: bool,
+    current: bool,
+    foldable: bool,
+    folded: Option<usize>, // how many lines are folded after this one
+    component: DomStruct<LineContents, HtmlSpanElement>,
+}
+
+impl EditLine {
+    pub fn new(factory: &ElementFactory, id: usize, string: &str) -> Self {
+        let mut ret = Self {
+            id,
+            text: IndexedText::default(),
+            tokens: Vec::new(),
+            parsed: ParsedLine::Empty,
+            nesting: LineNesting::default(),
+            check: LineCheck::default(),
+            external: Vec::new(),
+            diagnostics: Vec::new(),
+            segments: Vec::new(),
+            breakpoint: false,
+            current: false,
+            foldable: false,
+            folded: None,
+            component: DomStruct::new(
+                (
+                    Gutter::new((FoldToggle::new((), factory.span()), ()), factory.span()),
+                    (
+                        Badge::new((), factory.span()),
+                        (
+                            TokenVec::new(factory.span()),
+                            (
+                                Overlay::new(factory.span()),
+                                (
+                                    TextSpan::new((DomText::new(""), ()), factory.span()),
+                                    (DomBr::new((), factory.br()), ()),
+                                ),
+                            ),
+                        ),
+                    ),
+                ),
+                factory.span(),
+            ),
+        };
+        let (gutter, (badge, (_, (overlay, (placeholder, _))))) = ret.component.get_mut();
+        gutter.set_attribute("class", "gutter");
+        gutter.set_attribute("contenteditable", "false");
+        let toggle = &mut gutter.get_mut().0;
+        toggle.set_attribute("class", "fold");
+        toggle.set_attribute("hidden", "");
+        placeholder.set_attribute("class", "placeholder");
+        placeholder.set_attribute("contenteditable", "false");
+        placeholder.set_attribute("hidden", "");
+        badge.set_attribute("class", "badge");
+        badge.set_attribute("contenteditable", "false");
+        overlay.set_attribute("class", "overlay");
+        overlay.set_attribute("contenteditable", "false");
+        overlay.set_attribute("aria-hidden", "true");
+        ret.set_text(factory, string);
+        ret
+    }
+
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn text(&self) -> &str {
+        self.text.as_str()
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.text.len(Unit::Char)
+    }
+
+    pub fn tokens(&self) -> &[Token] {
+        &self.tokens
+    }
+
+    pub fn parsed(&self) -> &ParsedLine {
+        &self.parsed
+    }
+
+    pub fn instruction(&self) -> Option<&Instruction> {
+        self.parsed.instruction()
+    }
+
+    pub fn nesting(&self) -> &LineNesting {
+        &self.nesting
+    }
+
+    pub fn check(&self) -> &LineCheck {
+        &self.check
+    }
+
+    // Indent the line to its nesting depth. (Only the style attribute is touched, and
+    // only if the depth changed.)
+    pub fn set_nesting(&mut self, nesting: LineNesting) {
+        if nesting.depth != self.nesting.depth {
+            match nesting.depth {
+                0 => self.component.remove_attribute("style"),
+                depth => self
+                    .component
+                    .set_attribute("style", &format!("padding-left: {}ch", depth * INDENT_CH)),
+            }
+        }
+        self.nesting = nesting;
+    }
+
+    // Show the operand stack after the line's instruction.
+    pub fn set_check(&mut self, check: LineCheck) {
+        if check.stack != self.check.stack {
+            match &check.stack {
+                Some(stack) => self
+                    .token_vec_mut()
+                    .set_attribute("data-stack", &format_stack(stack)),
+                None => self.token_vec_mut().remove_attribute("data-stack"),
+            }
+        }
+        self.check = check;
+    }
+
+    pub fn breakpoint(&self) -> bool {
+        self.breakpoint
+    }
+
+    pub fn set_breakpoint(&mut self, breakpoint: bool) {
+        if breakpoint != self.breakpoint {
+            let class = if breakpoint {
+                "gutter breakpoint"
+            } else {
+                "gutter"
+            };
+            self.component.get_mut().0.set_attribute("class", class);
+            self.breakpoint = breakpoint;
+        }
+    }
+
+    pub fn set_ongutterclick<F: Fn(web_sys::MouseEvent) + 'static>(&mut self, handler: F) {
+        self.component.get_mut().0.set_onclick(handler);
+    }
+
+    pub fn is_foldable(&self) -> bool {
+        self.foldable
+    }
+
+    // Show the fold toggle (or not).
+    pub fn set_foldable(&mut self, foldable: bool) {
+        if foldable != self.foldable {
+            let toggle = &mut self.component.get_mut().0.get_mut().0;
+            match foldable {
+                true => toggle.remove_attribute("hidden"),
+                false => toggle.set_attribute("hidden", ""),
+            }
+            self.foldable = foldable;
+        }
+    }
+
+    pub fn folded(&self) -> Option<usize> {
+        self.folded
+    }
+
+    // Stand in for `count` folded lines after this one (or for none).
+    pub fn set_folded(&mut self, folded: Option<usize>) {
+        if folded == self.folded {
+            return;
+        }
+        let toggle = &mut self.component.get_mut().0.get_mut().0;
+        match folded {
+            Some(_) => toggle.set_attribute("class", "fold folded"),
+            None => toggle.set_attribute("class", "fold"),
+        }
+        let placeholder = self.placeholder_mut();
+        match folded {
+            Some(count) => {
+                placeholder.get_mut().0.set_data(&placeholder_text(count));
+                placeholder.remove_attribute("hidden");
+            }
+            None => placeholder.set_attribute("hidden", ""),
+        }
+        self.folded = folded;
+    }
+
+    // Clicking the fold toggle (or the placeholder) is handled by the editor, and isn't
+    // a click on the gutter.
+    pub fn set_onfoldclick<F: Fn(web_sys::MouseEvent) + 'static>(&mut self, handler: F) {
+        let handler = Rc::new(handler);
+        let on_toggle = handler.clone();
+        let toggle = &mut self.component.get_mut().0.get_mut().0;
+        toggle.set_onclick(move |ev| {
+            ev.stop_propagation();
+            on_toggle(ev)
+        });
+        self.placeholder_mut().set_onclick(move |ev| handler(ev));
+    }
+
+    pub fn is_current(&self) -> bool {
+        self.current
+    }
+
+    // Highlight the line (or not) as where execution has stopped.
+    pub fn set_current(&mut self, current: bool) {
+        if current != self.current {
+            match current {
+                true => self.component.set_attribute("class", "current"),
+                false => self.component.remove_attribute("class"),
+            }
+            self.current = current;
+        }
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    // Diagnostics from outside the line's own analysis (they are shown on the next
+    // refresh_diagnostics).
+    pub fn add_diagnostic(&mut self, diagnostic: Diagnostic) {
+        self.external.push(diagnostic);
+    }
+
+    pub fn clear_diagnostics(&mut self) {
+        self.external.clear();
+    }
+
+    // Remove the external diagnostics on the text from char `ch` on (which is moving to
+    // another line), returning them with their ranges counted from `ch`.
+    pub fn take_diagnostics_from(&mut self, ch: usize) -> Vec<Diagnostic> {
+        let mut ret: Vec<Diagnostic> = (self.external)
+            .extract_if(.., |diagnostic| diagnostic.range.start >= ch)
+            .collect();
+        for diagnostic in &mut ret {
+            diagnostic.range = diagnostic.range.start - ch..diagnostic.range.end - ch;
+        }
+        ret
+    }
+
+    // The chars from the first to the last token that isn't space or a comment.
+    fn content_range(&self) -> Range<usize> {
+        let mut content = self
+            .tokens
+            .iter()
+            .filter(|token| !matches!(token.kind, TokenKind::Space | TokenKind::Comment));
+        match (content.next(), content.next_back()) {
+            (Some(first), last) => {
+                let last = last.unwrap_or(first);
+                self.text.byte_to_char(first.range.start)..self.text.byte_to_char(last.range.end)
+            }
+            (None, _) => 0..0,
+        }
+    }
+
+    // The line's diagnostics: from its parse, nesting and type check, then any
+    // external ones.
+    pub fn wanted_diagnostics(&self) -> Vec<Diagnostic> {
+        let mut ret = Vec::new();
+        if let Some(error) = self.parsed.error() {
+            let start = self.text.byte_to_char(error.span.start);
+            let end = self.text.byte_to_char(error.span.end);
+            // an error at the end of the line underlines the last character
+            let range = match start == end {
+                true if start > 0 => self.text.prev_grapheme_boundary(start)..end,
+                true => start..self.text.next_grapheme_boundary(start),
+                false => start..end,
+            };
+            ret.push(Diagnostic::new(Severity::Error, range, &error.message));
+        }
+        let content = self.content_range();
+        let nesting_error = self.nesting.error.as_ref();
+        if let Some(error) = nesting_error {
+            ret.push(Diagnostic::new(Severity::Error, content.clone(), error));
+        }
+        // (the validator also reports a misplaced `else` or `end`)
+        if let Some(error) = self
+            .check
+            .error
+            .as_ref()
+            .filter(|&error| Some(error) != nesting_error)
+        {
+            ret.push(Diagnostic::new(Severity::Error, content.clone(), error));
+        }
+        if let Some(warning) = &self.check.warning {
+            ret.push(Diagnostic::new(Severity::Warning, content, warning));
+        }
+        ret.extend(self.external.iter().cloned());
+        ret
+    }
+
+    // The overlay's segments for the current text and diagnostics.
+    fn wanted_segments(&self) -> Vec<(String, Option<Severity>)> {
+        if self.diagnostics.is_empty() {
+            return Vec::new();
+        }
+        segments(self.len_chars(), &self.diagnostics)
+            .into_iter()
+            .map(|(range, severity)| {
+                let bytes = self.text.char_to_byte(range.start)..self.text.char_to_byte(range.end);
+                (self.text.as_str()[bytes].to_string(), severity)
+            })
+            .collect()
+    }
+
+    // Bring the badge and overlay up to date with the line's diagnostics, touching
+    // the DOM only if something changed.
+    pub fn refresh_diagnostics(&mut self, factory: &ElementFactory) {
+        let diagnostics = self.wanted_diagnostics();
+        let diagnostics_changed = diagnostics != self.diagnostics;
+        self.diagnostics = diagnostics;
+
+        if diagnostics_changed {
+            let worst = self
+                .diagnostics
+                .iter()
+                .map(|diagnostic| diagnostic.severity)
+                .max();
+            let messages: Vec<String> = self
+                .diagnostics
+                .iter()
+                .map(|diagnostic| {
+                    format!("{}: {}", diagnostic.severity.label(), diagnostic.message)
+                })
+                .collect();
+            let badge = &mut self.component.get_mut().1.0;
+            match worst {
+                Some(severity) => {
+                    badge.set_attribute("class", &format!("badge {}", severity.class()));
+                    badge.set_attribute("title", &messages.join("\n"));
+                }
+                None => {
+                    badge.set_attribute("class", "badge");
+                    badge.remove_attribute("title");
+                }
+            }
+        }
+
+        let segments = self.wanted_segments();
+        if segments != self.segments {
+            let overlay = self.overlay_mut();
+            while !overlay.is_empty() {
+                overlay.remove(0);
+            }
+            for (text, severity) in &segments {
+                let mut span = TextSpan::new((DomText::new(text), ()), factory.span());
+                if let Some(severity) = severity {
+                    span.set_attribute("class", severity.class());
+                }
+                overlay.push(span);
+            }
+            self.segments = segments;
+        }
+    }
+
+    delegate! {
+        to self.text {
+            pub fn char_to_byte(&self, char_idx: usize) -> usize;
+            pub fn byte_to_char(&self, byte_idx: usize) -> usize;
+            pub fn prev_grapheme_boundary(&self, char_idx: usize) -> usize;
+            pub fn next_grapheme_boundary(&self, char_idx: usize) -> usize;
+        }
+    }
+
+    // External diagnostics survive only if their text is in the part of the line
+    // that didn't change.
+    pub fn set_text(&mut self, factory: &ElementFactory, string: &str) {
+        let kept = (self.text.as_str().chars())
+            .zip(string.chars())
+            .take_while(|(a, b)| a == b)
+            .count();
+        self.external
+            .retain(|diagnostic| diagnostic.range.end <= kept);
+        self.text.set(string);
+        self.retokenize(factory);
+    }
+
+    pub fn insert_at_char(
+        &mut self,
+        factory: &ElementFactory,
+        char_idx: usize,
+        string: &str,
+    ) -> Result<()> {
+        let byte_idx = self.text.char_to_byte(char_idx);
+        self.text.replace_bytes(byte_idx..byte_idx, string);
+        let inserted = string.chars().count();
+        self.external
+            .retain_mut(|diagnostic| diagnostic.adjust(char_idx..char_idx, inserted));
+        self.retokenize(factory);
+        Ok(())
+    }
+
+    pub fn delete_chars(
+        &mut self,
+        factory: &ElementFactory,
+        char_range: Range<usize>,
+    ) -> Result<()> {
+        let bytes =
+            self.text.char_to_byte(char_range.start)..self.text.char_to_byte(char_range.end);
+        self.text.replace_bytes(bytes, "");
+        self.external
+            .retain_mut(|diagnostic| diagnostic.adjust(char_range.clone(), 0));
+        self.retokenize(factory);
+        Ok(())
+    }
+
+    fn token_vec(&self) -> &TokenVec {
+        &self.component.get().1.1.0
+    }
+
+    fn token_vec_mut(&mut self) -> &mut TokenVec {
+        &mut self.component.get_mut().1.1.0
+    }
+
+    fn overlay(&self) -> &Overlay {
+        &self.component.get().1.1.1.0
+    }
+
+    fn overlay_mut(&mut self) -> &mut Overlay {
+        &mut self.component.get_mut().1.1.1.0
+    }
+
+    fn placeholder(&self) -> &TextSpan {
+        &self.component.get().1.1.1.1.0
+    }
+
+    fn placeholder_mut(&mut self) -> &mut TextSpan {
+        &mut self.component.get_mut().1.1.1.1.0
+    }
+
+    fn token_span(factory: &ElementFactory, token: &Token, text: &str) -> TextSpan {
+        let mut span = TextSpan::new((DomText::new(text), ()), factory.span());
+        span.set_attribute("class", token.kind.class());
+        span
+    }
+
+    // Bring the token spans up to date with the text. Tokens that are unchanged at the
+    // start and end of the line are left alone. If the same number of tokens changed,
+    // each is edited in place (so typing within a token is a single DOM text edit);
+    // otherwise the changed spans are replaced.
+    fn retokenize(&mut self, factory: &ElementFactory) {
+        let new_tokens = tokenize(self.text.as_str());
+        let old_tokens = std::mem::take(&mut self.tokens);
+        let text = self.text.as_str();
+        let same = |old: &Token, old_index: usize, new: &Token| {
+            old.kind == new.kind
+                && self.token_vec().get(old_index).unwrap().get().0.get()
+                    == &text[new.range.clone()]
+        };
+
+        let prefix = old_tokens
+            .iter()
+            .zip(&new_tokens)
+            .enumerate()
+            .take_while(|(index, (old, new))| same(old, *index, new))
+            .count();
+        let suffix = old_tokens[prefix..]
+            .iter()
+            .rev()
+            .zip(new_tokens[prefix..].iter().rev())
+            .enumerate()
+            .take_while(|(index, (old, new))| same(old, old_tokens.len() - 1 - index, new))
+            .count();
+        let old_changed = prefix..old_tokens.len() - suffix;
+        let new_changed = prefix..new_tokens.len() - suffix;
+
+        if old_changed.len() == new_changed.len() {
+            for (index, new) in new_changed.zip(&new_tokens[prefix..]) {
+                let new_text = self.text.as_str()[new.range.clone()].to_string();
+                let kind_changed = old_tokens[index].kind != new.kind;
+                let span = self.token_vec_mut().get_mut(index).unwrap();
+                if kind_changed {
+                    span.set_attribute("class", new.kind.class());
+                }
+                edit_in_place(&mut span.get_mut().0, &new_text);
+            }
+        } else {
+            for _ in old_changed {
+                self.token_vec_mut().remove(prefix);
+            }
+            for (offset, new) in new_tokens[new_changed].iter().enumerate() {
+                let span = Self::token_span(factory, new, &self.text.as_str()[new.range.clone()]);
+                self.token_vec_mut().insert(prefix + offset, span);
+            }
+        }
+        self.tokens = new_tokens;
+        self.parsed = parse_line(self.text.as_str());
+    }
+
+    // The token containing a char offset, and the char offset where that token starts.
+    // (An offset on a boundary belongs to the token before it.)
+    fn token_at(&self, char_idx: usize) -> Option<(usize, usize)> {
+        let byte_idx = self.text.char_to_byte(char_idx);
+        let index = self
+            .tokens
+            .partition_point(|token| token.range.end < byte_idx)
+            .min(self.tokens.len().checked_sub(1)?);
+        Some((
+            index,
+            self.text.byte_to_char(self.tokens[index].range.start),
+        ))
+    }
+
+    // Place a collapsed caret at a char offset in this line.
+    pub fn set_caret(&self, selection: &SelectionHandle, char_idx: usize) {
+        match self.token_at(char_idx) {
+            Some((index, start)) => {
+                let text = &self.token_vec().get(index).unwrap().get().0;
+                selection.set_caret(text, text.char_to_utf16(char_idx - start));
+            }
+            None => selection.set_caret(self.token_vec(), 0),
+        }
+    }
+
+    // If an event happened on one of the line's tokens (e.g. the mouse moved over it),
+    // the char offset where that token starts.
+    pub fn token_at_event(&self, ev: &web_sys::Event) -> Option<usize> {
+        let index = (self.token_vec().iter()).position(|span| is_event_target(ev, span))?;
+        Some(self.text.byte_to_char(self.tokens[index].range.start))
+    }
+
+    // Find the char offset of a selection point, if it lies within this line: in a
+    // token's text, in a token's <span>, in the tokens' <span>, or in the line's <span>
+    // (before or after the tokens).
+    pub fn locate(&self, point: &SelectionPoint) -> Option<usize> {
+        let token_start = |index: usize| {
+            self.tokens.get(index).map_or(self.len_chars(), |token| {
+                self.text.byte_to_char(token.range.start)
+            })
+        };
+        for (index, span) in self.token_vec().iter().enumerate() {
+            let text = &span.get().0;
+            if let Some(offset) = point.offset_in(text) {
+                return Some(token_start(index) + text.utf16_to_char(offset));
+            }
+            if let Some(offset) = point.offset_in(span) {
+                return Some(if offset == 0 {
+                    token_start(index)
+                } else {
+                    token_start(index + 1)
+                });
+            }
+        }
+        if let Some(offset) = point.offset_in(self.token_vec()) {
+            return Some(token_start(offset));
+        }
+        point
+            .offset_in(&self.component)
+            .map(|offset| if offset <= 2 { 0 } else { self.len_chars() })
+    }
+}
+
+fn placeholder_text(count: usize) -> String {
+    match count {
+        1 => "\u{22ef} 1 line".to_string(),
+        count => format!("\u{22ef} {count} lines"),
+    }
+}
+
+// Change a DomText's contents to `new`, deleting and inserting only the part between
+// their common prefix and common suffix.
+fn edit_in_place(text: &mut DomText, new: &str) {
+    let old = text.get();
+    if old == new {
+        return;
+    }
+    let prefix = old
+        .chars()
+        .zip(new.chars())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let old_len = old.chars().count();
+    let new_len = new.chars().count();
+    let suffix = old
+        .chars()
+        .rev()
+        .zip(new.chars().rev())
+        .take(old_len.min(new_len) - prefix)
+        .take_while(|(a, b)| a == b)
+        .count();
+    let inserted: String = new
+        .chars()
+        .skip(prefix)
+        .take(new_len - prefix - suffix)
+        .collect();
+    if old_len - suffix > prefix {
+        text.delete_chars(prefix..old_len - suffix).expect("delete");
+    }
+    if !inserted.is_empty() {
+        text.insert_at_char(prefix, &inserted).expect("insert");
+    }
+}
+
+impl WithNode for EditLine {
+    fn with_node(&self, f: impl FnMut(&web_sys::Node), g: AccessToken) {
+        self.component.with_node(f, g);
+    }
+}
+
+// To audit, audit the DOM structure, then check that the tokens and parse are up to
+// date, that the concatenated token text equals the line's text, that the overlay
+// mirrors the text in the segments of the rendered diagnostics, and that the placeholder
+// is shown only while lines are folded.
+impl Component for EditLine {
+    fn try_audit(&self) -> Result<(), String> {
+        self.component.try_audit()?;
+        audit_eq!(self.tokens, tokenize(self.text.as_str()));
+        audit_eq!(self.parsed, parse_line(self.text.as_str()));
+        let spans = self.token_vec();
+        audit_eq!(spans.len(), self.tokens.len());
+        let mut concatenated = String::new();
+        for (span, token) in spans.iter().zip(&self.tokens) {
+            let text = span.get().0.get();
+            audit_eq!(text, &self.text.as_str()[token.range.clone()]);
+            concatenated.push_str(text);
+        }
+        audit_eq!(concatenated, self.text.as_str());
+
+        audit_eq!(self.segments, self.wanted_segments());
+        let overlay = self.overlay();
+        audit_eq!(overlay.len(), self.segments.len());
+        for (span, (text, _)) in overlay.iter().zip(&self.segments) {
+            audit_eq!(span.get().0.get(), text);
+        }
+
+        if let Some(count) = self.folded {
+            audit_eq!(self.placeholder().get().0.get(), placeholder_text(count));
+        }
+        Ok(())
+    }
+}
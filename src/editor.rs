@@ -1,68 +1,1242 @@
-// The Codillon code editor (doesn't do much, but does capture beforeinput and logs to console)
+// The Codillon code editor: a vector of EditLines (see edit_line.rs). Edits (from
+// beforeinput events or the public API) are applied to the Rust model and the DOM
+// together, and are recorded so they can be undone. Only the lines in (or near) the
+// scroll viewport are attached to the DOM. After every edit, the lines are analyzed as
+// a function body (see nesting.rs and validate.rs), and their problems are shown as
+// diagnostics on them (see diagnostic.rs).
 
 use crate::{
-    dom_struct::DomStruct,
-    dom_text::DomText,
-    dom_vec::DomVec,
-    web_support::{AccessToken, Component, ElementFactory, WithElement, WithNode},
+    assemble::{AssembleError, assemble},
+    completion::{Completions, Scope, complete},
+    completion_popup::CompletionPopup,
+    diagnostic::Diagnostic,
+    dom_tooltip::DomTooltip,
+    dom_virtual_vec::DomVirtualVec,
+    edit_line::EditLine,
+    header::FunctionText,
+    instruction::{ImmKind, InstrInfo, Instruction, lookup},
+    module::Module,
+    nesting::{IncrementalNesting, LineNesting, block_ends, nesting, open_blocks},
+    parse::ParsedLine,
+    sections::ModuleText,
+    token::TokenKind,
+    validate::{
+        Context, Func, Global, IncrementalCheck, LineCheck, function_context,
+        module_function_context, validate_function, validate_module_function,
+    },
+    wat::module_text,
+    web_support::{
+        AccessToken, Component, ElementFactory, FileHandle, SelectionHandle, SelectionPoint,
+        WithElement, WithNode, audit_eq, dom_op_scope, drags_files, dropped_files,
+    },
 };
-use std::{cell::RefCell, collections::HashMap, rc::Rc};
-use web_sys::{HtmlBrElement, HtmlDivElement, HtmlSpanElement, InputEvent};
+use anyhow::{Result, bail};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    ops::Range,
+    rc::{Rc, Weak},
+};
+use web_sys::{HtmlDivElement, InputEvent, KeyboardEvent, MouseEvent};
+
+// Every line is this tall (see `line-height` in the stylesheet).
+const LINE_HEIGHT_PX: f64 = 32.0;
+// How many lines to keep attached above and below the viewport.
+const OVERSCAN_LINES: usize = 40;
+// How far below the mouse pointer a tooltip goes.
+const TOOLTIP_OFFSET_PX: f64 = 20.0;
+
+// A location in the editor: a line index and a char index within that line.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Position {
+    pub line: usize,
+    pub ch: usize,
+}
+
+impl Position {
+    pub fn new(line: usize, ch: usize) -> Self {
+        Self { line, ch }
+    }
+
+    // The position just after `text`, if `text` were inserted here.
+    fn after(self, text: &str) -> Self {
+        match text.rsplit_once('\n') {
+            None => Self::new(self.line, self.ch + text.chars().count()),
+            Some((_, tail)) => {
+                Self::new(self.line + text.matches('\n').count(), tail.chars().count())
+            }
+        }
+    }
+}
 
-type DomBr = DomStruct<(), HtmlBrElement>;
-type LineContents = (DomText, (DomBr, ()));
-type EditLine = DomStruct<LineContents, HtmlSpanElement>;
+// What a function in a module knows about the rest of the module: the declaration
+// lines of its header (which come before the editor's lines), and the module's
+// functions (which it can call) and globals.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ModuleContext {
+    pub header: Vec<ParsedLine>,
+    pub funcs: Vec<Func>,
+    pub globals: Vec<Global>,
+}
+
+// A record of one edit: `inserted` replaced `removed` at `start`.
+struct Edit {
+    start: Position,
+    removed: String,
+    inserted: String,
+}
 
 struct _Editor {
-    _next_id: usize,
+    this: Weak<RefCell<_Editor>>, // for the lines' gutter click handlers
+    next_id: usize,
     _id_map: HashMap<usize, usize>,
-    component: DomVec<EditLine, HtmlDivElement>,
+    factory: ElementFactory,
+    component: DomVirtualVec<EditLine, HtmlDivElement>,
+    nesting: IncrementalNesting,
+    checks: IncrementalCheck,
+    undo_stack: Vec<Edit>,
+    module: Option<ModuleContext>,
+    onchange: Option<Rc<dyn Fn()>>,
+    popup: CompletionPopup,
+    completing: Option<(usize, Range<usize>)>, // the line and chars the popup would replace
+    tooltip: DomTooltip,
 }
 
+// A handle to the editor (clones refer to the same editor).
+#[derive(Clone)]
 pub struct Editor(Rc<RefCell<_Editor>>);
 
 impl Editor {
     pub fn new(factory: &ElementFactory) -> Self {
-        let mut inner = _Editor {
-            _next_id: 0,
-            _id_map: HashMap::default(),
-            component: DomVec::new(factory.div()),
+        Self::with_text(factory, "Hello, world.")
+    }
+
+    pub fn with_text(factory: &ElementFactory, text: &str) -> Self {
+        let ret = Editor(Rc::new_cyclic(|this| {
+            RefCell::new(_Editor {
+                this: this.clone(),
+                next_id: 0,
+                _id_map: HashMap::default(),
+                factory: factory.clone(),
+                component: DomVirtualVec::new(factory.div(), factory, LINE_HEIGHT_PX),
+                nesting: IncrementalNesting::default(),
+                checks: IncrementalCheck::default(),
+                undo_stack: Vec::new(),
+                module: None,
+                onchange: None,
+                popup: CompletionPopup::new(factory),
+                completing: None,
+                tooltip: DomTooltip::new(factory),
+            })
+        }));
+
+        {
+            let component = &mut ret.0.borrow_mut().component;
+            component.set_attribute("class", "textentry");
+            component.set_attribute("contenteditable", "true");
+            component.set_attribute("spellcheck", "false");
+        }
+
+        let editor_ref = Rc::downgrade(&ret.0);
+        ret.0.borrow_mut().component.set_onbeforeinput(move |ev| {
+            if let Some(editor_ref) = editor_ref.upgrade() {
+                Editor(editor_ref).handle_input(ev);
+            }
+        });
+
+        let editor_ref = Rc::downgrade(&ret.0);
+        ret.0.borrow_mut().component.set_onkeydown(move |ev| {
+            if let Some(editor_ref) = editor_ref.upgrade() {
+                Editor(editor_ref).handle_keydown(ev);
+            }
+        });
+
+        let editor_ref = Rc::downgrade(&ret.0);
+        ret.0.borrow_mut().component.set_onscroll(move |_| {
+            if let Some(editor_ref) = editor_ref.upgrade() {
+                let mut inner = editor_ref.borrow_mut();
+                inner.component.fit_window_to_viewport(OVERSCAN_LINES);
+                inner.hide_completions();
+                inner.tooltip.hide();
+            }
+        });
+
+        let editor_ref = Rc::downgrade(&ret.0);
+        ret.0.borrow_mut().component.set_onmouseover(move |ev| {
+            if let Some(editor_ref) = editor_ref.upgrade() {
+                Editor(editor_ref).handle_mouseover(ev);
+            }
+        });
+
+        let editor_ref = Rc::downgrade(&ret.0);
+        ret.0.borrow_mut().component.set_onmouseleave(move |_| {
+            if let Some(editor_ref) = editor_ref.upgrade() {
+                let mut inner = editor_ref.borrow_mut();
+                let _scope = dom_op_scope("tooltip");
+                inner.tooltip.hide();
+            }
+        });
+
+        let editor_ref = Rc::downgrade(&ret.0);
+        ret.0.borrow_mut().popup.set_onaccept(move || {
+            if let Some(editor_ref) = editor_ref.upgrade() {
+                let result = Editor(editor_ref).accept_completion();
+                if let Err(e) = result {
+                    web_sys::console::log_1(&format!("edit failed: {e}").into());
+                }
+            }
+        });
+
+        {
+            let mut inner = ret.0.borrow_mut();
+            for string in text.split('\n') {
+                let line = inner.new_line(string);
+                inner.component.push(line);
+            }
+            inner.component.fit_window_to_viewport(OVERSCAN_LINES);
+            let count = inner.component.len();
+            inner.update_analysis(0..0, count);
+        }
+
+        ret
+    }
+
+    // Analyze the lines as the body of a function in a module (or, with None, as a
+    // function on its own): after the declarations of the function's header, and able
+    // to call the module's other functions.
+    pub fn set_module_context(&mut self, module: Option<ModuleContext>) {
+        let mut inner = self.0.borrow_mut();
+        if inner.module != module {
+            inner.module = module;
+            let _scope = dom_op_scope("editor");
+            inner.update_analysis(0..0, 0);
+        }
+    }
+
+    pub fn module_context(&self) -> Option<ModuleContext> {
+        self.0.borrow().module.clone()
+    }
+
+    // Call `handler` after every edit (when the editor is no longer borrowed).
+    pub fn set_onchange(&mut self, handler: impl Fn() + 'static) {
+        self.0.borrow_mut().onchange = Some(Rc::new(handler));
+    }
+
+    fn changed(&self) {
+        let onchange = self.0.borrow().onchange.clone();
+        if let Some(onchange) = onchange {
+            onchange();
+        }
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let inner = self.0.borrow();
+        (0..inner.component.len())
+            .map(|index| inner.line_text(index).to_string())
+            .collect()
+    }
+
+    // The parse of each line (in line order).
+    pub fn parsed_lines(&self) -> Vec<ParsedLine> {
+        let inner = self.0.borrow();
+        inner
+            .component
+            .iter()
+            .map(|line| line.parsed().clone())
+            .collect()
+    }
+
+    // The instructions of the lines that parse as one (skipping blank lines and errors).
+    pub fn instructions(&self) -> Vec<Instruction> {
+        let inner = self.0.borrow();
+        inner
+            .component
+            .iter()
+            .filter_map(|line| line.instruction().cloned())
+            .collect()
+    }
+
+    // The block nesting of each line.
+    pub fn nesting(&self) -> Vec<LineNesting> {
+        let inner = self.0.borrow();
+        inner
+            .component
+            .iter()
+            .map(|line| line.nesting().clone())
+            .collect()
+    }
+
+    // The type check of each line.
+    pub fn checks(&self) -> Vec<LineCheck> {
+        let inner = self.0.borrow();
+        inner
+            .component
+            .iter()
+            .map(|line| line.check().clone())
+            .collect()
+    }
+
+    // Every line's diagnostics, with their line index.
+    pub fn diagnostics(&self) -> Vec<(usize, Diagnostic)> {
+        let inner = self.0.borrow();
+        (inner.component.iter().enumerate())
+            .flat_map(|(index, line)| {
+                (line.diagnostics().iter()).map(move |diagnostic| (index, diagnostic.clone()))
+            })
+            .collect()
+    }
+
+    // The lines assembled into a module, or the errors (with the index of their line)
+    // that prevent it.
+    pub fn assemble(&self) -> Result<Module, Vec<AssembleError>> {
+        assemble(&self.parsed_lines())
+    }
+
+    // The lines as a binary .wasm module.
+    pub fn to_wasm(&self) -> Result<Vec<u8>, Vec<AssembleError>> {
+        Ok(self.assemble()?.encode())
+    }
+
+    // The lines as a module in the text format (even if they don't assemble).
+    pub fn to_wat(&self) -> String {
+        module_text(&ModuleText {
+            funcs: vec![FunctionText {
+                lines: self.lines(),
+                ..FunctionText::default()
+            }],
+            ..ModuleText::default()
+        })
+    }
+
+    // Attach a diagnostic to a line (in addition to the editor's own). It moves with its
+    // text as the lines are edited.
+    pub fn add_diagnostic(&mut self, line: usize, diagnostic: Diagnostic) -> Result<()> {
+        let mut inner = self.0.borrow_mut();
+        if line >= inner.component.len() {
+            bail!("line {line} out of range");
+        }
+        if diagnostic.range.start > diagnostic.range.end
+            || diagnostic.range.end > inner.line_len(line)
+        {
+            bail!(
+                "char range {:?} out of range on line {line}",
+                diagnostic.range
+            );
+        }
+        let _scope = dom_op_scope("editor");
+        let factory = inner.factory.clone();
+        let edit_line = inner.component.get_mut(line).expect("line index");
+        edit_line.add_diagnostic(diagnostic);
+        edit_line.refresh_diagnostics(&factory);
+        Ok(())
+    }
+
+    // Remove all diagnostics added with add_diagnostic.
+    pub fn clear_diagnostics(&mut self) {
+        let mut inner = self.0.borrow_mut();
+        let _scope = dom_op_scope("editor");
+        let factory = inner.factory.clone();
+        for index in 0..inner.component.len() {
+            let line = inner.component.get_mut(index).expect("line index");
+            line.clear_diagnostics();
+            line.refresh_diagnostics(&factory);
+        }
+    }
+
+    // The lines with a breakpoint.
+    pub fn breakpoints(&self) -> Vec<usize> {
+        let inner = self.0.borrow();
+        (inner.component.iter().enumerate())
+            .filter(|(_, line)| line.breakpoint())
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    pub fn set_breakpoint(&mut self, line: usize, breakpoint: bool) -> Result<()> {
+        let mut inner = self.0.borrow_mut();
+        if line >= inner.component.len() {
+            bail!("line {line} out of range");
+        }
+        let _scope = dom_op_scope("editor");
+        let edit_line = inner.component.get_mut(line).expect("line index");
+        edit_line.set_breakpoint(breakpoint);
+        Ok(())
+    }
+
+    pub fn toggle_breakpoint(&mut self, line: usize) -> Result<()> {
+        let breakpoint = self
+            .0
+            .borrow()
+            .component
+            .get(line)
+            .map(EditLine::breakpoint);
+        self.set_breakpoint(line, !breakpoint.unwrap_or_default())
+    }
+
+    // The line where execution has stopped (if any), for a debugger to highlight. Like a
+    // breakpoint, it stays with its line through edits.
+    pub fn current_line(&self) -> Option<usize> {
+        let inner = self.0.borrow();
+        inner.component.iter().position(EditLine::is_current)
+    }
+
+    pub fn set_current_line(&mut self, line: Option<usize>) -> Result<()> {
+        let mut inner = self.0.borrow_mut();
+        if let Some(line) = line
+            && line >= inner.component.len()
+        {
+            bail!("line {line} out of range");
+        }
+        let _scope = dom_op_scope("editor");
+        if let Some(line) = line {
+            inner.reveal(line);
+        }
+        if let Some(old) = inner.component.iter().position(EditLine::is_current) {
+            inner
+                .component
+                .get_mut(old)
+                .expect("line index")
+                .set_current(false);
+        }
+        if let Some(line) = line {
+            inner.component.ensure_attached(line, OVERSCAN_LINES);
+            inner
+                .component
+                .get_mut(line)
+                .expect("line index")
+                .set_current(true);
+        }
+        Ok(())
+    }
+
+    // The lines whose elements are in the document (the others are stood in for by the
+    // spacers above and below them).
+    pub fn attached_lines(&self) -> Range<usize> {
+        self.0.borrow().component.window()
+    }
+
+    // The folded lines (if any): they are kept, but hidden (see dom_virtual_vec.rs),
+    // and the line before them stands in for them, with a placeholder. One range is
+    // folded at a time.
+    pub fn folded(&self) -> Option<Range<usize>> {
+        let hidden = self.0.borrow().component.hidden();
+        (!hidden.is_empty()).then_some(hidden)
+    }
+
+    // Fold the block opened by `line` (up to and including its `end`).
+    pub fn fold(&mut self, line: usize) -> Result<()> {
+        let mut inner = self.0.borrow_mut();
+        let ends = inner.compute_block_ends();
+        let Some(Some(end)) = ends.get(line) else {
+            bail!("line {line} does not open a block");
         };
+        let _scope = dom_op_scope("editor");
+        inner.set_folded(line + 1..end + 1);
+        Ok(())
+    }
+
+    // Fold all of the body after its first line.
+    pub fn fold_body(&mut self) -> Result<()> {
+        let mut inner = self.0.borrow_mut();
+        let len = inner.component.len();
+        if len < 2 {
+            bail!("no lines to fold");
+        }
+        let _scope = dom_op_scope("editor");
+        inner.set_folded(1..len);
+        Ok(())
+    }
 
-        inner.component.set_attribute("class", "textentry");
-        inner.component.set_attribute("contenteditable", "true");
-        inner.component.set_attribute("spellcheck", "false");
+    pub fn unfold(&mut self) {
+        let mut inner = self.0.borrow_mut();
+        let _scope = dom_op_scope("editor");
+        inner.set_folded(0..0);
+    }
 
-        let mut ret = Editor(Rc::new(RefCell::new(inner)));
+    // Unfold the lines folded after `line`, or else fold the block it opens.
+    pub fn toggle_fold(&mut self, line: usize) -> Result<()> {
+        match self.folded() {
+            Some(folded) if folded.start == line + 1 => {
+                self.unfold();
+                Ok(())
+            }
+            _ => self.fold(line),
+        }
+    }
 
-        let editor_ref = Rc::clone(&ret.0);
-        ret.0
-            .borrow_mut()
+    // Clicking a block's fold toggle (in its gutter) folds or unfolds it, and clicking
+    // the placeholder unfolds it.
+    fn handle_fold_click(&mut self, id: usize) {
+        let line = self
+            .0
+            .borrow()
             .component
-            .set_onbeforeinput(move |ev| Editor(editor_ref.clone()).handle_input(ev));
+            .iter()
+            .position(|line| line.id() == id);
+        if let Some(line) = line {
+            self.hide_completions();
+            if let Err(e) = self.toggle_fold(line) {
+                web_sys::console::log_1(&format!("fold failed: {e}").into());
+            }
+            let mut inner = self.0.borrow_mut();
+            inner.component.fit_window_to_viewport(OVERSCAN_LINES);
+        }
+    }
 
-        ret.push_line(factory, "Hello, world.");
+    // Clicking a line's gutter toggles a breakpoint on it.
+    fn handle_gutter_click(&mut self, id: usize) {
+        let line = self
+            .0
+            .borrow()
+            .component
+            .iter()
+            .position(|line| line.id() == id);
+        if let Some(line) = line {
+            self.toggle_breakpoint(line).expect("line index");
+        }
+    }
 
-        ret
+    // Each editing operation returns the caret position after the edit.
+    pub fn insert(&mut self, pos: Position, text: &str) -> Result<Position> {
+        self.replace(pos, pos, text)
+    }
+
+    pub fn delete(&mut self, start: Position, end: Position) -> Result<Position> {
+        self.replace(start, end, "")
+    }
+
+    fn replace(&mut self, start: Position, end: Position, text: &str) -> Result<Position> {
+        let pos = self.0.borrow_mut().replace(start, end, text)?;
+        self.changed();
+        Ok(pos)
+    }
+
+    pub fn split_line(&mut self, pos: Position) -> Result<Position> {
+        self.insert(pos, "\n")
+    }
+
+    // Join line `line` with the line that follows it.
+    pub fn join_lines(&mut self, line: usize) -> Result<Position> {
+        let end_of_line = {
+            let inner = self.0.borrow();
+            if line + 1 >= inner.component.len() {
+                bail!("no line after line {line}");
+            }
+            Position::new(line, inner.line_len(line))
+        };
+        self.delete(end_of_line, Position::new(line + 1, 0))
     }
 
-    fn push_line(&mut self, factory: &ElementFactory, string: &str) {
-        self.0.borrow_mut().component.push(EditLine::new(
-            (DomText::new(string), (DomBr::new((), factory.br()), ())),
-            factory.span(),
-        ));
+    // Replace the text between two positions (the selection, if any) with text from the
+    // clipboard, normalizing its line endings, as one edit.
+    pub fn paste(&mut self, start: Position, end: Position, text: &str) -> Result<Position> {
+        self.replace(start, end, &text.replace("\r\n", "\n").replace('\r', "\n"))
+    }
+
+    // Delete the grapheme cluster before (or after) a position, joining lines at the
+    // start (or end) of a line.
+    pub fn delete_backward(&mut self, pos: Position) -> Result<Position> {
+        let prev = self.caret_left(pos);
+        self.delete(prev, pos)
+    }
+
+    pub fn delete_forward(&mut self, pos: Position) -> Result<Position> {
+        let next = self.caret_right(pos);
+        self.delete(pos, next)
+    }
+
+    // Where the caret goes when moved left or right: the adjacent grapheme-cluster
+    // boundary, or across a line break.
+    pub fn caret_left(&self, pos: Position) -> Position {
+        self.0.borrow().prev_position(pos)
+    }
+
+    pub fn caret_right(&self, pos: Position) -> Position {
+        self.0.borrow().next_position(pos)
+    }
+
+    // Undo the most recent edit (if any).
+    pub fn undo(&mut self) -> Result<Option<Position>> {
+        let pos = self.0.borrow_mut().undo()?;
+        if pos.is_some() {
+            self.changed();
+        }
+        Ok(pos)
+    }
+
+    // Replace all of the text, as one edit. Breakpoints don't carry over to the new
+    // text.
+    pub fn set_text(&mut self, text: &str) -> Result<Position> {
+        let end = {
+            let mut inner = self.0.borrow_mut();
+            let _scope = dom_op_scope("editor");
+            for index in 0..inner.component.len() {
+                let line = inner.component.get_mut(index).expect("line index");
+                line.set_breakpoint(false);
+            }
+            let last = inner.component.len() - 1;
+            Position::new(last, inner.line_len(last))
+        };
+        self.replace(Position::new(0, 0), end, text)
+    }
+
+    // The popup of completions (for the editor's parent to put in the document).
+    pub fn completion_popup(&self) -> CompletionPopup {
+        self.0.borrow().popup.clone()
+    }
+
+    // The completions for the word at a position (see completion.rs).
+    pub fn completions(&self, pos: Position) -> Result<Option<Completions>> {
+        let inner = self.0.borrow();
+        inner.check_position(pos)?;
+        Ok(inner.completions(pos))
+    }
+
+    // Move the caret to a position and show the completions for its word in the popup,
+    // at the caret (or hide the popup if there are none). Returns whether it is shown.
+    pub fn show_completions(&mut self, pos: Position) -> Result<bool> {
+        let completions = self.completions(pos)?;
+        let mut inner = self.0.borrow_mut();
+        inner.set_caret(pos);
+        let _scope = dom_op_scope("completion");
+        let Some(completions) = completions else {
+            inner.hide_completions();
+            return Ok(false);
+        };
+        let (left, top) = (SelectionHandle::get())
+            .and_then(|selection| selection.caret_position())
+            .unwrap_or_default();
+        inner.popup.show(completions.items, left, top);
+        inner.completing = Some((pos.line, completions.range));
+        Ok(true)
+    }
+
+    pub fn hide_completions(&mut self) {
+        let mut inner = self.0.borrow_mut();
+        let _scope = dom_op_scope("completion");
+        inner.hide_completions();
+    }
+
+    // Replace the word with the popup's selected completion, as one edit, and hide the
+    // popup (returning the caret position after the edit, or None if the popup wasn't
+    // shown). A mnemonic whose instruction refers to a local, global, function or label
+    // is followed by a space, and then the completions for that.
+    pub fn accept_completion(&mut self) -> Result<Option<Position>> {
+        let (line, range, text, follow) = {
+            let mut inner = self.0.borrow_mut();
+            let Some(completion) = inner.popup.selected() else {
+                return Ok(None);
+            };
+            let Some((line, range)) = inner.completing.clone() else {
+                return Ok(None);
+            };
+            let _scope = dom_op_scope("completion");
+            inner.hide_completions();
+            inner.check_position(Position::new(line, range.end))?;
+            let first_word = (inner.line_text(line)[..inner.byte_idx(line, range.start)])
+                .trim()
+                .is_empty();
+            let at_end = range.end == inner.line_len(line);
+            let refers = lookup(&completion.text).is_some_and(|info| {
+                matches!(
+                    info.imm,
+                    ImmKind::Local
+                        | ImmKind::Global
+                        | ImmKind::Func
+                        | ImmKind::Label
+                        | ImmKind::BrTable
+                )
+            });
+            (line, range, completion.text, first_word && at_end && refers)
+        };
+        let text = match follow {
+            true => format!("{text} "),
+            false => text,
+        };
+        let pos = self.replace(
+            Position::new(line, range.start),
+            Position::new(line, range.end),
+            &text,
+        )?;
+        match follow {
+            true => self.show_completions(pos)?,
+            false => {
+                self.0.borrow_mut().set_caret(pos);
+                false
+            }
+        };
+        Ok(Some(pos))
+    }
+
+    // The tooltip for instructions' references (for the editor's parent to put in the
+    // document).
+    pub fn tooltip(&self) -> DomTooltip {
+        self.0.borrow().tooltip.clone()
+    }
+
+    // The reference of the instruction whose mnemonic is at a position (if any).
+    pub fn reference(&self, pos: Position) -> Result<Option<Vec<String>>> {
+        let inner = self.0.borrow();
+        inner.check_position(pos)?;
+        Ok(inner.mnemonic_at(pos).map(InstrInfo::reference))
+    }
+
+    // Show the reference of the instruction whose mnemonic is at a position in the
+    // tooltip, with its top left corner at (`left`, `top`) in the viewport (or hide the
+    // tooltip if there is no mnemonic there). Returns whether it is shown.
+    pub fn show_reference(&mut self, pos: Position, left: f64, top: f64) -> Result<bool> {
+        let reference = self.reference(pos)?;
+        let mut inner = self.0.borrow_mut();
+        let _scope = dom_op_scope("tooltip");
+        match reference {
+            Some(lines) => inner.tooltip.show(&lines, left, top),
+            None => inner.tooltip.hide(),
+        }
+        Ok(inner.tooltip.is_shown())
+    }
+
+    pub fn hide_reference(&mut self) {
+        let mut inner = self.0.borrow_mut();
+        let _scope = dom_op_scope("tooltip");
+        inner.tooltip.hide();
+    }
+
+    // When the mouse moves onto a token, show the reference of the instruction it is
+    // the mnemonic of, just below the pointer (or hide the tooltip if it isn't one).
+    fn handle_mouseover(&mut self, ev: MouseEvent) {
+        let pos = {
+            let inner = self.0.borrow();
+            (inner.component.window()).find_map(|index| {
+                let ch = inner.line(index).token_at_event(&ev)?;
+                Some(Position::new(index, ch))
+            })
+        };
+        match pos {
+            Some(pos) => {
+                let (left, top) = (ev.client_x() as f64, ev.client_y() as f64);
+                (self.show_reference(pos, left, top + TOOLTIP_OFFSET_PX)).expect("token position");
+            }
+            None => self.hide_reference(),
+        }
+    }
+
+    // Call `handler` with the first file dropped on the editor (dropped text is
+    // inserted as usual).
+    pub fn set_onfiledrop(&mut self, handler: impl Fn(FileHandle) + 'static) {
+        let component = &mut self.0.borrow_mut().component;
+        component.set_ondragover(|ev| {
+            if drags_files(&ev) {
+                ev.prevent_default();
+            }
+        });
+        component.set_ondrop(move |ev| {
+            if let Some(file) = dropped_files(&ev).into_iter().next() {
+                ev.prevent_default();
+                handler(file);
+            }
+        });
     }
 
     fn handle_input(&mut self, ev: InputEvent) {
         ev.prevent_default();
-        web_sys::console::log_1(
-            &format!(
-                "got: {} + {}",
-                ev.input_type(),
-                ev.data().unwrap_or_default()
-            )
-            .into(),
-        );
+        self.hide_reference();
+        let Some((start, end)) = self.0.borrow().selection_range() else {
+            return;
+        };
+
+        let result = match ev.input_type().as_str() {
+            "insertText" | "insertReplacementText" => {
+                self.replace(start, end, &ev.data().unwrap_or_default())
+            }
+            "insertParagraph" | "insertLineBreak" => self.replace(start, end, "\n"),
+            "insertFromPaste" | "insertFromDrop" => {
+                let text = ev
+                    .data_transfer()
+                    .and_then(|transfer| transfer.get_data("text/plain").ok())
+                    .unwrap_or_default();
+                self.paste(start, end, &text)
+            }
+            "deleteContentBackward" if start == end => self.delete_backward(start),
+            "deleteContentForward" if start == end => self.delete_forward(start),
+            "deleteContentBackward" | "deleteContentForward" => self.delete(start, end),
+            "historyUndo" => match self.undo() {
+                Ok(Some(pos)) => Ok(pos),
+                Ok(None) => return,
+                Err(e) => Err(e),
+            },
+            other => {
+                web_sys::console::log_1(&format!("unhandled input: {other}").into());
+                return;
+            }
+        };
+
+        let pos = match result {
+            Ok(pos) => pos,
+            Err(e) => {
+                web_sys::console::log_1(&format!("edit failed: {e}").into());
+                return;
+            }
+        };
+        self.0.borrow_mut().set_caret(pos);
+        // typing offers completions, and deleting updates them (if they are shown)
+        let completing = self.0.borrow().completing.is_some();
+        let complete = match ev.input_type().as_str() {
+            "insertText" => true,
+            "deleteContentBackward" | "deleteContentForward" => completing,
+            _ => false,
+        };
+        match complete {
+            true => {
+                self.show_completions(pos).expect("caret position");
+            }
+            false => self.hide_completions(),
+        }
+    }
+
+    // Move the caret left or right by grapheme cluster (collapsing any selection), and
+    // handle the keys of the completion popup (Ctrl+Space opens it).
+    fn handle_keydown(&mut self, ev: KeyboardEvent) {
+        if ev.ctrl_key() && ev.key() == " " {
+            let start = self.0.borrow().selection_range().map(|(start, _)| start);
+            if let Some(start) = start {
+                ev.prevent_default();
+                self.show_completions(start).expect("caret position");
+            }
+            return;
+        }
+        if ev.shift_key() || ev.alt_key() || ev.ctrl_key() || ev.meta_key() {
+            return;
+        }
+        if self.handle_completion_key(&ev) {
+            ev.prevent_default();
+            return;
+        }
+        let forward = match ev.key().as_str() {
+            "ArrowLeft" => false,
+            "ArrowRight" => true,
+            _ => return,
+        };
+        let Some((start, end)) = self.0.borrow().selection_range() else {
+            return;
+        };
+        ev.prevent_default();
+        self.hide_completions();
+        let pos = match (start == end, forward) {
+            (true, false) => self.caret_left(start),
+            (true, true) => self.caret_right(start),
+            (false, false) => start,
+            (false, true) => end,
+        };
+        self.0.borrow_mut().set_caret(pos);
+    }
+
+    // Up and down choose a completion, Enter or Tab accepts it, and Escape closes the
+    // popup. Returns whether the key was for the popup (it isn't if the popup is hidden,
+    // or the caret has left the word, which hides the popup).
+    fn handle_completion_key(&mut self, ev: &KeyboardEvent) -> bool {
+        let at_word = {
+            let inner = self.0.borrow();
+            match (&inner.completing, inner.selection_range()) {
+                (None, _) => return false,
+                (Some((line, range)), Some((start, end))) => {
+                    start == end
+                        && start.line == *line
+                        && (range.start..=range.end).contains(&start.ch)
+                }
+                (Some(_), None) => false,
+            }
+        };
+        if !at_word {
+            self.hide_completions();
+            return false;
+        }
+        match ev.key().as_str() {
+            "ArrowDown" | "ArrowUp" => {
+                let mut popup = self.completion_popup();
+                let _scope = dom_op_scope("completion");
+                popup.select_next(ev.key() == "ArrowDown");
+            }
+            "Enter" | "Tab" => {
+                if let Err(e) = self.accept_completion() {
+                    web_sys::console::log_1(&format!("edit failed: {e}").into());
+                }
+            }
+            "Escape" => self.hide_completions(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl _Editor {
+    fn new_line(&mut self, string: &str) -> EditLine {
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut line = EditLine::new(&self.factory, id, string);
+        let editor_ref = self.this.clone();
+        line.set_ongutterclick(move |_| {
+            if let Some(editor_ref) = editor_ref.upgrade() {
+                Editor(editor_ref).handle_gutter_click(id);
+            }
+        });
+        let editor_ref = self.this.clone();
+        line.set_onfoldclick(move |_| {
+            if let Some(editor_ref) = editor_ref.upgrade() {
+                Editor(editor_ref).handle_fold_click(id);
+            }
+        });
+        line
+    }
+
+    fn line(&self, index: usize) -> &EditLine {
+        self.component.get(index).expect("line index")
+    }
+
+    fn line_text(&self, index: usize) -> &str {
+        self.line(index).text()
+    }
+
+    fn line_len(&self, index: usize) -> usize {
+        self.line(index).len_chars()
+    }
+
+    fn check_position(&self, pos: Position) -> Result<()> {
+        if pos.line >= self.component.len() {
+            bail!("line {} out of range", pos.line);
+        }
+        if pos.ch > self.line_len(pos.line) {
+            bail!("char {} out of range on line {}", pos.ch, pos.line);
+        }
+        Ok(())
+    }
+
+    fn prev_position(&self, pos: Position) -> Position {
+        match pos {
+            Position { line: 0, ch: 0 } => pos,
+            Position { line, ch: 0 } => Position::new(line - 1, self.line_len(line - 1)),
+            Position { line, ch } => {
+                Position::new(line, self.line(line).prev_grapheme_boundary(ch))
+            }
+        }
+    }
+
+    fn next_position(&self, pos: Position) -> Position {
+        if pos.ch < self.line_len(pos.line) {
+            Position::new(pos.line, self.line(pos.line).next_grapheme_boundary(pos.ch))
+        } else if pos.line + 1 < self.component.len() {
+            Position::new(pos.line + 1, 0)
+        } else {
+            pos
+        }
+    }
+
+    // The char index `ch` of a line, as a byte index.
+    fn byte_idx(&self, line: usize, ch: usize) -> usize {
+        self.line(line).char_to_byte(ch)
+    }
+
+    fn text_range(&self, start: Position, end: Position) -> String {
+        if start.line == end.line {
+            let text = self.line_text(start.line);
+            return text[self.byte_idx(start.line, start.ch)..self.byte_idx(end.line, end.ch)]
+                .to_string();
+        }
+        let mut ret = self.line_text(start.line)[self.byte_idx(start.line, start.ch)..].to_string();
+        for line in start.line + 1..end.line {
+            ret.push('\n');
+            ret.push_str(self.line_text(line));
+        }
+        ret.push('\n');
+        ret.push_str(&self.line_text(end.line)[..self.byte_idx(end.line, end.ch)]);
+        ret
+    }
+
+    // Replace the text between two positions, recording the edit for undo.
+    fn replace(&mut self, start: Position, end: Position, text: &str) -> Result<Position> {
+        self.check_position(start)?;
+        self.check_position(end)?;
+        let (start, end) = (start.min(end), start.max(end));
+        let removed = self.text_range(start, end);
+        self.splice(start, end, text)?;
+        self.undo_stack.push(Edit {
+            start,
+            removed,
+            inserted: text.to_string(),
+        });
+        Ok(start.after(text))
+    }
+
+    fn undo(&mut self) -> Result<Option<Position>> {
+        let Some(edit) = self.undo_stack.pop() else {
+            return Ok(None);
+        };
+        self.splice(edit.start, edit.start.after(&edit.inserted), &edit.removed)?;
+        Ok(Some(edit.start.after(&edit.removed)))
+    }
+
+    // Replace the text between two (valid, ordered) positions, then bring the lines'
+    // analysis up to date. An edit touching the folded lines, or the line before them,
+    // unfolds them; otherwise they stay folded (and move with the lines). Inserted lines
+    // grow the window, so it is fitted to the viewport again afterwards.
+    fn splice(&mut self, start: Position, end: Position, text: &str) -> Result<()> {
+        let _scope = dom_op_scope("editor");
+        let hidden = self.component.hidden();
+        if !hidden.is_empty() && start.line < hidden.end && end.line + 1 >= hidden.start {
+            self.set_folded(0..0);
+        }
+        self.splice_lines(start, end, text)?;
+        self.component.fit_window_to_viewport(OVERSCAN_LINES);
+        let (range, count) = (start.line..end.line + 1, text.matches('\n').count() + 1);
+        self.update_analysis(range, count);
+        Ok(())
+    }
+
+    // Edits within a line touch only that line; otherwise the first line is rewritten
+    // and the following lines are removed or inserted as needed. External diagnostics
+    // go with their text: those before the edit stay on the first line, and those after
+    // it move to the last line (e.g. onto the first line, when lines are joined, or
+    // onto the new line, when a line is split). A breakpoint stays on
+    // the first line, unless the edit starts at the beginning of its line: then the
+    // breakpoint (of the first or the last line edited) goes with the text after the
+    // edit, on the last line.
+    fn splice_lines(&mut self, start: Position, end: Position, text: &str) -> Result<()> {
+        if start.line == end.line && !text.contains('\n') {
+            let line = self.component.get_mut(start.line).expect("line index");
+            if start.ch < end.ch {
+                line.delete_chars(&self.factory, start.ch..end.ch)?;
+            }
+            if !text.is_empty() {
+                line.insert_at_char(&self.factory, start.ch, text)?;
+            }
+            return Ok(());
+        }
+
+        let prefix = &self.line_text(start.line)[..self.byte_idx(start.line, start.ch)];
+        let suffix = &self.line_text(end.line)[self.byte_idx(end.line, end.ch)..];
+        let joined = format!("{prefix}{text}{suffix}");
+        let suffix_chars = suffix.chars().count();
+        let mut new_lines = joined.split('\n');
+        let breakpoint = self.line(start.line).breakpoint()
+            || (start.ch == 0 && self.line(end.line).breakpoint());
+        let moved =
+            (self.component.get_mut(end.line).expect("line index")).take_diagnostics_from(end.ch);
+
+        self.component
+            .get_mut(start.line)
+            .expect("line index")
+            .set_text(&self.factory, new_lines.next().expect("at least one line"));
+        for _ in start.line + 1..=end.line {
+            self.component.remove(start.line + 1);
+        }
+        let mut last = start.line;
+        for (offset, string) in new_lines.enumerate() {
+            let line = self.new_line(string);
+            last = start.line + 1 + offset;
+            self.component.insert(last, line);
+        }
+        let last_line = self.component.get_mut(last).expect("line index");
+        let offset = last_line.len_chars() - suffix_chars;
+        for mut diagnostic in moved {
+            diagnostic.range = diagnostic.range.start + offset..diagnostic.range.end + offset;
+            last_line.add_diagnostic(diagnostic);
+        }
+        let (on, off) = match start.ch {
+            0 => (last, start.line),
+            _ => (start.line, last),
+        };
+        self.component
+            .get_mut(off)
+            .expect("line index")
+            .set_breakpoint(false);
+        (self.component.get_mut(on).expect("line index")).set_breakpoint(breakpoint);
+        Ok(())
+    }
+
+    fn compute_nesting(&self) -> Vec<LineNesting> {
+        nesting(self.component.iter().map(|line| line.instruction()))
+    }
+
+    fn compute_block_ends(&self) -> Vec<Option<usize>> {
+        block_ends(self.component.iter().map(|line| line.instruction()))
+    }
+
+    fn compute_checks(&self) -> Vec<LineCheck> {
+        let body = self.component.iter().map(EditLine::parsed);
+        match &self.module {
+            None => validate_function(body),
+            Some(module) => {
+                let lines = module.header.iter().chain(body);
+                let mut checks = validate_module_function(lines, &module.funcs, &module.globals);
+                checks.split_off(module.header.len())
+            }
+        }
+    }
+
+    // The function's context, and the errors of its declarations (on the editor's
+    // lines).
+    fn context(&self) -> (Context, Vec<(usize, String)>) {
+        let body = self.component.iter().map(EditLine::parsed);
+        match &self.module {
+            None => function_context(body),
+            Some(module) => {
+                let lines = module.header.iter().chain(body);
+                let (ctx, errors) = module_function_context(lines, &module.funcs, &module.globals);
+                let header = module.header.len();
+                let errors = (errors.into_iter())
+                    .filter_map(|(index, error)| Some((index.checked_sub(header)?, error)))
+                    .collect();
+                (ctx, errors)
+            }
+        }
+    }
+
+    // Bring the analysis up to date after the lines in `range` were replaced by `count`
+    // lines (or the module context changed), then refresh the diagnostics of the lines
+    // whose parse, nesting or check changed.
+    fn update_analysis(&mut self, range: Range<usize>, count: usize) {
+        let mut changed = self.renest(range.clone(), count);
+        changed.extend(self.recheck(range, count));
+        changed.sort_unstable();
+        changed.dedup();
+        for index in changed {
+            let line = self.component.get_mut(index).expect("line index");
+            line.refresh_diagnostics(&self.factory);
+        }
+    }
+
+    // Bring the nesting up to date, touching only the lines whose nesting changed
+    // (which are returned, with the edited lines).
+    fn renest(&mut self, range: Range<usize>, count: usize) -> Vec<usize> {
+        let component = &self.component;
+        let changed = self.nesting.splice(range, count, |index| {
+            component.get(index).expect("line index").instruction()
+        });
+        for &index in &changed {
+            let line_nesting = self.nesting.get(index).expect("line index").clone();
+            let foldable = self.nesting.is_closed(index);
+            let line = self.component.get_mut(index).expect("line index");
+            if line.nesting() != &line_nesting {
+                line.set_nesting(line_nesting);
+            }
+            line.set_foldable(foldable);
+        }
+        changed
+    }
+
+    // Bring the checks up to date, touching only the lines whose check changed (which
+    // are returned, with the edited lines).
+    fn recheck(&mut self, range: Range<usize>, count: usize) -> Vec<usize> {
+        let context = self.context();
+        let component = &self.component;
+        let changed = self.checks.splice(context, range, count, |index| {
+            component.get(index).expect("line index").instruction()
+        });
+        for &index in &changed {
+            let check = self.checks.get(index).expect("line index");
+            let line = self.component.get_mut(index).expect("line index");
+            if line.check() != &check {
+                line.set_check(check);
+            }
+        }
+        changed
+    }
+
+    // Hide the lines in `hidden` (none, if it is empty), showing a placeholder on the
+    // line before them.
+    fn set_folded(&mut self, hidden: Range<usize>) {
+        let old = self.component.hidden();
+        if old == hidden {
+            return;
+        }
+        if !old.is_empty() {
+            let line = self.component.get_mut(old.start - 1).expect("line index");
+            line.set_folded(None);
+        }
+        self.component.set_hidden(hidden.clone());
+        if !hidden.is_empty() {
+            let line = self
+                .component
+                .get_mut(hidden.start - 1)
+                .expect("line index");
+            line.set_folded(Some(hidden.len()));
+        }
+    }
+
+    // Unfold the lines if this one is folded (when the caret or the debugger's current
+    // line moves into them).
+    fn reveal(&mut self, line: usize) {
+        if self.component.hidden().contains(&line) {
+            self.set_folded(0..0);
+        }
+    }
+
+    // Find the editor position of one end of the DOM selection. The point may be within
+    // a (necessarily attached) line, or in the editor <div> itself (between lines).
+    fn locate(&self, point: &SelectionPoint) -> Option<Position> {
+        if let Some(offset) = point.offset_in(&self.component) {
+            let index = self.component.index_at_dom_offset(offset);
+            let last = self.component.len() - 1;
+            return Some(if index <= last {
+                Position::new(index, 0)
+            } else {
+                Position::new(last, self.line_len(last))
+            });
+        }
+        for index in self.component.window() {
+            if let Some(ch) = self.line(index).locate(point) {
+                return Some(Position::new(index, ch));
+            }
+        }
+        None
+    }
+
+    fn selection_range(&self) -> Option<(Position, Position)> {
+        let selection = SelectionHandle::get()?;
+        let anchor = self.locate(&selection.anchor())?;
+        let focus = self.locate(&selection.focus())?;
+        Some((anchor.min(focus), anchor.max(focus)))
+    }
+
+    // The completions for the word at a (valid) position, given the function's context,
+    // the operand stack after the last instruction before the line, and the blocks open
+    // at the line.
+    fn completions(&self, pos: Position) -> Option<Completions> {
+        let (ctx, _) = self.context();
+        let stack = (0..pos.line)
+            .rev()
+            .find_map(|index| self.line(index).check().stack.clone())
+            .unwrap_or_default();
+        let blocks = open_blocks((0..pos.line).map(|index| self.line(index).instruction()));
+        let scope = Scope {
+            ctx: &ctx,
+            stack: Some(&stack),
+            blocks: &blocks,
+        };
+        complete(self.line_text(pos.line), pos.ch, &scope)
+    }
+
+    // The instruction whose mnemonic is the token at a (valid) position.
+    fn mnemonic_at(&self, pos: Position) -> Option<&'static InstrInfo> {
+        let line = self.line(pos.line);
+        let byte_idx = line.char_to_byte(pos.ch);
+        let token = (line.tokens().iter()).find(|token| {
+            token.range.contains(&byte_idx) && token.kind == TokenKind::Instruction
+        })?;
+        lookup(&line.text()[token.range.clone()])
+    }
+
+    fn hide_completions(&mut self) {
+        if self.completing.take().is_some() {
+            self.popup.hide();
+        }
+    }
+
+    fn set_caret(&mut self, pos: Position) {
+        self.reveal(pos.line);
+        self.component.ensure_attached(pos.line, OVERSCAN_LINES);
+        if let Some(selection) = SelectionHandle::get() {
+            self.line(pos.line).set_caret(&selection, pos.ch);
+        }
     }
 }
 
@@ -79,8 +1253,8 @@ impl WithElement<HtmlDivElement> for Editor {
 }
 
 impl Component for Editor {
-    fn audit(&self) {
-        self.0.borrow().audit()
+    fn try_audit(&self) -> Result<(), String> {
+        self.0.borrow().try_audit()
     }
 }
 
@@ -90,8 +1264,30 @@ impl WithNode for _Editor {
     }
 }
 
+// To audit, audit the lines, then check that their analysis is up to date, and that
+// only the line before the folded lines (if any) stands in for them.
 impl Component for _Editor {
-    fn audit(&self) {
-        self.component.audit()
+    fn try_audit(&self) -> Result<(), String> {
+        self.component.try_audit()?;
+        let analysis = self
+            .compute_nesting()
+            .into_iter()
+            .zip(self.compute_checks())
+            .zip(self.compute_block_ends());
+        let hidden = self.component.hidden();
+        for (index, (line, ((line_nesting, check), end))) in
+            self.component.iter().zip(analysis).enumerate()
+        {
+            audit_eq!(line.nesting(), &line_nesting);
+            audit_eq!(line.check(), &check);
+            audit_eq!(line.diagnostics(), line.wanted_diagnostics());
+            audit_eq!(line.is_foldable(), end.is_some());
+            match !hidden.is_empty() && index + 1 == hidden.start {
+                true => audit_eq!(line.folded(), Some(hidden.len())),
+                false => audit_eq!(line.folded(), None),
+            }
+        }
+        audit_eq!(self.completing.is_some(), self.popup.is_shown());
+        Ok(())
     }
 }
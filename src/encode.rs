@@ -0,0 +1,392 @@
+// The binary encoder: write a Module in the WebAssembly binary format (the module
+// header, then the type, import, function, table, memory, global, export, element,
+// code and data sections, in that order, each only if it has something in it).
+
+use crate::{
+    instruction::{BlockType, Immediate, Index, Instruction, ValType},
+    module::{ExportKind, Function, Limits, Module},
+    validate::FuncType,
+    value::Value,
+};
+
+pub const MAGIC: &[u8; 4] = b"\0asm";
+pub const VERSION: u32 = 1;
+
+pub fn write_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+pub fn write_i64(out: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        // done once the rest is all sign bits, and the sign bit of `byte` agrees
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_len(out: &mut Vec<u8>, len: usize) {
+    write_u32(out, len.try_into().expect("length fits in u32"));
+}
+
+fn write_name(out: &mut Vec<u8>, name: &str) {
+    write_len(out, name.len());
+    out.extend_from_slice(name.as_bytes());
+}
+
+pub fn valtype_byte(ty: ValType) -> u8 {
+    match ty {
+        ValType::I32 => 0x7f,
+        ValType::I64 => 0x7e,
+        ValType::F32 => 0x7d,
+        ValType::F64 => 0x7c,
+    }
+}
+
+fn write_types(out: &mut Vec<u8>, types: &[ValType]) {
+    write_len(out, types.len());
+    out.extend(types.iter().map(|ty| valtype_byte(*ty)));
+}
+
+fn write_index(out: &mut Vec<u8>, index: &Index) {
+    match index {
+        Index::Num(num) => write_u32(out, *num),
+        Index::Id(id) => panic!("unresolved index ${id}"),
+    }
+}
+
+// A section: its id, then its contents prefixed by their size.
+fn write_section(out: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    out.push(id);
+    write_len(out, contents.len());
+    out.extend_from_slice(contents);
+}
+
+// A vector of items, prefixed by their count.
+fn vec_of<T>(items: &[T], mut write: impl FnMut(&mut Vec<u8>, &T)) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_len(&mut out, items.len());
+    for item in items {
+        write(&mut out, item);
+    }
+    out
+}
+
+fn write_func_type(out: &mut Vec<u8>, ty: &FuncType) {
+    out.push(0x60);
+    write_types(out, &ty.params);
+    write_types(out, &ty.results);
+}
+
+pub fn write_instruction(out: &mut Vec<u8>, instr: &Instruction) {
+    out.push(instr.info.opcode);
+    match &instr.imm {
+        Immediate::None => {
+            if instr.mnemonic().starts_with("memory.") {
+                out.push(0x00); // the memory index
+            }
+        }
+        Immediate::Label(_) => {}
+        Immediate::I32(value) => write_i64(out, *value as i64),
+        Immediate::I64(value) => write_i64(out, *value),
+        Immediate::F32(bits) => out.extend_from_slice(&bits.to_le_bytes()),
+        Immediate::F64(bits) => out.extend_from_slice(&bits.to_le_bytes()),
+        Immediate::Index(index) => write_index(out, index),
+        Immediate::Block { ty, .. } => out.push(match ty {
+            BlockType::Empty => 0x40,
+            BlockType::Value(ty) => valtype_byte(*ty),
+        }),
+        Immediate::BrTable { labels, default } => {
+            write_len(out, labels.len());
+            for label in labels {
+                write_index(out, label);
+            }
+            write_index(out, default);
+        }
+        Immediate::MemArg(memarg) => {
+            write_u32(out, memarg.align);
+            write_u32(out, memarg.offset);
+        }
+    }
+}
+
+fn write_limits(out: &mut Vec<u8>, limits: &Limits) {
+    match limits.max {
+        None => {
+            out.push(0x00);
+            write_u32(out, limits.min);
+        }
+        Some(max) => {
+            out.push(0x01);
+            write_u32(out, limits.min);
+            write_u32(out, max);
+        }
+    }
+}
+
+// A constant expression: one `const` instruction, then `end`.
+fn write_const(out: &mut Vec<u8>, value: Value) {
+    match value {
+        Value::I32(value) => {
+            out.push(0x41);
+            write_i64(out, value as i64);
+        }
+        Value::I64(value) => {
+            out.push(0x42);
+            write_i64(out, value);
+        }
+        Value::F32(value) => {
+            out.push(0x43);
+            out.extend_from_slice(&value.to_bits().to_le_bytes());
+        }
+        Value::F64(value) => {
+            out.push(0x44);
+            out.extend_from_slice(&value.to_bits().to_le_bytes());
+        }
+    }
+    out.push(0x0b);
+}
+
+// An offset into the table or the memory, as a constant expression.
+fn write_offset(out: &mut Vec<u8>, offset: u32) {
+    write_const(out, Value::I32(offset as i32));
+}
+
+// A function's code: its size, its locals (as runs of the same type), and its body.
+fn write_code(out: &mut Vec<u8>, func: &Function) {
+    let mut runs: Vec<(u32, ValType)> = Vec::new();
+    for &ty in &func.locals {
+        match runs.last_mut() {
+            Some((count, last)) if *last == ty => *count += 1,
+            _ => runs.push((1, ty)),
+        }
+    }
+    let mut code = vec_of(&runs, |out, (count, ty)| {
+        write_u32(out, *count);
+        out.push(valtype_byte(*ty));
+    });
+    for instr in &func.body {
+        write_instruction(&mut code, instr);
+    }
+    code.push(0x0b);
+    write_len(out, code.len());
+    out.extend_from_slice(&code);
+}
+
+impl Module {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.extend_from_slice(&VERSION.to_le_bytes());
+        if !self.types.is_empty() {
+            write_section(&mut out, 1, &vec_of(&self.types, write_func_type));
+        }
+        if !self.imports.is_empty() {
+            let imports = vec_of(&self.imports, |out, import| {
+                write_name(out, &import.module);
+                write_name(out, &import.name);
+                out.push(0x00); // a function
+                write_u32(out, import.type_index);
+            });
+            write_section(&mut out, 2, &imports);
+        }
+        if !self.funcs.is_empty() {
+            let function = vec_of(&self.funcs, |out, func| write_u32(out, func.type_index));
+            write_section(&mut out, 3, &function);
+        }
+        if let Some(limits) = &self.table {
+            let table = vec_of(&[limits], |out, limits| {
+                out.push(0x70); // of function references
+                write_limits(out, limits);
+            });
+            write_section(&mut out, 4, &table);
+        }
+        if let Some(limits) = &self.memory {
+            write_section(
+                &mut out,
+                5,
+                &vec_of(&[limits], |out, limits| write_limits(out, limits)),
+            );
+        }
+        if !self.globals.is_empty() {
+            let globals = vec_of(&self.globals, |out, global| {
+                out.push(valtype_byte(global.ty));
+                out.push(global.mutable as u8);
+                write_const(out, global.init);
+            });
+            write_section(&mut out, 6, &globals);
+        }
+        if !self.exports.is_empty() {
+            let exports = vec_of(&self.exports, |out, export| {
+                write_name(out, &export.name);
+                out.push(match export.kind {
+                    ExportKind::Func => 0x00,
+                    ExportKind::Table => 0x01,
+                    ExportKind::Memory => 0x02,
+                    ExportKind::Global => 0x03,
+                });
+                write_u32(out, export.index);
+            });
+            write_section(&mut out, 7, &exports);
+        }
+        if !self.elems.is_empty() {
+            let elems = vec_of(&self.elems, |out, elem| {
+                out.push(0x00); // active, in table 0
+                write_offset(out, elem.offset);
+                out.extend(vec_of(&elem.funcs, |out, func| write_u32(out, *func)));
+            });
+            write_section(&mut out, 9, &elems);
+        }
+        if !self.funcs.is_empty() {
+            write_section(&mut out, 10, &vec_of(&self.funcs, write_code));
+        }
+        if !self.data.is_empty() {
+            let data = vec_of(&self.data, |out, data| {
+                out.push(0x00); // active, in memory 0
+                write_offset(out, data.offset);
+                write_len(out, data.bytes.len());
+                out.extend_from_slice(&data.bytes);
+            });
+            write_section(&mut out, 11, &data);
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble::assemble, parse::parse_line};
+
+    fn leb_u32(value: u32) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_u32(&mut out, value);
+        out
+    }
+
+    fn leb_i64(value: i64) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_i64(&mut out, value);
+        out
+    }
+
+    #[test]
+    fn leb128() {
+        assert_eq!(leb_u32(0), vec![0x00]);
+        assert_eq!(leb_u32(127), vec![0x7f]);
+        assert_eq!(leb_u32(128), vec![0x80, 0x01]);
+        assert_eq!(leb_u32(624485), vec![0xe5, 0x8e, 0x26]);
+        assert_eq!(leb_u32(u32::MAX), vec![0xff, 0xff, 0xff, 0xff, 0x0f]);
+        assert_eq!(leb_i64(0), vec![0x00]);
+        assert_eq!(leb_i64(63), vec![0x3f]);
+        assert_eq!(leb_i64(64), vec![0xc0, 0x00]);
+        assert_eq!(leb_i64(-1), vec![0x7f]);
+        assert_eq!(leb_i64(-64), vec![0x40]);
+        assert_eq!(leb_i64(-65), vec![0xbf, 0x7f]);
+        assert_eq!(leb_i64(-123456), vec![0xc0, 0xbb, 0x78]);
+        assert_eq!(leb_i64(i64::MIN).len(), 10);
+    }
+
+    fn encode(text: &str) -> Vec<u8> {
+        let parsed: Vec<_> = text.lines().map(parse_line).collect();
+        assemble(&parsed).unwrap().encode()
+    }
+
+    #[test]
+    fn small_modules() {
+        // the sections of a module: (id, contents)
+        let sections = |text: &str| -> Vec<(u8, Vec<u8>)> {
+            let wasm = encode(text);
+            assert_eq!(wasm[..8], [0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00]);
+            let mut ret = Vec::new();
+            let mut rest = &wasm[8..];
+            while let [id, len, tail @ ..] = rest {
+                assert!(*len < 0x80, "short sections only");
+                ret.push((*id, tail[..*len as usize].to_vec()));
+                rest = &tail[*len as usize..];
+            }
+            ret
+        };
+
+        let mut imports = vec![0x04];
+        for (index, name) in ["print_i32", "print_i64", "print_f32", "print_f64"]
+            .iter()
+            .enumerate()
+        {
+            imports.extend([0x04, b'h', b'o', b's', b't', 0x09]);
+            imports.extend(name.as_bytes());
+            imports.extend([0x00, index as u8]);
+        }
+        assert_eq!(
+            sections(""),
+            vec![
+                (
+                    1,
+                    vec![
+                        0x05, // the print functions' types, then [] -> []
+                        0x60, 0x01, 0x7f, 0x00, 0x60, 0x01, 0x7e, 0x00, 0x60, 0x01, 0x7d, 0x00,
+                        0x60, 0x01, 0x7c, 0x00, 0x60, 0x00, 0x00,
+                    ]
+                ),
+                (2, imports),
+                (3, vec![0x01, 0x04]),              // function: type 4
+                (10, vec![0x01, 0x02, 0x00, 0x0b]), // code: no locals, `end`
+            ]
+        );
+
+        let text = "(param $x i32)\n(result i32)\n(export \"inc\")\n(local i64 i64 f32)\nlocal.get $x\ni32.const -1\ni32.sub";
+        let sections = sections(text);
+        assert_eq!(sections[0].1[17..], [0x60, 0x01, 0x7f, 0x01, 0x7f]); // [i32] -> [i32]
+        assert_eq!(
+            sections[3..],
+            [
+                (7, vec![0x01, 0x03, b'i', b'n', b'c', 0x00, 0x04]), // export "inc" (func 4)
+                (
+                    10,
+                    vec![
+                        0x01, 0x0b, // one body of 11 bytes
+                        0x02, 0x02, 0x7e, 0x01, 0x7d, // locals: 2 i64, 1 f32
+                        0x20, 0x00, 0x41, 0x7f, 0x6b,
+                        0x0b, // local.get 0, i32.const -1, i32.sub, end
+                    ]
+                )
+            ]
+        );
+    }
+
+    #[test]
+    fn immediates() {
+        let body = |text: &str| -> Vec<u8> {
+            let mut out = Vec::new();
+            let parsed: Vec<_> = text.lines().map(parse_line).collect();
+            for instr in &assemble(&parsed).unwrap().funcs[0].body {
+                write_instruction(&mut out, instr);
+            }
+            out
+        };
+        assert_eq!(
+            body("block $b (result f32)\nf32.const 1.5\ni32.const 0\nbr_table $b $b\nend\ndrop"),
+            vec![
+                0x02, 0x7d, 0x43, 0x00, 0x00, 0xc0, 0x3f, 0x41, 0x00, 0x0e, 0x01, 0x00, 0x00, 0x0b,
+                0x1a
+            ]
+        );
+        assert_eq!(
+            body("i32.const 0\ni64.load offset=16 align=4\nmemory.size\nmemory.grow\ndrop\ndrop"),
+            vec![
+                0x41, 0x00, 0x29, 0x02, 0x10, 0x3f, 0x00, 0x40, 0x00, 0x1a, 0x1a
+            ]
+        );
+    }
+}
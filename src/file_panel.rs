@@ -0,0 +1,280 @@
+// The File panel: opens a binary .wasm module, chosen with the file input or dropped
+// on a function's body, and puts its functions into the module editor, replacing the
+// ones there (see decode.rs and disassemble.rs).
+//
+// It also downloads the module editor's module, either in the text format (see
+// wat.rs) or assembled to a binary, under the file name given in the panel.
+
+use crate::{
+    disassemble::disassemble_wasm,
+    dom_struct::DomStruct,
+    dom_text::DomText,
+    module_editor::ModuleEditor,
+    web_support::{
+        AccessToken, BlobUrl, Component, ElementFactory, FileHandle, WithElement, WithNode,
+        audit_eq, dom_op_scope,
+    },
+};
+use std::{cell::RefCell, rc::Rc};
+use web_sys::{HtmlButtonElement, HtmlDivElement, HtmlInputElement};
+
+type Input = DomStruct<(), HtmlInputElement>;
+type Button = DomStruct<(DomText, ()), HtmlButtonElement>;
+type TextDiv = DomStruct<(DomText, ()), HtmlDivElement>;
+type Downloads = DomStruct<(Input, (Button, (Button, ()))), HtmlDivElement>;
+type PanelContents = (TextDiv, (Input, (TextDiv, (Downloads, (TextDiv, ())))));
+
+// The file name for downloads when none is given.
+const DEFAULT_NAME: &str = "module";
+
+// The kinds of file the panel downloads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Wat,
+    Wasm,
+}
+
+impl Format {
+    fn extension(&self) -> &'static str {
+        match self {
+            Format::Wat => "wat",
+            Format::Wasm => "wasm",
+        }
+    }
+
+    fn mime_type(&self) -> &'static str {
+        match self {
+            Format::Wat => "text/plain",
+            Format::Wasm => "application/wasm",
+        }
+    }
+}
+
+struct _FilePanel {
+    factory: ElementFactory,
+    module: ModuleEditor,
+    component: DomStruct<PanelContents, HtmlDivElement>,
+    status: String,            // as shown
+    download: Option<BlobUrl>, // the last download's contents (kept until the next one)
+}
+
+pub struct FilePanel(Rc<RefCell<_FilePanel>>);
+
+impl FilePanel {
+    pub fn new(factory: &ElementFactory, module: &ModuleEditor) -> Self {
+        let text_div = |text: &str, class: &str| {
+            let mut div = TextDiv::new((DomText::new(text), ()), factory.div());
+            div.set_attribute("class", class);
+            div
+        };
+        let button = |label: &str| Button::new((DomText::new(label), ()), factory.button());
+        let mut input = Input::new((), factory.input());
+        input.set_attribute("type", "file");
+        input.set_attribute("accept", ".wasm,application/wasm");
+        let mut name = Input::new((), factory.input());
+        name.set_attribute("placeholder", "file name");
+        name.set_attribute("spellcheck", "false");
+        name.set_value(DEFAULT_NAME);
+        let mut downloads = Downloads::new(
+            (
+                name,
+                (button("Download .wat"), (button("Download .wasm"), ())),
+            ),
+            factory.div(),
+        );
+        downloads.set_attribute("class", "controls");
+        let mut component = DomStruct::new(
+            (
+                text_div("Open a .wasm module", "heading"),
+                (
+                    input,
+                    (
+                        text_div("Download", "heading"),
+                        (downloads, (text_div("", "status"), ())),
+                    ),
+                ),
+            ),
+            factory.div(),
+        );
+        component.set_attribute("class", "filepanel");
+
+        let ret = FilePanel(Rc::new(RefCell::new(_FilePanel {
+            factory: factory.clone(),
+            module: module.clone(),
+            component,
+            status: String::new(),
+            download: None,
+        })));
+
+        for format in [Format::Wat, Format::Wasm] {
+            let panel_ref = Rc::clone(&ret.0);
+            (ret.0.borrow_mut().button_mut(format))
+                .set_onclick(move |_| panel_ref.borrow_mut().download(format));
+        }
+
+        let panel_ref = Rc::clone(&ret.0);
+        ret.0.borrow_mut().input_mut().set_onchange(move |_| {
+            let file = {
+                let mut inner = panel_ref.borrow_mut();
+                let file = inner.input().files().into_iter().next();
+                inner.input_mut().set_value(""); // so choosing the same file opens it again
+                file
+            };
+            if let Some(file) = file {
+                let panel = FilePanel(panel_ref.clone());
+                wasm_bindgen_futures::spawn_local(async move { panel.open(file).await });
+            }
+        });
+
+        let panel_ref = Rc::clone(&ret.0);
+        module.clone().set_onfiledrop(move |file| {
+            let panel = FilePanel(panel_ref.clone());
+            wasm_bindgen_futures::spawn_local(async move { panel.open(file).await });
+        });
+
+        ret
+    }
+
+    pub fn status(&self) -> String {
+        self.0.borrow().status.clone()
+    }
+
+    pub async fn open(&self, file: FileHandle) {
+        let name = file.name();
+        match file.bytes().await {
+            Ok(bytes) => self.0.borrow_mut().open_wasm(&name, &bytes),
+            Err(e) => self.0.borrow_mut().show(&e.to_string()),
+        }
+    }
+
+    // Put the functions of a binary module (named `name`) into the module editor.
+    pub fn open_wasm(&mut self, name: &str, bytes: &[u8]) {
+        self.0.borrow_mut().open_wasm(name, bytes);
+    }
+
+    pub fn set_filename(&mut self, name: &str) {
+        self.0.borrow_mut().name_input_mut().set_value(name);
+    }
+
+    // The name a download in `format` gets: the name given (without an extension of
+    // its own), or "module".
+    pub fn filename(&self, format: Format) -> String {
+        self.0.borrow().filename(format)
+    }
+
+    // Download the module (as long as it assembles, for a binary).
+    pub fn download(&mut self, format: Format) {
+        self.0.borrow_mut().download(format);
+    }
+}
+
+impl _FilePanel {
+    fn input(&self) -> &Input {
+        &self.component.get().1.0
+    }
+
+    fn input_mut(&mut self) -> &mut Input {
+        &mut self.component.get_mut().1.0
+    }
+
+    fn name_input(&self) -> &Input {
+        &self.component.get().1.1.1.0.get().0
+    }
+
+    fn name_input_mut(&mut self) -> &mut Input {
+        &mut self.component.get_mut().1.1.1.0.get_mut().0
+    }
+
+    fn button_mut(&mut self, format: Format) -> &mut Button {
+        let buttons = &mut self.component.get_mut().1.1.1.0.get_mut().1;
+        match format {
+            Format::Wat => &mut buttons.0,
+            Format::Wasm => &mut buttons.1.0,
+        }
+    }
+
+    fn filename(&self, format: Format) -> String {
+        let name = self.name_input().value();
+        let name = name.trim();
+        let name = (name
+            .strip_suffix(".wat")
+            .or_else(|| name.strip_suffix(".wasm")))
+        .unwrap_or(name);
+        let name = if name.is_empty() { DEFAULT_NAME } else { name };
+        format!("{name}.{}", format.extension())
+    }
+
+    fn download(&mut self, format: Format) {
+        let filename = self.filename(format);
+        let contents = match format {
+            Format::Wat => Ok(self.module.to_wat().into_bytes()),
+            Format::Wasm => self.module.to_wasm(),
+        };
+        let url = match contents {
+            Ok(contents) => BlobUrl::new(&contents, format.mime_type()),
+            Err(errors) => {
+                let more = match errors.len() {
+                    1 => String::new(),
+                    n => format!(" (and {} more)", n - 1),
+                };
+                return self.show(&format!("can't download {filename}: {}{more}", errors[0]));
+            }
+        };
+        match url {
+            Ok(url) => {
+                self.factory.download(&url, &filename);
+                self.download = Some(url);
+                self.show(&format!("downloaded {filename}"));
+            }
+            Err(e) => self.show(&format!("can't download {filename}: {e}")),
+        }
+    }
+
+    fn open_wasm(&mut self, name: &str, bytes: &[u8]) {
+        match disassemble_wasm(bytes) {
+            Ok(text) => {
+                self.module.load(text);
+                self.show(&format!("opened {name}"));
+            }
+            Err(e) => self.show(&format!("can't open {name}: {e:#}")),
+        }
+    }
+
+    fn show(&mut self, status: &str) {
+        let _scope = dom_op_scope("file panel");
+        self.status = status.to_string();
+        self.component
+            .get_mut()
+            .1
+            .1
+            .1
+            .1
+            .0
+            .get_mut()
+            .0
+            .set_data(status);
+    }
+}
+
+impl WithNode for FilePanel {
+    fn with_node(&self, f: impl FnMut(&web_sys::Node), g: AccessToken) {
+        self.0.borrow().component.with_node(f, g);
+    }
+}
+
+impl WithElement<HtmlDivElement> for FilePanel {
+    fn with_element(&self, f: impl FnMut(&HtmlDivElement), g: AccessToken) {
+        self.0.borrow().component.with_element(f, g);
+    }
+}
+
+// To audit, audit the panel's elements, then check that the status shows what was
+// recorded.
+impl Component for FilePanel {
+    fn try_audit(&self) -> Result<(), String> {
+        let inner = self.0.borrow();
+        inner.component.try_audit()?;
+        audit_eq!(inner.component.get().1.1.1.1.0.get().0.get(), &inner.status);
+        Ok(())
+    }
+}
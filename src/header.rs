@@ -0,0 +1,224 @@
+// A function's header, as the module editor shows it: fields for the function's name,
+// params, results and locals. Params and locals are separated by commas, each either
+// a named one (`$x i32`) or a list of types (`i32 i64`). Each field stands for the
+// declaration lines the function would otherwise start with (see parse.rs), so the
+// header's lines go before the body's for validation and assembly.
+
+use crate::{
+    assemble::ModuleFunction,
+    parse::{DeclKind, ParsedLine, parse_line},
+    token::is_id,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HeaderField {
+    Name,
+    Params,
+    Results,
+    Locals,
+}
+
+impl HeaderField {
+    pub const ALL: [HeaderField; 4] = [
+        HeaderField::Name,
+        HeaderField::Params,
+        HeaderField::Results,
+        HeaderField::Locals,
+    ];
+
+    pub fn placeholder(&self) -> &'static str {
+        match self {
+            HeaderField::Name => "name",
+            HeaderField::Params => "params",
+            HeaderField::Results => "results",
+            HeaderField::Locals => "locals",
+        }
+    }
+
+    fn kind(&self) -> Option<DeclKind> {
+        match self {
+            HeaderField::Name => None,
+            HeaderField::Params => Some(DeclKind::Param),
+            HeaderField::Results => Some(DeclKind::Result),
+            HeaderField::Locals => Some(DeclKind::Local),
+        }
+    }
+}
+
+// The text of each field, in the order of HeaderField::ALL.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Header {
+    pub fields: [String; 4],
+}
+
+impl Header {
+    pub fn field(&self, field: HeaderField) -> &str {
+        &self.fields[field as usize]
+    }
+
+    pub fn set_field(&mut self, field: HeaderField, text: &str) {
+        self.fields[field as usize] = text.to_string();
+    }
+
+    // The function's name (without the `$`, which is optional), if it has one.
+    pub fn name(&self) -> Result<Option<String>, String> {
+        let text = self.field(HeaderField::Name).trim();
+        let name = text.strip_prefix('$').unwrap_or(text);
+        if text.is_empty() {
+            Ok(None)
+        } else if is_id(name) {
+            Ok(Some(name.to_string()))
+        } else {
+            Err(format!("`{text}` is not a valid name"))
+        }
+    }
+
+    // The field's parts, as declarations (e.g. `(param $x i32)`).
+    fn parts(&self, field: HeaderField) -> impl Iterator<Item = String> + '_ {
+        let keyword = field.kind().map_or("", |kind| kind.keyword());
+        (self.field(field).split(','))
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .map(move |part| format!("({keyword} {part})"))
+    }
+
+    // The declaration lines of a field (none for the name), or what's wrong with it.
+    pub fn declarations(&self, field: HeaderField) -> Result<Vec<ParsedLine>, String> {
+        let Some(kind) = field.kind() else {
+            return Ok(Vec::new());
+        };
+        (self.parts(field))
+            .map(|part| match parse_line(&part) {
+                ParsedLine::Decl(decl) if decl.kind == kind => Ok(ParsedLine::Decl(decl)),
+                ParsedLine::Error(error) => Err(error.message),
+                _ => Err(format!("expected {} declarations", kind.keyword())),
+            })
+            .collect()
+    }
+
+    // The declaration lines of the fields without problems, and the problems of the
+    // others.
+    pub fn lines(&self) -> (Vec<ParsedLine>, Vec<(HeaderField, String)>) {
+        let mut lines = Vec::new();
+        let mut errors = Vec::new();
+        if let Err(error) = self.name() {
+            errors.push((HeaderField::Name, error));
+        }
+        for field in [
+            HeaderField::Params,
+            HeaderField::Results,
+            HeaderField::Locals,
+        ] {
+            match self.declarations(field) {
+                Ok(decls) => lines.extend(decls),
+                Err(error) => errors.push((field, error)),
+            }
+        }
+        (lines, errors)
+    }
+
+    // The header's declarations as text, one per line.
+    pub fn text_lines(&self) -> Vec<String> {
+        [
+            HeaderField::Params,
+            HeaderField::Results,
+            HeaderField::Locals,
+        ]
+        .into_iter()
+        .flat_map(|field| self.parts(field))
+        .collect()
+    }
+}
+
+// A function as text: its header and the lines of its body.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FunctionText {
+    pub header: Header,
+    pub lines: Vec<String>,
+}
+
+impl FunctionText {
+    // The function for the assembler: its header's declarations, then its lines (a
+    // header field with a problem declares nothing).
+    pub fn module_function(&self) -> ModuleFunction {
+        let (mut lines, _) = self.header.lines();
+        lines.extend(self.lines.iter().map(|line| parse_line(line)));
+        ModuleFunction {
+            name: self.header.name().ok().flatten(),
+            lines,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::ValType;
+
+    fn header(fields: [&str; 4]) -> Header {
+        Header {
+            fields: fields.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn fields() {
+        let header = header(["$add", "$a i32, $b i32", "i32", " i64 f32 , $t f64,"]);
+        assert_eq!(header.name(), Ok(Some("add".to_string())));
+        let (lines, errors) = header.lines();
+        assert!(errors.is_empty());
+        let decls: Vec<_> = (lines.iter())
+            .map(|line| {
+                let decl = line.declaration().unwrap();
+                (decl.kind, decl.name.as_deref(), decl.types.clone())
+            })
+            .collect();
+        assert_eq!(
+            decls,
+            vec![
+                (DeclKind::Param, Some("a"), vec![ValType::I32]),
+                (DeclKind::Param, Some("b"), vec![ValType::I32]),
+                (DeclKind::Result, None, vec![ValType::I32]),
+                (DeclKind::Local, None, vec![ValType::I64, ValType::F32]),
+                (DeclKind::Local, Some("t"), vec![ValType::F64]),
+            ]
+        );
+        assert_eq!(
+            header.text_lines(),
+            vec![
+                "(param $a i32)",
+                "(param $b i32)",
+                "(result i32)",
+                "(local i64 f32)",
+                "(local $t f64)"
+            ]
+        );
+
+        // the `$` is optional, and an empty header declares nothing
+        assert_eq!(
+            self::header(["add", "", "", ""]).name(),
+            Ok(Some("add".to_string()))
+        );
+        assert_eq!(Header::default().lines(), (Vec::new(), Vec::new()));
+        assert!(Header::default().text_lines().is_empty());
+    }
+
+    #[test]
+    fn problems() {
+        let header = header(["two words", "i32 i33", "i32", "$x i32 i64"]);
+        let (lines, errors) = header.lines();
+        assert_eq!(lines.len(), 1);
+        let fields: Vec<HeaderField> = errors.iter().map(|(field, _)| *field).collect();
+        assert_eq!(
+            fields,
+            [HeaderField::Name, HeaderField::Params, HeaderField::Locals]
+        );
+        assert_eq!(errors[0].1, "`two words` is not a valid name");
+        assert_eq!(errors[1].1, "expected a value type (i32, i64, f32 or f64)");
+        assert_eq!(errors[2].1, "a named local has only one type");
+        assert_eq!(
+            self::header(["", "", "$r i32", ""]).declarations(HeaderField::Results),
+            Err("results can't be named".to_string())
+        );
+    }
+}
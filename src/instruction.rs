@@ -0,0 +1,774 @@
+// WebAssembly instructions: the table of known instructions (mnemonic, opcode, what
+// immediates each takes, and its operand types, which the validator checks), and the
+// structured representation of one parsed instruction (see parse.rs). The table is also
+// the editor's instruction reference (see editor.rs): each instruction has a one-line
+// summary, and says when it traps (with the trap's message, as the interpreter gives
+// it).
+
+use std::{collections::HashMap, fmt, sync::OnceLock};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ValType {
+    I32,
+    I64,
+    F32,
+    F64,
+}
+
+impl ValType {
+    pub fn from_keyword(word: &str) -> Option<Self> {
+        match word {
+            "i32" => Some(ValType::I32),
+            "i64" => Some(ValType::I64),
+            "f32" => Some(ValType::F32),
+            "f64" => Some(ValType::F64),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ValType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ValType::I32 => "i32",
+            ValType::I64 => "i64",
+            ValType::F32 => "f32",
+            ValType::F64 => "f64",
+        })
+    }
+}
+
+// The kind of immediate(s) an instruction takes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImmKind {
+    None,
+    Memory,   // memory.size and memory.grow: the (implicit) memory 0
+    OptLabel, // `else` and `end` may repeat their block's label
+    I32,
+    I64,
+    F32,
+    F64,
+    Local,
+    Global,
+    Func,
+    Label,
+    BlockType,
+    BrTable,
+    MemArg(u32), // with the natural alignment (log2 of the access size)
+}
+
+// The operand types an instruction pops and pushes, for the instructions whose types
+// don't depend on their immediates or on the operand stack.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Signature {
+    pub params: &'static [ValType],
+    pub results: &'static [ValType],
+}
+
+// Operand types as the spec writes them, e.g. `[i32 i32] -> [i32]`.
+pub fn format_signature(params: &[ValType], results: &[ValType]) -> String {
+    let types = |types: &[ValType]| -> String {
+        let types: Vec<String> = types.iter().map(ValType::to_string).collect();
+        types.join(" ")
+    };
+    format!("[{}] -> [{}]", types(params), types(results))
+}
+
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&format_signature(self.params, self.results))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct InstrInfo {
+    pub mnemonic: &'static str,
+    pub opcode: u8,
+    pub imm: ImmKind,
+    pub sig: Option<Signature>, // None for control, variable and parametric instructions
+    pub typing: Option<&'static str>, // for those, how they are typed (as in the spec)
+    pub summary: &'static str,
+    pub traps: Option<&'static str>, // e.g. "if the divisor is 0 (integer divide by zero)"
+}
+
+impl InstrInfo {
+    // The instruction's operand types, e.g. `[i32 i32] -> [i32]` (or, for those whose
+    // types depend on their immediates or the stack, e.g. `[t] -> []`).
+    pub fn signature_text(&self) -> Option<String> {
+        match &self.sig {
+            Some(sig) => Some(sig.to_string()),
+            None => self.typing.map(str::to_string),
+        }
+    }
+
+    // The instruction's reference: its signature, what it does, and when it traps.
+    pub fn reference(&self) -> Vec<String> {
+        let mut ret = vec![match self.signature_text() {
+            Some(signature) => format!("{} : {signature}", self.mnemonic),
+            None => self.mnemonic.to_string(),
+        }];
+        ret.push(self.summary.to_string());
+        if let Some(traps) = self.traps {
+            ret.push(format!("Traps {traps}."));
+        }
+        ret
+    }
+}
+
+macro_rules! valtype {
+    (i32) => {
+        ValType::I32
+    };
+    (i64) => {
+        ValType::I64
+    };
+    (f32) => {
+        ValType::F32
+    };
+    (f64) => {
+        ValType::F64
+    };
+}
+
+macro_rules! instructions {
+    ($($opcode:literal $mnemonic:literal $imm:ident $(($arg:literal))?
+       $(: [$($param:ident)*] -> [$($result:ident)*])? $(~ $typing:literal)?
+       $summary:literal $(! $traps:literal)?;)*) => {
+        pub static INSTRUCTIONS: &[InstrInfo] = &[
+            $(InstrInfo {
+                mnemonic: $mnemonic,
+                opcode: $opcode,
+                imm: ImmKind::$imm $(($arg))?,
+                sig: instructions!(@sig $([$($param)*] -> [$($result)*])?),
+                typing: instructions!(@opt $($typing)?),
+                summary: $summary,
+                traps: instructions!(@opt $($traps)?),
+            },)*
+        ];
+    };
+    (@opt) => {
+        None
+    };
+    (@opt $text:literal) => {
+        Some($text)
+    };
+    (@sig) => {
+        None
+    };
+    (@sig [$($param:ident)*] -> [$($result:ident)*]) => {
+        Some(Signature {
+            params: &[$(valtype!($param)),*],
+            results: &[$(valtype!($result)),*],
+        })
+    };
+}
+
+instructions! {
+    0x00 "unreachable" None ~ "[t1*] -> [t2*]"
+        "Trap at once."
+        ! "always (unreachable)";
+    0x01 "nop" None : [] -> []
+        "Do nothing.";
+    0x02 "block" BlockType ~ "[] -> [t*]"
+        "Begin a block: a branch to its label goes to its `end`.";
+    0x03 "loop" BlockType ~ "[] -> [t*]"
+        "Begin a loop: a branch to its label goes back to its start.";
+    0x04 "if" BlockType ~ "[i32] -> [t*]"
+        "Begin a block that runs if the condition is nonzero (and otherwise its `else` part).";
+    0x05 "else" OptLabel
+        "Begin the part of an `if` that runs if the condition is zero.";
+    0x0b "end" OptLabel
+        "End a block, loop or if.";
+    0x0c "br" Label ~ "[t1* t*] -> [t2*]"
+        "Branch to a label: out of a block or if, or back to the start of a loop.";
+    0x0d "br_if" Label ~ "[t* i32] -> [t*]"
+        "Branch to a label if the condition is nonzero.";
+    0x0e "br_table" BrTable ~ "[t1* t* i32] -> [t2*]"
+        "Branch to the label an index chooses (the last label if the index is out of range).";
+    0x0f "return" None ~ "[t1* t*] -> [t2*]"
+        "Return from the function, with its results.";
+    0x10 "call" Func ~ "[t1*] -> [t2*]"
+        "Call a function, with its params from the stack."
+        ! "if the call stack is exhausted (call stack exhausted)";
+    0x1a "drop" None ~ "[t] -> []"
+        "Throw away the top operand.";
+    0x1b "select" None ~ "[t t i32] -> [t]"
+        "The first operand if the condition is nonzero, otherwise the second.";
+    0x20 "local.get" Local ~ "[] -> [t]"
+        "Push the value of a local.";
+    0x21 "local.set" Local ~ "[t] -> []"
+        "Pop a value into a local.";
+    0x22 "local.tee" Local ~ "[t] -> [t]"
+        "Copy the top operand into a local (leaving it on the stack).";
+    0x23 "global.get" Global ~ "[] -> [t]"
+        "Push the value of a global.";
+    0x24 "global.set" Global ~ "[t] -> []"
+        "Pop a value into a mutable global.";
+    0x28 "i32.load" MemArg(2) : [i32] -> [i32]
+        "Load an i32 from memory at the address (plus the offset)."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x29 "i64.load" MemArg(3) : [i32] -> [i64]
+        "Load an i64 from memory at the address (plus the offset)."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x2a "f32.load" MemArg(2) : [i32] -> [f32]
+        "Load an f32 from memory at the address (plus the offset)."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x2b "f64.load" MemArg(3) : [i32] -> [f64]
+        "Load an f64 from memory at the address (plus the offset)."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x2c "i32.load8_s" MemArg(0) : [i32] -> [i32]
+        "Load 8 bits from memory at the address (plus the offset), and sign-extend them to an i32."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x2d "i32.load8_u" MemArg(0) : [i32] -> [i32]
+        "Load 8 bits from memory at the address (plus the offset), and zero-extend them to an i32."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x2e "i32.load16_s" MemArg(1) : [i32] -> [i32]
+        "Load 16 bits from memory at the address (plus the offset), and sign-extend them to an i32."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x2f "i32.load16_u" MemArg(1) : [i32] -> [i32]
+        "Load 16 bits from memory at the address (plus the offset), and zero-extend them to an i32."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x30 "i64.load8_s" MemArg(0) : [i32] -> [i64]
+        "Load 8 bits from memory at the address (plus the offset), and sign-extend them to an i64."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x31 "i64.load8_u" MemArg(0) : [i32] -> [i64]
+        "Load 8 bits from memory at the address (plus the offset), and zero-extend them to an i64."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x32 "i64.load16_s" MemArg(1) : [i32] -> [i64]
+        "Load 16 bits from memory at the address (plus the offset), and sign-extend them to an i64."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x33 "i64.load16_u" MemArg(1) : [i32] -> [i64]
+        "Load 16 bits from memory at the address (plus the offset), and zero-extend them to an i64."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x34 "i64.load32_s" MemArg(2) : [i32] -> [i64]
+        "Load 32 bits from memory at the address (plus the offset), and sign-extend them to an i64."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x35 "i64.load32_u" MemArg(2) : [i32] -> [i64]
+        "Load 32 bits from memory at the address (plus the offset), and zero-extend them to an i64."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x36 "i32.store" MemArg(2) : [i32 i32] -> []
+        "Store an i32 into memory at the address (plus the offset)."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x37 "i64.store" MemArg(3) : [i32 i64] -> []
+        "Store an i64 into memory at the address (plus the offset)."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x38 "f32.store" MemArg(2) : [i32 f32] -> []
+        "Store an f32 into memory at the address (plus the offset)."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x39 "f64.store" MemArg(3) : [i32 f64] -> []
+        "Store an f64 into memory at the address (plus the offset)."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x3a "i32.store8" MemArg(0) : [i32 i32] -> []
+        "Store the low 8 bits of an i32 into memory at the address (plus the offset)."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x3b "i32.store16" MemArg(1) : [i32 i32] -> []
+        "Store the low 16 bits of an i32 into memory at the address (plus the offset)."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x3c "i64.store8" MemArg(0) : [i32 i64] -> []
+        "Store the low 8 bits of an i64 into memory at the address (plus the offset)."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x3d "i64.store16" MemArg(1) : [i32 i64] -> []
+        "Store the low 16 bits of an i64 into memory at the address (plus the offset)."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x3e "i64.store32" MemArg(2) : [i32 i64] -> []
+        "Store the low 32 bits of an i64 into memory at the address (plus the offset)."
+        ! "if the access is out of bounds (out of bounds memory access)";
+    0x3f "memory.size" Memory : [] -> [i32]
+        "Push the memory's size, in 64 KiB pages.";
+    0x40 "memory.grow" Memory : [i32] -> [i32]
+        "Grow the memory by a number of pages, and push its old size (or -1 if it can't grow).";
+    0x41 "i32.const" I32 : [] -> [i32]
+        "Push a constant i32.";
+    0x42 "i64.const" I64 : [] -> [i64]
+        "Push a constant i64.";
+    0x43 "f32.const" F32 : [] -> [f32]
+        "Push a constant f32.";
+    0x44 "f64.const" F64 : [] -> [f64]
+        "Push a constant f64.";
+    0x45 "i32.eqz" None : [i32] -> [i32]
+        "1 if the operand is zero, otherwise 0.";
+    0x46 "i32.eq" None : [i32 i32] -> [i32]
+        "1 if the operands are equal, otherwise 0.";
+    0x47 "i32.ne" None : [i32 i32] -> [i32]
+        "1 if the operands are not equal, otherwise 0.";
+    0x48 "i32.lt_s" None : [i32 i32] -> [i32]
+        "1 if the first operand is less than the second (as signed), otherwise 0.";
+    0x49 "i32.lt_u" None : [i32 i32] -> [i32]
+        "1 if the first operand is less than the second (as unsigned), otherwise 0.";
+    0x4a "i32.gt_s" None : [i32 i32] -> [i32]
+        "1 if the first operand is greater than the second (as signed), otherwise 0.";
+    0x4b "i32.gt_u" None : [i32 i32] -> [i32]
+        "1 if the first operand is greater than the second (as unsigned), otherwise 0.";
+    0x4c "i32.le_s" None : [i32 i32] -> [i32]
+        "1 if the first operand is less than or equal to the second (as signed), otherwise 0.";
+    0x4d "i32.le_u" None : [i32 i32] -> [i32]
+        "1 if the first operand is less than or equal to the second (as unsigned), otherwise 0.";
+    0x4e "i32.ge_s" None : [i32 i32] -> [i32]
+        "1 if the first operand is greater than or equal to the second (as signed), otherwise 0.";
+    0x4f "i32.ge_u" None : [i32 i32] -> [i32]
+        "1 if the first operand is greater than or equal to the second (as unsigned), otherwise 0.";
+    0x50 "i64.eqz" None : [i64] -> [i32]
+        "1 if the operand is zero, otherwise 0.";
+    0x51 "i64.eq" None : [i64 i64] -> [i32]
+        "1 if the operands are equal, otherwise 0.";
+    0x52 "i64.ne" None : [i64 i64] -> [i32]
+        "1 if the operands are not equal, otherwise 0.";
+    0x53 "i64.lt_s" None : [i64 i64] -> [i32]
+        "1 if the first operand is less than the second (as signed), otherwise 0.";
+    0x54 "i64.lt_u" None : [i64 i64] -> [i32]
+        "1 if the first operand is less than the second (as unsigned), otherwise 0.";
+    0x55 "i64.gt_s" None : [i64 i64] -> [i32]
+        "1 if the first operand is greater than the second (as signed), otherwise 0.";
+    0x56 "i64.gt_u" None : [i64 i64] -> [i32]
+        "1 if the first operand is greater than the second (as unsigned), otherwise 0.";
+    0x57 "i64.le_s" None : [i64 i64] -> [i32]
+        "1 if the first operand is less than or equal to the second (as signed), otherwise 0.";
+    0x58 "i64.le_u" None : [i64 i64] -> [i32]
+        "1 if the first operand is less than or equal to the second (as unsigned), otherwise 0.";
+    0x59 "i64.ge_s" None : [i64 i64] -> [i32]
+        "1 if the first operand is greater than or equal to the second (as signed), otherwise 0.";
+    0x5a "i64.ge_u" None : [i64 i64] -> [i32]
+        "1 if the first operand is greater than or equal to the second (as unsigned), otherwise 0.";
+    0x5b "f32.eq" None : [f32 f32] -> [i32]
+        "1 if the operands are equal, otherwise 0.";
+    0x5c "f32.ne" None : [f32 f32] -> [i32]
+        "1 if the operands are not equal, otherwise 0.";
+    0x5d "f32.lt" None : [f32 f32] -> [i32]
+        "1 if the first operand is less than the second, otherwise 0 (0 if either is NaN).";
+    0x5e "f32.gt" None : [f32 f32] -> [i32]
+        "1 if the first operand is greater than the second, otherwise 0 (0 if either is NaN).";
+    0x5f "f32.le" None : [f32 f32] -> [i32]
+        "1 if the first operand is less than or equal to the second, otherwise 0 (0 if either is NaN).";
+    0x60 "f32.ge" None : [f32 f32] -> [i32]
+        "1 if the first operand is greater than or equal to the second, otherwise 0 (0 if either is NaN).";
+    0x61 "f64.eq" None : [f64 f64] -> [i32]
+        "1 if the operands are equal, otherwise 0.";
+    0x62 "f64.ne" None : [f64 f64] -> [i32]
+        "1 if the operands are not equal, otherwise 0.";
+    0x63 "f64.lt" None : [f64 f64] -> [i32]
+        "1 if the first operand is less than the second, otherwise 0 (0 if either is NaN).";
+    0x64 "f64.gt" None : [f64 f64] -> [i32]
+        "1 if the first operand is greater than the second, otherwise 0 (0 if either is NaN).";
+    0x65 "f64.le" None : [f64 f64] -> [i32]
+        "1 if the first operand is less than or equal to the second, otherwise 0 (0 if either is NaN).";
+    0x66 "f64.ge" None : [f64 f64] -> [i32]
+        "1 if the first operand is greater than or equal to the second, otherwise 0 (0 if either is NaN).";
+    0x67 "i32.clz" None : [i32] -> [i32]
+        "Count the leading zero bits.";
+    0x68 "i32.ctz" None : [i32] -> [i32]
+        "Count the trailing zero bits.";
+    0x69 "i32.popcnt" None : [i32] -> [i32]
+        "Count the one bits.";
+    0x6a "i32.add" None : [i32 i32] -> [i32]
+        "Add (wrapping around on overflow).";
+    0x6b "i32.sub" None : [i32 i32] -> [i32]
+        "Subtract the second operand from the first (wrapping around on overflow).";
+    0x6c "i32.mul" None : [i32 i32] -> [i32]
+        "Multiply (wrapping around on overflow).";
+    0x6d "i32.div_s" None : [i32 i32] -> [i32]
+        "Divide the first operand by the second (as signed), rounding toward zero."
+        ! "if the divisor is 0 (integer divide by zero), or on -2^31 / -1 (integer overflow)";
+    0x6e "i32.div_u" None : [i32 i32] -> [i32]
+        "Divide the first operand by the second (as unsigned), rounding down."
+        ! "if the divisor is 0 (integer divide by zero)";
+    0x6f "i32.rem_s" None : [i32 i32] -> [i32]
+        "The remainder of dividing the first operand by the second (as signed), with the sign of the first."
+        ! "if the divisor is 0 (integer divide by zero)";
+    0x70 "i32.rem_u" None : [i32 i32] -> [i32]
+        "The remainder of dividing the first operand by the second (as unsigned)."
+        ! "if the divisor is 0 (integer divide by zero)";
+    0x71 "i32.and" None : [i32 i32] -> [i32]
+        "Bitwise and.";
+    0x72 "i32.or" None : [i32 i32] -> [i32]
+        "Bitwise or.";
+    0x73 "i32.xor" None : [i32 i32] -> [i32]
+        "Bitwise exclusive or.";
+    0x74 "i32.shl" None : [i32 i32] -> [i32]
+        "Shift the first operand left by the second (modulo 32).";
+    0x75 "i32.shr_s" None : [i32 i32] -> [i32]
+        "Shift the first operand right by the second (modulo 32), copying the sign bit.";
+    0x76 "i32.shr_u" None : [i32 i32] -> [i32]
+        "Shift the first operand right by the second (modulo 32), filling with zeros.";
+    0x77 "i32.rotl" None : [i32 i32] -> [i32]
+        "Rotate the first operand left by the second (modulo 32).";
+    0x78 "i32.rotr" None : [i32 i32] -> [i32]
+        "Rotate the first operand right by the second (modulo 32).";
+    0x79 "i64.clz" None : [i64] -> [i64]
+        "Count the leading zero bits.";
+    0x7a "i64.ctz" None : [i64] -> [i64]
+        "Count the trailing zero bits.";
+    0x7b "i64.popcnt" None : [i64] -> [i64]
+        "Count the one bits.";
+    0x7c "i64.add" None : [i64 i64] -> [i64]
+        "Add (wrapping around on overflow).";
+    0x7d "i64.sub" None : [i64 i64] -> [i64]
+        "Subtract the second operand from the first (wrapping around on overflow).";
+    0x7e "i64.mul" None : [i64 i64] -> [i64]
+        "Multiply (wrapping around on overflow).";
+    0x7f "i64.div_s" None : [i64 i64] -> [i64]
+        "Divide the first operand by the second (as signed), rounding toward zero."
+        ! "if the divisor is 0 (integer divide by zero), or on -2^63 / -1 (integer overflow)";
+    0x80 "i64.div_u" None : [i64 i64] -> [i64]
+        "Divide the first operand by the second (as unsigned), rounding down."
+        ! "if the divisor is 0 (integer divide by zero)";
+    0x81 "i64.rem_s" None : [i64 i64] -> [i64]
+        "The remainder of dividing the first operand by the second (as signed), with the sign of the first."
+        ! "if the divisor is 0 (integer divide by zero)";
+    0x82 "i64.rem_u" None : [i64 i64] -> [i64]
+        "The remainder of dividing the first operand by the second (as unsigned)."
+        ! "if the divisor is 0 (integer divide by zero)";
+    0x83 "i64.and" None : [i64 i64] -> [i64]
+        "Bitwise and.";
+    0x84 "i64.or" None : [i64 i64] -> [i64]
+        "Bitwise or.";
+    0x85 "i64.xor" None : [i64 i64] -> [i64]
+        "Bitwise exclusive or.";
+    0x86 "i64.shl" None : [i64 i64] -> [i64]
+        "Shift the first operand left by the second (modulo 64).";
+    0x87 "i64.shr_s" None : [i64 i64] -> [i64]
+        "Shift the first operand right by the second (modulo 64), copying the sign bit.";
+    0x88 "i64.shr_u" None : [i64 i64] -> [i64]
+        "Shift the first operand right by the second (modulo 64), filling with zeros.";
+    0x89 "i64.rotl" None : [i64 i64] -> [i64]
+        "Rotate the first operand left by the second (modulo 64).";
+    0x8a "i64.rotr" None : [i64 i64] -> [i64]
+        "Rotate the first operand right by the second (modulo 64).";
+    0x8b "f32.abs" None : [f32] -> [f32]
+        "The absolute value.";
+    0x8c "f32.neg" None : [f32] -> [f32]
+        "Negate.";
+    0x8d "f32.ceil" None : [f32] -> [f32]
+        "Round up to an integer.";
+    0x8e "f32.floor" None : [f32] -> [f32]
+        "Round down to an integer.";
+    0x8f "f32.trunc" None : [f32] -> [f32]
+        "Round toward zero to an integer.";
+    0x90 "f32.nearest" None : [f32] -> [f32]
+        "Round to the nearest integer (ties to even).";
+    0x91 "f32.sqrt" None : [f32] -> [f32]
+        "The square root.";
+    0x92 "f32.add" None : [f32 f32] -> [f32]
+        "Add.";
+    0x93 "f32.sub" None : [f32 f32] -> [f32]
+        "Subtract the second operand from the first.";
+    0x94 "f32.mul" None : [f32 f32] -> [f32]
+        "Multiply.";
+    0x95 "f32.div" None : [f32 f32] -> [f32]
+        "Divide the first operand by the second.";
+    0x96 "f32.min" None : [f32 f32] -> [f32]
+        "The smaller operand (NaN if either is NaN).";
+    0x97 "f32.max" None : [f32 f32] -> [f32]
+        "The larger operand (NaN if either is NaN).";
+    0x98 "f32.copysign" None : [f32 f32] -> [f32]
+        "The first operand, with the sign of the second.";
+    0x99 "f64.abs" None : [f64] -> [f64]
+        "The absolute value.";
+    0x9a "f64.neg" None : [f64] -> [f64]
+        "Negate.";
+    0x9b "f64.ceil" None : [f64] -> [f64]
+        "Round up to an integer.";
+    0x9c "f64.floor" None : [f64] -> [f64]
+        "Round down to an integer.";
+    0x9d "f64.trunc" None : [f64] -> [f64]
+        "Round toward zero to an integer.";
+    0x9e "f64.nearest" None : [f64] -> [f64]
+        "Round to the nearest integer (ties to even).";
+    0x9f "f64.sqrt" None : [f64] -> [f64]
+        "The square root.";
+    0xa0 "f64.add" None : [f64 f64] -> [f64]
+        "Add.";
+    0xa1 "f64.sub" None : [f64 f64] -> [f64]
+        "Subtract the second operand from the first.";
+    0xa2 "f64.mul" None : [f64 f64] -> [f64]
+        "Multiply.";
+    0xa3 "f64.div" None : [f64 f64] -> [f64]
+        "Divide the first operand by the second.";
+    0xa4 "f64.min" None : [f64 f64] -> [f64]
+        "The smaller operand (NaN if either is NaN).";
+    0xa5 "f64.max" None : [f64 f64] -> [f64]
+        "The larger operand (NaN if either is NaN).";
+    0xa6 "f64.copysign" None : [f64 f64] -> [f64]
+        "The first operand, with the sign of the second.";
+    0xa7 "i32.wrap_i64" None : [i64] -> [i32]
+        "The low 32 bits of an i64.";
+    0xa8 "i32.trunc_f32_s" None : [f32] -> [i32]
+        "Truncate an f32 toward zero to a signed i32."
+        ! "if the operand is NaN (invalid conversion to integer) or out of range (integer overflow)";
+    0xa9 "i32.trunc_f32_u" None : [f32] -> [i32]
+        "Truncate an f32 toward zero to a unsigned i32."
+        ! "if the operand is NaN (invalid conversion to integer) or out of range (integer overflow)";
+    0xaa "i32.trunc_f64_s" None : [f64] -> [i32]
+        "Truncate an f64 toward zero to a signed i32."
+        ! "if the operand is NaN (invalid conversion to integer) or out of range (integer overflow)";
+    0xab "i32.trunc_f64_u" None : [f64] -> [i32]
+        "Truncate an f64 toward zero to a unsigned i32."
+        ! "if the operand is NaN (invalid conversion to integer) or out of range (integer overflow)";
+    0xac "i64.extend_i32_s" None : [i32] -> [i64]
+        "Sign-extend an i32 to an i64.";
+    0xad "i64.extend_i32_u" None : [i32] -> [i64]
+        "Zero-extend an i32 to an i64.";
+    0xae "i64.trunc_f32_s" None : [f32] -> [i64]
+        "Truncate an f32 toward zero to a signed i64."
+        ! "if the operand is NaN (invalid conversion to integer) or out of range (integer overflow)";
+    0xaf "i64.trunc_f32_u" None : [f32] -> [i64]
+        "Truncate an f32 toward zero to a unsigned i64."
+        ! "if the operand is NaN (invalid conversion to integer) or out of range (integer overflow)";
+    0xb0 "i64.trunc_f64_s" None : [f64] -> [i64]
+        "Truncate an f64 toward zero to a signed i64."
+        ! "if the operand is NaN (invalid conversion to integer) or out of range (integer overflow)";
+    0xb1 "i64.trunc_f64_u" None : [f64] -> [i64]
+        "Truncate an f64 toward zero to a unsigned i64."
+        ! "if the operand is NaN (invalid conversion to integer) or out of range (integer overflow)";
+    0xb2 "f32.convert_i32_s" None : [i32] -> [f32]
+        "Convert a signed i32 to the nearest f32.";
+    0xb3 "f32.convert_i32_u" None : [i32] -> [f32]
+        "Convert a unsigned i32 to the nearest f32.";
+    0xb4 "f32.convert_i64_s" None : [i64] -> [f32]
+        "Convert a signed i64 to the nearest f32.";
+    0xb5 "f32.convert_i64_u" None : [i64] -> [f32]
+        "Convert a unsigned i64 to the nearest f32.";
+    0xb6 "f32.demote_f64" None : [f64] -> [f32]
+        "Round an f64 to the nearest f32.";
+    0xb7 "f64.convert_i32_s" None : [i32] -> [f64]
+        "Convert a signed i32 to the nearest f64.";
+    0xb8 "f64.convert_i32_u" None : [i32] -> [f64]
+        "Convert a unsigned i32 to the nearest f64.";
+    0xb9 "f64.convert_i64_s" None : [i64] -> [f64]
+        "Convert a signed i64 to the nearest f64.";
+    0xba "f64.convert_i64_u" None : [i64] -> [f64]
+        "Convert a unsigned i64 to the nearest f64.";
+    0xbb "f64.promote_f32" None : [f32] -> [f64]
+        "Convert an f32 to an f64 (exactly).";
+    0xbc "i32.reinterpret_f32" None : [f32] -> [i32]
+        "The bits of an f32, as an i32.";
+    0xbd "i64.reinterpret_f64" None : [f64] -> [i64]
+        "The bits of an f64, as an i64.";
+    0xbe "f32.reinterpret_i32" None : [i32] -> [f32]
+        "The bits of an i32, as an f32.";
+    0xbf "f64.reinterpret_i64" None : [i64] -> [f64]
+        "The bits of an i64, as an f64.";
+    0xc0 "i32.extend8_s" None : [i32] -> [i32]
+        "Sign-extend the low 8 bits.";
+    0xc1 "i32.extend16_s" None : [i32] -> [i32]
+        "Sign-extend the low 16 bits.";
+    0xc2 "i64.extend8_s" None : [i64] -> [i64]
+        "Sign-extend the low 8 bits.";
+    0xc3 "i64.extend16_s" None : [i64] -> [i64]
+        "Sign-extend the low 16 bits.";
+    0xc4 "i64.extend32_s" None : [i64] -> [i64]
+        "Sign-extend the low 32 bits.";
+}
+
+pub fn lookup(mnemonic: &str) -> Option<&'static InstrInfo> {
+    static BY_MNEMONIC: OnceLock<HashMap<&'static str, &'static InstrInfo>> = OnceLock::new();
+    BY_MNEMONIC
+        .get_or_init(|| {
+            INSTRUCTIONS
+                .iter()
+                .map(|info| (info.mnemonic, info))
+                .collect()
+        })
+        .get(mnemonic)
+        .copied()
+}
+
+pub fn lookup_opcode(opcode: u8) -> Option<&'static InstrInfo> {
+    static BY_OPCODE: OnceLock<HashMap<u8, &'static InstrInfo>> = OnceLock::new();
+    BY_OPCODE
+        .get_or_init(|| {
+            INSTRUCTIONS
+                .iter()
+                .map(|info| (info.opcode, info))
+                .collect()
+        })
+        .get(&opcode)
+        .copied()
+}
+
+// A reference to a local, global, function or label: by number, or by $name.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Index {
+    Num(u32),
+    Id(String),
+}
+
+impl fmt::Display for Index {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Index::Num(num) => write!(f, "{num}"),
+            Index::Id(id) => write!(f, "${id}"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BlockType {
+    Empty,
+    Value(ValType),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemArg {
+    pub offset: u32,
+    pub align: u32, // log2 of the alignment
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Immediate {
+    None,
+    I32(i32),
+    I64(i64),
+    F32(u32), // bit patterns, to keep NaN payloads
+    F64(u64),
+    Index(Index),
+    Block {
+        label: Option<String>,
+        ty: BlockType,
+    },
+    BrTable {
+        labels: Vec<Index>,
+        default: Index,
+    },
+    MemArg(MemArg),
+    Label(Option<String>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Instruction {
+    pub info: &'static InstrInfo,
+    pub imm: Immediate,
+}
+
+impl Instruction {
+    pub fn new(mnemonic: &str, imm: Immediate) -> Self {
+        Self {
+            info: lookup(mnemonic).expect("known mnemonic"),
+            imm,
+        }
+    }
+
+    pub fn mnemonic(&self) -> &'static str {
+        self.info.mnemonic
+    }
+
+    // Whether the instruction needs the module to have a memory.
+    pub fn uses_memory(&self) -> bool {
+        matches!(self.info.imm, ImmKind::MemArg(_) | ImmKind::Memory)
+    }
+}
+
+// Instructions print in the same syntax that parse.rs accepts.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.mnemonic())?;
+        match &self.imm {
+            Immediate::None | Immediate::Label(None) => Ok(()),
+            Immediate::I32(value) => write!(f, " {value}"),
+            Immediate::I64(value) => write!(f, " {value}"),
+            Immediate::F32(bits) => write!(f, " {}", format_f32(*bits)),
+            Immediate::F64(bits) => write!(f, " {}", format_f64(*bits)),
+            Immediate::Index(index) => write!(f, " {index}"),
+            Immediate::Block { label, ty } => {
+                if let Some(label) = label {
+                    write!(f, " ${label}")?;
+                }
+                match ty {
+                    BlockType::Empty => Ok(()),
+                    BlockType::Value(ty) => write!(f, " (result {ty})"),
+                }
+            }
+            Immediate::BrTable { labels, default } => {
+                for label in labels {
+                    write!(f, " {label}")?;
+                }
+                write!(f, " {default}")
+            }
+            Immediate::MemArg(memarg) => {
+                if memarg.offset != 0 {
+                    write!(f, " offset={}", memarg.offset)?;
+                }
+                match self.info.imm {
+                    ImmKind::MemArg(natural) if natural == memarg.align => Ok(()),
+                    _ => write!(f, " align={}", 1u64 << memarg.align),
+                }
+            }
+            Immediate::Label(Some(label)) => write!(f, " ${label}"),
+        }
+    }
+}
+
+// Print a float so that it reads back exactly: NaNs with their payload (in the
+// text format's `nan:0x...` syntax), and other values in Rust's shortest round-trip form.
+pub fn format_f32(bits: u32) -> String {
+    format_float(f32::from_bits(bits) as f64, bits as u64, 23)
+}
+
+pub fn format_f64(bits: u64) -> String {
+    format_float(f64::from_bits(bits), bits, 52)
+}
+
+fn format_float(value: f64, bits: u64, mantissa_bits: u32) -> String {
+    if value.is_nan() {
+        let payload = bits & ((1 << mantissa_bits) - 1);
+        let sign = if value.is_sign_negative() { "-" } else { "" };
+        if payload == 1 << (mantissa_bits - 1) {
+            format!("{sign}nan")
+        } else {
+            format!("{sign}nan:{payload:#x}")
+        }
+    } else if value.is_infinite() {
+        if value < 0.0 { "-inf" } else { "inf" }.to_string()
+    } else if mantissa_bits == 23 {
+        format!("{:?}", value as f32)
+    } else {
+        format!("{value:?}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn references() {
+        let reference = |mnemonic: &str| lookup(mnemonic).unwrap().reference();
+        assert_eq!(
+            reference("i32.add"),
+            [
+                "i32.add : [i32 i32] -> [i32]",
+                "Add (wrapping around on overflow)."
+            ]
+        );
+        assert_eq!(
+            reference("i64.rem_u"),
+            [
+                "i64.rem_u : [i64 i64] -> [i64]",
+                "The remainder of dividing the first operand by the second (as unsigned).",
+                "Traps if the divisor is 0 (integer divide by zero).",
+            ]
+        );
+        assert_eq!(reference("local.tee")[0], "local.tee : [t] -> [t]");
+        assert_eq!(reference("end")[0], "end");
+    }
+
+    #[test]
+    fn every_instruction_is_described() {
+        for info in INSTRUCTIONS {
+            // only `else` and `end` have no operand types of their own
+            let untyped = matches!(info.mnemonic, "else" | "end");
+            assert_eq!(
+                info.signature_text().is_none(),
+                untyped,
+                "{}",
+                info.mnemonic
+            );
+            assert!(info.sig.is_none() || info.typing.is_none());
+            assert!(info.summary.ends_with('.'), "{}", info.mnemonic);
+            assert!(
+                (info.traps.iter()).all(|traps| traps.ends_with(')')),
+                "{}: the trap's message",
+                info.mnemonic
+            );
+        }
+    }
+}
@@ -0,0 +1,1613 @@
+// An interpreter for assembled modules, one instruction at a time, so the debugger can
+// show the machine's state between any two instructions: the operand stack, and for
+// each call its locals and labels (the blocks it's inside), plus the memory (which
+// starts with the module's data segments) and the globals. A call to one of the host's
+// print functions records the value in `printed`.
+//
+// Each step also records how to undo it (the innermost call's frame and operands as
+// they were, and what it overwrote in memory and globals), so execution can step
+// backwards, or seek to any earlier step; seeking forwards executes again (which
+// arrives at the same state, as execution is deterministic). A step that traps is
+// undone at once, leaving the machine as it was before the trapping instruction.
+// The machine also remembers the latest load or store, and (from the last step's undo
+// record) which bytes of memory the last step changed.
+//
+// A call to any other import goes to the machine's host function, if it has one (so
+// a script can link modules together; see script.rs), and traps otherwise.
+//
+// Instantiating a module fails if its memory is larger than the interpreter allows, or
+// if a data segment doesn't fit in the memory (the spec's "out of bounds memory
+// access").
+
+use crate::{
+    instruction::{BlockType, Immediate, Index, Instruction, ValType},
+    module::{HOST_MODULE, Module, PRINT_FUNCS},
+    value::Value,
+};
+use anyhow::{Result, anyhow, bail};
+use std::{collections::VecDeque, fmt, rc::Rc};
+
+pub const PAGE_SIZE: usize = 65536;
+const MAX_PAGES: usize = 16384; // 1 GiB, rather than the 4 GiB a module could ask for
+const MAX_CALL_DEPTH: usize = 1000;
+// How many of the latest steps can be undone.
+const MAX_HISTORY: usize = 100_000;
+
+// Why execution stopped early (with the message the spec's tests expect).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Trap {
+    Unreachable,
+    DivideByZero,
+    IntegerOverflow,
+    InvalidConversion,
+    OutOfBounds,
+    CallStackExhausted,
+    Host(String),
+}
+
+impl fmt::Display for Trap {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Trap::Unreachable => "unreachable",
+            Trap::DivideByZero => "integer divide by zero",
+            Trap::IntegerOverflow => "integer overflow",
+            Trap::InvalidConversion => "invalid conversion to integer",
+            Trap::OutOfBounds => "out of bounds memory access",
+            Trap::CallStackExhausted => "call stack exhausted",
+            Trap::Host(message) => message,
+        })
+    }
+}
+
+impl std::error::Error for Trap {}
+
+// Calls an import other than the print functions: given its module name, its name and
+// the args, returns the results.
+pub type Host = Rc<dyn Fn(&str, &str, &[Value]) -> Result<Vec<Value>, Trap>>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Status {
+    Running,
+    Finished(Vec<Value>),
+}
+
+// A block, loop or if that execution is inside of.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Label {
+    pub mnemonic: &'static str,
+    pub start: usize, // the instruction that opened it
+    pub end: usize,   // its `end`
+    pub arity: usize, // how many values a branch to it carries
+    pub height: usize,
+}
+
+// The bytes of memory that a load read or a store wrote.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Access {
+    pub address: usize,
+    pub size: usize,
+    pub store: bool,
+}
+
+// One function call in progress.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    pub func: u32,
+    pub pc: usize, // the next instruction (the body's length for the final `end`)
+    pub locals: Vec<Value>,
+    pub labels: Vec<Label>,
+    height: usize,
+    arity: usize,
+}
+
+// Where each block of a function body ends (and where an `if` has its `else`).
+struct BlockMap {
+    ends: Vec<usize>,
+    elses: Vec<Option<usize>>,
+}
+
+impl BlockMap {
+    fn new(body: &[Instruction]) -> Self {
+        let mut ends = vec![0; body.len()];
+        let mut elses = vec![None; body.len()];
+        let mut open = Vec::new();
+        for (pc, instr) in body.iter().enumerate() {
+            match instr.mnemonic() {
+                "block" | "loop" | "if" => open.push(pc),
+                "else" => elses[*open.last().expect("open if")] = Some(pc),
+                "end" => ends[open.pop().expect("open block")] = pc,
+                _ => {}
+            }
+        }
+        Self { ends, elses }
+    }
+}
+
+pub struct Machine {
+    module: Module,
+    blocks: Vec<BlockMap>, // for each of the module's functions
+    state: State,
+}
+
+struct State {
+    stack: Vec<Value>,
+    frames: Vec<Frame>,
+    globals: Vec<Value>,
+    memory: Vec<u8>,
+    printed: Vec<Value>,
+    last_access: Option<Access>,
+    finished: Option<Result<Vec<Value>, Trap>>,
+    steps: usize,
+    history: VecDeque<Undo>,
+    writes: Vec<(usize, Vec<u8>)>, // by the step in progress: the bytes overwritten
+    global_writes: Vec<(usize, Value)>,
+    host: Option<Host>,
+}
+
+// How to undo a step. A step changes only the innermost call (which it may return
+// from, or call another function from) and the operands above its stack height.
+struct Undo {
+    depth: usize,
+    frame: Frame,
+    stack: Vec<Value>, // above the frame's height
+    memory_len: usize,
+    writes: Vec<(usize, Vec<u8>)>,
+    global_writes: Vec<(usize, Value)>,
+    printed: usize,
+    last_access: Option<Access>,
+}
+
+fn default_value(ty: ValType) -> Value {
+    match ty {
+        ValType::I32 => Value::I32(0),
+        ValType::I64 => Value::I64(0),
+        ValType::F32 => Value::F32(0.0),
+        ValType::F64 => Value::F64(0.0),
+    }
+}
+
+impl Machine {
+    pub fn new(module: Module) -> Result<Self> {
+        let blocks = (module.funcs.iter())
+            .map(|func| BlockMap::new(&func.body))
+            .collect();
+        let pages = module.memory.map_or(0, |limits| limits.min as usize);
+        if pages > MAX_PAGES {
+            bail!(
+                "a memory of {pages} pages is larger than the interpreter's limit of {MAX_PAGES}"
+            );
+        }
+        let mut memory = vec![0; pages * PAGE_SIZE];
+        for data in &module.data {
+            let start = data.offset as usize;
+            let target = (start.checked_add(data.bytes.len()))
+                .and_then(|end| memory.get_mut(start..end))
+                .ok_or(Trap::OutOfBounds)?;
+            target.copy_from_slice(&data.bytes);
+        }
+        let globals = module.globals.iter().map(|global| global.init).collect();
+        Ok(Self {
+            module,
+            blocks,
+            state: State {
+                stack: Vec::new(),
+                frames: Vec::new(),
+                globals,
+                memory,
+                printed: Vec::new(),
+                last_access: None,
+                finished: None,
+                steps: 0,
+                history: VecDeque::new(),
+                writes: Vec::new(),
+                global_writes: Vec::new(),
+                host: None,
+            },
+        })
+    }
+
+    pub fn module(&self) -> &Module {
+        &self.module
+    }
+
+    // Have calls to the module's other imports go to `host`.
+    pub fn set_host(&mut self, host: Host) {
+        self.state.host = Some(host);
+    }
+
+    // Start a call to a function (by index, counting imports), dropping any call in
+    // progress. The memory and globals keep their contents. An imported function is
+    // called at once (so the call has finished).
+    pub fn invoke(&mut self, func: u32, args: &[Value]) -> Result<()> {
+        let Some(ty) = self.module.func_type(func) else {
+            bail!("no function {func}");
+        };
+        let types: Vec<ValType> = args.iter().map(|arg| arg.ty()).collect();
+        if types != ty.params {
+            bail!("function {func} takes {:?}, not {types:?}", ty.params);
+        }
+        let state = &mut self.state;
+        state.stack.clear();
+        state.frames.clear();
+        state.printed.clear();
+        state.last_access = None;
+        state.finished = None;
+        state.steps = 0;
+        state.history.clear();
+        state.stack.extend_from_slice(args);
+        match func.checked_sub(self.module.imports.len() as u32) {
+            Some(index) => state.call(&self.module, index as usize)?,
+            None => {
+                let finished = (state.call_host(&self.module, func as usize))
+                    .map(|()| std::mem::take(&mut state.stack));
+                state.finished = Some(finished);
+            }
+        }
+        Ok(())
+    }
+
+    // Execute one instruction.
+    pub fn step(&mut self) -> Result<Status, Trap> {
+        let state = &mut self.state;
+        if let Some(finished) = &state.finished {
+            return finished.clone().map(Status::Finished);
+        }
+        match state.step(&self.module, &self.blocks) {
+            Ok(Status::Running) => Ok(Status::Running),
+            outcome => {
+                let finished = outcome.map(|status| match status {
+                    Status::Finished(results) => results,
+                    Status::Running => unreachable!(),
+                });
+                state.finished = Some(finished.clone());
+                finished.map(Status::Finished)
+            }
+        }
+    }
+
+    // Undo the last step (if there's one to undo).
+    pub fn step_back(&mut self) -> bool {
+        let state = &mut self.state;
+        let Some(undo) = state.history.pop_back() else {
+            return false;
+        };
+        state.undo(undo);
+        state.steps -= 1;
+        state.finished = None;
+        true
+    }
+
+    // How many steps the call has taken, and the earliest step it can go back to.
+    pub fn steps(&self) -> usize {
+        self.state.steps
+    }
+
+    pub fn earliest_step(&self) -> usize {
+        self.state.steps - self.state.history.len()
+    }
+
+    // Go back or forward to the state after `step` steps (or as near as possible: no
+    // earlier than earliest_step, and no later than where the call finishes or traps).
+    pub fn seek(&mut self, step: usize) -> Result<Status, Trap> {
+        while self.steps() > step.max(self.earliest_step()) {
+            self.step_back();
+        }
+        let mut status = match &self.state.finished {
+            Some(finished) => finished.clone().map(Status::Finished)?,
+            None => Status::Running,
+        };
+        while self.steps() < step && status == Status::Running {
+            status = self.step()?;
+        }
+        Ok(status)
+    }
+
+    // Execute until the call finishes (or traps), or `max_steps` instructions have run.
+    pub fn run(&mut self, max_steps: usize) -> Result<Status, Trap> {
+        let mut status = Status::Running;
+        for _ in 0..max_steps {
+            status = self.step()?;
+            if status != Status::Running {
+                break;
+            }
+        }
+        Ok(status)
+    }
+
+    // Execute at least one instruction, then stop before the next instruction on
+    // `line` (in any function), or when the call finishes or `max_steps` have run.
+    pub fn run_to_line(&mut self, line: usize, max_steps: usize) -> Result<Status, Trap> {
+        self.run_while(max_steps, |machine| machine.current_line() != Some(line))
+    }
+
+    // Like run_to_line, stopping at any of the breakpoints: each a function (counting
+    // the imports) and one of its lines.
+    pub fn run_to_breakpoint(
+        &mut self,
+        breakpoints: &[(u32, usize)],
+        max_steps: usize,
+    ) -> Result<Status, Trap> {
+        self.run_while(max_steps, |machine| !machine.at_breakpoint(breakpoints))
+    }
+
+    // Execute one instruction, and if it was a call, the rest of the call as well
+    // (unless it reaches a breakpoint first).
+    pub fn step_over(
+        &mut self,
+        breakpoints: &[(u32, usize)],
+        max_steps: usize,
+    ) -> Result<Status, Trap> {
+        let depth = self.state.frames.len();
+        self.run_while(max_steps, |machine| {
+            machine.state.frames.len() > depth && !machine.at_breakpoint(breakpoints)
+        })
+    }
+
+    fn at_breakpoint(&self, breakpoints: &[(u32, usize)]) -> bool {
+        (self.current_func().zip(self.current_line()))
+            .is_some_and(|location| breakpoints.contains(&location))
+    }
+
+    // Step at least once, then for as long as the call is running and `more` says to.
+    fn run_while(
+        &mut self,
+        max_steps: usize,
+        more: impl Fn(&Self) -> bool,
+    ) -> Result<Status, Trap> {
+        let mut status = Status::Running;
+        for _ in 0..max_steps {
+            status = self.step()?;
+            if status != Status::Running || !more(self) {
+                break;
+            }
+        }
+        Ok(status)
+    }
+
+    // Call a function and run it to the end.
+    pub fn call(&mut self, func: u32, args: &[Value], max_steps: usize) -> Result<Vec<Value>> {
+        self.invoke(func, args)?;
+        match self.run(max_steps) {
+            Ok(Status::Finished(results)) => Ok(results),
+            Ok(Status::Running) => bail!("still running after {max_steps} steps"),
+            Err(trap) => Err(anyhow!(trap)),
+        }
+    }
+
+    // How the call ended, once it has.
+    pub fn finished(&self) -> Option<&Result<Vec<Value>, Trap>> {
+        self.state.finished.as_ref()
+    }
+
+    // The function of the innermost call (counting the imports).
+    pub fn current_func(&self) -> Option<u32> {
+        self.state.frames.last().map(|frame| frame.func)
+    }
+
+    // The source line of the next instruction (None between calls, and at the
+    // implicit `end` of a function).
+    pub fn current_line(&self) -> Option<usize> {
+        let frame = self.state.frames.last()?;
+        let func = &self.module.funcs[frame.func as usize - self.module.imports.len()];
+        func.lines.get(frame.pc).copied()
+    }
+
+    pub fn stack(&self) -> &[Value] {
+        &self.state.stack
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.state.frames
+    }
+
+    // The locals and labels of the innermost call.
+    pub fn locals(&self) -> &[Value] {
+        self.state.frames.last().map_or(&[], |frame| &frame.locals)
+    }
+
+    pub fn labels(&self) -> &[Label] {
+        self.state.frames.last().map_or(&[], |frame| &frame.labels)
+    }
+
+    pub fn globals(&self) -> &[Value] {
+        &self.state.globals
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.state.memory
+    }
+
+    pub fn printed(&self) -> &[Value] {
+        &self.state.printed
+    }
+
+    // The latest load or store of the call (in any step so far).
+    pub fn last_access(&self) -> Option<Access> {
+        self.state.last_access
+    }
+
+    // The addresses of the bytes that the last step changed, in order.
+    pub fn changed_bytes(&self) -> Vec<usize> {
+        let Some(undo) = self.state.history.back() else {
+            return Vec::new();
+        };
+        let memory = &self.state.memory;
+        let mut ret: Vec<usize> = (undo.writes.iter())
+            .flat_map(|(address, old)| {
+                (old.iter().enumerate())
+                    .map(move |(index, byte)| (address + index, *byte))
+                    .filter(|(address, byte)| memory.get(*address) != Some(byte))
+                    .map(|(address, _)| address)
+            })
+            .collect();
+        ret.sort_unstable();
+        ret.dedup();
+        ret
+    }
+}
+
+// Pop an operand of a known type (the module has been validated).
+macro_rules! pop {
+    ($state:expr, $variant:ident) => {
+        match $state.stack.pop() {
+            Some(Value::$variant(value)) => value,
+            other => panic!("expected {} operand, found {other:?}", stringify!($variant)),
+        }
+    };
+}
+
+macro_rules! unop {
+    ($state:expr, $in:ident => $out:ident, |$a:ident| $body:expr) => {{
+        let $a = pop!($state, $in);
+        $state.stack.push(Value::$out($body));
+    }};
+}
+
+macro_rules! binop {
+    ($state:expr, $in:ident => $out:ident, |$a:ident, $b:ident| $body:expr) => {{
+        let $b = pop!($state, $in);
+        let $a = pop!($state, $in);
+        $state.stack.push(Value::$out($body));
+    }};
+}
+
+// Binary operators that can trap.
+macro_rules! binop_checked {
+    ($state:expr, $in:ident, |$a:ident, $b:ident| $body:expr) => {{
+        let $b = pop!($state, $in);
+        let $a = pop!($state, $in);
+        $state.stack.push(Value::$in($body?));
+    }};
+}
+
+fn nonzero<T: Eq + Default>(b: T) -> Result<(), Trap> {
+    if b == T::default() {
+        Err(Trap::DivideByZero)
+    } else {
+        Ok(())
+    }
+}
+
+// min and max: NaN if either is NaN, and -0 is less than +0.
+fn fmin(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else if a == b {
+        f64::from_bits(a.to_bits() | b.to_bits())
+    } else {
+        a.min(b)
+    }
+}
+
+fn fmax(a: f64, b: f64) -> f64 {
+    if a.is_nan() || b.is_nan() {
+        f64::NAN
+    } else if a == b {
+        f64::from_bits(a.to_bits() & b.to_bits())
+    } else {
+        a.max(b)
+    }
+}
+
+fn fmin32(a: f32, b: f32) -> f32 {
+    if a.is_nan() || b.is_nan() {
+        f32::NAN
+    } else if a == b {
+        f32::from_bits(a.to_bits() | b.to_bits())
+    } else {
+        a.min(b)
+    }
+}
+
+fn fmax32(a: f32, b: f32) -> f32 {
+    if a.is_nan() || b.is_nan() {
+        f32::NAN
+    } else if a == b {
+        f32::from_bits(a.to_bits() & b.to_bits())
+    } else {
+        a.max(b)
+    }
+}
+
+// Truncate a float to an integer in [min, max) (bounds as floats), or trap.
+fn trunc(value: f64, min: f64, max: f64) -> Result<f64, Trap> {
+    if value.is_nan() {
+        return Err(Trap::InvalidConversion);
+    }
+    let value = value.trunc();
+    if value < min || value >= max {
+        return Err(Trap::IntegerOverflow);
+    }
+    Ok(value)
+}
+
+const I32_MIN: f64 = -2147483648.0;
+const I32_END: f64 = 2147483648.0;
+const U32_END: f64 = 4294967296.0;
+const I64_MIN: f64 = -9223372036854775808.0;
+const I64_END: f64 = 9223372036854775808.0;
+const U64_END: f64 = 18446744073709551616.0;
+
+impl State {
+    // Enter a function (of this module): its params are on top of the stack.
+    fn call(&mut self, module: &Module, index: usize) -> Result<(), Trap> {
+        if self.frames.len() >= MAX_CALL_DEPTH {
+            return Err(Trap::CallStackExhausted);
+        }
+        let func = &module.funcs[index];
+        let ty = &module.types[func.type_index as usize];
+        let height = self.stack.len() - ty.params.len();
+        let mut locals = self.stack.split_off(height);
+        locals.extend(func.locals.iter().map(|ty| default_value(*ty)));
+        self.frames.push(Frame {
+            func: (index + module.imports.len()) as u32,
+            pc: 0,
+            locals,
+            labels: Vec::new(),
+            height,
+            arity: ty.results.len(),
+        });
+        Ok(())
+    }
+
+    fn call_host(&mut self, module: &Module, func: usize) -> Result<(), Trap> {
+        let import = &module.imports[func];
+        let ty = &module.types[import.type_index as usize];
+        let args = self.stack.split_off(self.stack.len() - ty.params.len());
+        if import.module == HOST_MODULE && PRINT_FUNCS.iter().any(|(name, _)| *name == import.name)
+        {
+            self.printed.extend(args);
+            return Ok(());
+        }
+        let Some(host) = &self.host else {
+            return Err(Trap::Host(format!(
+                "unknown import {}.{}",
+                import.module, import.name
+            )));
+        };
+        let results = host(&import.module, &import.name, &args)?;
+        if !results.iter().map(Value::ty).eq(ty.results.iter().copied()) {
+            return Err(Trap::Host(format!(
+                "import {}.{} returned the wrong types",
+                import.module, import.name
+            )));
+        }
+        self.stack.extend(results);
+        Ok(())
+    }
+
+    // Leave the innermost call, keeping its results. Returns them if it was the last.
+    fn return_from_call(&mut self) -> Option<Vec<Value>> {
+        let frame = self.frames.pop().expect("frame");
+        let results = self.stack.split_off(self.stack.len() - frame.arity);
+        self.stack.truncate(frame.height);
+        if self.frames.is_empty() {
+            return Some(results);
+        }
+        self.stack.extend(results);
+        None
+    }
+
+    fn frame(&mut self) -> &mut Frame {
+        self.frames.last_mut().expect("frame")
+    }
+
+    // Branch to the label `depth` levels out (the call itself if there's no such block).
+    fn branch(&mut self, depth: u32) -> Option<Vec<Value>> {
+        let frame = self.frames.last_mut().expect("frame");
+        let depth = depth as usize;
+        if depth >= frame.labels.len() {
+            return self.return_from_call();
+        }
+        let label = frame.labels[frame.labels.len() - 1 - depth].clone();
+        let values = self.stack.split_off(self.stack.len() - label.arity);
+        self.stack.truncate(label.height);
+        self.stack.extend(values);
+        let frame = self.frame();
+        if label.mnemonic == "loop" {
+            frame.labels.truncate(frame.labels.len() - depth);
+            frame.pc = label.start + 1;
+        } else {
+            frame.labels.truncate(frame.labels.len() - 1 - depth);
+            frame.pc = label.end + 1;
+        }
+        None
+    }
+
+    // The `size` bytes at a dynamic address plus an offset, if they're in bounds.
+    fn address(&self, base: i32, offset: u32, size: usize) -> Result<usize, Trap> {
+        let address = base as u32 as u64 + offset as u64;
+        if address + size as u64 > self.memory.len() as u64 {
+            return Err(Trap::OutOfBounds);
+        }
+        Ok(address as usize)
+    }
+
+    fn load<const N: usize>(&mut self, offset: u32) -> Result<[u8; N], Trap> {
+        let base = pop!(self, I32);
+        let address = self.address(base, offset, N)?;
+        self.last_access = Some(Access {
+            address,
+            size: N,
+            store: false,
+        });
+        Ok(self.memory[address..address + N]
+            .try_into()
+            .expect("N bytes"))
+    }
+
+    fn store(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Trap> {
+        let base = pop!(self, I32);
+        let address = self.address(base, offset, bytes.len())?;
+        let range = address..address + bytes.len();
+        self.last_access = Some(Access {
+            address,
+            size: bytes.len(),
+            store: true,
+        });
+        self.writes
+            .push((address, self.memory[range.clone()].to_vec()));
+        self.memory[range].copy_from_slice(bytes);
+        Ok(())
+    }
+
+    // Execute an instruction, keeping a record to undo it; if it traps, undo it now.
+    fn step(&mut self, module: &Module, blocks: &[BlockMap]) -> Result<Status, Trap> {
+        let frame = self.frames.last().expect("a call in progress").clone();
+        let stack = self.stack[frame.height..].to_vec();
+        let mut undo = Undo {
+            depth: self.frames.len(),
+            frame,
+            stack,
+            memory_len: self.memory.len(),
+            writes: Vec::new(),
+            global_writes: Vec::new(),
+            printed: self.printed.len(),
+            last_access: self.last_access,
+        };
+        let outcome = self.execute(module, blocks);
+        undo.writes = std::mem::take(&mut self.writes);
+        undo.global_writes = std::mem::take(&mut self.global_writes);
+        match outcome {
+            Ok(_) => {
+                if self.history.len() == MAX_HISTORY {
+                    self.history.pop_front();
+                }
+                self.history.push_back(undo);
+                self.steps += 1;
+            }
+            Err(_) => self.undo(undo),
+        }
+        outcome
+    }
+
+    fn undo(&mut self, undo: Undo) {
+        self.frames.truncate(undo.depth - 1);
+        self.stack.truncate(undo.frame.height);
+        self.stack.extend(undo.stack);
+        self.frames.push(undo.frame);
+        for (address, bytes) in undo.writes.into_iter().rev() {
+            self.memory[address..address + bytes.len()].copy_from_slice(&bytes);
+        }
+        self.memory.truncate(undo.memory_len);
+        for (index, value) in undo.global_writes.into_iter().rev() {
+            self.globals[index] = value;
+        }
+        self.printed.truncate(undo.printed);
+        self.last_access = undo.last_access;
+    }
+
+    fn execute(&mut self, module: &Module, blocks: &[BlockMap]) -> Result<Status, Trap> {
+        let frame = self.frames.last().expect("a call in progress");
+        let index = frame.func as usize - module.imports.len();
+        let pc = frame.pc;
+        let Some(instr) = module.funcs[index].body.get(pc) else {
+            // the function's final `end`
+            return Ok(match self.return_from_call() {
+                Some(results) => Status::Finished(results),
+                None => Status::Running,
+            });
+        };
+        let blocks = &blocks[index];
+        self.frame().pc += 1;
+        let index_imm = || match &instr.imm {
+            Immediate::Index(Index::Num(num)) => *num,
+            imm => panic!("unresolved immediate {imm:?}"),
+        };
+        let offset = match &instr.imm {
+            Immediate::MemArg(memarg) => memarg.offset,
+            _ => 0,
+        };
+
+        match instr.info.opcode {
+            0x00 => return Err(Trap::Unreachable),
+            0x01 => {}
+            0x02..=0x04 => {
+                let Immediate::Block { ty, .. } = &instr.imm else {
+                    panic!("block without a block type");
+                };
+                let results = match ty {
+                    BlockType::Empty => 0,
+                    BlockType::Value(_) => 1,
+                };
+                let mnemonic = instr.mnemonic();
+                let end = blocks.ends[pc];
+                if mnemonic == "if" && pop!(self, I32) == 0 {
+                    match blocks.elses[pc] {
+                        Some(else_pc) => self.frame().pc = else_pc + 1,
+                        None => {
+                            self.frame().pc = end + 1;
+                            return Ok(Status::Running);
+                        }
+                    }
+                }
+                let height = self.stack.len();
+                self.frame().labels.push(Label {
+                    mnemonic,
+                    start: pc,
+                    end,
+                    arity: if mnemonic == "loop" { 0 } else { results },
+                    height,
+                });
+            }
+            // the end of an `if`'s first branch: skip the second
+            0x05 => {
+                let label = self.frame().labels.pop().expect("label");
+                self.frame().pc = label.end + 1;
+            }
+            0x0b => {
+                self.frame().labels.pop();
+            }
+            0x0c => {
+                if let Some(results) = self.branch(index_imm()) {
+                    return Ok(Status::Finished(results));
+                }
+            }
+            0x0d => {
+                if pop!(self, I32) != 0
+                    && let Some(results) = self.branch(index_imm())
+                {
+                    return Ok(Status::Finished(results));
+                }
+            }
+            0x0e => {
+                let Immediate::BrTable { labels, default } = &instr.imm else {
+                    panic!("br_table without labels");
+                };
+                let choice = pop!(self, I32) as u32 as usize;
+                let target = labels.get(choice).unwrap_or(default);
+                let Index::Num(depth) = target else {
+                    panic!("unresolved label {target}");
+                };
+                if let Some(results) = self.branch(*depth) {
+                    return Ok(Status::Finished(results));
+                }
+            }
+            0x0f => {
+                if let Some(results) = self.return_from_call() {
+                    return Ok(Status::Finished(results));
+                }
+            }
+            0x10 => {
+                let func = index_imm() as usize;
+                match func.checked_sub(module.imports.len()) {
+                    Some(index) => self.call(module, index)?,
+                    None => self.call_host(module, func)?,
+                }
+            }
+            0x1a => {
+                self.stack.pop();
+            }
+            0x1b => {
+                let condition = pop!(self, I32);
+                let second = self.stack.pop().expect("operand");
+                let first = self.stack.pop().expect("operand");
+                self.stack.push(if condition != 0 { first } else { second });
+            }
+            0x20 => {
+                let value = self.frame().locals[index_imm() as usize];
+                self.stack.push(value);
+            }
+            0x21 => {
+                let value = self.stack.pop().expect("operand");
+                self.frame().locals[index_imm() as usize] = value;
+            }
+            0x22 => {
+                let value = *self.stack.last().expect("operand");
+                self.frame().locals[index_imm() as usize] = value;
+            }
+            0x23 => self.stack.push(self.globals[index_imm() as usize]),
+            0x24 => {
+                let index = index_imm() as usize;
+                let value = self.stack.pop().expect("operand");
+                let old = std::mem::replace(&mut self.globals[index], value);
+                self.global_writes.push((index, old));
+            }
+
+            0x28 => {
+                let bytes = self.load(offset)?;
+                self.stack.push(Value::I32(i32::from_le_bytes(bytes)));
+            }
+            0x29 => {
+                let bytes = self.load(offset)?;
+                self.stack.push(Value::I64(i64::from_le_bytes(bytes)));
+            }
+            0x2a => {
+                let bytes = self.load(offset)?;
+                self.stack.push(Value::F32(f32::from_le_bytes(bytes)));
+            }
+            0x2b => {
+                let bytes = self.load(offset)?;
+                self.stack.push(Value::F64(f64::from_le_bytes(bytes)));
+            }
+            0x2c => {
+                let bytes = self.load::<1>(offset)?;
+                self.stack.push(Value::I32(bytes[0] as i8 as i32));
+            }
+            0x2d => {
+                let bytes = self.load::<1>(offset)?;
+                self.stack.push(Value::I32(bytes[0] as i32));
+            }
+            0x2e => {
+                let bytes = self.load(offset)?;
+                self.stack
+                    .push(Value::I32(i16::from_le_bytes(bytes) as i32));
+            }
+            0x2f => {
+                let bytes = self.load(offset)?;
+                self.stack
+                    .push(Value::I32(u16::from_le_bytes(bytes) as i32));
+            }
+            0x30 => {
+                let bytes = self.load::<1>(offset)?;
+                self.stack.push(Value::I64(bytes[0] as i8 as i64));
+            }
+            0x31 => {
+                let bytes = self.load::<1>(offset)?;
+                self.stack.push(Value::I64(bytes[0] as i64));
+            }
+            0x32 => {
+                let bytes = self.load(offset)?;
+                self.stack
+                    .push(Value::I64(i16::from_le_bytes(bytes) as i64));
+            }
+            0x33 => {
+                let bytes = self.load(offset)?;
+                self.stack
+                    .push(Value::I64(u16::from_le_bytes(bytes) as i64));
+            }
+            0x34 => {
+                let bytes = self.load(offset)?;
+                self.stack
+                    .push(Value::I64(i32::from_le_bytes(bytes) as i64));
+            }
+            0x35 => {
+                let bytes = self.load(offset)?;
+                self.stack
+                    .push(Value::I64(u32::from_le_bytes(bytes) as i64));
+            }
+            0x36 => {
+                let value = pop!(self, I32);
+                self.store(offset, &value.to_le_bytes())?;
+            }
+            0x37 => {
+                let value = pop!(self, I64);
+                self.store(offset, &value.to_le_bytes())?;
+            }
+            0x38 => {
+                let value = pop!(self, F32);
+                self.store(offset, &value.to_le_bytes())?;
+            }
+            0x39 => {
+                let value = pop!(self, F64);
+                self.store(offset, &value.to_le_bytes())?;
+            }
+            0x3a => {
+                let value = pop!(self, I32);
+                self.store(offset, &value.to_le_bytes()[..1])?;
+            }
+            0x3b => {
+                let value = pop!(self, I32);
+                self.store(offset, &value.to_le_bytes()[..2])?;
+            }
+            0x3c => {
+                let value = pop!(self, I64);
+                self.store(offset, &value.to_le_bytes()[..1])?;
+            }
+            0x3d => {
+                let value = pop!(self, I64);
+                self.store(offset, &value.to_le_bytes()[..2])?;
+            }
+            0x3e => {
+                let value = pop!(self, I64);
+                self.store(offset, &value.to_le_bytes()[..4])?;
+            }
+            0x3f => {
+                let pages = self.memory.len() / PAGE_SIZE;
+                self.stack.push(Value::I32(pages as i32));
+            }
+            0x40 => {
+                let delta = pop!(self, I32) as u32 as usize;
+                let pages = self.memory.len() / PAGE_SIZE;
+                let max = (module.memory.and_then(|limits| limits.max))
+                    .map_or(MAX_PAGES, |max| (max as usize).min(MAX_PAGES));
+                if delta > max.saturating_sub(pages) {
+                    self.stack.push(Value::I32(-1));
+                } else {
+                    self.memory.resize((pages + delta) * PAGE_SIZE, 0);
+                    self.stack.push(Value::I32(pages as i32));
+                }
+            }
+
+            0x41 => match instr.imm {
+                Immediate::I32(value) => self.stack.push(Value::I32(value)),
+                _ => panic!("i32.const without a value"),
+            },
+            0x42 => match instr.imm {
+                Immediate::I64(value) => self.stack.push(Value::I64(value)),
+                _ => panic!("i64.const without a value"),
+            },
+            0x43 => match instr.imm {
+                Immediate::F32(bits) => self.stack.push(Value::F32(f32::from_bits(bits))),
+                _ => panic!("f32.const without a value"),
+            },
+            0x44 => match instr.imm {
+                Immediate::F64(bits) => self.stack.push(Value::F64(f64::from_bits(bits))),
+                _ => panic!("f64.const without a value"),
+            },
+
+            0x45 => unop!(self, I32 => I32, |a| (a == 0) as i32),
+            0x46 => binop!(self, I32 => I32, |a, b| (a == b) as i32),
+            0x47 => binop!(self, I32 => I32, |a, b| (a != b) as i32),
+            0x48 => binop!(self, I32 => I32, |a, b| (a < b) as i32),
+            0x49 => binop!(self, I32 => I32, |a, b| ((a as u32) < (b as u32)) as i32),
+            0x4a => binop!(self, I32 => I32, |a, b| (a > b) as i32),
+            0x4b => binop!(self, I32 => I32, |a, b| ((a as u32) > (b as u32)) as i32),
+            0x4c => binop!(self, I32 => I32, |a, b| (a <= b) as i32),
+            0x4d => binop!(self, I32 => I32, |a, b| ((a as u32) <= (b as u32)) as i32),
+            0x4e => binop!(self, I32 => I32, |a, b| (a >= b) as i32),
+            0x4f => binop!(self, I32 => I32, |a, b| ((a as u32) >= (b as u32)) as i32),
+            0x50 => unop!(self, I64 => I32, |a| (a == 0) as i32),
+            0x51 => binop!(self, I64 => I32, |a, b| (a == b) as i32),
+            0x52 => binop!(self, I64 => I32, |a, b| (a != b) as i32),
+            0x53 => binop!(self, I64 => I32, |a, b| (a < b) as i32),
+            0x54 => binop!(self, I64 => I32, |a, b| ((a as u64) < (b as u64)) as i32),
+            0x55 => binop!(self, I64 => I32, |a, b| (a > b) as i32),
+            0x56 => binop!(self, I64 => I32, |a, b| ((a as u64) > (b as u64)) as i32),
+            0x57 => binop!(self, I64 => I32, |a, b| (a <= b) as i32),
+            0x58 => binop!(self, I64 => I32, |a, b| ((a as u64) <= (b as u64)) as i32),
+            0x59 => binop!(self, I64 => I32, |a, b| (a >= b) as i32),
+            0x5a => binop!(self, I64 => I32, |a, b| ((a as u64) >= (b as u64)) as i32),
+            0x5b => binop!(self, F32 => I32, |a, b| (a == b) as i32),
+            0x5c => binop!(self, F32 => I32, |a, b| (a != b) as i32),
+            0x5d => binop!(self, F32 => I32, |a, b| (a < b) as i32),
+            0x5e => binop!(self, F32 => I32, |a, b| (a > b) as i32),
+            0x5f => binop!(self, F32 => I32, |a, b| (a <= b) as i32),
+            0x60 => binop!(self, F32 => I32, |a, b| (a >= b) as i32),
+            0x61 => binop!(self, F64 => I32, |a, b| (a == b) as i32),
+            0x62 => binop!(self, F64 => I32, |a, b| (a != b) as i32),
+            0x63 => binop!(self, F64 => I32, |a, b| (a < b) as i32),
+            0x64 => binop!(self, F64 => I32, |a, b| (a > b) as i32),
+            0x65 => binop!(self, F64 => I32, |a, b| (a <= b) as i32),
+            0x66 => binop!(self, F64 => I32, |a, b| (a >= b) as i32),
+
+            0x67 => unop!(self, I32 => I32, |a| a.leading_zeros() as i32),
+            0x68 => unop!(self, I32 => I32, |a| a.trailing_zeros() as i32),
+            0x69 => unop!(self, I32 => I32, |a| a.count_ones() as i32),
+            0x6a => binop!(self, I32 => I32, |a, b| a.wrapping_add(b)),
+            0x6b => binop!(self, I32 => I32, |a, b| a.wrapping_sub(b)),
+            0x6c => binop!(self, I32 => I32, |a, b| a.wrapping_mul(b)),
+            0x6d => binop_checked!(self, I32, |a, b| nonzero(b)
+                .and_then(|_| a.checked_div(b).ok_or(Trap::IntegerOverflow))),
+            0x6e => binop_checked!(self, I32, |a, b| nonzero(b)
+                .map(|_| ((a as u32) / (b as u32)) as i32)),
+            0x6f => binop_checked!(self, I32, |a, b| nonzero(b).map(|_| a.wrapping_rem(b))),
+            0x70 => binop_checked!(self, I32, |a, b| nonzero(b)
+                .map(|_| ((a as u32) % (b as u32)) as i32)),
+            0x71 => binop!(self, I32 => I32, |a, b| a & b),
+            0x72 => binop!(self, I32 => I32, |a, b| a | b),
+            0x73 => binop!(self, I32 => I32, |a, b| a ^ b),
+            0x74 => binop!(self, I32 => I32, |a, b| a.wrapping_shl(b as u32)),
+            0x75 => binop!(self, I32 => I32, |a, b| a.wrapping_shr(b as u32)),
+            0x76 => binop!(self, I32 => I32, |a, b| (a as u32).wrapping_shr(b as u32) as i32),
+            0x77 => binop!(self, I32 => I32, |a, b| a.rotate_left(b as u32 % 32)),
+            0x78 => binop!(self, I32 => I32, |a, b| a.rotate_right(b as u32 % 32)),
+            0x79 => unop!(self, I64 => I64, |a| a.leading_zeros() as i64),
+            0x7a => unop!(self, I64 => I64, |a| a.trailing_zeros() as i64),
+            0x7b => unop!(self, I64 => I64, |a| a.count_ones() as i64),
+            0x7c => binop!(self, I64 => I64, |a, b| a.wrapping_add(b)),
+            0x7d => binop!(self, I64 => I64, |a, b| a.wrapping_sub(b)),
+            0x7e => binop!(self, I64 => I64, |a, b| a.wrapping_mul(b)),
+            0x7f => binop_checked!(self, I64, |a, b| nonzero(b)
+                .and_then(|_| a.checked_div(b).ok_or(Trap::IntegerOverflow))),
+            0x80 => binop_checked!(self, I64, |a, b| nonzero(b)
+                .map(|_| ((a as u64) / (b as u64)) as i64)),
+            0x81 => binop_checked!(self, I64, |a, b| nonzero(b).map(|_| a.wrapping_rem(b))),
+            0x82 => binop_checked!(self, I64, |a, b| nonzero(b)
+                .map(|_| ((a as u64) % (b as u64)) as i64)),
+            0x83 => binop!(self, I64 => I64, |a, b| a & b),
+            0x84 => binop!(self, I64 => I64, |a, b| a | b),
+            0x85 => binop!(self, I64 => I64, |a, b| a ^ b),
+            0x86 => binop!(self, I64 => I64, |a, b| a.wrapping_shl(b as u32)),
+            0x87 => binop!(self, I64 => I64, |a, b| a.wrapping_shr(b as u32)),
+            0x88 => binop!(self, I64 => I64, |a, b| (a as u64).wrapping_shr(b as u32) as i64),
+            0x89 => binop!(self, I64 => I64, |a, b| a.rotate_left((b as u64 % 64) as u32)),
+            0x8a => binop!(self, I64 => I64, |a, b| a.rotate_right((b as u64 % 64) as u32)),
+
+            0x8b => unop!(self, F32 => F32, |a| f32::from_bits(a.to_bits() & 0x7fff_ffff)),
+            0x8c => unop!(self, F32 => F32, |a| f32::from_bits(a.to_bits() ^ 0x8000_0000)),
+            0x8d => unop!(self, F32 => F32, |a| a.ceil()),
+            0x8e => unop!(self, F32 => F32, |a| a.floor()),
+            0x8f => unop!(self, F32 => F32, |a| a.trunc()),
+            0x90 => unop!(self, F32 => F32, |a| a.round_ties_even()),
+            0x91 => unop!(self, F32 => F32, |a| a.sqrt()),
+            0x92 => binop!(self, F32 => F32, |a, b| a + b),
+            0x93 => binop!(self, F32 => F32, |a, b| a - b),
+            0x94 => binop!(self, F32 => F32, |a, b| a * b),
+            0x95 => binop!(self, F32 => F32, |a, b| a / b),
+            0x96 => binop!(self, F32 => F32, |a, b| fmin32(a, b)),
+            0x97 => binop!(self, F32 => F32, |a, b| fmax32(a, b)),
+            0x98 => binop!(self, F32 => F32, |a, b| f32::from_bits(
+                (a.to_bits() & 0x7fff_ffff) | (b.to_bits() & 0x8000_0000)
+            )),
+            0x99 => unop!(self, F64 => F64, |a| f64::from_bits(a.to_bits() & (u64::MAX >> 1))),
+            0x9a => unop!(self, F64 => F64, |a| f64::from_bits(a.to_bits() ^ (1 << 63))),
+            0x9b => unop!(self, F64 => F64, |a| a.ceil()),
+            0x9c => unop!(self, F64 => F64, |a| a.floor()),
+            0x9d => unop!(self, F64 => F64, |a| a.trunc()),
+            0x9e => unop!(self, F64 => F64, |a| a.round_ties_even()),
+            0x9f => unop!(self, F64 => F64, |a| a.sqrt()),
+            0xa0 => binop!(self, F64 => F64, |a, b| a + b),
+            0xa1 => binop!(self, F64 => F64, |a, b| a - b),
+            0xa2 => binop!(self, F64 => F64, |a, b| a * b),
+            0xa3 => binop!(self, F64 => F64, |a, b| a / b),
+            0xa4 => binop!(self, F64 => F64, |a, b| fmin(a, b)),
+            0xa5 => binop!(self, F64 => F64, |a, b| fmax(a, b)),
+            0xa6 => binop!(self, F64 => F64, |a, b| f64::from_bits(
+                (a.to_bits() & (u64::MAX >> 1)) | (b.to_bits() & (1 << 63))
+            )),
+
+            0xa7 => unop!(self, I64 => I32, |a| a as i32),
+            0xa8 => unop!(self, F32 => I32, |a| trunc(a as f64, I32_MIN, I32_END)? as i32),
+            0xa9 => unop!(self, F32 => I32, |a| trunc(a as f64, 0.0, U32_END)? as u32 as i32),
+            0xaa => unop!(self, F64 => I32, |a| trunc(a, I32_MIN, I32_END)? as i32),
+            0xab => unop!(self, F64 => I32, |a| trunc(a, 0.0, U32_END)? as u32 as i32),
+            0xac => unop!(self, I32 => I64, |a| a as i64),
+            0xad => unop!(self, I32 => I64, |a| a as u32 as i64),
+            0xae => unop!(self, F32 => I64, |a| trunc(a as f64, I64_MIN, I64_END)? as i64),
+            0xaf => unop!(self, F32 => I64, |a| trunc(a as f64, 0.0, U64_END)? as u64 as i64),
+            0xb0 => unop!(self, F64 => I64, |a| trunc(a, I64_MIN, I64_END)? as i64),
+            0xb1 => unop!(self, F64 => I64, |a| trunc(a, 0.0, U64_END)? as u64 as i64),
+            0xb2 => unop!(self, I32 => F32, |a| a as f32),
+            0xb3 => unop!(self, I32 => F32, |a| a as u32 as f32),
+            0xb4 => unop!(self, I64 => F32, |a| a as f32),
+            0xb5 => unop!(self, I64 => F32, |a| a as u64 as f32),
+            0xb6 => unop!(self, F64 => F32, |a| a as f32),
+            0xb7 => unop!(self, I32 => F64, |a| a as f64),
+            0xb8 => unop!(self, I32 => F64, |a| a as u32 as f64),
+            0xb9 => unop!(self, I64 => F64, |a| a as f64),
+            0xba => unop!(self, I64 => F64, |a| a as u64 as f64),
+            0xbb => unop!(self, F32 => F64, |a| a as f64),
+            0xbc => unop!(self, F32 => I32, |a| a.to_bits() as i32),
+            0xbd => unop!(self, F64 => I64, |a| a.to_bits() as i64),
+            0xbe => unop!(self, I32 => F32, |a| f32::from_bits(a as u32)),
+            0xbf => unop!(self, I64 => F64, |a| f64::from_bits(a as u64)),
+            0xc0 => unop!(self, I32 => I32, |a| a as i8 as i32),
+            0xc1 => unop!(self, I32 => I32, |a| a as i16 as i32),
+            0xc2 => unop!(self, I64 => I64, |a| a as i8 as i64),
+            0xc3 => unop!(self, I64 => I64, |a| a as i16 as i64),
+            0xc4 => unop!(self, I64 => I64, |a| a as i32 as i64),
+            opcode => panic!("unknown opcode {opcode:#x}"),
+        }
+        Ok(Status::Running)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assemble::{ModuleFunction, assemble, assemble_module},
+        module::Limits,
+        parse::parse_line,
+        sections::{ModuleSections, Section, Sections},
+    };
+
+    const STEPS: usize = 100_000;
+
+    fn machine(text: &str) -> Machine {
+        let parsed: Vec<_> = text.lines().map(parse_line).collect();
+        Machine::new(assemble(&parsed).unwrap()).unwrap()
+    }
+
+    fn call(text: &str, args: &[Value]) -> Result<Vec<Value>, String> {
+        machine(text)
+            .call(4, args, STEPS)
+            .map_err(|error| error.to_string())
+    }
+
+    #[test]
+    fn numeric() {
+        let binop = |ty: &str, op: &str, a: &str, b: &str| {
+            call(
+                &format!("(result {ty})\n{ty}.const {a}\n{ty}.const {b}\n{ty}.{op}"),
+                &[],
+            )
+        };
+        assert_eq!(
+            binop("i32", "add", "0x7fffffff", "1"),
+            Ok(vec![Value::I32(i32::MIN)])
+        );
+        assert_eq!(
+            binop("i32", "div_u", "-1", "2"),
+            Ok(vec![Value::I32(i32::MAX)])
+        );
+        assert_eq!(binop("i32", "rem_s", "-7", "2"), Ok(vec![Value::I32(-1)]));
+        assert_eq!(
+            binop("i32", "rem_s", "0x80000000", "-1"),
+            Ok(vec![Value::I32(0)])
+        );
+        assert_eq!(
+            binop("i32", "shr_u", "-1", "33"),
+            Ok(vec![Value::I32(i32::MAX)])
+        );
+        assert_eq!(
+            binop("i32", "rotl", "0x80000001", "1"),
+            Ok(vec![Value::I32(3)])
+        );
+        assert_eq!(
+            binop("i64", "mul", "0x100000000", "0x100000000"),
+            Ok(vec![Value::I64(0)])
+        );
+        let lt_u = "(result i32)\ni64.const 1\ni64.const -1\ni64.lt_u";
+        assert_eq!(call(lt_u, &[]), Ok(vec![Value::I32(1)]));
+        assert_eq!(
+            binop("f32", "min", "0.0", "-0.0"),
+            Ok(vec![Value::F32(-0.0)])
+        );
+        assert_eq!(
+            binop("f64", "copysign", "2", "-0.0"),
+            Ok(vec![Value::F64(-2.0)])
+        );
+        let max = binop("f64", "max", "nan", "1").unwrap();
+        assert!(matches!(max[..], [Value::F64(value)] if value.is_nan()));
+
+        let unop =
+            |ty: &str, op: &str, arg: &str| call(&format!("(result {ty})\n{arg}\n{op}"), &[]);
+        assert_eq!(
+            unop("f32", "f32.nearest", "f32.const 2.5"),
+            Ok(vec![Value::F32(2.0)])
+        );
+        assert_eq!(
+            unop("i32", "i32.clz", "i32.const 1"),
+            Ok(vec![Value::I32(31)])
+        );
+        assert_eq!(
+            unop("i64", "i64.extend_i32_u", "i32.const -1"),
+            Ok(vec![Value::I64(0xffff_ffff)])
+        );
+        assert_eq!(
+            unop("i32", "i32.trunc_f64_s", "f64.const -2147483648.9"),
+            Ok(vec![Value::I32(i32::MIN)])
+        );
+        assert_eq!(
+            unop("i32", "i32.trunc_f32_u", "f32.const -0.5"),
+            Ok(vec![Value::I32(0)])
+        );
+        assert_eq!(
+            unop("i32", "i32.reinterpret_f32", "f32.const -0.0"),
+            Ok(vec![Value::I32(i32::MIN)])
+        );
+        assert_eq!(
+            unop("i64", "i64.extend8_s", "i64.const 0x80"),
+            Ok(vec![Value::I64(-128)])
+        );
+    }
+
+    #[test]
+    fn traps() {
+        let trap = |text: &str| call(text, &[]).unwrap_err();
+        assert_eq!(trap("unreachable"), "unreachable");
+        assert_eq!(
+            trap("(result i32)\ni32.const 1\ni32.const 0\ni32.div_s"),
+            "integer divide by zero"
+        );
+        assert_eq!(
+            trap("(result i64)\ni64.const 1\ni64.const 0\ni64.rem_u"),
+            "integer divide by zero"
+        );
+        assert_eq!(
+            trap("(result i32)\ni32.const 0x80000000\ni32.const -1\ni32.div_s"),
+            "integer overflow"
+        );
+        assert_eq!(
+            trap("(result i32)\nf32.const 3e9\ni32.trunc_f32_s"),
+            "integer overflow"
+        );
+        assert_eq!(
+            trap("(result i64)\nf64.const nan\ni64.trunc_f64_u"),
+            "invalid conversion to integer"
+        );
+        assert_eq!(
+            trap("(result i32)\ni32.const 65533\ni32.load"),
+            "out of bounds memory access"
+        );
+        assert_eq!(
+            trap("(result i32)\ni32.const 1\ni32.load offset=65535"),
+            "out of bounds memory access"
+        );
+        assert_eq!(trap("call 4"), "call stack exhausted");
+
+        // a trap stays: the machine doesn't step past it
+        let mut machine = machine("nop\nunreachable\nnop");
+        machine.invoke(4, &[]).unwrap();
+        assert_eq!(machine.step(), Ok(Status::Running));
+        assert_eq!(machine.step(), Err(Trap::Unreachable));
+        assert_eq!(machine.current_line(), Some(1));
+        assert_eq!(machine.step(), Err(Trap::Unreachable));
+        assert_eq!(machine.finished(), Some(&Err(Trap::Unreachable)));
+    }
+
+    #[test]
+    fn control() {
+        // the sum of 1..=n, counting down
+        let sum = "(param $n i32)\n(result i32)\n(local $sum i32)\nblock $done\nloop $top\nlocal.get $n\ni32.eqz\nbr_if $done\nlocal.get $sum\nlocal.get $n\ni32.add\nlocal.set $sum\nlocal.get $n\ni32.const 1\ni32.sub\nlocal.set $n\nbr $top\nend\nend\nlocal.get $sum";
+        assert_eq!(call(sum, &[Value::I32(100)]), Ok(vec![Value::I32(5050)]));
+
+        let choose = "(param i32)\n(result i32)\nlocal.get 0\nif (result i32)\ni32.const 1\nelse\ni32.const 2\nend";
+        assert_eq!(call(choose, &[Value::I32(7)]), Ok(vec![Value::I32(1)]));
+        assert_eq!(call(choose, &[Value::I32(0)]), Ok(vec![Value::I32(2)]));
+
+        let one_armed =
+            "(param i32)\n(result i32)\nlocal.get 0\nif\ni32.const 5\nreturn\nend\ni32.const 6";
+        assert_eq!(call(one_armed, &[Value::I32(1)]), Ok(vec![Value::I32(5)]));
+        assert_eq!(call(one_armed, &[Value::I32(0)]), Ok(vec![Value::I32(6)]));
+
+        // br_table: branches carry their values out, discarding what's beneath
+        let table = "(param i32)\n(result i32)\nblock $b (result i32)\nblock $a (result i32)\ni32.const 7\ni32.const 99\nlocal.get 0\nbr_table $a $b 2\nend\ni32.const 10\ni32.add\nend\ni32.const 1\ni32.add";
+        assert_eq!(call(table, &[Value::I32(0)]), Ok(vec![Value::I32(110)]));
+        assert_eq!(call(table, &[Value::I32(1)]), Ok(vec![Value::I32(100)]));
+        assert_eq!(call(table, &[Value::I32(5)]), Ok(vec![Value::I32(99)]));
+
+        let select = "(param i32)\n(result i64)\ni64.const 3\ni64.const 4\nlocal.get 0\nselect";
+        assert_eq!(call(select, &[Value::I32(0)]), Ok(vec![Value::I64(4)]));
+    }
+
+    #[test]
+    fn calls_and_prints() {
+        let factorial = "(param $n i64)\n(result i64)\nlocal.get $n\ni64.eqz\nif (result i64)\ni64.const 1\nelse\nlocal.get $n\ncall $print_i64\nlocal.get $n\nlocal.get $n\ni64.const 1\ni64.sub\ncall 4\ni64.mul\nend";
+        let mut machine = machine(factorial);
+        assert_eq!(
+            machine.call(4, &[Value::I64(5)], STEPS).unwrap(),
+            vec![Value::I64(120)]
+        );
+        let printed: Vec<Value> = (1..=5).rev().map(Value::I64).collect();
+        assert_eq!(machine.printed(), printed);
+        assert!(machine.frames().is_empty() && machine.stack().is_empty());
+        assert!(machine.invoke(4, &[Value::I32(5)]).is_err());
+
+        // an imported function can be invoked too
+        assert_eq!(machine.call(0, &[Value::I32(5)], STEPS).unwrap(), []);
+        assert_eq!(machine.printed(), [Value::I32(5)]);
+    }
+
+    #[test]
+    fn memory() {
+        let text = "(result i64)\ni32.const 8\ni64.const -2\ni64.store32 offset=4\ni32.const 12\ni32.load16_u\ni32.const 1\nmemory.grow\ni32.add\nmemory.size\ni32.add\ni64.extend_i32_u\ni32.const 12\ni64.load32_s\ni64.add";
+        let mut machine = machine(text);
+        machine.invoke(4, &[]).unwrap();
+        assert_eq!(machine.memory().len(), PAGE_SIZE);
+        let status = machine.run(STEPS).unwrap();
+        assert_eq!(status, Status::Finished(vec![Value::I64(65535)]));
+        assert_eq!(machine.memory().len(), 2 * PAGE_SIZE);
+        assert_eq!(&machine.memory()[12..16], &[0xfe, 0xff, 0xff, 0xff]);
+    }
+
+    #[test]
+    fn stepping() {
+        let text = "(param $x i32)\n(result i32)\n(local $y i32)\nblock\nlocal.get $x\nlocal.set $y\nloop $top\ni32.const 1\nlocal.get $y\ni32.add\nlocal.tee $y\ni32.const 3\ni32.lt_s\nbr_if $top\nend\nend\nlocal.get $y";
+        let mut machine = machine(text);
+        machine.invoke(4, &[Value::I32(0)]).unwrap();
+        assert_eq!(machine.current_line(), Some(3));
+        assert_eq!(machine.locals(), &[Value::I32(0), Value::I32(0)]);
+        assert!(machine.labels().is_empty());
+
+        // stop at `i32.add` in the loop's first and second iterations
+        assert_eq!(machine.run_to_line(9, STEPS), Ok(Status::Running));
+        assert_eq!(machine.stack(), &[Value::I32(1), Value::I32(0)]);
+        let mnemonics: Vec<&str> = machine
+            .labels()
+            .iter()
+            .map(|label| label.mnemonic)
+            .collect();
+        assert_eq!(mnemonics, vec!["block", "loop"]);
+        assert_eq!(
+            (machine.labels()[1].start, machine.labels()[1].end),
+            (3, 11)
+        );
+        assert_eq!(machine.run_to_line(9, STEPS), Ok(Status::Running));
+        assert_eq!(machine.stack(), &[Value::I32(1), Value::I32(1)]);
+        assert_eq!(machine.labels().len(), 2);
+
+        assert_eq!(machine.step(), Ok(Status::Running));
+        assert_eq!(machine.stack(), &[Value::I32(2)]);
+        assert_eq!(machine.locals()[1], Value::I32(1));
+
+        // past the last line: the call finishes
+        let status = machine.run_to_line(100, STEPS);
+        assert_eq!(status, Ok(Status::Finished(vec![Value::I32(3)])));
+        assert_eq!(machine.current_line(), None);
+        assert_eq!(machine.step(), Ok(Status::Finished(vec![Value::I32(3)])));
+        assert_eq!(machine.run(3), Ok(Status::Finished(vec![Value::I32(3)])));
+    }
+
+    #[test]
+    fn breakpoints_and_step_over() {
+        let text = "(param $n i32)\n(result i32)\nlocal.get $n\ni32.eqz\nif (result i32)\ni32.const 0\nelse\nlocal.get $n\ni32.const 1\ni32.sub\ncall 4\nlocal.get $n\ni32.add\nend";
+        let mut machine = machine(text);
+        machine.invoke(4, &[Value::I32(3)]).unwrap();
+
+        // each recursive call stops at the breakpoint
+        for n in [2, 1, 0] {
+            assert_eq!(
+                machine.run_to_breakpoint(&[(4, 2)], STEPS),
+                Ok(Status::Running)
+            );
+            assert_eq!(machine.locals(), &[Value::I32(n)]);
+        }
+
+        // stepping over a call runs all of it
+        let mut machine = self::machine(text);
+        machine.invoke(4, &[Value::I32(3)]).unwrap();
+        machine.run_to_line(10, STEPS).unwrap();
+        assert_eq!(machine.frames().len(), 1);
+        assert_eq!(machine.step_over(&[], STEPS), Ok(Status::Running));
+        assert_eq!(
+            (machine.frames().len(), machine.current_line()),
+            (1, Some(11))
+        );
+        assert_eq!(machine.stack(), &[Value::I32(3)]);
+        assert_eq!(machine.step_over(&[], STEPS), Ok(Status::Running));
+        assert_eq!(machine.current_line(), Some(12));
+
+        // ...unless a breakpoint inside it comes first
+        let mut machine = self::machine(text);
+        machine.invoke(4, &[Value::I32(3)]).unwrap();
+        machine.run_to_line(10, STEPS).unwrap();
+        assert_eq!(machine.step_over(&[(4, 5)], STEPS), Ok(Status::Running));
+        assert_eq!(
+            (machine.frames().len(), machine.current_line()),
+            (4, Some(5))
+        );
+        assert_eq!(
+            machine.run_to_breakpoint(&[], STEPS),
+            Ok(Status::Finished(vec![Value::I32(6)]))
+        );
+    }
+
+    #[test]
+    fn data_and_globals() {
+        let mut sections = Sections::default();
+        sections.push(Section::Memory, &["1", "2"]);
+        sections.push(Section::Globals, &["$count", "mut i32", "40"]);
+        sections.push(Section::Data, &["4", "\"\\02\""]);
+        let funcs = [ModuleFunction {
+            name: None,
+            lines: "(result i32 i32)\ni32.const 4\ni32.load8_u\nglobal.get $count\ni32.add\nglobal.set $count\nglobal.get $count\ni32.const 2\nmemory.grow\n"
+                .lines()
+                .map(parse_line)
+                .collect(),
+        }];
+        let (sections, errors) = sections.resolve(&funcs);
+        assert!(errors.is_empty());
+        let mut machine = Machine::new(assemble_module(&funcs, &sections).unwrap()).unwrap();
+
+        // the memory starts with the data and grows no further than its maximum
+        assert_eq!(
+            machine.call(4, &[], STEPS).unwrap(),
+            [Value::I32(42), Value::I32(-1)]
+        );
+        assert_eq!(machine.globals(), &[Value::I32(42)]);
+
+        // instantiating fails if a data segment doesn't fit in the memory, or if the
+        // memory is too large
+        let error = |module: Module| Machine::new(module).err().map(|error| error.to_string());
+        let module = assemble_module(&funcs, &sections).unwrap();
+        let mut outside = module.clone();
+        outside.data[0].offset = PAGE_SIZE as u32;
+        assert_eq!(
+            error(outside),
+            Some("out of bounds memory access".to_string())
+        );
+        let mut large = module;
+        large.memory = Some(Limits {
+            min: 20_000,
+            max: None,
+        });
+        assert_eq!(
+            error(large),
+            Some(
+                "a memory of 20000 pages is larger than the interpreter's limit of 16384"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn host_imports() {
+        let mut sections = Sections::default();
+        sections.push(Section::Imports, &["env", "twice", "$twice", "i32", "i32"]);
+        let funcs = [ModuleFunction {
+            name: None,
+            lines: "(result i32)\ni32.const 20\ncall $twice\ni32.const 2\ni32.add"
+                .lines()
+                .map(parse_line)
+                .collect(),
+        }];
+        let (sections, errors) = sections.resolve(&funcs);
+        assert!(errors.is_empty());
+        let mut machine = Machine::new(assemble_module(&funcs, &sections).unwrap()).unwrap();
+        assert_eq!(
+            machine.call(5, &[], STEPS).unwrap_err().to_string(),
+            "unknown import env.twice"
+        );
+
+        // the host gets the import's names and args (and its results are checked)
+        machine.set_host(Rc::new(|module, name, args| match (module, name, args) {
+            ("env", "twice", [Value::I32(x)]) => Ok(vec![Value::I32(x * 2)]),
+            _ => Err(Trap::Host("no".to_string())),
+        }));
+        assert_eq!(machine.call(5, &[], STEPS).unwrap(), [Value::I32(42)]);
+        assert_eq!(
+            machine.call(4, &[Value::I32(4)], STEPS).unwrap(),
+            [Value::I32(8)]
+        );
+        machine.set_host(Rc::new(|_, _, _| Ok(Vec::new())));
+        assert_eq!(
+            machine.call(5, &[], STEPS).unwrap_err().to_string(),
+            "import env.twice returned the wrong types"
+        );
+    }
+
+    #[test]
+    fn calls_between_functions() {
+        let funcs: Vec<_> = [
+            "(param $n i32)\n(result i32)\nlocal.get $n\ncall $square\ni32.const 1\ni32.add",
+            "(param $x i32)\n(result i32)\nlocal.get $x\nlocal.get $x\ni32.mul",
+        ]
+        .iter()
+        .zip([None, Some("square".to_string())])
+        .map(|(text, name)| ModuleFunction {
+            name,
+            lines: text.lines().map(parse_line).collect(),
+        })
+        .collect();
+        let mut machine =
+            Machine::new(assemble_module(&funcs, &ModuleSections::default()).unwrap()).unwrap();
+        machine.invoke(4, &[Value::I32(5)]).unwrap();
+
+        // a breakpoint is on a line of one function (line 2 of the first is a
+        // different place)
+        assert_eq!(
+            machine.run_to_breakpoint(&[(5, 2)], STEPS),
+            Ok(Status::Running)
+        );
+        assert_eq!(
+            (machine.current_func(), machine.current_line()),
+            (Some(5), Some(2))
+        );
+        assert_eq!(machine.locals(), &[Value::I32(5)]);
+        assert_eq!(
+            machine.run_to_breakpoint(&[(4, 3)], STEPS),
+            Ok(Status::Finished(vec![Value::I32(26)]))
+        );
+        assert_eq!(machine.current_func(), None);
+    }
+
+    #[test]
+    fn stepping_back() {
+        // store, grow, print and call, then trap
+        let text = "(param $n i32)\n(result i32)\ni32.const 4\nlocal.get $n\ni32.store\ni32.const 1\nmemory.grow\ncall $print_i32\nlocal.get $n\nif\ni32.const 0\ncall 4\ndrop\nend\ni32.const 4\ni32.load\ni32.const 0\ni32.div_u";
+        let mut machine = machine(text);
+        machine.invoke(4, &[Value::I32(7)]).unwrap();
+        let mut states = Vec::new();
+        let snapshot = |machine: &Machine| {
+            (
+                machine.stack().to_vec(),
+                machine.frames().to_vec(),
+                machine.memory()[..8].to_vec(),
+                machine.memory().len(),
+                machine.printed().to_vec(),
+            )
+        };
+        let trap = loop {
+            states.push(snapshot(&machine));
+            match machine.step() {
+                Ok(Status::Running) => {}
+                outcome => break outcome,
+            }
+        };
+        assert_eq!(trap, Err(Trap::DivideByZero));
+        // the trapping step was undone, and stepping back retraces every step
+        assert_eq!(&snapshot(&machine), states.last().unwrap());
+        assert_eq!(machine.steps(), states.len() - 1);
+        assert_eq!(machine.memory()[..8], [0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(machine.printed(), &[Value::I32(1), Value::I32(2)]);
+        for state in states.iter().rev().skip(1) {
+            assert!(machine.step_back());
+            assert_eq!(&snapshot(&machine), state);
+        }
+        assert!(!machine.step_back());
+        assert_eq!(machine.earliest_step(), 0);
+
+        // seeking: back, forwards (executing again), and to the trap
+        assert_eq!(machine.seek(9), Ok(Status::Running));
+        assert_eq!(snapshot(&machine), states[9]);
+        assert_eq!(machine.seek(3), Ok(Status::Running));
+        assert_eq!(snapshot(&machine), states[3]);
+        assert_eq!(machine.seek(1000), Err(Trap::DivideByZero));
+        assert_eq!(machine.steps(), states.len() - 1);
+        assert_eq!(machine.seek(0), Ok(Status::Running));
+        assert_eq!(snapshot(&machine), states[0]);
+    }
+
+    #[test]
+    fn memory_accesses() {
+        let text = "i32.const 8\ni32.const 0x01020304\ni32.store\ni32.const 8\ni32.const 0x01020399\ni32.store\ni32.const 9\ni32.load8_u\ndrop";
+        let mut machine = machine(text);
+        machine.invoke(4, &[]).unwrap();
+        let store = |address, size| Access {
+            address,
+            size,
+            store: true,
+        };
+        assert_eq!(machine.run(3), Ok(Status::Running));
+        assert_eq!(machine.last_access(), Some(store(8, 4)));
+        assert_eq!(machine.changed_bytes(), [8, 9, 10, 11]);
+
+        // only the bytes that differ have changed, and a step that doesn't store
+        // changes none (but the last access is remembered)
+        assert_eq!(machine.run(3), Ok(Status::Running));
+        assert_eq!(machine.changed_bytes(), [8]);
+        assert_eq!(machine.run(1), Ok(Status::Running));
+        assert!(machine.changed_bytes().is_empty());
+        assert_eq!(machine.last_access(), Some(store(8, 4)));
+        assert_eq!(machine.run(1), Ok(Status::Running));
+        assert_eq!(
+            machine.last_access(),
+            Some(Access {
+                address: 9,
+                size: 1,
+                store: false
+            })
+        );
+
+        // stepping back restores both
+        assert_eq!(machine.seek(6), Ok(Status::Running));
+        assert_eq!(machine.last_access(), Some(store(8, 4)));
+        assert_eq!(machine.changed_bytes(), [8]);
+        assert_eq!(machine.seek(0), Ok(Status::Running));
+        assert_eq!(machine.last_access(), None);
+        assert!(machine.changed_bytes().is_empty());
+    }
+}
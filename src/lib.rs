@@ -1,6 +1,40 @@
+pub mod assemble;
+pub mod completion;
+pub mod decode;
+pub mod diagnostic;
+pub mod disassemble;
+pub mod dom_overlay;
 pub mod dom_struct;
 pub mod dom_text;
+pub mod dom_tooltip;
 pub mod dom_vec;
+pub mod dom_virtual_vec;
+pub mod encode;
+pub mod header;
+pub mod instruction;
+pub mod interpreter;
+pub mod memory_view;
+pub mod module;
+pub mod nesting;
+pub mod parse;
+pub mod run;
+pub mod script;
+pub mod sections;
+pub mod text_index;
+pub mod text_module;
+pub mod token;
+pub mod validate;
+pub mod value;
+pub mod wat;
 pub mod web_support;
 
+pub mod completion_popup;
+pub mod debugger;
+pub mod edit_line;
 pub mod editor;
+pub mod file_panel;
+pub mod memory_inspector;
+pub mod module_editor;
+pub mod run_panel;
+pub mod sections_editor;
+pub mod test_panel;
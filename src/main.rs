@@ -1,8 +1,17 @@
 use anyhow::Result;
 use std::cell::RefCell;
-use trunkfun::{dom_struct::DomStruct, editor::Editor, web_support::DocumentHandle};
+use trunkfun::{
+    debugger::Debugger, dom_struct::DomStruct, file_panel::FilePanel, module_editor::ModuleEditor,
+    run_panel::RunPanel, test_panel::TestPanel, web_support::DocumentHandle,
+};
 
-type Body = DomStruct<(Editor, ()), web_sys::HtmlBodyElement>;
+type Body = DomStruct<
+    (
+        ModuleEditor,
+        (RunPanel, (TestPanel, (Debugger, (FilePanel, ())))),
+    ),
+    web_sys::HtmlBodyElement,
+>;
 type Document = DocumentHandle<Body>;
 
 thread_local! {
@@ -12,7 +21,18 @@ thread_local! {
 fn setup() -> Result<()> {
     DOCUMENT.with_borrow_mut(|doc| {
         let factory = doc.element_factory();
-        doc.set_body(Body::new((Editor::new(&factory), ()), factory.body()));
+        let module = ModuleEditor::new(&factory);
+        let run_panel = RunPanel::new(&factory, &module);
+        let test_panel = TestPanel::new(&factory, &module);
+        let debugger = Debugger::new(&factory, &module);
+        let file_panel = FilePanel::new(&factory, &module);
+        doc.set_body(Body::new(
+            (
+                module,
+                (run_panel, (test_panel, (debugger, (file_panel, ())))),
+            ),
+            factory.body(),
+        ));
         doc.audit();
     });
 
@@ -0,0 +1,412 @@
+// The memory inspector, at the end of the debugger panel: the machine's linear memory
+// as a grid of rows (an address, 16 bytes in hex, and the same bytes as ASCII), with
+// the bytes that the last step changed highlighted, and the value of each type stored
+// at an address. While Follow is on, the address is the one the latest load or store
+// used, and the grid scrolls to it; typing an address turns Follow off.
+//
+// A memory can have millions of rows, so the grid is virtualized: only the rows in
+// view (and a few on either side) have elements, between two spacers as tall as the
+// rows they stand in for. Unlike a DomVirtualVec, which keeps a component for every
+// child, the grid has components only for the rows with elements, and reuses them
+// (with new text) as it scrolls. A browser won't make an element taller than some
+// millions of pixels, so past MAX_SCROLL_PX the spacers shrink and the scroll position
+// maps to rows in proportion (a pixel of scrolling passes several rows).
+//
+// Like the sections editor, the inspector reports its events to a handler (the
+// debugger), which has the memory to show.
+
+use crate::{
+    dom_struct::DomStruct,
+    dom_text::DomText,
+    dom_vec::DomVec,
+    interpreter::Access,
+    memory_view::{
+        ROW_BYTES, ascii, hex_byte, parse_address, row_address, row_bytes, row_count, typed_values,
+    },
+    web_support::{AccessToken, Component, ElementFactory, WithElement, WithNode, audit_eq},
+};
+use std::{ops::Range, rc::Rc};
+use web_sys::{HtmlButtonElement, HtmlDivElement, HtmlInputElement, HtmlSpanElement};
+
+type Input = DomStruct<(), HtmlInputElement>;
+type Button = DomStruct<(DomText, ()), HtmlButtonElement>;
+type TextDiv = DomStruct<(DomText, ()), HtmlDivElement>;
+type Span = DomStruct<(DomText, ()), HtmlSpanElement>;
+type RowDiv = DomStruct<(Span, (DomVec<Span, HtmlDivElement>, (Span, ()))), HtmlDivElement>;
+type Spacer = DomStruct<(), HtmlDivElement>;
+type Grid = DomStruct<(Spacer, (DomVec<Row, HtmlDivElement>, (Spacer, ()))), HtmlDivElement>;
+type Controls = DomStruct<(Input, (Button, ())), HtmlDivElement>;
+type Contents = (
+    TextDiv,
+    (Controls, (DomVec<TextDiv, HtmlDivElement>, (Grid, ()))),
+);
+
+// Every row is this tall (see `div.memory div.row` in the stylesheet).
+const ROW_HEIGHT_PX: f64 = 16.0;
+// How many rows count as in view before the grid has been laid out.
+const MIN_ROWS: usize = 32;
+// How many rows to keep above and below the viewport.
+const OVERSCAN_ROWS: usize = 8;
+// The most the grid scrolls (well under any browser's limit on an element's height).
+const MAX_SCROLL_PX: f64 = 4_000_000.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MemoryEvent {
+    Address, // the address was typed
+    Follow,  // Follow was clicked
+    Scroll,
+}
+
+// One of the grid's rows, showing any row of memory.
+struct Row {
+    changed: Vec<bool>, // as shown, for each byte
+    component: RowDiv,
+}
+
+impl Row {
+    fn new(factory: &ElementFactory) -> Self {
+        let span = |class: &str| {
+            let mut span = Span::new((DomText::new(""), ()), factory.span());
+            span.set_attribute("class", class);
+            span
+        };
+        let mut bytes = DomVec::new(factory.div());
+        bytes.set_attribute("class", "bytes");
+        for _ in 0..ROW_BYTES {
+            bytes.push(Span::new((DomText::new(""), ()), factory.span()));
+        }
+        let mut component = RowDiv::new(
+            (span("address"), (bytes, (span("ascii"), ()))),
+            factory.div(),
+        );
+        component.set_attribute("class", "row");
+        Row {
+            changed: vec![false; ROW_BYTES],
+            component,
+        }
+    }
+
+    // Show a row of memory, with the (sorted) addresses of the changed bytes (setting
+    // only the text that differs).
+    fn show(&mut self, memory: &[u8], row: usize, changed: &[usize]) {
+        let bytes = row_bytes(memory, row);
+        let contents = self.component.get_mut();
+        set_text(&mut contents.0, &row_address(row));
+        for (index, span) in contents.1.0.iter_mut().enumerate() {
+            let text = bytes.get(index).map(|&byte| hex_byte(byte));
+            set_text(span, text.as_deref().unwrap_or_default());
+            let is_changed = changed.binary_search(&(row * ROW_BYTES + index)).is_ok();
+            if is_changed != self.changed[index] {
+                match is_changed {
+                    true => span.set_attribute("class", "changed"),
+                    false => span.remove_attribute("class"),
+                }
+                self.changed[index] = is_changed;
+            }
+        }
+        set_text(&mut contents.1.1.0, &ascii(bytes));
+    }
+
+    // The row as one line of text (as memory_view::row_text has it).
+    fn text(&self) -> String {
+        let contents = self.component.get();
+        let hex: Vec<&str> = (contents.1.0.iter())
+            .map(|span| span.get().0.get())
+            .filter(|text| !text.is_empty())
+            .collect();
+        format!(
+            "{}  {}  {}",
+            contents.0.get().0.get(),
+            hex.join(" "),
+            contents.1.1.0.get().0.get()
+        )
+    }
+}
+
+fn set_text(span: &mut Span, text: &str) {
+    if span.get().0.get() != text {
+        span.get_mut().0.set_data(text);
+    }
+}
+
+impl WithNode for Row {
+    fn with_node(&self, f: impl FnMut(&web_sys::Node), g: AccessToken) {
+        self.component.with_node(f, g);
+    }
+}
+
+impl Component for Row {
+    fn try_audit(&self) -> Result<(), String> {
+        self.component.try_audit()
+    }
+}
+
+pub struct MemoryInspector {
+    factory: ElementFactory,
+    follow: bool,
+    address: Option<usize>, // for the typed values
+    top_row: usize,         // the row at the top of the view
+    total: usize,           // how many rows the memory has, as shown
+    rows: Range<usize>,     // as shown
+    spacers: (f64, f64),    // their heights, as shown
+    values: Vec<String>,    // as shown
+    changed: Vec<usize>,    // as shown
+    component: DomStruct<Contents, HtmlDivElement>,
+}
+
+impl MemoryInspector {
+    // An empty inspector, reporting its events to `handler`.
+    pub fn new(factory: &ElementFactory, handler: impl Fn(MemoryEvent) + 'static) -> Self {
+        let handler: Rc<dyn Fn(MemoryEvent)> = Rc::new(handler);
+        let mut heading = TextDiv::new((DomText::new("Memory"), ()), factory.div());
+        heading.set_attribute("class", "heading");
+        let mut input = Input::new((), factory.input());
+        input.set_attribute("placeholder", "address");
+        input.set_attribute("spellcheck", "false");
+        let input_handler = handler.clone();
+        input.set_oninput(move |_| input_handler(MemoryEvent::Address));
+        let mut follow = Button::new((DomText::new("Follow"), ()), factory.button());
+        follow.set_attribute("aria-pressed", "true");
+        let follow_handler = handler.clone();
+        follow.set_onclick(move |_| follow_handler(MemoryEvent::Follow));
+        let mut controls = Controls::new((input, (follow, ())), factory.div());
+        controls.set_attribute("class", "controls");
+        let mut values = DomVec::new(factory.div());
+        values.set_attribute("class", "values");
+        let mut grid = Grid::new(
+            (
+                Spacer::new((), factory.div()),
+                (
+                    DomVec::new(factory.div()),
+                    (Spacer::new((), factory.div()), ()),
+                ),
+            ),
+            factory.div(),
+        );
+        grid.set_attribute("class", "grid");
+        (grid.get_mut().0).set_attribute("class", "spacer");
+        (grid.get_mut().1.1.0).set_attribute("class", "spacer");
+        grid.set_onscroll(move |_| handler(MemoryEvent::Scroll));
+        let mut component =
+            DomStruct::new((heading, (controls, (values, (grid, ())))), factory.div());
+        component.set_attribute("class", "memory");
+        MemoryInspector {
+            factory: factory.clone(),
+            follow: true,
+            address: None,
+            top_row: 0,
+            total: 0,
+            rows: 0..0,
+            spacers: (0.0, 0.0),
+            values: Vec::new(),
+            changed: Vec::new(),
+            component,
+        }
+    }
+
+    // Take the change an event made (from the inspector's elements). The caller shows
+    // the memory again afterwards.
+    pub fn handle(&mut self, event: MemoryEvent) {
+        match event {
+            MemoryEvent::Address => {
+                self.address = parse_address(&self.input().value());
+                self.set_follow(false);
+                if let Some(address) = self.address {
+                    self.reveal(address);
+                }
+            }
+            MemoryEvent::Follow => self.set_follow(!self.follow),
+            MemoryEvent::Scroll => {
+                let row_scroll = self.row_scroll(self.total);
+                self.top_row = (self.grid().scroll_top() / row_scroll).floor() as usize;
+            }
+        }
+    }
+
+    // Type an address (turning Follow off).
+    pub fn set_address(&mut self, text: &str) {
+        self.input_mut().set_value(text);
+        self.handle(MemoryEvent::Address);
+    }
+
+    pub fn address(&self) -> String {
+        self.input().value()
+    }
+
+    pub fn follow(&self) -> bool {
+        self.follow
+    }
+
+    pub fn set_follow(&mut self, follow: bool) {
+        if follow == self.follow {
+            return;
+        }
+        let button = &mut self.controls_mut().get_mut().1.0;
+        button.set_attribute("aria-pressed", &follow.to_string());
+        self.follow = follow;
+    }
+
+    // Show a memory, the addresses of its bytes that the last step changed, and the
+    // latest load or store (which the address follows, if Follow is on).
+    pub fn show(&mut self, memory: &[u8], changed: &[usize], access: Option<Access>) {
+        let total = row_count(memory.len());
+        self.total = total;
+        if let Some(access) = access.filter(|_| self.follow) {
+            if self.address != Some(access.address) {
+                self.input_mut()
+                    .set_value(&format!("0x{:x}", access.address));
+                self.address = Some(access.address);
+            }
+            self.reveal(access.address);
+        }
+
+        let mut changed = changed.to_vec();
+        changed.sort_unstable();
+        let top_row = self.top_row.min(total);
+        let top = top_row as f64 * self.row_scroll(total);
+        // (the rows above the top one have to fit above it)
+        let above = OVERSCAN_ROWS.min((top / ROW_HEIGHT_PX).floor() as usize);
+        let start = top_row.saturating_sub(above);
+        let end = (start + self.window_len()).min(total);
+        let top_spacer = top - (top_row - start) as f64 * ROW_HEIGHT_PX;
+        let height = (total as f64 * ROW_HEIGHT_PX).min(MAX_SCROLL_PX);
+        let rows_height = (end - start) as f64 * ROW_HEIGHT_PX;
+        let spacers = (top_spacer, (height - top_spacer - rows_height).max(0.0));
+        let factory = self.factory.clone();
+        // (a spacer's style is set only when its height changes)
+        let shown = std::mem::replace(&mut self.spacers, spacers);
+        let contents = self.grid_mut().get_mut();
+        if spacers.0 != shown.0 {
+            contents.0.set_attribute("style", &px_height(spacers.0));
+        }
+        if spacers.1 != shown.1 {
+            contents.1.1.0.set_attribute("style", &px_height(spacers.1));
+        }
+        let dom_rows = &mut contents.1.0;
+        while dom_rows.len() > end - start {
+            dom_rows.remove(dom_rows.len() - 1);
+        }
+        while dom_rows.len() < end - start {
+            dom_rows.push(Row::new(&factory));
+        }
+        // (rows already showing the right memory only need the changes)
+        for (row, dom_row) in (start..end).zip(dom_rows.iter_mut()) {
+            dom_row.show(memory, row, &changed);
+        }
+        self.rows = start..end;
+        self.changed = changed;
+
+        let values = match self.address {
+            Some(address) if !memory.is_empty() => typed_values(memory, address),
+            _ => Vec::new(),
+        };
+        if values != self.values {
+            let factory = self.factory.clone();
+            let dom_values = &mut self.component.get_mut().1.1.0;
+            dom_values.clear();
+            for text in &values {
+                dom_values.push(TextDiv::new((DomText::new(text), ()), factory.div()));
+            }
+            self.values = values;
+        }
+    }
+
+    // The rows with elements (as memory_view::row_text has them), and which rows of
+    // memory they are.
+    pub fn rows(&self) -> (Range<usize>, Vec<String>) {
+        let dom_rows = &self.grid().get().1.0;
+        (self.rows.clone(), dom_rows.iter().map(Row::text).collect())
+    }
+
+    // The value of each type at the address.
+    pub fn values(&self) -> &[String] {
+        &self.values
+    }
+
+    // The addresses of the bytes highlighted as changed, in order.
+    pub fn changed(&self) -> &[usize] {
+        &self.changed
+    }
+
+    // How many rows have elements: those that fit in the grid, and some to spare.
+    fn window_len(&self) -> usize {
+        let in_view = (self.grid().client_height() / ROW_HEIGHT_PX).ceil() as usize;
+        in_view.max(MIN_ROWS) + 2 * OVERSCAN_ROWS
+    }
+
+    // How far the grid scrolls per row: a row's height, unless that would make the grid
+    // taller than MAX_SCROLL_PX, when the scroll range maps to the rows in proportion
+    // (so that scrolling to the end shows the last rows).
+    fn row_scroll(&self, total: usize) -> f64 {
+        if total as f64 * ROW_HEIGHT_PX <= MAX_SCROLL_PX {
+            return ROW_HEIGHT_PX;
+        }
+        let view = self.grid().client_height();
+        let in_view = (view / ROW_HEIGHT_PX).floor();
+        (MAX_SCROLL_PX - view) / (total as f64 - in_view)
+    }
+
+    // Scroll the grid to the row with an address (unless it already has an element).
+    fn reveal(&mut self, address: usize) {
+        let row = address / ROW_BYTES;
+        let first_row = self.top_row.saturating_sub(OVERSCAN_ROWS);
+        if (first_row..first_row + self.window_len()).contains(&row) {
+            return;
+        }
+        self.top_row = row;
+        let top = row as f64 * self.row_scroll(self.total);
+        self.grid_mut().set_scroll_top(top);
+    }
+
+    fn controls_mut(&mut self) -> &mut Controls {
+        &mut self.component.get_mut().1.0
+    }
+
+    fn input(&self) -> &Input {
+        &self.component.get().1.0.get().0
+    }
+
+    fn input_mut(&mut self) -> &mut Input {
+        &mut self.controls_mut().get_mut().0
+    }
+
+    fn grid(&self) -> &Grid {
+        &self.component.get().1.1.1.0
+    }
+
+    fn grid_mut(&mut self) -> &mut Grid {
+        &mut self.component.get_mut().1.1.1.0
+    }
+}
+
+// The style of a spacer.
+fn px_height(height: f64) -> String {
+    format!("height: {height}px")
+}
+
+impl WithNode for MemoryInspector {
+    fn with_node(&self, f: impl FnMut(&web_sys::Node), g: AccessToken) {
+        self.component.with_node(f, g);
+    }
+}
+
+impl WithElement<HtmlDivElement> for MemoryInspector {
+    fn with_element(&self, f: impl FnMut(&HtmlDivElement), g: AccessToken) {
+        self.component.with_element(f, g);
+    }
+}
+
+// To audit, audit the elements, then check that there's an element for each row shown
+// and for each value.
+impl Component for MemoryInspector {
+    fn try_audit(&self) -> Result<(), String> {
+        self.component.try_audit()?;
+        audit_eq!(self.grid().get().1.0.len(), self.rows.len());
+        let dom_values = &self.component.get().1.1.0;
+        audit_eq!(dom_values.len(), self.values.len());
+        for (text, dom_value) in self.values.iter().zip(dom_values.iter()) {
+            audit_eq!(dom_value.get().0.get(), text);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,135 @@
+// Views of a machine's linear memory for the memory inspector (see
+// memory_inspector.rs): rows of 16 bytes, each byte shown in hex and as ASCII, and the
+// value of each type stored at an address (little-endian, as WebAssembly stores them).
+
+use crate::{instruction::ValType, parse::parse_int, value::Value};
+
+pub const ROW_BYTES: usize = 16;
+
+// How many rows a memory of `len` bytes takes.
+pub fn row_count(len: usize) -> usize {
+    len.div_ceil(ROW_BYTES)
+}
+
+// A row's address, e.g. `00000010`.
+pub fn row_address(row: usize) -> String {
+    format!("{:08x}", row * ROW_BYTES)
+}
+
+// The bytes of a row (fewer for a last row that memory ends in the middle of).
+pub fn row_bytes(memory: &[u8], row: usize) -> &[u8] {
+    let start = (row * ROW_BYTES).min(memory.len());
+    &memory[start..(start + ROW_BYTES).min(memory.len())]
+}
+
+pub fn hex_byte(byte: u8) -> String {
+    format!("{byte:02x}")
+}
+
+// Printable ASCII characters stand for themselves, and other bytes for `.`.
+pub fn ascii(bytes: &[u8]) -> String {
+    (bytes.iter())
+        .map(|&byte| match byte {
+            b' '..=b'~' => byte as char,
+            _ => '.',
+        })
+        .collect()
+}
+
+// A row as one line of text: its address, its bytes in hex, and as ASCII.
+pub fn row_text(memory: &[u8], row: usize) -> String {
+    let bytes = row_bytes(memory, row);
+    let hex: Vec<String> = bytes.iter().map(|&byte| hex_byte(byte)).collect();
+    format!("{}  {}  {}", row_address(row), hex.join(" "), ascii(bytes))
+}
+
+// The value of each type at an address, e.g. `i32: 42` (or what keeps it from having
+// one).
+pub fn typed_values(memory: &[u8], address: usize) -> Vec<String> {
+    [ValType::I32, ValType::I64, ValType::F32, ValType::F64]
+        .into_iter()
+        .map(|ty| {
+            let size = match ty {
+                ValType::I32 | ValType::F32 => 4,
+                ValType::I64 | ValType::F64 => 8,
+            };
+            let bytes = (address.checked_add(size)).and_then(|end| memory.get(address..end));
+            let Some(bytes) = bytes else {
+                return format!("{ty}: out of bounds");
+            };
+            let value = match ty {
+                ValType::I32 => Value::I32(i32::from_le_bytes(bytes.try_into().unwrap())),
+                ValType::I64 => Value::I64(i64::from_le_bytes(bytes.try_into().unwrap())),
+                ValType::F32 => Value::F32(f32::from_le_bytes(bytes.try_into().unwrap())),
+                ValType::F64 => Value::F64(f64::from_le_bytes(bytes.try_into().unwrap())),
+            };
+            format!("{ty}: {value}")
+        })
+        .collect()
+}
+
+// An address as the user types it: a number (hex with `0x`), not negative.
+pub fn parse_address(text: &str) -> Option<usize> {
+    let text = text.trim();
+    if text.starts_with(['-', '+']) {
+        return None;
+    }
+    parse_int(text, 64).map(|address| address as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows() {
+        let mut memory = vec![0; 40];
+        memory[16..21].copy_from_slice(b"hi\0\x7f~");
+        assert_eq!(row_count(memory.len()), 3);
+        assert_eq!(row_count(0), 0);
+        assert_eq!(
+            row_text(&memory, 1),
+            "00000010  68 69 00 7f 7e 00 00 00 00 00 00 00 00 00 00 00  hi..~..........."
+        );
+        assert_eq!(
+            row_text(&memory, 2),
+            "00000020  00 00 00 00 00 00 00 00  ........"
+        );
+        assert!(row_bytes(&memory, 3).is_empty());
+    }
+
+    #[test]
+    fn typed() {
+        let mut memory = vec![0; 16];
+        memory[8..16].copy_from_slice(&1.5f64.to_le_bytes());
+        memory[0..4].copy_from_slice(&(-2i32).to_le_bytes());
+        assert_eq!(
+            typed_values(&memory, 0),
+            [
+                "i32: -2",
+                "i64: 4294967294",
+                "f32: -nan:0x7ffffe",
+                "f64: 2.12199579e-314"
+            ]
+        );
+        assert_eq!(typed_values(&memory, 8)[3], "f64: 1.5");
+        assert_eq!(
+            typed_values(&memory, 10),
+            [
+                "i32: 0",
+                "i64: out of bounds",
+                "f32: 0.0",
+                "f64: out of bounds"
+            ]
+        );
+        assert_eq!(typed_values(&memory, usize::MAX)[0], "i32: out of bounds");
+    }
+
+    #[test]
+    fn addresses() {
+        assert_eq!(parse_address(" 16 "), Some(16));
+        assert_eq!(parse_address("0x10"), Some(16));
+        assert_eq!(parse_address("-1"), None);
+        assert_eq!(parse_address("sixteen"), None);
+    }
+}
@@ -0,0 +1,158 @@
+// A WebAssembly module as the assembler builds it and the encoder writes it out: the
+// sections' contents, with every index resolved to a number. Every module imports the
+// host's print functions (see run.rs), so they are functions 0 to 3; any other imports
+// come after them.
+
+use crate::{
+    instruction::{Instruction, ValType},
+    validate::FuncType,
+    value::Value,
+};
+
+// The module name of the host imports, and each print function with its param type.
+pub const HOST_MODULE: &str = "host";
+pub const PRINT_FUNCS: [(&str, ValType); 4] = [
+    ("print_i32", ValType::I32),
+    ("print_i64", ValType::I64),
+    ("print_f32", ValType::F32),
+    ("print_f64", ValType::F64),
+];
+
+pub fn print_func_type(ty: ValType) -> FuncType {
+    FuncType {
+        params: vec![ty],
+        results: Vec::new(),
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Import {
+    pub module: String,
+    pub name: String,
+    pub type_index: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Function {
+    pub type_index: u32,
+    pub locals: Vec<ValType>,   // the declared locals (not the params)
+    pub body: Vec<Instruction>, // without the function's final `end`
+    pub lines: Vec<usize>,      // the source line of each instruction
+}
+
+// The size of a table (in entries) or a memory (in 64 KiB pages).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub min: u32,
+    pub max: Option<u32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Global {
+    pub ty: ValType,
+    pub mutable: bool,
+    pub init: Value,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExportKind {
+    Func,
+    Table,
+    Memory,
+    Global,
+}
+
+impl ExportKind {
+    pub const ALL: [ExportKind; 4] = [
+        ExportKind::Func,
+        ExportKind::Table,
+        ExportKind::Memory,
+        ExportKind::Global,
+    ];
+
+    // As in the text format (e.g. `(export "f" (func 4))`).
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            ExportKind::Func => "func",
+            ExportKind::Table => "table",
+            ExportKind::Memory => "memory",
+            ExportKind::Global => "global",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Export {
+    pub name: String,
+    pub kind: ExportKind,
+    pub index: u32,
+}
+
+// An element segment: function indices for the table's entries from `offset` on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Elem {
+    pub offset: u32,
+    pub funcs: Vec<u32>,
+}
+
+// A data segment: bytes for the memory from `offset` on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Data {
+    pub offset: u32,
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Module {
+    pub types: Vec<FuncType>,
+    pub imports: Vec<Import>, // all functions, numbered before `funcs`
+    pub funcs: Vec<Function>,
+    pub table: Option<Limits>, // of function references
+    pub memory: Option<Limits>,
+    pub globals: Vec<Global>,
+    pub exports: Vec<Export>,
+    pub elems: Vec<Elem>,
+    pub data: Vec<Data>,
+}
+
+impl Module {
+    // The index of a function type, adding it if it's new.
+    pub fn type_index(&mut self, ty: &FuncType) -> u32 {
+        match self.types.iter().position(|existing| existing == ty) {
+            Some(index) => index as u32,
+            None => {
+                self.types.push(ty.clone());
+                (self.types.len() - 1) as u32
+            }
+        }
+    }
+
+    // The type of a function, by its index (counting the imports first).
+    pub fn func_type(&self, index: u32) -> Option<&FuncType> {
+        let type_index = match index.checked_sub(self.imports.len() as u32) {
+            None => self.imports[index as usize].type_index,
+            Some(index) => self.funcs.get(index as usize)?.type_index,
+        };
+        self.types.get(type_index as usize)
+    }
+
+    // The type of an exported function, by its export name.
+    pub fn export_type(&self, name: &str) -> Option<&FuncType> {
+        let export = (self.exports.iter())
+            .find(|export| export.name == name && export.kind == ExportKind::Func)?;
+        self.func_type(export.index)
+    }
+
+    // The function exported as `name` (or if `name` is empty, the first exported
+    // function), or why there isn't one.
+    pub fn func_export(&self, name: &str) -> Result<&Export, String> {
+        let mut funcs = (self.exports.iter()).filter(|export| export.kind == ExportKind::Func);
+        match name {
+            "" => funcs.next().ok_or_else(|| {
+                "nothing to run: export the function with `(export \"name\")`".to_string()
+            }),
+            name => (funcs.find(|export| export.name == name))
+                .ok_or_else(|| format!("no function is exported as \"{name}\"")),
+        }
+    }
+}
@@ -0,0 +1,783 @@
+// The module editor: panels for the module's imports, table, memory, globals, exports
+// and segments (see sections_editor.rs), then the module's functions, in order, each
+// with a header (inputs for its name, params, results and locals; see header.rs),
+// buttons to move it up or down, remove it, or fold its body, and an editor for its
+// body (see editor.rs), with the editor's completion popup and tooltip. Below the
+// functions, a button adds another.
+//
+// Validation, assembly and the text format work on the whole module. A function's
+// body is analyzed after its header's declarations, and can call any of the module's
+// functions, by name or by number (the host's print functions are 0 to 3, then come
+// the other imports, then the module's own functions), and use its globals. A header
+// or section field with a problem is marked, with the problem as its tooltip. After
+// any change to a function or a section, everything's analysis is brought up to date,
+// since each may refer to the others.
+
+use crate::{
+    assemble::{ModuleFunction, assemble_module},
+    completion_popup::CompletionPopup,
+    dom_struct::DomStruct,
+    dom_text::DomText,
+    dom_tooltip::DomTooltip,
+    dom_vec::DomVec,
+    editor::{Editor, ModuleContext},
+    header::{FunctionText, Header, HeaderField},
+    module::{Module, PRINT_FUNCS},
+    sections::{ModuleText, Section, SectionError, Sections},
+    sections_editor::{SectionEvent, SectionsEditor},
+    wat::module_text,
+    web_support::{
+        AccessToken, Component, ElementFactory, FileHandle, WithElement, WithNode, audit_eq,
+        dom_op_scope,
+    },
+};
+use anyhow::{Result, bail};
+use std::{
+    cell::RefCell,
+    fmt,
+    rc::{Rc, Weak},
+};
+use web_sys::{HtmlButtonElement, HtmlDivElement, HtmlInputElement};
+
+type Input = DomStruct<(), HtmlInputElement>;
+type Button = DomStruct<(DomText, ()), HtmlButtonElement>;
+type Fields = (
+    Input,
+    (
+        Input,
+        (Input, (Input, (Button, (Button, (Button, (Button, ())))))),
+    ),
+);
+type HeaderDiv = DomStruct<Fields, HtmlDivElement>;
+type FunctionContents = (HeaderDiv, (Editor, (CompletionPopup, (DomTooltip, ()))));
+type Toolbar = DomStruct<(Button, ()), HtmlDivElement>;
+type Functions = DomVec<FunctionEditor, HtmlDivElement>;
+type Contents = (SectionsEditor, (Functions, (Toolbar, ())));
+// What a function should show: the problems of its header's fields, and the context
+// its body is analyzed in.
+type Analysis = ([Option<String>; 4], ModuleContext);
+
+// A problem that keeps the module from assembling, and where it is.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ModuleError {
+    pub func: Option<usize>, // None for a problem in one of the other sections
+    pub line: Option<usize>, // in the function's body (None for its header)
+    pub message: String,
+    place: String, // as described to the user
+}
+
+impl fmt::Display for ModuleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.place, self.message)
+    }
+}
+
+// One function: its header and its body.
+struct FunctionEditor {
+    id: usize,
+    header: Header,              // as typed
+    errors: [Option<String>; 4], // as shown, in the order of HeaderField::ALL
+    component: DomStruct<FunctionContents, HtmlDivElement>,
+}
+
+impl FunctionEditor {
+    fn editor(&self) -> &Editor {
+        &self.component.get().1.0
+    }
+
+    fn editor_mut(&mut self) -> &mut Editor {
+        &mut self.component.get_mut().1.0
+    }
+
+    fn input_mut(&mut self, field: HeaderField) -> &mut Input {
+        let fields = self.component.get_mut().0.get_mut();
+        match field {
+            HeaderField::Name => &mut fields.0,
+            HeaderField::Params => &mut fields.1.0,
+            HeaderField::Results => &mut fields.1.1.0,
+            HeaderField::Locals => &mut fields.1.1.1.0,
+        }
+    }
+
+    fn button_mut(&mut self, index: usize) -> &mut Button {
+        let buttons = &mut self.component.get_mut().0.get_mut().1.1.1.1;
+        match index {
+            0 => &mut buttons.0,
+            1 => &mut buttons.1.0,
+            2 => &mut buttons.1.1.0,
+            _ => &mut buttons.1.1.1.0,
+        }
+    }
+
+    fn show_errors(&mut self, errors: [Option<String>; 4]) {
+        for field in HeaderField::ALL {
+            let error = &errors[field as usize];
+            if *error == self.errors[field as usize] {
+                continue;
+            }
+            let input = self.input_mut(field);
+            match error {
+                Some(error) => {
+                    input.set_attribute("class", "error");
+                    input.set_attribute("title", error);
+                }
+                None => {
+                    input.remove_attribute("class");
+                    input.remove_attribute("title");
+                }
+            }
+        }
+        self.errors = errors;
+    }
+
+    // The function for the assembler.
+    fn module_function(&self) -> ModuleFunction {
+        let (mut lines, _) = self.header.lines();
+        lines.extend(self.editor().parsed_lines());
+        ModuleFunction {
+            name: self.header.name().ok().flatten(),
+            lines,
+        }
+    }
+}
+
+impl WithNode for FunctionEditor {
+    fn with_node(&self, f: impl FnMut(&web_sys::Node), g: AccessToken) {
+        self.component.with_node(f, g);
+    }
+}
+
+impl Component for FunctionEditor {
+    fn try_audit(&self) -> Result<(), String> {
+        self.component.try_audit()
+    }
+}
+
+struct _ModuleEditor {
+    this: Weak<RefCell<_ModuleEditor>>, // for the functions' event handlers
+    factory: ElementFactory,
+    next_id: usize,
+    component: DomStruct<Contents, HtmlDivElement>,
+    onfiledrop: Option<Rc<dyn Fn(FileHandle)>>,
+}
+
+// A handle to the module editor (clones refer to the same module editor).
+#[derive(Clone)]
+pub struct ModuleEditor(Rc<RefCell<_ModuleEditor>>);
+
+impl ModuleEditor {
+    // A module with one function (with the editor's usual greeting), and nothing in
+    // its other sections.
+    pub fn new(factory: &ElementFactory) -> Self {
+        let mut toolbar = Toolbar::new(
+            (
+                Button::new((DomText::new("Add function"), ()), factory.button()),
+                (),
+            ),
+            factory.div(),
+        );
+        toolbar.set_attribute("class", "controls");
+        let ret = ModuleEditor(Rc::new_cyclic(|this: &Weak<RefCell<_ModuleEditor>>| {
+            let module_ref = this.clone();
+            let sections = SectionsEditor::new(factory, move |event| {
+                if let Some(module_ref) = module_ref.upgrade() {
+                    ModuleEditor(module_ref).handle_section_event(event);
+                }
+            });
+            let mut component = DomStruct::new(
+                (sections, (Functions::new(factory.div()), (toolbar, ()))),
+                factory.div(),
+            );
+            component.set_attribute("class", "module");
+            RefCell::new(_ModuleEditor {
+                this: this.clone(),
+                factory: factory.clone(),
+                next_id: 0,
+                component,
+                onfiledrop: None,
+            })
+        }));
+
+        let module_ref = Rc::clone(&ret.0);
+        ret.0
+            .borrow_mut()
+            .component
+            .get_mut()
+            .1
+            .1
+            .0
+            .get_mut()
+            .0
+            .set_onclick(move |_| {
+                ModuleEditor(module_ref.clone()).add_function();
+            });
+
+        {
+            let mut inner = ret.0.borrow_mut();
+            let function = inner.new_function(FunctionText::default(), Editor::new(factory));
+            inner.functions_mut().push(function);
+            inner.refresh();
+        }
+        ret
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.borrow().functions().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // The editor of a function's body.
+    pub fn editor(&self, index: usize) -> Option<Editor> {
+        let inner = self.0.borrow();
+        inner
+            .functions()
+            .get(index)
+            .map(|func| func.editor().clone())
+    }
+
+    pub fn header(&self, index: usize) -> Option<Header> {
+        let inner = self.0.borrow();
+        inner.functions().get(index).map(|func| func.header.clone())
+    }
+
+    // How many declaration lines a function's header stands for (its lines in an
+    // assembled module come after them).
+    pub fn header_len(&self, index: usize) -> usize {
+        self.0.borrow().header_len(index)
+    }
+
+    pub fn set_header_field(&mut self, index: usize, field: HeaderField, text: &str) -> Result<()> {
+        let mut inner = self.0.borrow_mut();
+        inner.check_index(index)?;
+        let _scope = dom_op_scope("module editor");
+        let func = inner
+            .functions_mut()
+            .get_mut(index)
+            .expect("function index");
+        func.input_mut(field).set_value(text);
+        func.header.set_field(field, text);
+        inner.refresh();
+        Ok(())
+    }
+
+    // The problems shown on a function's header fields.
+    pub fn header_errors(&self, index: usize) -> Vec<(HeaderField, String)> {
+        let inner = self.0.borrow();
+        let Some(func) = inner.functions().get(index) else {
+            return Vec::new();
+        };
+        (HeaderField::ALL.into_iter())
+            .filter_map(|field| Some((field, func.errors[field as usize].clone()?)))
+            .collect()
+    }
+
+    // Add an empty function at the end, returning its index.
+    pub fn add_function(&mut self) -> usize {
+        let mut inner = self.0.borrow_mut();
+        let _scope = dom_op_scope("module editor");
+        let editor = Editor::with_text(&inner.factory, "");
+        let function = inner.new_function(FunctionText::default(), editor);
+        inner.functions_mut().push(function);
+        inner.refresh();
+        inner.functions().len() - 1
+    }
+
+    pub fn remove_function(&mut self, index: usize) -> Result<()> {
+        let mut inner = self.0.borrow_mut();
+        inner.check_index(index)?;
+        let _scope = dom_op_scope("module editor");
+        inner.functions_mut().remove(index);
+        inner.refresh();
+        Ok(())
+    }
+
+    // Fold the function's body after its first line, or unfold it if it is folded.
+    pub fn toggle_fold(&mut self, index: usize) -> Result<()> {
+        let Some(mut editor) = self.editor(index) else {
+            bail!("function {index} out of range");
+        };
+        if editor.folded().is_some_and(|folded| folded.start == 1) {
+            editor.unfold();
+            return Ok(());
+        }
+        editor.fold_body()
+    }
+
+    // Move a function to another place in the module (calls by number then refer to
+    // whichever function is in their place).
+    pub fn move_function(&mut self, from: usize, to: usize) -> Result<()> {
+        let mut inner = self.0.borrow_mut();
+        inner.check_index(from)?;
+        inner.check_index(to)?;
+        if from != to {
+            let _scope = dom_op_scope("module editor");
+            let function = inner.functions_mut().remove(from);
+            inner.functions_mut().insert(to, function);
+            inner.refresh();
+        }
+        Ok(())
+    }
+
+    // Each of the other sections' entries, as typed.
+    pub fn sections(&self) -> Sections {
+        self.0.borrow().sections().sections().clone()
+    }
+
+    // Add an empty entry at the end of a section, returning its index (a module has
+    // only one table and one memory).
+    pub fn add_entry(&mut self, section: Section) -> Result<usize> {
+        let mut inner = self.0.borrow_mut();
+        let _scope = dom_op_scope("module editor");
+        let index = inner.sections_mut().add_entry(section)?;
+        inner.refresh();
+        Ok(index)
+    }
+
+    pub fn set_entry_field(
+        &mut self,
+        section: Section,
+        index: usize,
+        field: usize,
+        text: &str,
+    ) -> Result<()> {
+        let mut inner = self.0.borrow_mut();
+        let _scope = dom_op_scope("module editor");
+        inner
+            .sections_mut()
+            .set_field(section, index, field, text)?;
+        inner.refresh();
+        Ok(())
+    }
+
+    pub fn remove_entry(&mut self, section: Section, index: usize) -> Result<()> {
+        let mut inner = self.0.borrow_mut();
+        let _scope = dom_op_scope("module editor");
+        inner.sections_mut().remove_entry(section, index)?;
+        inner.refresh();
+        Ok(())
+    }
+
+    // The problems shown on the other sections' fields.
+    pub fn section_errors(&self) -> Vec<SectionError> {
+        self.0.borrow().sections().errors().to_vec()
+    }
+
+    // Replace all of the functions and sections (e.g. with those of an opened module).
+    pub fn load(&mut self, text: ModuleText) {
+        let mut inner = self.0.borrow_mut();
+        let _scope = dom_op_scope("module editor");
+        inner.sections_mut().load(&text.sections);
+        inner.functions_mut().clear();
+        for func in text.funcs {
+            let editor = Editor::with_text(&inner.factory, &func.lines.join("\n"));
+            let function = inner.new_function(func, editor);
+            inner.functions_mut().push(function);
+        }
+        inner.refresh();
+    }
+
+    // The other sections' entries, and each function's header and lines.
+    pub fn module_text(&self) -> ModuleText {
+        ModuleText {
+            sections: self.sections(),
+            funcs: self.functions(),
+        }
+    }
+
+    // Each function's header and lines.
+    pub fn functions(&self) -> Vec<FunctionText> {
+        (self.0.borrow().functions().iter())
+            .map(|func| FunctionText {
+                header: func.header.clone(),
+                lines: func.editor().lines(),
+            })
+            .collect()
+    }
+
+    // Each function as the assembler sees it: its header's declarations, then its
+    // lines.
+    pub fn module_functions(&self) -> Vec<ModuleFunction> {
+        self.0.borrow().module_functions()
+    }
+
+    // The functions and sections assembled into a module, or the problems that
+    // prevent it (those of the sections first).
+    pub fn assemble(&self) -> Result<Module, Vec<ModuleError>> {
+        let inner = self.0.borrow();
+        let module_functions = inner.module_functions();
+        let (sections, section_errors) = inner.sections().sections().resolve(&module_functions);
+        let mut errors: Vec<ModuleError> = (section_errors.iter())
+            .map(|error| ModuleError {
+                func: None,
+                line: None,
+                message: error.message.clone(),
+                place: error.place(),
+            })
+            .collect();
+        for (index, func) in inner.functions().iter().enumerate() {
+            for (field, error) in HeaderField::ALL.into_iter().zip(&func.errors) {
+                if let Some(error) = error {
+                    errors.push(inner.error(
+                        index,
+                        None,
+                        format!("{}: {error}", field.placeholder()),
+                    ));
+                }
+            }
+        }
+        match assemble_module(&module_functions, &sections) {
+            Ok(module) if errors.is_empty() => Ok(module),
+            Ok(_) => Err(errors),
+            Err(assemble_errors) => {
+                for error in assemble_errors {
+                    let header_len = inner.header_len(error.func);
+                    let line = error.line.checked_sub(header_len);
+                    errors.push(inner.error(error.func, line, error.message));
+                }
+                errors.sort_by_key(|error| (error.func, error.line));
+                Err(errors)
+            }
+        }
+    }
+
+    // The module as a binary .wasm module.
+    pub fn to_wasm(&self) -> Result<Vec<u8>, Vec<ModuleError>> {
+        Ok(self.assemble()?.encode())
+    }
+
+    // The module in the text format (even if it doesn't assemble).
+    pub fn to_wat(&self) -> String {
+        module_text(&self.module_text())
+    }
+
+    // Where a line of a function is, as shown to the user: e.g. "line 3", or in a
+    // module with more than one function, "line 3 of $add" (or of "function 5", for a
+    // function without a name, counting the imports). A line of None is the function's
+    // header.
+    pub fn place(&self, func: usize, line: Option<usize>) -> String {
+        self.0.borrow().place(func, line)
+    }
+
+    // The lines with a breakpoint, as (function, line) pairs.
+    pub fn breakpoints(&self) -> Vec<(usize, usize)> {
+        (self.0.borrow().functions().iter().enumerate())
+            .flat_map(|(index, func)| {
+                (func.editor().breakpoints().into_iter()).map(move |line| (index, line))
+            })
+            .collect()
+    }
+
+    // The line where execution has stopped (if any), as a (function, line) pair.
+    pub fn current_line(&self) -> Option<(usize, usize)> {
+        (self.0.borrow().functions().iter().enumerate())
+            .find_map(|(index, func)| Some((index, func.editor().current_line()?)))
+    }
+
+    pub fn set_current_line(&mut self, location: Option<(usize, usize)>) -> Result<()> {
+        let mut inner = self.0.borrow_mut();
+        if let Some((func, _)) = location {
+            inner.check_index(func)?;
+        }
+        for (index, func) in inner
+            .functions_mut()
+            .iter_mut()
+            .map(FunctionEditor::editor_mut)
+            .enumerate()
+        {
+            match location {
+                Some((current, line)) if current == index => func.set_current_line(Some(line))?,
+                _ => func.set_current_line(None)?,
+            }
+        }
+        Ok(())
+    }
+
+    // Call `handler` with the first file dropped on any function's body.
+    pub fn set_onfiledrop(&mut self, handler: impl Fn(FileHandle) + 'static) {
+        let mut inner = self.0.borrow_mut();
+        let handler: Rc<dyn Fn(FileHandle)> = Rc::new(handler);
+        for editor in inner
+            .functions_mut()
+            .iter_mut()
+            .map(FunctionEditor::editor_mut)
+        {
+            let handler = handler.clone();
+            editor.set_onfiledrop(move |file| handler(file));
+        }
+        inner.onfiledrop = Some(handler);
+    }
+
+    fn find(&self, id: usize) -> Option<usize> {
+        (self.0.borrow().functions().iter()).position(|func| func.id == id)
+    }
+
+    fn handle_header_input(&mut self, id: usize, field: HeaderField) {
+        let mut inner = self.0.borrow_mut();
+        let Some(func) = inner.functions_mut().iter_mut().find(|func| func.id == id) else {
+            return;
+        };
+        let text = func.input_mut(field).value();
+        func.header.set_field(field, &text);
+        let _scope = dom_op_scope("module editor");
+        inner.refresh();
+    }
+
+    fn handle_button(&mut self, id: usize, button: usize) {
+        let Some(index) = self.find(id) else {
+            return;
+        };
+        let _ = match button {
+            0 if index > 0 => self.move_function(index, index - 1),
+            1 if index + 1 < self.len() => self.move_function(index, index + 1),
+            2 => self.remove_function(index),
+            3 => self.toggle_fold(index),
+            _ => Ok(()),
+        };
+    }
+
+    fn handle_change(&mut self) {
+        let mut inner = self.0.borrow_mut();
+        let _scope = dom_op_scope("module editor");
+        inner.refresh();
+    }
+
+    fn handle_section_event(&mut self, event: SectionEvent) {
+        let _ = match event {
+            SectionEvent::Add(section) => self.add_entry(section).map(|_| ()),
+            SectionEvent::Input(section, id, field) => {
+                let mut inner = self.0.borrow_mut();
+                let Some(index) = inner.sections().find(section, id) else {
+                    return;
+                };
+                inner.sections_mut().update_field(section, index, field);
+                let _scope = dom_op_scope("module editor");
+                inner.refresh();
+                Ok(())
+            }
+            SectionEvent::Remove(section, id) => {
+                let index = self.0.borrow().sections().find(section, id);
+                match index {
+                    Some(index) => self.remove_entry(section, index),
+                    None => Ok(()),
+                }
+            }
+        };
+    }
+}
+
+impl _ModuleEditor {
+    fn sections(&self) -> &SectionsEditor {
+        &self.component.get().0
+    }
+
+    fn sections_mut(&mut self) -> &mut SectionsEditor {
+        &mut self.component.get_mut().0
+    }
+
+    fn functions(&self) -> &Functions {
+        &self.component.get().1.0
+    }
+
+    fn functions_mut(&mut self) -> &mut Functions {
+        &mut self.component.get_mut().1.0
+    }
+
+    fn check_index(&self, index: usize) -> Result<()> {
+        if index >= self.functions().len() {
+            bail!("function {index} out of range");
+        }
+        Ok(())
+    }
+
+    fn new_function(&mut self, text: FunctionText, mut editor: Editor) -> FunctionEditor {
+        let id = self.next_id;
+        self.next_id += 1;
+        let factory = &self.factory;
+        let input = |field: HeaderField| {
+            let mut input = Input::new((), factory.input());
+            input.set_attribute("placeholder", field.placeholder());
+            input.set_attribute("spellcheck", "false");
+            input.set_value(text.header.field(field));
+            input
+        };
+        let button = |label: &str| Button::new((DomText::new(label), ()), factory.button());
+        let mut header = HeaderDiv::new(
+            (
+                input(HeaderField::Name),
+                (
+                    input(HeaderField::Params),
+                    (
+                        input(HeaderField::Results),
+                        (
+                            input(HeaderField::Locals),
+                            (
+                                button("Up"),
+                                (button("Down"), (button("Remove"), (button("Fold"), ()))),
+                            ),
+                        ),
+                    ),
+                ),
+            ),
+            factory.div(),
+        );
+        header.set_attribute("class", "header");
+
+        let module_ref = self.this.clone();
+        editor.set_onchange(move || {
+            if let Some(module_ref) = module_ref.upgrade() {
+                ModuleEditor(module_ref).handle_change();
+            }
+        });
+        if let Some(handler) = &self.onfiledrop {
+            let handler = handler.clone();
+            editor.set_onfiledrop(move |file| handler(file));
+        }
+        let (popup, tooltip) = (editor.completion_popup(), editor.tooltip());
+        let mut component =
+            DomStruct::new((header, (editor, (popup, (tooltip, ())))), factory.div());
+        component.set_attribute("class", "function");
+        let mut function = FunctionEditor {
+            id,
+            header: text.header,
+            errors: Default::default(),
+            component,
+        };
+
+        for field in HeaderField::ALL {
+            let module_ref = self.this.clone();
+            function.input_mut(field).set_oninput(move |_| {
+                if let Some(module_ref) = module_ref.upgrade() {
+                    ModuleEditor(module_ref).handle_header_input(id, field);
+                }
+            });
+        }
+        for index in 0..4 {
+            let module_ref = self.this.clone();
+            function.button_mut(index).set_onclick(move |_| {
+                if let Some(module_ref) = module_ref.upgrade() {
+                    ModuleEditor(module_ref).handle_button(id, index);
+                }
+            });
+        }
+        function
+    }
+
+    fn module_functions(&self) -> Vec<ModuleFunction> {
+        (self.functions().iter())
+            .map(FunctionEditor::module_function)
+            .collect()
+    }
+
+    fn header_len(&self, index: usize) -> usize {
+        (self.functions().get(index)).map_or(0, |func| func.header.lines().0.len())
+    }
+
+    fn place(&self, func: usize, line: Option<usize>) -> String {
+        let place = match line {
+            Some(line) => format!("line {}", line + 1),
+            None => "the header".to_string(),
+        };
+        if self.functions().len() < 2 {
+            return place;
+        }
+        let name = (self.functions().get(func)).and_then(|func| func.header.name().ok().flatten());
+        let imports = self.sections().sections().entries(Section::Imports).len();
+        match name {
+            Some(name) => format!("{place} of ${name}"),
+            None => format!("{place} of function {}", PRINT_FUNCS.len() + imports + func),
+        }
+    }
+
+    fn error(&self, func: usize, line: Option<usize>, message: String) -> ModuleError {
+        ModuleError {
+            func: Some(func),
+            line,
+            message,
+            place: self.place(func, line),
+        }
+    }
+
+    // What each function should show: the problems of its header's fields (including
+    // a name that a print function, an import or an earlier function already has), and
+    // the context its body is analyzed in; and the problems of the other sections.
+    fn analysis(&self) -> (Vec<Analysis>, Vec<SectionError>) {
+        let module_functions = self.module_functions();
+        let (sections, section_errors) = self.sections().sections().resolve(&module_functions);
+        let funcs = sections.funcs(&module_functions);
+        let globals = sections.globals();
+        let mut names: Vec<String> = (PRINT_FUNCS.iter())
+            .map(|(name, _)| name.to_string())
+            .chain(
+                sections
+                    .imports
+                    .iter()
+                    .filter_map(|import| import.func.name.clone()),
+            )
+            .collect();
+        let functions = (self.functions().iter())
+            .map(|func| {
+                let (header, field_errors) = func.header.lines();
+                let mut errors: [Option<String>; 4] = Default::default();
+                for (field, error) in field_errors {
+                    errors[field as usize] = Some(error);
+                }
+                if let Ok(Some(name)) = func.header.name() {
+                    if names.contains(&name) {
+                        errors[HeaderField::Name as usize] =
+                            Some(format!("another function is named ${name}"));
+                    }
+                    names.push(name);
+                }
+                let context = ModuleContext {
+                    header,
+                    funcs: funcs.clone(),
+                    globals: globals.clone(),
+                };
+                (errors, context)
+            })
+            .collect();
+        (functions, section_errors)
+    }
+
+    // Bring every function's header problems and analysis, and the sections'
+    // problems, up to date.
+    fn refresh(&mut self) {
+        let (functions, section_errors) = self.analysis();
+        for (func, (errors, context)) in self.functions_mut().iter_mut().zip(functions) {
+            func.show_errors(errors);
+            func.editor_mut().set_module_context(Some(context));
+        }
+        self.sections_mut().show_errors(section_errors);
+    }
+}
+
+impl WithNode for ModuleEditor {
+    fn with_node(&self, f: impl FnMut(&web_sys::Node), g: AccessToken) {
+        self.0.borrow().component.with_node(f, g);
+    }
+}
+
+impl WithElement<HtmlDivElement> for ModuleEditor {
+    fn with_element(&self, f: impl FnMut(&HtmlDivElement), g: AccessToken) {
+        self.0.borrow().component.with_element(f, g);
+    }
+}
+
+// To audit, audit the sections and functions, then check that their problems and the
+// functions' analysis are up to date.
+impl Component for ModuleEditor {
+    fn try_audit(&self) -> Result<(), String> {
+        let inner = self.0.borrow();
+        inner.component.try_audit()?;
+        let (functions, section_errors) = inner.analysis();
+        for (func, (errors, context)) in inner.functions().iter().zip(functions) {
+            audit_eq!(func.errors, errors);
+            audit_eq!(func.editor().module_context(), Some(context));
+        }
+        audit_eq!(inner.sections().errors(), section_errors);
+        Ok(())
+    }
+}
@@ -0,0 +1,432 @@
+// Block structure of a sequence of lines: how deeply each line is nested inside
+// block/loop/if, and which lines break the structure (an `else` outside an `if`, an
+// `end` with nothing to close, a mismatched label, or a block that is never closed).
+// The editor folds a block from its opening line to the `end` that closes it, and keeps
+// the nesting up to date incrementally as its lines are edited.
+
+use std::ops::Range;
+
+use crate::instruction::{Immediate, Instruction};
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LineNesting {
+    pub depth: usize,
+    pub error: Option<String>,
+}
+
+// A block that is open, with the key of the line that opened it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct Frame {
+    key: usize,
+    mnemonic: &'static str,
+    label: Option<String>,
+    has_else: bool,
+}
+
+fn never_closed(frame: &Frame) -> String {
+    format!("`{}` is never closed by `end`", frame.mnemonic)
+}
+
+// An `else` or `end` that repeats a label must repeat the label of its own block.
+fn check_label(frame: &Frame, instr: &Instruction) -> Option<String> {
+    match (&instr.imm, &frame.label) {
+        (Immediate::Label(Some(label)), Some(expected)) if label != expected => Some(format!(
+            "`{} ${label}` does not match `{} ${expected}`",
+            instr.mnemonic(),
+            frame.mnemonic
+        )),
+        (Immediate::Label(Some(label)), None) => Some(format!(
+            "`{} ${label}` closes a {} without a label",
+            instr.mnemonic(),
+            frame.mnemonic
+        )),
+        _ => None,
+    }
+}
+
+// Nest a line (with the given key) inside the blocks open before it, updating `stack`
+// to the blocks open after it. Returns the line's nesting, leaving out whether a block
+// it opens is ever closed, and the block it closes (if any).
+fn nest_line(
+    stack: &mut Vec<Frame>,
+    key: usize,
+    instr: Option<&Instruction>,
+) -> (LineNesting, Option<Frame>) {
+    let mut line = LineNesting {
+        depth: stack.len(),
+        error: None,
+    };
+    let mut closed = None;
+    if let Some(instr) = instr {
+        match (instr.mnemonic(), &instr.imm) {
+            ("block" | "loop" | "if", Immediate::Block { label, .. }) => stack.push(Frame {
+                key,
+                mnemonic: instr.mnemonic(),
+                label: label.clone(),
+                has_else: false,
+            }),
+            ("else", _) => match stack.last_mut() {
+                Some(frame) if frame.mnemonic == "if" && !frame.has_else => {
+                    frame.has_else = true;
+                    line.depth -= 1;
+                    line.error = check_label(frame, instr);
+                }
+                Some(frame) if frame.mnemonic == "if" => {
+                    line.error = Some("this `if` already has an `else`".to_string())
+                }
+                _ => line.error = Some("`else` outside of an `if`".to_string()),
+            },
+            ("end", _) => match stack.pop() {
+                Some(frame) => {
+                    line.depth -= 1;
+                    line.error = check_label(&frame, instr);
+                    closed = Some(frame);
+                }
+                None => line.error = Some("`end` without a matching block".to_string()),
+            },
+            _ => {}
+        }
+    }
+    (line, closed)
+}
+
+// The nesting of each line, given each line's instruction (if it has one). A block's
+// opening and closing lines (and an `else`) are at the depth outside the block.
+pub fn nesting<'a>(lines: impl IntoIterator<Item = Option<&'a Instruction>>) -> Vec<LineNesting> {
+    let mut ret = Vec::new();
+    let mut stack = Vec::new();
+    for (index, instr) in lines.into_iter().enumerate() {
+        ret.push(nest_line(&mut stack, index, instr).0);
+    }
+    for frame in stack {
+        ret[frame.key].error = Some(never_closed(&frame));
+    }
+    ret
+}
+
+// The nesting of a sequence of lines, kept up to date as they are edited: after an
+// edit, the lines are renested from the first edited line only until the blocks open
+// after a line are the same as before the edit, since the lines after it then nest as
+// they did. (Each line has a key, so that a block is not mistaken for another block
+// that looks the same.)
+#[derive(Default)]
+pub struct IncrementalNesting {
+    lines: Vec<NestedLine>,
+    next_key: usize,
+}
+
+#[derive(Default)]
+struct NestedLine {
+    key: usize,
+    nesting: LineNesting,
+    open: Vec<Frame>, // the blocks open after the line
+    closed: bool,     // whether the line opens a block that an `end` closes
+}
+
+impl IncrementalNesting {
+    pub fn get(&self, index: usize) -> Option<&LineNesting> {
+        self.lines.get(index).map(|line| &line.nesting)
+    }
+
+    // Whether the line opens a block that is closed (and so could be folded).
+    pub fn is_closed(&self, index: usize) -> bool {
+        self.lines.get(index).is_some_and(|line| line.closed)
+    }
+
+    // Replace the lines in `range` with `count` lines, then renest them and the lines
+    // after them (as far as needed), given each line's instruction after the edit.
+    // Returns the lines whose nesting or closing changed (including every new line).
+    pub fn splice<'a>(
+        &mut self,
+        range: Range<usize>,
+        count: usize,
+        instruction: impl Fn(usize) -> Option<&'a Instruction>,
+    ) -> Vec<usize> {
+        // A replaced line's key (and, until it is renested, whether it was closed)
+        // carries over to the new line in its place, so that an edit within a line
+        // that opens a block doesn't make its block look new.
+        let removed: Vec<NestedLine> = self.lines.drain(range.clone()).collect();
+        let added: Vec<NestedLine> = (0..count)
+            .map(|offset| match removed.get(offset) {
+                Some(line) => NestedLine {
+                    key: line.key,
+                    closed: line.closed,
+                    ..NestedLine::default()
+                },
+                None => {
+                    self.next_key += 1;
+                    NestedLine {
+                        key: self.next_key,
+                        ..NestedLine::default()
+                    }
+                }
+            })
+            .collect();
+        self.lines.splice(range.start..range.start, added);
+
+        let mut changed: Vec<usize> = (range.start..range.start + count).collect();
+        let mut stack = match range.start {
+            0 => Vec::new(),
+            start => self.lines[start - 1].open.clone(),
+        };
+        let mut index = range.start;
+        let mut converged = false;
+        while index < self.lines.len() && !converged {
+            let key = self.lines[index].key;
+            let (mut nesting, closed) = nest_line(&mut stack, key, instruction(index));
+            if let Some(frame) = closed {
+                self.set_closed(index, &frame, true, &mut changed);
+            }
+            let line = &mut self.lines[index];
+            // (a block the line opens keeps its closing until it is closed here, or
+            // left open at the end)
+            let opened = stack.last().filter(|frame| frame.key == key);
+            let closed = opened.is_some() && line.closed;
+            if let Some(frame) = opened.filter(|_| !closed) {
+                nesting.error = Some(never_closed(frame));
+            }
+            if line.nesting != nesting || line.closed != closed {
+                line.nesting = nesting;
+                line.closed = closed;
+                changed.push(index);
+            }
+            converged = index >= range.start + count && line.open == stack;
+            if !converged {
+                line.open = stack.clone();
+            }
+            index += 1;
+        }
+        if !converged {
+            for frame in &stack {
+                self.set_closed(index, frame, false, &mut changed);
+            }
+        }
+        changed.sort_unstable();
+        changed.dedup();
+        changed
+    }
+
+    // Record whether the block opened by a line (before line `before`) is closed.
+    fn set_closed(&mut self, before: usize, frame: &Frame, closed: bool, changed: &mut Vec<usize>) {
+        let index = self.lines[..before]
+            .iter()
+            .rposition(|line| line.key == frame.key)
+            .expect("line that opened the block");
+        let line = &mut self.lines[index];
+        if line.closed != closed {
+            line.closed = closed;
+            line.nesting.error = match closed {
+                true => None,
+                false => Some(never_closed(frame)),
+            };
+            changed.push(index);
+        }
+    }
+}
+
+// The blocks still open after the lines (each one's mnemonic and label), outermost
+// first.
+pub fn open_blocks<'a>(
+    lines: impl IntoIterator<Item = Option<&'a Instruction>>,
+) -> Vec<(&'static str, Option<String>)> {
+    let mut stack = Vec::new();
+    for instr in lines.into_iter().flatten() {
+        match (instr.mnemonic(), &instr.imm) {
+            ("block" | "loop" | "if", Immediate::Block { label, .. }) => {
+                stack.push((instr.mnemonic(), label.clone()))
+            }
+            ("end", _) => {
+                stack.pop();
+            }
+            _ => {}
+        }
+    }
+    stack
+}
+
+// For each line that opens a block, the line of the `end` that closes it (None for
+// other lines, and for blocks that are never closed).
+pub fn block_ends<'a>(
+    lines: impl IntoIterator<Item = Option<&'a Instruction>>,
+) -> Vec<Option<usize>> {
+    let mut ret = Vec::new();
+    let mut stack = Vec::new();
+    for (index, instr) in lines.into_iter().enumerate() {
+        ret.push(None);
+        match instr.map(|instr| (instr.mnemonic(), &instr.imm)) {
+            Some(("block" | "loop" | "if", Immediate::Block { .. })) => stack.push(index),
+            Some(("end", _)) => {
+                if let Some(start) = stack.pop() {
+                    ret[start] = Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::{ParsedLine, parse_line};
+    use proptest::prelude::*;
+
+    fn nest(text: &str) -> Vec<(usize, Option<String>)> {
+        let parsed: Vec<_> = text.lines().map(parse_line).collect();
+        nesting(parsed.iter().map(|line| line.instruction()))
+            .into_iter()
+            .map(|line| (line.depth, line.error))
+            .collect()
+    }
+
+    fn depths(text: &str) -> Vec<usize> {
+        nest(text).into_iter().map(|(depth, _)| depth).collect()
+    }
+
+    #[test]
+    fn depths_follow_blocks() {
+        let text = "i32.const 1\nif $a (result i32)\n\ni32.const 2\nelse $a\nloop\nbr 0\nend\ni32.const 3\nend $a\ndrop";
+        assert_eq!(depths(text), vec![0, 0, 1, 1, 0, 1, 2, 1, 1, 0, 0]);
+        assert!(nest(text).iter().all(|(_, error)| error.is_none()));
+        // a line that doesn't parse doesn't affect nesting
+        assert_eq!(depths("block\nend x\nnop"), vec![0, 1, 1]);
+    }
+
+    #[test]
+    fn blocks_left_open() {
+        let parsed: Vec<_> = "block $a\nloop\nif $b\nend\nnop"
+            .lines()
+            .map(parse_line)
+            .collect();
+        let open =
+            |count: usize| open_blocks(parsed[..count].iter().map(|line| line.instruction()));
+        assert_eq!(open(0), []);
+        assert_eq!(
+            open(3),
+            [
+                ("block", Some("a".to_string())),
+                ("loop", None),
+                ("if", Some("b".to_string()))
+            ]
+        );
+        assert_eq!(open(5), [("block", Some("a".to_string())), ("loop", None)]);
+    }
+
+    #[test]
+    fn ends_of_blocks() {
+        let parsed: Vec<_> = "block $a\nloop\nif\nelse\nend\nend\nnop\nend\nblock"
+            .lines()
+            .map(parse_line)
+            .collect();
+        assert_eq!(
+            block_ends(parsed.iter().map(|line| line.instruction())),
+            [
+                Some(7),
+                Some(5),
+                Some(4),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn structure_errors() {
+        let errors = |text: &str| -> Vec<Option<String>> {
+            nest(text).into_iter().map(|(_, error)| error).collect()
+        };
+        assert_eq!(
+            errors("end\nelse"),
+            vec![
+                Some("`end` without a matching block".to_string()),
+                Some("`else` outside of an `if`".to_string())
+            ]
+        );
+        assert_eq!(
+            errors("block\nelse\nend")[1],
+            Some("`else` outside of an `if`".to_string())
+        );
+        assert_eq!(
+            errors("if\nelse\nelse\nend")[2],
+            Some("this `if` already has an `else`".to_string())
+        );
+        assert_eq!(
+            errors("block $x\nend $y")[1],
+            Some("`end $y` does not match `block $x`".to_string())
+        );
+        assert_eq!(
+            errors("loop\nblock\nend"),
+            vec![
+                Some("`loop` is never closed by `end`".to_string()),
+                None,
+                None
+            ]
+        );
+    }
+
+    fn line() -> impl Strategy<Value = &'static str> {
+        prop::sample::select(vec![
+            "block", "block $a", "loop", "if", "else", "end", "end $a", "nop", "",
+        ])
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+        #[test]
+        fn incremental_nesting_matches(
+            edits in prop::collection::vec(
+                (any::<usize>(), 0..4usize, prop::collection::vec(line(), 0..6)),
+                1..20,
+            )
+        ) {
+            let mut lines: Vec<ParsedLine> = Vec::new();
+            let mut incremental = IncrementalNesting::default();
+            for (start, removed, inserted) in edits {
+                let start = start % (lines.len() + 1);
+                let range = start..(start + removed).min(lines.len());
+                let count = inserted.len();
+                lines.splice(range.clone(), inserted.into_iter().map(parse_line));
+                let changed = incremental.splice(range, count, |index| lines[index].instruction());
+
+                let instrs = || lines.iter().map(|line| line.instruction());
+                let ends = block_ends(instrs());
+                for (index, line_nesting) in nesting(instrs()).iter().enumerate() {
+                    prop_assert_eq!(incremental.get(index), Some(line_nesting), "line {}", index);
+                    prop_assert_eq!(incremental.is_closed(index), ends[index].is_some());
+                }
+                prop_assert_eq!(incremental.get(lines.len()), None);
+                prop_assert!(changed.iter().all(|&index| index < lines.len()));
+            }
+        }
+    }
+
+    #[test]
+    fn incremental_nesting_stops_where_it_matches() {
+        let lines: Vec<_> = "block\nnop\nnop\nend\nnop\nnop"
+            .lines()
+            .map(parse_line)
+            .collect();
+        let mut incremental = IncrementalNesting::default();
+        incremental.splice(0..0, lines.len(), |index| lines[index].instruction());
+
+        // an edit inside the block that leaves it open changes only the edited line
+        let changed = incremental.splice(1..2, 1, |index| lines[index].instruction());
+        assert_eq!(changed, [1]);
+
+        // closing the block early renests the lines up to its old `end` (which now
+        // closes nothing), and no further
+        let edited: Vec<_> = "block\nend\nnop\nend\nnop\nnop"
+            .lines()
+            .map(parse_line)
+            .collect();
+        let changed = incremental.splice(1..2, 1, |index| edited[index].instruction());
+        assert_eq!(changed, [1, 2, 3]);
+        assert_eq!(incremental.get(2).unwrap().depth, 0);
+        assert!(incremental.is_closed(0));
+    }
+}
@@ -0,0 +1,817 @@
+// A parser for one line of the editor: either nothing (blank or only comments), one
+// instruction with its immediates, a declaration of the function's params, results,
+// locals or exports (e.g. `(param $x i32)` or `(export "f")`), or an error with the
+// byte span it refers to.
+
+use crate::{
+    instruction::{
+        BlockType, ImmKind, Immediate, Index, InstrInfo, Instruction, MemArg, ValType, lookup,
+    },
+    token::{Token, TokenKind, tokenize},
+};
+use std::ops::Range;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Range<usize>, // in bytes
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DeclKind {
+    Param,
+    Result,
+    Local,
+    Export,
+}
+
+impl DeclKind {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            DeclKind::Param => "param",
+            DeclKind::Result => "result",
+            DeclKind::Local => "local",
+            DeclKind::Export => "export",
+        }
+    }
+}
+
+// A named param or local has exactly one type. For an export, `name` is the exported
+// name (and there are no types).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Declaration {
+    pub kind: DeclKind,
+    pub name: Option<String>,
+    pub types: Vec<ValType>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ParsedLine {
+    Empty,
+    Instr(Instruction),
+    Decl(Declaration),
+    Error(ParseError),
+}
+
+impl ParsedLine {
+    pub fn instruction(&self) -> Option<&Instruction> {
+        match self {
+            ParsedLine::Instr(instr) => Some(instr),
+            _ => None,
+        }
+    }
+
+    pub fn declaration(&self) -> Option<&Declaration> {
+        match self {
+            ParsedLine::Decl(decl) => Some(decl),
+            _ => None,
+        }
+    }
+
+    pub fn error(&self) -> Option<&ParseError> {
+        match self {
+            ParsedLine::Error(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+pub fn parse_line(line: &str) -> ParsedLine {
+    let tokens: Vec<Token> = tokenize(line)
+        .into_iter()
+        .filter(|token| !matches!(token.kind, TokenKind::Space | TokenKind::Comment))
+        .collect();
+    if tokens.is_empty() {
+        return ParsedLine::Empty;
+    }
+    let mut parser = Parser {
+        line,
+        tokens,
+        pos: 0,
+    };
+    let result = if parser.peek_text() == Some("(") {
+        parser.declaration().map(ParsedLine::Decl)
+    } else {
+        parser.instruction().map(ParsedLine::Instr)
+    };
+    result.unwrap_or_else(ParsedLine::Error)
+}
+
+struct Parser<'a> {
+    line: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+type ParseResult<T> = Result<T, ParseError>;
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn text(&self, token: &Token) -> &'a str {
+        &self.line[token.range.clone()]
+    }
+
+    fn peek_text(&self) -> Option<&'a str> {
+        self.peek().map(|token| self.text(token))
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let ret = self.tokens.get(self.pos).cloned();
+        self.pos += ret.is_some() as usize;
+        ret
+    }
+
+    // An error at the current token, or at the end of the line.
+    fn error<T>(&self, message: impl Into<String>) -> ParseResult<T> {
+        let span = match self.peek() {
+            Some(token) => token.range.clone(),
+            None => self.line.len()..self.line.len(),
+        };
+        Err(ParseError {
+            message: message.into(),
+            span,
+        })
+    }
+
+    fn instruction(&mut self) -> ParseResult<Instruction> {
+        let Some(word) = self.peek_text() else {
+            return self.error("expected an instruction");
+        };
+        let info = match (self.peek().unwrap().kind, lookup(word)) {
+            (TokenKind::Instruction, Some(info)) => info,
+            (TokenKind::Other, _) => return self.error(format!("unknown instruction `{word}`")),
+            _ => return self.error(format!("expected an instruction, found `{word}`")),
+        };
+        self.next();
+        let imm = self.immediate(info)?;
+        if let Some(extra) = self.peek_text() {
+            return self.error(format!("unexpected `{extra}` after {}", info.mnemonic));
+        }
+        Ok(Instruction { info, imm })
+    }
+
+    fn declaration(&mut self) -> ParseResult<Declaration> {
+        self.next();
+        let kind = match self.peek_text() {
+            Some("param") => DeclKind::Param,
+            Some("result") => DeclKind::Result,
+            Some("local") => DeclKind::Local,
+            Some("export") => DeclKind::Export,
+            _ => return self.error("expected `param`, `result`, `local` or `export`"),
+        };
+        self.next();
+        if kind == DeclKind::Export {
+            let name = match self.peek() {
+                Some(token) if token.kind == TokenKind::String => {
+                    match parse_string(self.text(token)) {
+                        Some(name) => name,
+                        None => return self.error("invalid string"),
+                    }
+                }
+                _ => return self.error("expected the export's name (a string)"),
+            };
+            self.next();
+            if self.peek_text() != Some(")") {
+                return self.error("expected `)`");
+            }
+            self.next();
+            if let Some(extra) = self.peek_text() {
+                return self.error(format!("unexpected `{extra}` after the declaration"));
+            }
+            return Ok(Declaration {
+                kind,
+                name: Some(name),
+                types: Vec::new(),
+            });
+        }
+        let name = match kind {
+            DeclKind::Result if self.peek().is_some_and(|token| token.kind == TokenKind::Id) => {
+                return self.error("results can't be named");
+            }
+            _ => self.opt_id(),
+        };
+        let mut types = Vec::new();
+        while let Some(ty) = self.peek_text().and_then(ValType::from_keyword) {
+            if name.is_some() && types.len() == 1 {
+                return self.error(format!("a named {} has only one type", kind.keyword()));
+            }
+            types.push(ty);
+            self.next();
+        }
+        match self.peek_text() {
+            Some(")") => {
+                self.next();
+            }
+            Some(_) => return self.error("expected a value type (i32, i64, f32 or f64)"),
+            None => return self.error("expected `)`"),
+        }
+        if name.is_some() && types.is_empty() {
+            return self.error(format!("expected the type of the {}", kind.keyword()));
+        }
+        if let Some(extra) = self.peek_text() {
+            return self.error(format!("unexpected `{extra}` after the declaration"));
+        }
+        Ok(Declaration { kind, name, types })
+    }
+
+    fn immediate(&mut self, info: &'static InstrInfo) -> ParseResult<Immediate> {
+        Ok(match info.imm {
+            ImmKind::None | ImmKind::Memory => Immediate::None,
+            ImmKind::OptLabel => Immediate::Label(self.opt_id()),
+            ImmKind::I32 => {
+                let text = self.number("an i32 constant")?;
+                match parse_int(text, 32) {
+                    Some(value) => {
+                        self.next();
+                        Immediate::I32(value as i32)
+                    }
+                    None => return self.error(format!("invalid i32 constant `{text}`")),
+                }
+            }
+            ImmKind::I64 => {
+                let text = self.number("an i64 constant")?;
+                match parse_int(text, 64) {
+                    Some(value) => {
+                        self.next();
+                        Immediate::I64(value as i64)
+                    }
+                    None => return self.error(format!("invalid i64 constant `{text}`")),
+                }
+            }
+            ImmKind::F32 => {
+                let text = self.number("an f32 constant")?;
+                match parse_float(text, FloatFormat::F32) {
+                    Some(bits) => {
+                        self.next();
+                        Immediate::F32(bits as u32)
+                    }
+                    None => return self.error(format!("invalid f32 constant `{text}`")),
+                }
+            }
+            ImmKind::F64 => {
+                let text = self.number("an f64 constant")?;
+                match parse_float(text, FloatFormat::F64) {
+                    Some(bits) => {
+                        self.next();
+                        Immediate::F64(bits)
+                    }
+                    None => return self.error(format!("invalid f64 constant `{text}`")),
+                }
+            }
+            ImmKind::Local => Immediate::Index(self.index("a local")?),
+            ImmKind::Global => Immediate::Index(self.index("a global")?),
+            ImmKind::Func => Immediate::Index(self.index("a function")?),
+            ImmKind::Label => Immediate::Index(self.index("a label")?),
+            ImmKind::BlockType => {
+                let label = self.opt_id();
+                let ty = self.block_type()?;
+                Immediate::Block { label, ty }
+            }
+            ImmKind::BrTable => {
+                let mut labels = vec![self.index("a label")?];
+                while matches!(self.peek(), Some(token) if matches!(token.kind, TokenKind::Number | TokenKind::Id))
+                {
+                    labels.push(self.index("a label")?);
+                }
+                let default = labels.pop().expect("at least one label");
+                Immediate::BrTable { labels, default }
+            }
+            ImmKind::MemArg(natural) => Immediate::MemArg(self.memarg(natural)?),
+        })
+    }
+
+    fn opt_id(&mut self) -> Option<String> {
+        match self.peek() {
+            Some(token) if token.kind == TokenKind::Id => {
+                let token = self.next().unwrap();
+                Some(self.text(&token)[1..].to_string())
+            }
+            _ => None,
+        }
+    }
+
+    // The text of the next token, which must be a number (it is not consumed).
+    fn number(&self, what: &str) -> ParseResult<&'a str> {
+        match self.peek() {
+            Some(token) if token.kind == TokenKind::Number => Ok(self.text(token)),
+            _ => self.error(format!("expected {what}")),
+        }
+    }
+
+    fn index(&mut self, what: &str) -> ParseResult<Index> {
+        match self.peek() {
+            Some(token) if token.kind == TokenKind::Id => Ok(Index::Id(self.opt_id().expect("id"))),
+            Some(token) if token.kind == TokenKind::Number => {
+                let text = self.text(token);
+                match parse_uint(text, 32) {
+                    Some(value) => {
+                        self.next();
+                        Ok(Index::Num(value as u32))
+                    }
+                    None => self.error(format!("invalid index `{text}`")),
+                }
+            }
+            _ => self.error(format!("expected {what} (a number or $name)")),
+        }
+    }
+
+    // An optional `(result t)`.
+    fn block_type(&mut self) -> ParseResult<BlockType> {
+        if self.peek_text() != Some("(") {
+            return Ok(BlockType::Empty);
+        }
+        self.next();
+        match self.peek_text() {
+            Some("result") => {
+                self.next();
+            }
+            Some("param") | Some("type") => {
+                return self.error("only a (result t) block type is supported");
+            }
+            _ => return self.error("expected `result`"),
+        }
+        let ty = match self.peek_text().and_then(ValType::from_keyword) {
+            Some(ty) => ty,
+            None => return self.error("expected a value type (i32, i64, f32 or f64)"),
+        };
+        self.next();
+        match self.peek_text() {
+            Some(")") => {
+                self.next();
+                Ok(BlockType::Value(ty))
+            }
+            Some(word) if ValType::from_keyword(word).is_some() => {
+                self.error("blocks with multiple results are not supported")
+            }
+            _ => self.error("expected `)`"),
+        }
+    }
+
+    // Optional `offset=N` then `align=N` (a power of two, stored as its log2).
+    fn memarg(&mut self, natural: u32) -> ParseResult<MemArg> {
+        let mut memarg = MemArg {
+            offset: 0,
+            align: natural,
+        };
+        if let Some(text) = self
+            .peek_text()
+            .and_then(|text| text.strip_prefix("offset="))
+        {
+            match parse_uint(text, 32) {
+                Some(offset) => memarg.offset = offset as u32,
+                None => return self.error(format!("invalid offset `{text}`")),
+            }
+            self.next();
+        }
+        if let Some(text) = self
+            .peek_text()
+            .and_then(|text| text.strip_prefix("align="))
+        {
+            match parse_uint(text, 32) {
+                Some(align) if align.is_power_of_two() => memarg.align = align.trailing_zeros(),
+                _ => return self.error(format!("alignment `{text}` is not a power of two")),
+            }
+            self.next();
+        }
+        Ok(memarg)
+    }
+}
+
+// A quoted string token (which must be valid UTF-8), with its escapes decoded.
+pub fn parse_string(text: &str) -> Option<String> {
+    let bytes = parse_string_bytes(text)?;
+    String::from_utf8(bytes).ok()
+}
+
+pub fn parse_string_bytes(text: &str) -> Option<Vec<u8>> {
+    let inner = text.strip_prefix('"')?.strip_suffix('"')?;
+    let mut ret = Vec::new();
+    let mut chars = inner.chars();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            let mut buf = [0; 4];
+            ret.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+            continue;
+        }
+        match chars.next()? {
+            'n' => ret.push(b'\n'),
+            't' => ret.push(b'\t'),
+            'r' => ret.push(b'\r'),
+            '\\' => ret.push(b'\\'),
+            '\'' => ret.push(b'\''),
+            '"' => ret.push(b'"'),
+            'u' => {
+                let rest = chars.as_str().strip_prefix('{')?;
+                let end = rest.find('}')?;
+                let ch = char::from_u32(parse_digits(&rest[..end], 16)?.try_into().ok()?)?;
+                let mut buf = [0; 4];
+                ret.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+                chars = rest[end + 1..].chars();
+            }
+            high => {
+                let low = chars.next()?;
+                ret.push((high.to_digit(16)? * 16 + low.to_digit(16)?) as u8);
+            }
+        }
+    }
+    Some(ret)
+}
+
+// Digits in a base, with single underscores allowed between digits.
+fn parse_digits(text: &str, radix: u32) -> Option<u128> {
+    if text.is_empty() || text.starts_with('_') || text.ends_with('_') || text.contains("__") {
+        return None;
+    }
+    let mut value: u128 = 0;
+    for ch in text.chars().filter(|&ch| ch != '_') {
+        value = value
+            .checked_mul(radix as u128)?
+            .checked_add(ch.to_digit(radix)? as u128)?;
+    }
+    Some(value)
+}
+
+fn parse_unsigned(text: &str) -> Option<u128> {
+    match text.strip_prefix("0x") {
+        Some(hex) => parse_digits(hex, 16),
+        None => parse_digits(text, 10),
+    }
+}
+
+// An unsigned integer (no sign allowed) that fits in `bits` bits.
+pub fn parse_uint(text: &str, bits: u32) -> Option<u64> {
+    let value = parse_unsigned(text)?;
+    (value < 1 << bits).then_some(value as u64)
+}
+
+// An integer constant of `bits` bits: either signed or unsigned interpretation is
+// allowed (as in the text format), returned as the two's-complement bit pattern.
+pub fn parse_int(text: &str, bits: u32) -> Option<u64> {
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let magnitude = parse_unsigned(unsigned)?;
+    let mask = (1u128 << bits) - 1;
+    if negative {
+        (magnitude <= 1 << (bits - 1)).then(|| (magnitude.wrapping_neg() & mask) as u64)
+    } else {
+        (magnitude <= mask).then_some(magnitude as u64)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FloatFormat {
+    F32,
+    F64,
+}
+
+impl FloatFormat {
+    fn mantissa_bits(self) -> u32 {
+        match self {
+            FloatFormat::F32 => 23,
+            FloatFormat::F64 => 52,
+        }
+    }
+
+    fn bits(self, value: f64) -> u64 {
+        match self {
+            FloatFormat::F32 => (value as f32).to_bits() as u64,
+            FloatFormat::F64 => value.to_bits(),
+        }
+    }
+
+    fn sign_bit(self) -> u64 {
+        match self {
+            FloatFormat::F32 => 1 << 31,
+            FloatFormat::F64 => 1 << 63,
+        }
+    }
+}
+
+// A float constant (decimal, hexadecimal, inf, nan, or nan:0x payload), returned as
+// its bit pattern. Values that round to infinity are rejected.
+pub fn parse_float(text: &str, format: FloatFormat) -> Option<u64> {
+    let (negative, unsigned) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let sign = if negative { format.sign_bit() } else { 0 };
+    let mantissa_bits = format.mantissa_bits();
+    let nan_bits = format.bits(f64::INFINITY); // all-ones exponent, zero mantissa
+    if unsigned == "inf" {
+        return Some(sign | nan_bits);
+    }
+    if unsigned == "nan" {
+        return Some(sign | nan_bits | 1 << (mantissa_bits - 1));
+    }
+    if let Some(payload) = unsigned.strip_prefix("nan:0x") {
+        let payload = parse_digits(payload, 16)?;
+        return (payload >= 1 && payload < 1 << mantissa_bits)
+            .then_some(sign | nan_bits | payload as u64);
+    }
+
+    let value = match unsigned.strip_prefix("0x") {
+        Some(hex) => parse_hex_float(hex)?,
+        None => parse_decimal_float(unsigned, format)?,
+    };
+    let bits = format.bits(value);
+    (bits != nan_bits).then_some(sign | bits)
+}
+
+// Split a float body into integer digits, fraction digits and exponent text.
+fn split_float(text: &str, exponent_char: char) -> Option<(&str, &str, Option<&str>)> {
+    let (mantissa, exponent) = match text.find([exponent_char, exponent_char.to_ascii_uppercase()])
+    {
+        Some(pos) => (&text[..pos], Some(&text[pos + 1..])),
+        None => (text, None),
+    };
+    let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    Some((int, frac, exponent))
+}
+
+fn parse_exponent(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let value = parse_digits(digits, 10)?.min(100_000) as i64;
+    Some(if negative { -value } else { value })
+}
+
+fn parse_hex_float(text: &str) -> Option<f64> {
+    let (int, frac, exponent) = split_float(text, 'p')?;
+    parse_digits(int, 16)?;
+    if !frac.is_empty() {
+        parse_digits(frac, 16)?;
+    }
+    let mut exponent = exponent.map_or(Some(0), parse_exponent)?;
+
+    // Keep the first 60 significant bits (plus a sticky bit for the rest), which is
+    // more than enough to round correctly to 53 bits.
+    let mut significand: u64 = 0;
+    let mut sticky = false;
+    exponent -= 4 * frac.chars().filter(|&ch| ch != '_').count() as i64;
+    for ch in int.chars().chain(frac.chars()).filter(|&ch| ch != '_') {
+        let digit = ch.to_digit(16)? as u64;
+        if significand >> 56 == 0 {
+            significand = significand << 4 | digit;
+        } else {
+            sticky |= digit != 0;
+            exponent += 4;
+        }
+    }
+    let significand = significand | sticky as u64;
+    let mut value = significand as f64;
+    // scale in steps so that intermediate results don't overflow or underflow early
+    let mut remaining = exponent;
+    while remaining != 0 {
+        let step = remaining.clamp(-1000, 1000);
+        value *= 2f64.powi(step as i32);
+        remaining -= step;
+    }
+    Some(value)
+}
+
+fn parse_decimal_float(text: &str, format: FloatFormat) -> Option<f64> {
+    let (int, frac, exponent) = split_float(text, 'e')?;
+    parse_digits(int, 10)?;
+    if !frac.is_empty() {
+        parse_digits(frac, 10)?;
+    }
+    if let Some(exponent) = exponent {
+        parse_exponent(exponent)?;
+    }
+    let cleaned: String = text.chars().filter(|&ch| ch != '_').collect();
+    // parse at the target precision, so that rounding happens only once
+    match format {
+        FloatFormat::F32 => cleaned.parse::<f32>().ok().map(|value| value as f64),
+        FloatFormat::F64 => cleaned.parse::<f64>().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::instruction::INSTRUCTIONS;
+
+    fn instr(line: &str) -> Instruction {
+        match parse_line(line) {
+            ParsedLine::Instr(instr) => instr,
+            other => panic!("{line:?} parsed as {other:?}"),
+        }
+    }
+
+    fn error(line: &str) -> (String, &str) {
+        match parse_line(line) {
+            ParsedLine::Error(err) => (err.message, &line[err.span]),
+            other => panic!("{line:?} parsed as {other:?}"),
+        }
+    }
+
+    #[test]
+    fn instructions_with_immediates() {
+        assert_eq!(parse_line("  ;; just a comment"), ParsedLine::Empty);
+        assert_eq!(
+            instr("  i32.const 42 ;; answer"),
+            Instruction::new("i32.const", Immediate::I32(42))
+        );
+        assert_eq!(instr("i32.const 0xffff_ffff").imm, Immediate::I32(-1));
+        assert_eq!(
+            instr("i32.const -0x8000_0000").imm,
+            Immediate::I32(i32::MIN)
+        );
+        assert_eq!(instr("i64.const -1").imm, Immediate::I64(-1));
+        assert_eq!(
+            instr("local.get $x").imm,
+            Immediate::Index(Index::Id("x".to_string()))
+        );
+        assert_eq!(instr("br_if 1").imm, Immediate::Index(Index::Num(1)));
+        assert_eq!(
+            instr("block (result i32)").imm,
+            Immediate::Block {
+                label: None,
+                ty: BlockType::Value(ValType::I32)
+            }
+        );
+        assert_eq!(
+            instr("loop $top").imm,
+            Immediate::Block {
+                label: Some("top".to_string()),
+                ty: BlockType::Empty
+            }
+        );
+        assert_eq!(
+            instr("br_table 0 $out 2").imm,
+            Immediate::BrTable {
+                labels: vec![Index::Num(0), Index::Id("out".to_string())],
+                default: Index::Num(2)
+            }
+        );
+        assert_eq!(
+            instr("i64.load offset=8 align=4").imm,
+            Immediate::MemArg(MemArg {
+                offset: 8,
+                align: 2
+            })
+        );
+        assert_eq!(
+            instr("i32.store8").imm,
+            Immediate::MemArg(MemArg {
+                offset: 0,
+                align: 0
+            })
+        );
+        assert_eq!(
+            instr("end $top").imm,
+            Immediate::Label(Some("top".to_string()))
+        );
+    }
+
+    #[test]
+    fn declarations() {
+        let decl = |line| match parse_line(line) {
+            ParsedLine::Decl(decl) => decl,
+            other => panic!("{line:?} parsed as {other:?}"),
+        };
+        assert_eq!(
+            decl("(param $x i32) ;; first"),
+            Declaration {
+                kind: DeclKind::Param,
+                name: Some("x".to_string()),
+                types: vec![ValType::I32]
+            }
+        );
+        assert_eq!(
+            decl("(result i64 f32)"),
+            Declaration {
+                kind: DeclKind::Result,
+                name: None,
+                types: vec![ValType::I64, ValType::F32]
+            }
+        );
+        assert_eq!(decl("(local)").types, vec![]);
+        assert_eq!(
+            error("(local $y i32 i32)"),
+            ("a named local has only one type".to_string(), "i32")
+        );
+        assert_eq!(
+            error("(result $r i32)"),
+            ("results can't be named".to_string(), "$r")
+        );
+        assert_eq!(error("(param i32"), ("expected `)`".to_string(), ""));
+        assert_eq!(error("(func)").1, "func");
+        assert_eq!(
+            decl(r#"(export "add\u{1F600}\41")"#),
+            Declaration {
+                kind: DeclKind::Export,
+                name: Some("add\u{1F600}A".to_string()),
+                types: vec![]
+            }
+        );
+        assert_eq!(
+            error("(export $f)"),
+            ("expected the export's name (a string)".to_string(), "$f")
+        );
+        assert_eq!(parse_string_bytes(r#""\ff\n""#), Some(vec![0xff, b'\n']));
+        assert_eq!(parse_string(r#""\ff""#), None);
+    }
+
+    #[test]
+    fn floats() {
+        let f32_bits = |text| parse_float(text, FloatFormat::F32).map(|bits| bits as u32);
+        let f64_bits = |text| parse_float(text, FloatFormat::F64);
+        assert_eq!(f32_bits("1.5"), Some(1.5f32.to_bits()));
+        assert_eq!(f32_bits("-0x1.8p1"), Some((-3.0f32).to_bits()));
+        assert_eq!(f32_bits("0x1p-149"), Some(1));
+        assert_eq!(f32_bits("nan:0x200000"), Some(0x7fa0_0000));
+        assert_eq!(f32_bits("-inf"), Some(f32::NEG_INFINITY.to_bits()));
+        assert_eq!(f32_bits("1e39"), None);
+        assert_eq!(f32_bits("nan:0x800000"), None);
+        assert_eq!(f64_bits("1_000.25e-2"), Some(10.0025f64.to_bits()));
+        assert_eq!(f64_bits("0x1.fffffffffffffp1023"), Some(f64::MAX.to_bits()));
+        assert_eq!(f64_bits("nan"), Some(0x7ff8_0000_0000_0000));
+        assert_eq!(f64_bits(".5"), None);
+        assert_eq!(f64_bits("1__0"), None);
+    }
+
+    #[test]
+    fn errors_have_spans() {
+        assert_eq!(
+            error("i32.cnst 1"),
+            ("unknown instruction `i32.cnst`".to_string(), "i32.cnst")
+        );
+        assert_eq!(
+            error("i32.const 4294967296"),
+            (
+                "invalid i32 constant `4294967296`".to_string(),
+                "4294967296"
+            )
+        );
+        assert_eq!(
+            error("local.get"),
+            ("expected a local (a number or $name)".to_string(), "")
+        );
+        assert_eq!(
+            error("i32.add 1"),
+            ("unexpected `1` after i32.add".to_string(), "1")
+        );
+        assert_eq!(
+            error("block (result i32 i64)"),
+            (
+                "blocks with multiple results are not supported".to_string(),
+                "i64"
+            )
+        );
+        assert_eq!(
+            error("i32.load align=3"),
+            ("alignment `3` is not a power of two".to_string(), "align=3")
+        );
+        assert_eq!(error("42").1, "42");
+    }
+
+    // Every instruction prints in a form that parses back to the same instruction.
+    #[test]
+    fn display_round_trips() {
+        let mut examples: Vec<Instruction> = INSTRUCTIONS
+            .iter()
+            .map(|info| {
+                let imm = match info.imm {
+                    ImmKind::None | ImmKind::Memory => Immediate::None,
+                    ImmKind::OptLabel => Immediate::Label(Some("l".to_string())),
+                    ImmKind::I32 => Immediate::I32(-7),
+                    ImmKind::I64 => Immediate::I64(i64::MIN),
+                    ImmKind::F32 => Immediate::F32(0.1f32.to_bits()),
+                    ImmKind::F64 => Immediate::F64((-1e300f64).to_bits()),
+                    ImmKind::Local | ImmKind::Global | ImmKind::Func | ImmKind::Label => {
+                        Immediate::Index(Index::Num(3))
+                    }
+                    ImmKind::BlockType => Immediate::Block {
+                        label: Some("b".to_string()),
+                        ty: BlockType::Value(ValType::F64),
+                    },
+                    ImmKind::BrTable => Immediate::BrTable {
+                        labels: vec![Index::Id("a".to_string())],
+                        default: Index::Num(0),
+                    },
+                    ImmKind::MemArg(natural) => Immediate::MemArg(MemArg {
+                        offset: 16,
+                        align: natural.saturating_sub(1),
+                    }),
+                };
+                Instruction { info, imm }
+            })
+            .collect();
+        examples.push(Instruction::new("f32.const", Immediate::F32(0x7fa0_0000)));
+        examples.push(Instruction::new(
+            "f64.const",
+            Immediate::F64(0xfff8_0000_0000_0000),
+        ));
+        for example in examples {
+            assert_eq!(instr(&example.to_string()), example, "{example}");
+        }
+    }
+}
@@ -0,0 +1,107 @@
+// Running a module in the browser's own WebAssembly engine (through js_sys): instantiate
+// it with the host's print functions, call one of its exports, and convert the results
+// (or the trap) back.
+
+use crate::{
+    instruction::ValType,
+    module::{HOST_MODULE, PRINT_FUNCS},
+    validate::FuncType,
+    value::Value,
+};
+use js_sys::{Array, Function, Object, Reflect, WebAssembly};
+use std::rc::Rc;
+use wasm_bindgen::{JsCast, JsValue, closure::Closure};
+use wasm_bindgen_futures::JsFuture;
+
+// How a call ended: with its results, or with a message (e.g. for a trap).
+pub type Outcome = Result<Vec<Value>, String>;
+
+fn to_js(value: Value) -> JsValue {
+    match value {
+        Value::I32(value) => value.into(),
+        Value::I64(value) => value.into(), // a BigInt
+        Value::F32(value) => (value as f64).into(),
+        Value::F64(value) => value.into(),
+    }
+}
+
+fn from_js(value: &JsValue, ty: ValType) -> Option<Value> {
+    Some(match ty {
+        ValType::I32 => Value::I32(value.as_f64()? as i32),
+        ValType::I64 => Value::I64(i64::try_from(value.clone()).ok()?),
+        ValType::F32 => Value::F32(value.as_f64()? as f32),
+        ValType::F64 => Value::F64(value.as_f64()?),
+    })
+}
+
+// The message of a JS exception (e.g. "RuntimeError: unreachable").
+fn error_message(error: &JsValue) -> String {
+    match error.dyn_ref::<js_sys::Error>() {
+        Some(error) => format!(
+            "{}: {}",
+            String::from(error.name()),
+            String::from(error.message())
+        ),
+        None => format!("{error:?}"),
+    }
+}
+
+fn set(target: &Object, key: &str, value: &JsValue) -> Result<(), String> {
+    Reflect::set(target, &key.into(), value)
+        .map(|_| ())
+        .map_err(|error| error_message(&error))
+}
+
+fn get(target: &JsValue, key: &str) -> Result<JsValue, String> {
+    Reflect::get(target, &key.into()).map_err(|error| error_message(&error))
+}
+
+// Instantiate `wasm`, and call its export `name` (of type `ty`) with `args`. The
+// host's print functions pass each value they're called with to `print`.
+pub async fn run(
+    wasm: &[u8],
+    name: &str,
+    ty: &FuncType,
+    args: &[Value],
+    print: Rc<dyn Fn(Value)>,
+) -> Outcome {
+    let host = Object::new();
+    let mut closures = Vec::new();
+    for (func, param) in PRINT_FUNCS {
+        let print = print.clone();
+        let closure = Closure::<dyn Fn(JsValue)>::new(move |value: JsValue| {
+            if let Some(value) = from_js(&value, param) {
+                print(value)
+            }
+        });
+        set(&host, func, closure.as_ref())?;
+        closures.push(closure);
+    }
+    let imports = Object::new();
+    set(&imports, HOST_MODULE, &host)?;
+
+    let instantiated = JsFuture::from(WebAssembly::instantiate_buffer(wasm, &imports))
+        .await
+        .map_err(|error| format!("can't instantiate the module: {}", error_message(&error)))?;
+    let exports = get(&get(&instantiated, "instance")?, "exports")?;
+    let func: Function = get(&exports, name)?
+        .dyn_into()
+        .map_err(|_| format!("no function is exported as \"{name}\""))?;
+
+    let js_args: Array = args.iter().map(|value| to_js(*value)).collect();
+    let returned = Reflect::apply(&func, &JsValue::UNDEFINED, &js_args)
+        .map_err(|error| error_message(&error))?;
+    drop(closures);
+
+    // one result is returned as is, and several as an array
+    let returned = match ty.results.len() {
+        0 => Vec::new(),
+        1 => vec![returned],
+        _ => Array::from(&returned).to_vec(),
+    };
+    (returned.iter().zip(&ty.results))
+        .map(|(value, ty)| {
+            from_js(value, *ty).ok_or_else(|| format!("expected an {ty} result, got {value:?}"))
+        })
+        .collect()
+}
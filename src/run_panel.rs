@@ -0,0 +1,263 @@
+// The Run panel, next to the editor: inputs for which export to call and with what
+// arguments, a Run button, and the output of the last run (the values the program
+// printed, then its results or the reason it failed). Running assembles the module's
+// functions and calls the export in the browser's WebAssembly engine (see run.rs).
+
+use crate::{
+    dom_struct::DomStruct,
+    dom_text::DomText,
+    dom_vec::DomVec,
+    module_editor::ModuleEditor,
+    run::{Outcome, run},
+    validate::FuncType,
+    value::{Value, format_results, parse_args},
+    web_support::{
+        AccessToken, Component, ElementFactory, WithElement, WithNode, audit_eq, dom_op_scope,
+    },
+};
+use std::{cell::RefCell, rc::Rc};
+use web_sys::{HtmlButtonElement, HtmlDivElement, HtmlInputElement};
+
+type Input = DomStruct<(), HtmlInputElement>;
+type Button = DomStruct<(DomText, ()), HtmlButtonElement>;
+type Controls = DomStruct<(Input, (Input, (Button, ()))), HtmlDivElement>;
+type OutputLine = DomStruct<(DomText, ()), HtmlDivElement>;
+type Output = DomVec<OutputLine, HtmlDivElement>;
+type PanelContents = (Controls, (Output, ()));
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputKind {
+    Print,
+    Result,
+    Error,
+}
+
+impl OutputKind {
+    fn class(&self) -> &'static str {
+        match self {
+            OutputKind::Print => "out-print",
+            OutputKind::Result => "out-result",
+            OutputKind::Error => "out-error",
+        }
+    }
+}
+
+// What a run needs once the module has assembled.
+struct Call {
+    wasm: Vec<u8>,
+    name: String,
+    ty: FuncType,
+    args: Vec<Value>,
+}
+
+struct _RunPanel {
+    factory: ElementFactory,
+    module: ModuleEditor,
+    component: DomStruct<PanelContents, HtmlDivElement>,
+    output: Vec<(OutputKind, String)>,
+    run_count: usize, // output from an earlier run (still finishing) is dropped
+}
+
+pub struct RunPanel(Rc<RefCell<_RunPanel>>);
+
+impl RunPanel {
+    pub fn new(factory: &ElementFactory, module: &ModuleEditor) -> Self {
+        let input = |placeholder: &str| {
+            let mut input = Input::new((), factory.input());
+            input.set_attribute("placeholder", placeholder);
+            input.set_attribute("spellcheck", "false");
+            input
+        };
+        let mut controls = Controls::new(
+            (
+                input("export"),
+                (
+                    input("arguments"),
+                    (Button::new((DomText::new("Run"), ()), factory.button()), ()),
+                ),
+            ),
+            factory.div(),
+        );
+        controls.set_attribute("class", "controls");
+        let mut output = Output::new(factory.div());
+        output.set_attribute("class", "output");
+        let mut component = DomStruct::new((controls, (output, ())), factory.div());
+        component.set_attribute("class", "runpanel");
+
+        let ret = RunPanel(Rc::new(RefCell::new(_RunPanel {
+            factory: factory.clone(),
+            module: module.clone(),
+            component,
+            output: Vec::new(),
+            run_count: 0,
+        })));
+
+        let panel_ref = Rc::clone(&ret.0);
+        ret.0.borrow_mut().button_mut().set_onclick(move |_| {
+            let panel = RunPanel(panel_ref.clone());
+            wasm_bindgen_futures::spawn_local(async move { panel.run().await });
+        });
+
+        ret
+    }
+
+    pub fn set_export(&mut self, name: &str) {
+        self.0.borrow_mut().export_input_mut().set_value(name);
+    }
+
+    pub fn set_args(&mut self, args: &str) {
+        self.0.borrow_mut().args_input_mut().set_value(args);
+    }
+
+    // The output of the last run, one entry per line.
+    pub fn output(&self) -> Vec<(OutputKind, String)> {
+        self.0.borrow().output.clone()
+    }
+
+    // Assemble the module, and call the export (or, if none is given, the
+    // first exported function) with the arguments.
+    pub async fn run(&self) {
+        let run_count = {
+            let mut inner = self.0.borrow_mut();
+            inner.run_count += 1;
+            inner.clear_output();
+            inner.run_count
+        };
+        let call = self.0.borrow().prepare();
+        let call = match call {
+            Ok(call) => call,
+            Err(messages) => {
+                let mut inner = self.0.borrow_mut();
+                for message in messages {
+                    inner.show(OutputKind::Error, message);
+                }
+                return;
+            }
+        };
+
+        let panel_ref = Rc::clone(&self.0);
+        let print = Rc::new(move |value: Value| {
+            let mut inner = panel_ref.borrow_mut();
+            if inner.run_count == run_count {
+                inner.show(OutputKind::Print, value.to_string());
+            }
+        });
+        let outcome = run(&call.wasm, &call.name, &call.ty, &call.args, print).await;
+        let mut inner = self.0.borrow_mut();
+        if inner.run_count == run_count {
+            inner.show_outcome(&call, outcome);
+        }
+    }
+}
+
+impl _RunPanel {
+    fn controls_mut(&mut self) -> &mut Controls {
+        &mut self.component.get_mut().0
+    }
+
+    fn export_input(&self) -> &Input {
+        &self.component.get().0.get().0
+    }
+
+    fn export_input_mut(&mut self) -> &mut Input {
+        &mut self.controls_mut().get_mut().0
+    }
+
+    fn args_input(&self) -> &Input {
+        &self.component.get().0.get().1.0
+    }
+
+    fn args_input_mut(&mut self) -> &mut Input {
+        &mut self.controls_mut().get_mut().1.0
+    }
+
+    fn button_mut(&mut self) -> &mut Button {
+        &mut self.controls_mut().get_mut().1.1.0
+    }
+
+    fn output_vec_mut(&mut self) -> &mut Output {
+        &mut self.component.get_mut().1.0
+    }
+
+    fn clear_output(&mut self) {
+        let _scope = dom_op_scope("run panel");
+        self.output.clear();
+        self.output_vec_mut().clear();
+    }
+
+    fn show(&mut self, kind: OutputKind, text: String) {
+        let _scope = dom_op_scope("run panel");
+        let mut line = OutputLine::new((DomText::new(&text), ()), self.factory.div());
+        line.set_attribute("class", kind.class());
+        self.output_vec_mut().push(line);
+        self.output.push((kind, text));
+    }
+
+    fn show_outcome(&mut self, call: &Call, outcome: Outcome) {
+        match outcome {
+            Ok(results) => {
+                let args: Vec<String> = call.args.iter().map(|arg| arg.to_string()).collect();
+                let text = format!(
+                    "{}({}) returned {}",
+                    call.name,
+                    args.join(", "),
+                    format_results(&results)
+                );
+                self.show(OutputKind::Result, text);
+            }
+            Err(message) => self.show(OutputKind::Error, message),
+        }
+    }
+
+    // The module, the export and its arguments, or what's wrong with them.
+    fn prepare(&self) -> Result<Call, Vec<String>> {
+        let module = (self.module.assemble()).map_err(|errors| {
+            errors
+                .iter()
+                .map(|error| error.to_string())
+                .collect::<Vec<_>>()
+        })?;
+        let export = (module.func_export(self.export_input().value().trim()))
+            .map_err(|error| vec![error])?;
+        let name = export.name.clone();
+        let ty = module
+            .func_type(export.index)
+            .expect("exported function")
+            .clone();
+        let args =
+            parse_args(&self.args_input().value(), &ty.params).map_err(|error| vec![error])?;
+        Ok(Call {
+            wasm: module.encode(),
+            name,
+            ty,
+            args,
+        })
+    }
+}
+
+impl WithNode for RunPanel {
+    fn with_node(&self, f: impl FnMut(&web_sys::Node), g: AccessToken) {
+        self.0.borrow().component.with_node(f, g);
+    }
+}
+
+impl WithElement<HtmlDivElement> for RunPanel {
+    fn with_element(&self, f: impl FnMut(&HtmlDivElement), g: AccessToken) {
+        self.0.borrow().component.with_element(f, g);
+    }
+}
+
+// To audit, audit the panel's elements, then check that the output lines show the
+// recorded output.
+impl Component for RunPanel {
+    fn try_audit(&self) -> Result<(), String> {
+        let inner = self.0.borrow();
+        inner.component.try_audit()?;
+        let lines = &inner.component.get().1.0;
+        audit_eq!(lines.len(), inner.output.len());
+        for (line, (_, text)) in lines.iter().zip(&inner.output) {
+            audit_eq!(line.get().0.get(), text);
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,1054 @@
+// Test scripts in the syntax of the spec's tests: a series of commands, each an
+// s-expression such as
+// `(assert_return (invoke "add" (i32.const 1) (i32.const 2)) (i32.const 3))`
+// (which expects the call to return 3) or
+// `(assert_trap (invoke "div" (i32.const 1) (i32.const 0)) "integer divide by zero")`
+// (which expects it to trap, with a message starting with the given text). Running a
+// script checks each of its assertions against a module in the interpreter (see
+// interpreter.rs), in order, so an assertion sees the memory and globals as the calls
+// before it left them.
+//
+// A script can also define its own modules, as the spec's .wast scripts do:
+// `(module $id? ...)` in the text format (see text_module.rs), or as `binary` or
+// `quote` strings, each becoming the module that later commands invoke (or, with its
+// $id, `(invoke $id "name")`). `(register "name" $id?)` makes a module's exports
+// importable by later modules under that name, and the "spectest" module's print
+// functions are provided (doing nothing). `assert_invalid` and `assert_malformed`
+// expect a module to be rejected with a message starting with the given text; as the
+// assembler's messages are the editor's own, its reasons are put in the spec's words
+// first (see spec_message). The spec's commands for what the interpreter doesn't have
+// (such as `assert_exhaustion`, or a `module definition`) are skipped rather than
+// failed.
+
+use crate::{
+    disassemble::disassemble_wasm,
+    instruction::ValType,
+    interpreter::{Machine, Trap},
+    module::{ExportKind, HOST_MODULE, Module},
+    parse::{parse_string, parse_string_bytes},
+    sections::TextError,
+    text_module::text_module,
+    value::{Value, format_results},
+};
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fmt,
+    rc::{Rc, Weak},
+};
+
+// How many instructions an invocation runs before it's given up on.
+const MAX_STEPS: usize = 1_000_000;
+// The module whose functions the spec's tests import (its print functions, at least).
+const SPECTEST: &str = "spectest";
+
+// An s-expression: an atom (a keyword, number, $id or string, as written) or a list,
+// and the line (counting from 0) it starts on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SExpr {
+    pub line: usize,
+    pub kind: SExprKind,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SExprKind {
+    Atom(String),
+    List(Vec<SExpr>),
+}
+
+impl SExpr {
+    pub(crate) fn atom(&self) -> Option<&str> {
+        match &self.kind {
+            SExprKind::Atom(atom) => Some(atom),
+            SExprKind::List(_) => None,
+        }
+    }
+
+    // A list's items after its first, if it starts with the keyword.
+    pub(crate) fn form(&self, keyword: &str) -> Option<&[SExpr]> {
+        match &self.kind {
+            SExprKind::List(items) if items.first()?.atom() == Some(keyword) => Some(&items[1..]),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn string(&self) -> Option<String> {
+        parse_string(self.atom()?)
+    }
+}
+
+// As written, but with one space between a list's items.
+impl fmt::Display for SExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            SExprKind::Atom(atom) => f.write_str(atom),
+            SExprKind::List(items) => {
+                f.write_str("(")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(" ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+// Why a script can't be read, and the line the problem is on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ScriptError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line + 1, self.message)
+    }
+}
+
+struct Reader<'a> {
+    text: &'a str,
+    pos: usize,
+    line: usize,
+}
+
+impl Reader<'_> {
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += ch.len_utf8();
+        if ch == '\n' {
+            self.line += 1;
+        }
+        Some(ch)
+    }
+
+    fn error(&self, line: usize, message: &str) -> ScriptError {
+        ScriptError {
+            line,
+            message: message.to_string(),
+        }
+    }
+
+    // Skip whitespace and comments (`;; to the end of the line` and `(; nested ;)`).
+    fn skip_space(&mut self) -> Result<(), ScriptError> {
+        loop {
+            let rest = &self.text[self.pos..];
+            if rest.starts_with(";;") {
+                while self.peek().is_some_and(|ch| ch != '\n') {
+                    self.bump();
+                }
+            } else if rest.starts_with("(;") {
+                let line = self.line;
+                let mut depth = 0;
+                loop {
+                    let rest = &self.text[self.pos..];
+                    if rest.starts_with("(;") {
+                        depth += 1;
+                        self.pos += 2;
+                    } else if rest.starts_with(";)") {
+                        depth -= 1;
+                        self.pos += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else if self.bump().is_none() {
+                        return Err(self.error(line, "unclosed comment"));
+                    }
+                }
+            } else if self.peek().is_some_and(char::is_whitespace) {
+                self.bump();
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    // The next s-expression, if there's one before the end.
+    fn read(&mut self) -> Result<Option<SExpr>, ScriptError> {
+        self.skip_space()?;
+        let line = self.line;
+        let Some(ch) = self.peek() else {
+            return Ok(None);
+        };
+        let kind = match ch {
+            '(' => {
+                self.bump();
+                let mut items = Vec::new();
+                loop {
+                    self.skip_space()?;
+                    match self.peek() {
+                        Some(')') => break,
+                        Some(_) => items.extend(self.read()?),
+                        None => return Err(self.error(line, "unclosed parenthesis")),
+                    }
+                }
+                self.bump();
+                SExprKind::List(items)
+            }
+            ')' => return Err(self.error(line, "unexpected `)`")),
+            _ => {
+                let start = self.pos;
+                if ch == '"' {
+                    self.bump();
+                    loop {
+                        match self.bump() {
+                            Some('"') => break,
+                            Some('\\') => {
+                                self.bump();
+                            }
+                            Some('\n') | None => {
+                                return Err(self.error(line, "unclosed string"));
+                            }
+                            Some(_) => {}
+                        }
+                    }
+                } else {
+                    while (self.peek())
+                        .is_some_and(|ch| !ch.is_whitespace() && !"()\";".contains(ch))
+                    {
+                        self.bump();
+                    }
+                }
+                SExprKind::Atom(self.text[start..self.pos].to_string())
+            }
+        };
+        Ok(Some(SExpr { line, kind }))
+    }
+}
+
+// Read the s-expressions of a script (stopping at the first that can't be read).
+pub fn read_sexprs(text: &str) -> (Vec<SExpr>, Option<ScriptError>) {
+    let mut reader = Reader {
+        text,
+        pos: 0,
+        line: 0,
+    };
+    let mut sexprs = Vec::new();
+    loop {
+        match reader.read() {
+            Ok(Some(sexpr)) => sexprs.push(sexpr),
+            Ok(None) => return (sexprs, None),
+            Err(error) => return (sexprs, Some(error)),
+        }
+    }
+}
+
+// A call to an export (of the module with the $id, or of the latest module).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Invoke {
+    pub module: Option<String>,
+    pub name: String,
+    pub args: Vec<Value>,
+}
+
+// A module of a script: the fields of a module in the text format, the bytes of a
+// binary module, or text to read as a module.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ModuleSource {
+    Text(Vec<SExpr>),
+    Binary(Vec<u8>),
+    Quote(String),
+}
+
+impl ModuleSource {
+    // The module, assembled, or why it can't be.
+    pub fn module(&self) -> Result<Module, Vec<TextError>> {
+        let rejected = |message: String| vec![message.into()];
+        let text = match self {
+            ModuleSource::Text(fields) => text_module(fields).map_err(rejected)?,
+            ModuleSource::Binary(bytes) => {
+                disassemble_wasm(bytes).map_err(|error| rejected(format!("{error:#}")))?
+            }
+            ModuleSource::Quote(text) => {
+                let (sexprs, error) = read_sexprs(text);
+                if let Some(error) = error {
+                    return Err(rejected(error.message));
+                }
+                // (the text is either a whole module or its fields)
+                let fields = match &sexprs[..] {
+                    [module] => {
+                        (module.form("module")).map_or(&sexprs[..], |items| module_fields(items).1)
+                    }
+                    fields => fields,
+                };
+                text_module(fields).map_err(rejected)?
+            }
+        };
+        text.assemble()
+    }
+}
+
+// A result an assertion expects: a value (floats compare by their bits), or any NaN
+// of a kind (`nan:canonical` or `nan:arithmetic`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Expected {
+    Value(Value),
+    CanonicalNan(ValType),
+    ArithmeticNan(ValType),
+}
+
+impl Expected {
+    fn matches(&self, value: &Value) -> bool {
+        // (the quiet bit, and the rest of the payload)
+        let nan_bits = |value: &Value| match value {
+            Value::F32(value) => Some((value.to_bits() as u64 & 0x7fffff, 0x400000)),
+            Value::F64(value) => Some((value.to_bits() & 0xfffffffffffff, 0x8000000000000)),
+            _ => None,
+        };
+        let is_nan = |value: &Value| match value {
+            Value::F32(value) => value.is_nan(),
+            Value::F64(value) => value.is_nan(),
+            _ => false,
+        };
+        match *self {
+            Expected::Value(expected) => match (expected, *value) {
+                (Value::F32(a), Value::F32(b)) => a.to_bits() == b.to_bits(),
+                (Value::F64(a), Value::F64(b)) => a.to_bits() == b.to_bits(),
+                (a, b) => a == b,
+            },
+            Expected::CanonicalNan(ty) => {
+                value.ty() == ty
+                    && is_nan(value)
+                    && nan_bits(value).is_some_and(|(payload, quiet)| payload == quiet)
+            }
+            Expected::ArithmeticNan(ty) => {
+                value.ty() == ty
+                    && is_nan(value)
+                    && nan_bits(value).is_some_and(|(payload, quiet)| payload & quiet != 0)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Value(value) => write!(f, "{value} : {}", value.ty()),
+            Expected::CanonicalNan(ty) => write!(f, "nan:canonical : {ty}"),
+            Expected::ArithmeticNan(ty) => write!(f, "nan:arithmetic : {ty}"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Module(Option<String>, ModuleSource),
+    Register(String, Option<String>),
+    Invoke(Invoke),
+    AssertReturn(Invoke, Vec<Expected>),
+    AssertTrap(Invoke, String),
+    AssertInvalid(ModuleSource, String),
+    AssertMalformed(ModuleSource, String),
+    Skip(String), // why
+}
+
+// The spec's commands that scripts can have but the runner doesn't support.
+const UNSUPPORTED: &[&str] = &[
+    "assert_exhaustion",
+    "assert_unlinkable",
+    "assert_uninstantiable",
+    "assert_exception",
+    "get",
+];
+
+// A command of a script (or why it isn't one), and the line it starts on.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Entry {
+    pub line: usize,
+    pub command: Result<Command, String>,
+}
+
+// A constant's type and number, e.g. `(i32.const 1)`.
+fn constant(sexpr: &SExpr) -> Result<(ValType, &str), String> {
+    let parts = match &sexpr.kind {
+        SExprKind::List(items) => match &items[..] {
+            [op, number] => op.atom().zip(number.atom()),
+            _ => None,
+        },
+        SExprKind::Atom(_) => None,
+    };
+    (parts)
+        .and_then(|(op, number)| Some((ValType::from_keyword(op.strip_suffix(".const")?)?, number)))
+        .ok_or_else(|| "expected a constant such as `(i32.const 1)`".to_string())
+}
+
+fn value(sexpr: &SExpr) -> Result<Value, String> {
+    let (ty, number) = constant(sexpr)?;
+    Value::parse(number, ty).ok_or_else(|| format!("invalid {ty} `{number}`"))
+}
+
+fn expected(sexpr: &SExpr) -> Result<Expected, String> {
+    let (ty, number) = constant(sexpr)?;
+    let float = matches!(ty, ValType::F32 | ValType::F64);
+    match number {
+        "nan:canonical" if float => Ok(Expected::CanonicalNan(ty)),
+        "nan:arithmetic" if float => Ok(Expected::ArithmeticNan(ty)),
+        _ => value(sexpr).map(Expected::Value),
+    }
+}
+
+// A module's $id (if it has one), and the items after it.
+fn module_fields(items: &[SExpr]) -> (Option<String>, &[SExpr]) {
+    match items.split_first() {
+        Some((id, rest)) if id.atom().is_some_and(|atom| atom.starts_with('$')) => {
+            (id.atom().map(str::to_string), rest)
+        }
+        _ => (None, items),
+    }
+}
+
+fn invoke(sexpr: &SExpr) -> Result<Invoke, String> {
+    let Some(items) = sexpr.form("invoke") else {
+        return Err("expected `(invoke \"name\" ...)`".to_string());
+    };
+    let (module, items) = module_fields(items);
+    let name = (items.first())
+        .and_then(SExpr::string)
+        .ok_or("expected the name of an export")?;
+    let args = items[1..]
+        .iter()
+        .map(value)
+        .collect::<Result<_, String>>()?;
+    Ok(Invoke { module, name, args })
+}
+
+// A `(module ...)`, and its $id.
+fn module(sexpr: &SExpr) -> Result<(Option<String>, ModuleSource), String> {
+    let Some(items) = sexpr.form("module") else {
+        return Err("expected `(module ...)`".to_string());
+    };
+    let (id, items) = module_fields(items);
+    let strings = |items: &[SExpr]| -> Result<Vec<u8>, String> {
+        let mut bytes = Vec::new();
+        for item in items {
+            let string = item.atom().and_then(parse_string_bytes);
+            bytes.extend(string.ok_or_else(|| format!("expected a string, not `{item}`"))?);
+        }
+        Ok(bytes)
+    };
+    let source = match items.split_first() {
+        Some((first, rest)) if first.atom() == Some("binary") => {
+            ModuleSource::Binary(strings(rest)?)
+        }
+        Some((first, rest)) if first.atom() == Some("quote") => {
+            let text = String::from_utf8(strings(rest)?)
+                .map_err(|_| "the module's text isn't UTF-8".to_string())?;
+            ModuleSource::Quote(text)
+        }
+        _ => ModuleSource::Text(items.to_vec()),
+    };
+    Ok((id, source))
+}
+
+// The module and message of an assert_invalid or assert_malformed.
+fn rejected(items: &[SExpr]) -> Result<(ModuleSource, String), String> {
+    let [source, message] = items else {
+        return Err("expected a module and a message".to_string());
+    };
+    let message = message.string().ok_or("expected the expected message")?;
+    Ok((module(source)?.1, message))
+}
+
+fn command(sexpr: &SExpr) -> Result<Command, String> {
+    if let Some(keyword) = (UNSUPPORTED.iter()).find(|keyword| sexpr.form(keyword).is_some()) {
+        return Ok(Command::Skip(format!("`{keyword}` isn't supported")));
+    }
+    if let Some(items) = sexpr.form("module") {
+        let kind = items.first().and_then(SExpr::atom);
+        if let Some(kind @ ("definition" | "instance")) = kind {
+            return Ok(Command::Skip(format!("`module {kind}` isn't supported")));
+        }
+        let (id, source) = module(sexpr)?;
+        return Ok(Command::Module(id, source));
+    }
+    if let Some(items) = sexpr.form("register") {
+        let (name, id) = match items {
+            [name] => (name, None),
+            [name, id] => (name, id.atom().map(str::to_string)),
+            _ => return Err("expected `(register \"name\" $id)`".to_string()),
+        };
+        let name = name.string().ok_or("expected the name to register")?;
+        return Ok(Command::Register(name, id));
+    }
+    if sexpr.form("invoke").is_some() {
+        return Ok(Command::Invoke(invoke(sexpr)?));
+    }
+    if let Some(items) = sexpr.form("assert_return") {
+        let Some((call, results)) = items.split_first() else {
+            return Err("expected an invocation".to_string());
+        };
+        let results = results
+            .iter()
+            .map(expected)
+            .collect::<Result<_, String>>()?;
+        return Ok(Command::AssertReturn(invoke(call)?, results));
+    }
+    if let Some(items) = sexpr.form("assert_trap") {
+        let [call, message] = items else {
+            return Err("expected an invocation and a message".to_string());
+        };
+        let message = message.string().ok_or("expected the trap's message")?;
+        return Ok(Command::AssertTrap(invoke(call)?, message));
+    }
+    if let Some(items) = sexpr.form("assert_invalid") {
+        let (source, message) = rejected(items)?;
+        return Ok(Command::AssertInvalid(source, message));
+    }
+    if let Some(items) = sexpr.form("assert_malformed") {
+        let (source, message) = rejected(items)?;
+        return Ok(Command::AssertMalformed(source, message));
+    }
+    let name = match &sexpr.kind {
+        SExprKind::List(items) => items.first().and_then(SExpr::atom).unwrap_or_default(),
+        SExprKind::Atom(atom) => atom,
+    };
+    Err(format!("unknown command `{name}`"))
+}
+
+// The commands of a script (with a last entry for the problem, if it can't all be
+// read).
+pub fn parse_script(text: &str) -> Vec<Entry> {
+    let (sexprs, error) = read_sexprs(text);
+    let mut entries: Vec<Entry> = (sexprs.iter())
+        .map(|sexpr| Entry {
+            line: sexpr.line,
+            command: command(sexpr),
+        })
+        .collect();
+    if let Some(error) = error {
+        entries.push(Entry {
+            line: error.line,
+            command: Err(error.message),
+        });
+    }
+    entries
+}
+
+// How a command of a script came out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Passed,
+    Skipped(String), // why
+    Failed(String),  // why
+}
+
+impl Outcome {
+    pub fn is_failed(&self) -> bool {
+        matches!(self, Outcome::Failed(_))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestResult {
+    pub line: usize,
+    pub outcome: Outcome,
+}
+
+impl fmt::Display for TestResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.outcome {
+            Outcome::Passed => write!(f, "line {}: passed", self.line + 1),
+            Outcome::Skipped(reason) => write!(f, "line {}: skipped: {reason}", self.line + 1),
+            Outcome::Failed(message) => write!(f, "line {}: failed: {message}", self.line + 1),
+        }
+    }
+}
+
+// Why the assembler rejected a module (one of the reasons it gives, after where the
+// problem is), in the words the spec's scripts expect, where there's a match: by its
+// kind, for the validator's errors, or else by its text.
+fn spec_message(error: &TextError) -> &str {
+    if let Some(invalid) = error.invalid {
+        return invalid.spec_message();
+    }
+    let reason = (error.message.rsplit(": ").next()).unwrap_or(&error.message);
+    // (a number that's too big is out of range; other text isn't a constant at all)
+    if let Some((_, constant)) = reason.split_once(" constant `") {
+        return match constant
+            .trim_start_matches(['-', '+'])
+            .starts_with(|ch: char| ch.is_ascii_digit())
+        {
+            true => "constant out of range",
+            false => "unknown operator",
+        };
+    }
+    const REASONS: &[(&str, &str)] = &[
+        ("unknown instruction", "unknown operator"),
+        ("expected a value type", "unexpected token"),
+        ("duplicate export", "duplicate export name"),
+        (
+            "than the natural alignment",
+            "alignment must not be larger than natural",
+        ),
+        (
+            "the maximum is less than the minimum",
+            "size minimum must not be greater than maximum",
+        ),
+        (
+            "expected a number of pages, up to 65536",
+            "memory size must be at most 65536 pages (4GiB)",
+        ),
+        ("does not match", "mismatching label"),
+        ("closes a block without a label", "mismatching label"),
+        ("unclosed parenthesis", "unexpected end"),
+        ("unexpected end", "unexpected end"),
+        ("not a WebAssembly module", "magic header not detected"),
+        (
+            "unsupported binary format version",
+            "unknown binary version",
+        ),
+    ];
+    (REASONS.iter())
+        .find(|(text, _)| reason.contains(text))
+        .map_or(reason, |(_, spec)| spec)
+}
+
+fn join_errors(errors: &[TextError]) -> String {
+    let errors: Vec<String> = errors.iter().map(TextError::to_string).collect();
+    errors.join("; ")
+}
+
+// The function a module exports as `name`.
+fn exported_func(module: &Module, name: &str) -> Option<u32> {
+    (module.exports.iter())
+        .find(|export| export.kind == ExportKind::Func && export.name == name)
+        .map(|export| export.index)
+}
+
+// Call an export, returning its results or how it trapped.
+fn call(machine: &mut Machine, invoke: &Invoke) -> Result<Result<Vec<Value>, Trap>, String> {
+    let module = machine.module();
+    let func = exported_func(module, &invoke.name)
+        .ok_or_else(|| format!("no function is exported as \"{}\"", invoke.name))?;
+    let params = &module.func_type(func).expect("exported function").params;
+    if !(invoke.args.iter().map(Value::ty)).eq(params.iter().copied()) {
+        let params: Vec<String> = params.iter().map(ValType::to_string).collect();
+        return Err(format!("\"{}\" takes ({})", invoke.name, params.join(", ")));
+    }
+    match machine.call(func, &invoke.args, MAX_STEPS) {
+        Ok(results) => Ok(Ok(results)),
+        Err(error) => match error.downcast::<Trap>() {
+            Ok(trap) => Ok(Err(trap)),
+            Err(error) => Err(error.to_string()),
+        },
+    }
+}
+
+// The modules registered for others to import from, by name.
+type Registry = HashMap<String, Rc<RefCell<Machine>>>;
+
+// A call from a module to one of its imports: to a registered module's export, or to
+// a spectest print function (which does nothing).
+fn call_import(
+    registry: &Weak<RefCell<Registry>>,
+    module: &str,
+    name: &str,
+    args: &[Value],
+) -> Result<Vec<Value>, Trap> {
+    if module == SPECTEST {
+        return Ok(Vec::new());
+    }
+    let unknown = || Trap::Host(format!("unknown import {module}.{name}"));
+    let registry = registry.upgrade().ok_or_else(unknown)?;
+    let instance = registry.borrow().get(module).cloned().ok_or_else(unknown)?;
+    // (a module that's running can't be called into again)
+    let Ok(mut machine) = instance.try_borrow_mut() else {
+        return Err(Trap::Host(format!(
+            "{module}.{name} calls back into its caller"
+        )));
+    };
+    let func = exported_func(machine.module(), name).ok_or_else(unknown)?;
+    (machine.call(func, args, MAX_STEPS)).map_err(|error| {
+        error
+            .downcast::<Trap>()
+            .unwrap_or_else(|error| Trap::Host(error.to_string()))
+    })
+}
+
+// The modules of a script: the latest, those with a $id, and those registered.
+struct Runner {
+    current: Option<Rc<RefCell<Machine>>>,
+    named: HashMap<String, Rc<RefCell<Machine>>>,
+    registry: Rc<RefCell<Registry>>,
+}
+
+impl Runner {
+    fn new(module: Option<Module>) -> Result<Self, String> {
+        let mut ret = Runner {
+            current: None,
+            named: HashMap::new(),
+            registry: Rc::default(),
+        };
+        ret.current = module.map(|module| ret.instantiate(module)).transpose()?;
+        Ok(ret)
+    }
+
+    fn instantiate(&self, module: Module) -> Result<Rc<RefCell<Machine>>, String> {
+        let mut machine = Machine::new(module).map_err(|error| error.to_string())?;
+        let registry = Rc::downgrade(&self.registry);
+        machine.set_host(Rc::new(move |module, name, args| {
+            call_import(&registry, module, name, args)
+        }));
+        Ok(Rc::new(RefCell::new(machine)))
+    }
+
+    // Check that each of a module's imports is there, with the right type.
+    fn link(&self, module: &Module) -> Result<(), String> {
+        for import in &module.imports {
+            let ty = &module.types[import.type_index as usize];
+            let found = match import.module.as_str() {
+                HOST_MODULE => continue,
+                SPECTEST if import.name.starts_with("print") && ty.results.is_empty() => continue,
+                SPECTEST => None,
+                name => (self.registry.borrow().get(name)).and_then(|instance| {
+                    let machine = instance.borrow();
+                    let func = exported_func(machine.module(), &import.name)?;
+                    machine.module().func_type(func).cloned()
+                }),
+            };
+            match found {
+                Some(found) if found == *ty => {}
+                Some(_) => {
+                    return Err(format!(
+                        "incompatible import type for {}.{}",
+                        import.module, import.name
+                    ));
+                }
+                None => return Err(format!("unknown import {}.{}", import.module, import.name)),
+            }
+        }
+        Ok(())
+    }
+
+    // The module with the $id, or the latest.
+    fn instance(&self, id: Option<&str>) -> Result<Rc<RefCell<Machine>>, String> {
+        match id {
+            Some(id) => (self.named.get(id).cloned()).ok_or_else(|| format!("no module {id}")),
+            None => (self.current.clone()).ok_or_else(|| "there's no module yet".to_string()),
+        }
+    }
+
+    fn call(&self, invoke: &Invoke) -> Result<Result<Vec<Value>, Trap>, String> {
+        let instance = self.instance(invoke.module.as_deref())?;
+        let mut machine = instance.borrow_mut();
+        call(&mut machine, invoke)
+    }
+
+    fn run(&mut self, command: &Command) -> Result<(), String> {
+        match command {
+            Command::Module(id, source) => {
+                let module = source.module().map_err(|errors| join_errors(&errors))?;
+                self.link(&module)?;
+                let instance = self.instantiate(module)?;
+                if let Some(id) = id {
+                    self.named.insert(id.clone(), Rc::clone(&instance));
+                }
+                self.current = Some(instance);
+                Ok(())
+            }
+            Command::Register(name, id) => {
+                let instance = self.instance(id.as_deref())?;
+                self.registry.borrow_mut().insert(name.clone(), instance);
+                Ok(())
+            }
+            Command::Invoke(invoke) => match self.call(invoke)? {
+                Ok(_) => Ok(()),
+                Err(trap) => Err(format!("trapped: {trap}")),
+            },
+            Command::AssertInvalid(source, message) | Command::AssertMalformed(source, message) => {
+                match source.module() {
+                    Ok(_) => Err(format!("expected the module to be rejected ({message})")),
+                    Err(errors)
+                        if (errors.iter().map(spec_message))
+                            .any(|reason| reason.starts_with(message.as_str())) =>
+                    {
+                        Ok(())
+                    }
+                    Err(errors) => Err(format!(
+                        "expected the module to be rejected ({message}), got: {}",
+                        join_errors(&errors)
+                    )),
+                }
+            }
+            Command::Skip(_) => Ok(()),
+            Command::AssertReturn(invoke, expected) => match self.call(invoke)? {
+                Ok(results)
+                    if results.len() == expected.len()
+                        && (expected.iter().zip(&results))
+                            .all(|(expected, result)| expected.matches(result)) =>
+                {
+                    Ok(())
+                }
+                Ok(results) => {
+                    let expected: Vec<String> = expected.iter().map(Expected::to_string).collect();
+                    let expected = match expected.is_empty() {
+                        true => "no results".to_string(),
+                        false => expected.join(", "),
+                    };
+                    Err(format!(
+                        "expected {expected}, got {}",
+                        format_results(&results)
+                    ))
+                }
+                Err(trap) => Err(format!("trapped: {trap}")),
+            },
+            Command::AssertTrap(invoke, message) => match self.call(invoke)? {
+                Ok(results) => Err(format!(
+                    "expected a trap ({message}), got {}",
+                    format_results(&results)
+                )),
+                Err(trap) if trap.to_string().starts_with(message.as_str()) => Ok(()),
+                Err(trap) => Err(format!("expected a trap ({message}), got: {trap}")),
+            },
+        }
+    }
+}
+
+// (If the module given can't be instantiated, every command fails.)
+fn run(mut runner: Result<Runner, String>, text: &str) -> Vec<TestResult> {
+    (parse_script(text).into_iter())
+        .map(|entry| {
+            let outcome = match (entry.command, &mut runner) {
+                (Ok(Command::Skip(reason)), _) => Outcome::Skipped(reason),
+                (Ok(command), Ok(runner)) => match runner.run(&command) {
+                    Ok(()) => Outcome::Passed,
+                    Err(error) => Outcome::Failed(error),
+                },
+                (Ok(_), Err(error)) => Outcome::Failed(error.clone()),
+                (Err(error), _) => Outcome::Failed(error),
+            };
+            TestResult {
+                line: entry.line,
+                outcome,
+            }
+        })
+        .collect()
+}
+
+// Run a script's commands against a module, in order.
+pub fn run_script(module: Module, text: &str) -> Vec<TestResult> {
+    run(Runner::new(Some(module)), text)
+}
+
+// Run a script that defines its own modules (such as the spec's .wast scripts).
+pub fn run_wast(text: &str) -> Vec<TestResult> {
+    run(Runner::new(None), text)
+}
+
+// Whether the script starts by defining a module (rather than testing one it's given).
+pub fn defines_module(text: &str) -> bool {
+    let (sexprs, _) = read_sexprs(text);
+    sexprs
+        .first()
+        .is_some_and(|sexpr| sexpr.form("module").is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{assemble::assemble, parse::parse_line};
+
+    fn module(text: &str) -> Module {
+        let lines: Vec<_> = text.lines().map(parse_line).collect();
+        assemble(&lines).unwrap()
+    }
+
+    #[test]
+    fn reading() {
+        let (sexprs, error) =
+            read_sexprs(";; a comment\n(a (; (; nested ;) ;) \"b c\\\"\" (d))\n  e");
+        assert_eq!(error, None);
+        assert_eq!(sexprs.len(), 2);
+        assert_eq!((sexprs[0].line, sexprs[1].line), (1, 2));
+        let SExprKind::List(items) = &sexprs[0].kind else {
+            panic!("a list");
+        };
+        let atoms: Vec<_> = items.iter().map(SExpr::atom).collect();
+        assert_eq!(atoms, [Some("a"), Some("\"b c\\\"\""), None]);
+
+        let (sexprs, error) = read_sexprs("(a)\n(b\n");
+        assert_eq!(sexprs.len(), 1);
+        assert_eq!(error.unwrap().to_string(), "line 2: unclosed parenthesis");
+        assert_eq!(read_sexprs(")").1.unwrap().message, "unexpected `)`");
+        assert_eq!(read_sexprs("\"a").1.unwrap().message, "unclosed string");
+    }
+
+    #[test]
+    fn commands() {
+        let entries = parse_script(
+            "(assert_return (invoke \"f\" (i32.const 1) (f64.const -0.5)) (f32.const nan:canonical))\n(assert_trap (invoke \"g\") \"unreachable\")\n(assert_return (invoke \"f\" (f32.const nan:canonical)))\n(frobnicate)",
+        );
+        assert_eq!(
+            entries[0].command,
+            Ok(Command::AssertReturn(
+                Invoke {
+                    module: None,
+                    name: "f".to_string(),
+                    args: vec![Value::I32(1), Value::F64(-0.5)]
+                },
+                vec![Expected::CanonicalNan(ValType::F32)]
+            ))
+        );
+        assert_eq!(
+            entries[1].command,
+            Ok(Command::AssertTrap(
+                Invoke {
+                    module: None,
+                    name: "g".to_string(),
+                    args: Vec::new()
+                },
+                "unreachable".to_string()
+            ))
+        );
+        assert_eq!(
+            entries[2].command,
+            Err("invalid f32 `nan:canonical`".to_string())
+        );
+        assert_eq!(
+            entries[3],
+            Entry {
+                line: 3,
+                command: Err("unknown command `frobnicate`".to_string())
+            }
+        );
+    }
+
+    #[test]
+    fn running() {
+        let module = module(
+            "(export \"div\")\n(param i32 i32)\n(result i32)\nlocal.get 0\nlocal.get 1\ni32.div_s",
+        );
+        let results = run_script(
+            module,
+            "(assert_return (invoke \"div\" (i32.const 7) (i32.const 2)) (i32.const 3))
+             (assert_return (invoke \"div\" (i32.const 7) (i32.const 2)) (i32.const 4))
+             (assert_trap (invoke \"div\" (i32.const 1) (i32.const 0)) \"integer divide\")
+             (assert_trap (invoke \"div\" (i32.const 1) (i32.const 1)) \"integer divide\")
+             (assert_return (invoke \"mul\"))
+             (assert_return (invoke \"div\" (i32.const 1) (i32.const 0)) (i32.const 0))
+             (assert_return (invoke \"div\" (i64.const 1) (i32.const 0)))",
+        );
+        let results: Vec<String> = results.iter().map(TestResult::to_string).collect();
+        assert_eq!(
+            results,
+            [
+                "line 1: passed",
+                "line 2: failed: expected 4 : i32, got 3 : i32",
+                "line 3: passed",
+                "line 4: failed: expected a trap (integer divide), got 1 : i32",
+                "line 5: failed: no function is exported as \"mul\"",
+                "line 6: failed: trapped: integer divide by zero",
+                "line 7: failed: \"div\" takes (i32, i32)",
+            ]
+        );
+    }
+
+    #[test]
+    fn modules() {
+        let entries = parse_script(
+            "(module $m (func (export \"f\")))\n(register \"m\" $m)\n(invoke $m \"f\" (i32.const 1))\n(module binary \"\\00asm\" \"\\01\\00\\00\\00\")\n(assert_malformed (module quote \"(func\") \"unexpected end\")",
+        );
+        let Ok(Command::Module(Some(id), ModuleSource::Text(fields))) = &entries[0].command else {
+            panic!("a module");
+        };
+        assert_eq!((id.as_str(), fields.len()), ("$m", 1));
+        assert_eq!(
+            entries[1].command,
+            Ok(Command::Register("m".to_string(), Some("$m".to_string())))
+        );
+        assert_eq!(
+            entries[2].command,
+            Ok(Command::Invoke(Invoke {
+                module: Some("$m".to_string()),
+                name: "f".to_string(),
+                args: vec![Value::I32(1)]
+            }))
+        );
+        assert_eq!(
+            entries[3].command,
+            Ok(Command::Module(
+                None,
+                ModuleSource::Binary(b"\0asm\x01\0\0\0".to_vec())
+            ))
+        );
+        assert_eq!(
+            entries[4].command,
+            Ok(Command::AssertMalformed(
+                ModuleSource::Quote("(func".to_string()),
+                "unexpected end".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn running_wast() {
+        let results = run_wast(
+            r#"(assert_return (invoke "f"))
+(module $a
+  (func $twice (export "twice") (param i32) (result i32)
+    (i32.mul (local.get 0) (i32.const 2))))
+(register "a")
+(module
+  (import "a" "twice" (func $twice (param i32) (result i32)))
+  (import "spectest" "print_i32" (func $print (param i32)))
+  (func (export "quad") (param i32) (result i32)
+    (call $print (local.get 0))
+    (call $twice (call $twice (local.get 0)))))
+(assert_return (invoke "quad" (i32.const 5)) (i32.const 20))
+(assert_return (invoke $a "twice" (i32.const 5)) (i32.const 10))
+(module (import "a" "thrice" (func)))
+(module (import "a" "twice" (func (param i64))))
+(assert_invalid (module (func (result i32) (i64.const 0))) "type mismatch")
+(assert_invalid (module (func)) "type mismatch")
+(assert_malformed (module quote "(func i32.frob)") "unknown operator")
+(invoke "quad" (i32.const 1))
+(assert_invalid (module (func (result i32) (i64.const 0))) "unknown local")
+(assert_malformed (module quote "(func (i32.const 0x1_0000_0000) drop)") "constant out of range")
+(assert_exhaustion (invoke "quad" (i32.const 1)) "call stack exhausted")
+(module definition $d (func))"#,
+        );
+        let results: Vec<String> = results.iter().map(TestResult::to_string).collect();
+        assert_eq!(
+            results,
+            [
+                "line 1: failed: there's no module yet",
+                "line 2: passed",
+                "line 5: passed",
+                "line 6: passed",
+                "line 12: passed",
+                "line 13: passed",
+                "line 14: failed: unknown import a.thrice",
+                "line 15: failed: incompatible import type for a.twice",
+                "line 16: passed",
+                "line 17: failed: expected the module to be rejected (type mismatch)",
+                "line 18: passed",
+                "line 19: passed",
+                "line 20: failed: expected the module to be rejected (unknown local), got: function 1, line 1: at the end of the function: `end` of func expects [i32] but the stack has [i64]",
+                "line 21: passed",
+                "line 22: skipped: `assert_exhaustion` isn't supported",
+                "line 23: skipped: `module definition` isn't supported",
+            ]
+        );
+        assert!(defines_module("  ;; a comment\n(module)"));
+        assert!(!defines_module("(assert_return (invoke \"f\"))"));
+    }
+
+    #[test]
+    fn nan_patterns() {
+        let canonical = Expected::CanonicalNan(ValType::F32);
+        let arithmetic = Expected::ArithmeticNan(ValType::F64);
+        assert!(canonical.matches(&Value::F32(f32::from_bits(0xffc00000))));
+        assert!(!canonical.matches(&Value::F32(f32::from_bits(0x7fc00001))));
+        assert!(!canonical.matches(&Value::F64(f64::NAN)));
+        assert!(arithmetic.matches(&Value::F64(f64::from_bits(0x7ff8000000000001))));
+        assert!(!arithmetic.matches(&Value::F64(f64::from_bits(0x7ff0000000000001))));
+        assert!(!Expected::Value(Value::F32(0.0)).matches(&Value::F32(-0.0)));
+    }
+}
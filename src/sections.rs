@@ -0,0 +1,766 @@
+// A module's sections besides its functions, as the module editor shows them: for each
+// section a list of entries, and for each entry a row of text fields (e.g. an import's
+// module, name, $id, params and results). Each field is checked on its own, and the
+// functions and globals it names are looked up in the whole module, so a problem can
+// be shown on the field it's in. The entries become the parts of the Module that the
+// assembler adds to the functions (see assemble.rs).
+//
+// An entry with a problem still declares whatever the rest of the module may refer to
+// by number (an import or a global, the table or the memory), as if its bad fields
+// were empty, so that one mistake doesn't renumber everything after it.
+
+use crate::{
+    assemble::{ModuleFunction, assemble_module, module_funcs},
+    header::{FunctionText, Header, HeaderField},
+    instruction::ValType,
+    interpreter::PAGE_SIZE,
+    module::{Data, Elem, Export, ExportKind, Global, Limits, Module, PRINT_FUNCS},
+    parse::{DeclKind, parse_int, parse_string_bytes},
+    token::is_id,
+    validate::{self, Func, Invalid, function_type},
+    value::Value,
+};
+use std::fmt;
+
+// A memory has at most 4 GiB, in 64 KiB pages, and browsers allow tables of up to ten
+// million entries.
+const MAX_PAGES: u32 = 65536;
+const MAX_TABLE_ENTRIES: u32 = 10_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Section {
+    Imports,
+    Table,
+    Memory,
+    Globals,
+    Exports,
+    Elements,
+    Data,
+}
+
+impl Section {
+    // In the order of the binary format.
+    pub const ALL: [Section; 7] = [
+        Section::Imports,
+        Section::Table,
+        Section::Memory,
+        Section::Globals,
+        Section::Exports,
+        Section::Elements,
+        Section::Data,
+    ];
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            Section::Imports => "Imports",
+            Section::Table => "Table",
+            Section::Memory => "Memory",
+            Section::Globals => "Globals",
+            Section::Exports => "Exports",
+            Section::Elements => "Elements",
+            Section::Data => "Data",
+        }
+    }
+
+    // What one entry is called (e.g. in "global 2, value: ...").
+    pub fn entry_name(&self) -> &'static str {
+        match self {
+            Section::Imports => "import",
+            Section::Table => "table",
+            Section::Memory => "memory",
+            Section::Globals => "global",
+            Section::Exports => "export",
+            Section::Elements => "element segment",
+            Section::Data => "data segment",
+        }
+    }
+
+    // The placeholders of an entry's fields.
+    pub fn fields(&self) -> &'static [&'static str] {
+        match self {
+            Section::Imports => &["module", "name", "$id", "params", "results"],
+            Section::Table => &["min entries", "max entries"],
+            Section::Memory => &["min pages", "max pages"],
+            Section::Globals => &["$id", "type", "value"],
+            Section::Exports => &["name", "kind", "index"],
+            Section::Elements => &["offset", "functions"],
+            Section::Data => &["offset", "\"string\""],
+        }
+    }
+
+    // A module has at most one table and one memory.
+    pub fn max_entries(&self) -> Option<usize> {
+        match self {
+            Section::Table | Section::Memory => Some(1),
+            _ => None,
+        }
+    }
+}
+
+// The text of each section's entries, in the order of Section::ALL (and each entry's
+// fields in the order of Section::fields).
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Sections {
+    pub entries: [Vec<Vec<String>>; 7],
+}
+
+// A module as text: its other sections, and its functions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ModuleText {
+    pub sections: Sections,
+    pub funcs: Vec<FunctionText>,
+}
+
+// A problem with a field of an entry.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SectionError {
+    pub section: Section,
+    pub entry: usize,
+    pub field: usize,
+    pub message: String,
+}
+
+impl SectionError {
+    // Where the problem is, as shown to the user (e.g. "export 1, index").
+    pub fn place(&self) -> String {
+        format!(
+            "{} {}, {}",
+            self.section.entry_name(),
+            self.entry + 1,
+            self.section.fields()[self.field]
+        )
+    }
+}
+
+impl fmt::Display for SectionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.place(), self.message)
+    }
+}
+
+// An import of a function (other than the print functions).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FuncImport {
+    pub module: String,
+    pub name: String,
+    pub func: Func,
+}
+
+// The sections, with every name resolved to a number.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ModuleSections {
+    pub imports: Vec<FuncImport>,
+    pub table: Option<Limits>,
+    pub memory: Option<Limits>,
+    pub globals: Vec<(Option<String>, Global)>,
+    pub exports: Vec<Export>,
+    pub elems: Vec<Elem>,
+    pub data: Vec<Data>,
+}
+
+impl ModuleSections {
+    // The functions after the print functions: the imported ones, then `funcs`.
+    pub fn funcs(&self, funcs: &[ModuleFunction]) -> Vec<Func> {
+        (self.imports.iter())
+            .map(|import| import.func.clone())
+            .chain(module_funcs(funcs))
+            .collect()
+    }
+
+    // The globals, as function bodies see them.
+    pub fn globals(&self) -> Vec<validate::Global> {
+        (self.globals.iter())
+            .map(|(name, global)| validate::Global {
+                name: name.clone(),
+                ty: global.ty,
+                mutable: global.mutable,
+            })
+            .collect()
+    }
+}
+
+// A name (whose `$` is optional), if there is one.
+fn name(text: &str) -> Result<Option<String>, String> {
+    let name = text.strip_prefix('$').unwrap_or(text);
+    if text.is_empty() {
+        Ok(None)
+    } else if is_id(name) {
+        Ok(Some(name.to_string()))
+    } else {
+        Err(format!("`{text}` is not a valid name"))
+    }
+}
+
+fn number(text: &str) -> Option<u32> {
+    if text.starts_with(['-', '+']) {
+        return None;
+    }
+    parse_int(text, 32).map(|value| value as u32)
+}
+
+// A table's or a memory's size: its min (required) and max (optional).
+fn limits(min: &str, max: &str, what: &str, most: u32) -> Result<Limits, (usize, String)> {
+    let Some(min) = number(min).filter(|min| *min <= most) else {
+        return Err((0, format!("expected a number of {what}, up to {most}")));
+    };
+    let max = match max {
+        "" => None,
+        max => match number(max).filter(|max| *max <= most) {
+            Some(max) if max < min => {
+                return Err((1, "the maximum is less than the minimum".to_string()));
+            }
+            Some(max) => Some(max),
+            None => return Err((1, format!("expected a number of {what}, up to {most}"))),
+        },
+    };
+    Ok(Limits { min, max })
+}
+
+fn plural(count: u64, one: &str, many: &str) -> String {
+    match count {
+        1 => format!("1 {one}"),
+        count => format!("{count} {many}"),
+    }
+}
+
+struct Resolver<'a> {
+    sections: &'a Sections,
+    errors: Vec<SectionError>,
+    func_names: Vec<Option<String>>, // every function's, counting the print functions
+}
+
+impl<'a> Resolver<'a> {
+    // The entries of a section, with their fields trimmed (missing ones are empty).
+    fn entries(&self, section: Section) -> Vec<Vec<&'a str>> {
+        (self.sections.entries(section).iter())
+            .map(|fields| {
+                (0..section.fields().len())
+                    .map(|index| fields.get(index).map_or("", |field| field.trim()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn fail(&mut self, section: Section, entry: usize, field: usize, message: String) {
+        self.errors.push(SectionError {
+            section,
+            entry,
+            field,
+            message,
+        });
+    }
+
+    // A function, by $name or number.
+    fn func(&self, text: &str) -> Result<u32, String> {
+        let found = match text.strip_prefix('$') {
+            Some(id) => (self.func_names.iter()).position(|name| name.as_deref() == Some(id)),
+            None => number(text)
+                .map(|index| index as usize)
+                .filter(|index| *index < self.func_names.len()),
+        };
+        found.map(|index| index as u32).ok_or_else(|| match text {
+            "" => "expected a function".to_string(),
+            text => format!("unknown function {text}"),
+        })
+    }
+
+    fn imports(&mut self, ret: &mut ModuleSections) {
+        let section = Section::Imports;
+        for (entry, fields) in self.entries(section).into_iter().enumerate() {
+            if fields[0].is_empty() {
+                self.fail(section, entry, 0, "expected a module name".to_string());
+            }
+            if fields[1].is_empty() {
+                self.fail(section, entry, 1, "expected a name".to_string());
+            }
+            let id = match name(fields[2]) {
+                Ok(Some(id)) if self.func_names.contains(&Some(id.clone())) => {
+                    let message = format!("another function is named ${id}");
+                    self.fail(section, entry, 2, message);
+                    None
+                }
+                Ok(id) => id,
+                Err(message) => {
+                    self.fail(section, entry, 2, message);
+                    None
+                }
+            };
+            let header = Header {
+                fields: [
+                    String::new(),
+                    fields[3].into(),
+                    fields[4].into(),
+                    String::new(),
+                ],
+            };
+            let mut lines = Vec::new();
+            for (field, header_field) in [(3, HeaderField::Params), (4, HeaderField::Results)] {
+                match header.declarations(header_field) {
+                    Ok(decls) => lines.extend(decls),
+                    Err(message) => self.fail(section, entry, field, message),
+                }
+            }
+            self.func_names.push(id.clone());
+            ret.imports.push(FuncImport {
+                module: fields[0].to_string(),
+                name: fields[1].to_string(),
+                func: Func {
+                    name: id,
+                    ty: function_type(&lines),
+                },
+            });
+        }
+    }
+
+    // The table and the memory.
+    fn limits(&mut self, section: Section, what: &str, most: u32) -> Option<Limits> {
+        let fields = self.entries(section).into_iter().next()?;
+        match limits(fields[0], fields[1], what, most) {
+            Ok(limits) => Some(limits),
+            Err((field, message)) => {
+                self.fail(section, 0, field, message);
+                Some(Limits { min: 0, max: None })
+            }
+        }
+    }
+
+    fn globals(&mut self, ret: &mut ModuleSections) {
+        let section = Section::Globals;
+        for (entry, fields) in self.entries(section).into_iter().enumerate() {
+            let id = match name(fields[0]) {
+                Ok(Some(id))
+                    if ret
+                        .globals
+                        .iter()
+                        .any(|(name, _)| *name == Some(id.clone())) =>
+                {
+                    self.fail(section, entry, 0, format!("another global is named ${id}"));
+                    None
+                }
+                Ok(id) => id,
+                Err(message) => {
+                    self.fail(section, entry, 0, message);
+                    None
+                }
+            };
+            let (mutable, ty) = match fields[1].strip_prefix("mut ") {
+                Some(ty) => (true, ty.trim()),
+                None => (false, fields[1]),
+            };
+            let ty = ValType::from_keyword(ty).unwrap_or_else(|| {
+                let message = "expected a value type, or `mut` and one (e.g. `mut i32`)";
+                self.fail(section, entry, 1, message.to_string());
+                ValType::I32
+            });
+            let init = Value::parse(fields[2], ty).unwrap_or_else(|| {
+                self.fail(section, entry, 2, format!("expected an {ty} constant"));
+                Value::parse("0", ty).expect("zero")
+            });
+            ret.globals.push((id, Global { ty, mutable, init }));
+        }
+    }
+
+    // `exported` holds the names the functions' `export` lines export.
+    fn exports(&mut self, ret: &mut ModuleSections, exported: &[String], has_memory: bool) {
+        let section = Section::Exports;
+        let mut names = exported.to_vec();
+        for (entry, fields) in self.entries(section).into_iter().enumerate() {
+            let errors = self.errors.len();
+            let name = fields[0].to_string();
+            if name.is_empty() {
+                self.fail(section, entry, 0, "expected a name".to_string());
+            } else if names.contains(&name) {
+                self.fail(section, entry, 0, format!("duplicate export \"{name}\""));
+            }
+            names.push(name.clone());
+            let kind = (ExportKind::ALL.into_iter()).find(|kind| kind.keyword() == fields[1]);
+            let index = match (kind, fields[2]) {
+                (None, _) => Err((1, "expected func, table, memory or global".to_string())),
+                (Some(ExportKind::Func), text) => self.func(text).map_err(|message| (2, message)),
+                (Some(ExportKind::Table), "" | "0") if ret.table.is_none() => {
+                    Err((2, "the module has no table".to_string()))
+                }
+                (Some(ExportKind::Memory), "" | "0") if !has_memory => {
+                    Err((2, "the module has no memory".to_string()))
+                }
+                (Some(ExportKind::Table | ExportKind::Memory), "" | "0") => Ok(0),
+                (Some(ExportKind::Table), _) => Err((2, "a module has only table 0".to_string())),
+                (Some(ExportKind::Memory), _) => Err((2, "a module has only memory 0".to_string())),
+                (Some(ExportKind::Global), text) => {
+                    let found = match text.strip_prefix('$') {
+                        Some(id) => {
+                            (ret.globals.iter()).position(|(name, _)| name.as_deref() == Some(id))
+                        }
+                        None => number(text)
+                            .map(|index| index as usize)
+                            .filter(|index| *index < ret.globals.len()),
+                    };
+                    found.map(|index| index as u32).ok_or_else(|| match text {
+                        "" => (2, "expected a global".to_string()),
+                        text => (2, format!("unknown global {text}")),
+                    })
+                }
+            };
+            match (kind, index) {
+                (Some(kind), Ok(index)) if self.errors.len() == errors => {
+                    ret.exports.push(Export { name, kind, index })
+                }
+                (_, Err((field, message))) => self.fail(section, entry, field, message),
+                _ => {}
+            }
+        }
+    }
+
+    fn elems(&mut self, ret: &mut ModuleSections) {
+        let section = Section::Elements;
+        for (entry, fields) in self.entries(section).into_iter().enumerate() {
+            let errors = self.errors.len();
+            let offset = number(fields[0]);
+            if offset.is_none() {
+                self.fail(section, entry, 0, "expected an offset".to_string());
+            }
+            let funcs: Vec<u32> = (fields[1].split_whitespace())
+                .filter_map(|text| match self.func(text) {
+                    Ok(index) => Some(index),
+                    Err(message) => {
+                        self.fail(section, entry, 1, message);
+                        None
+                    }
+                })
+                .collect();
+            let Some(offset) = offset else {
+                continue;
+            };
+            let end = offset as u64 + funcs.len() as u64;
+            match ret.table {
+                None => self.fail(section, entry, 0, "the module has no table".to_string()),
+                Some(table) if end > table.min as u64 => {
+                    let size = plural(table.min as u64, "entry", "entries");
+                    let message = format!("the segment doesn't fit in the table's {size}");
+                    self.fail(section, entry, 0, message);
+                }
+                _ if self.errors.len() == errors => ret.elems.push(Elem { offset, funcs }),
+                _ => {}
+            }
+        }
+    }
+
+    // `pages` is the memory's size.
+    fn data(&mut self, ret: &mut ModuleSections, pages: u32) {
+        let section = Section::Data;
+        for (entry, fields) in self.entries(section).into_iter().enumerate() {
+            let offset = number(fields[0]);
+            if offset.is_none() {
+                self.fail(section, entry, 0, "expected an offset".to_string());
+            }
+            let bytes = parse_string_bytes(fields[1]);
+            if bytes.is_none() {
+                let message = "expected a string in quotes, e.g. \"hi\\0a\"".to_string();
+                self.fail(section, entry, 1, message);
+            }
+            let (Some(offset), Some(bytes)) = (offset, bytes) else {
+                continue;
+            };
+            let size = pages as u64 * PAGE_SIZE as u64;
+            if offset as u64 + bytes.len() as u64 > size {
+                let pages = plural(pages as u64, "page", "pages");
+                let message = format!("the segment doesn't fit in the memory's {pages}");
+                self.fail(section, entry, 0, message);
+            } else {
+                ret.data.push(Data { offset, bytes });
+            }
+        }
+    }
+}
+
+impl Sections {
+    pub fn entries(&self, section: Section) -> &[Vec<String>] {
+        &self.entries[section as usize]
+    }
+
+    pub fn entries_mut(&mut self, section: Section) -> &mut Vec<Vec<String>> {
+        &mut self.entries[section as usize]
+    }
+
+    // Add an entry with the given fields (and any others empty).
+    pub fn push(&mut self, section: Section, fields: &[&str]) {
+        let entry = (0..section.fields().len())
+            .map(|index| fields.get(index).copied().unwrap_or_default().to_string())
+            .collect();
+        self.entries_mut(section).push(entry);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.iter().all(Vec::is_empty)
+    }
+
+    // The sections, for a module with the functions `funcs`, and the problems with
+    // their entries (the entries of exports, elements and data with a problem are left
+    // out). The module has a memory if it declares one, or if it needs one: for data,
+    // or for a function's memory instructions (see assemble.rs).
+    pub fn resolve(&self, funcs: &[ModuleFunction]) -> (ModuleSections, Vec<SectionError>) {
+        let mut ret = ModuleSections::default();
+        let mut resolver = Resolver {
+            sections: self,
+            errors: Vec::new(),
+            func_names: (PRINT_FUNCS.iter())
+                .map(|(name, _)| Some(name.to_string()))
+                .collect(),
+        };
+        resolver.imports(&mut ret);
+        resolver
+            .func_names
+            .extend(funcs.iter().map(|func| func.name.clone()));
+        ret.table = resolver.limits(Section::Table, "entries", MAX_TABLE_ENTRIES);
+        ret.memory = resolver.limits(Section::Memory, "pages", MAX_PAGES);
+        resolver.globals(&mut ret);
+
+        let lines = || funcs.iter().flat_map(|func| func.lines.iter());
+        let exported: Vec<String> = (lines().filter_map(|line| line.declaration()))
+            .filter(|decl| decl.kind == DeclKind::Export)
+            .filter_map(|decl| decl.name.clone())
+            .collect();
+        let uses_memory =
+            (lines().filter_map(|line| line.instruction())).any(|instr| instr.uses_memory());
+        let has_data = !self.entries(Section::Data).is_empty();
+        let pages = match ret.memory {
+            Some(memory) => memory.min,
+            None if uses_memory || has_data => 1,
+            None => 0,
+        };
+        let has_memory = ret.memory.is_some() || uses_memory || has_data;
+        resolver.exports(&mut ret, &exported, has_memory);
+        resolver.elems(&mut ret);
+        resolver.data(&mut ret, pages);
+        (ret, resolver.errors)
+    }
+}
+
+// A problem that keeps a module in the text format from assembling, as text (e.g.
+// "function 2, line 3: ...", counting the body's lines), with its kind if it's one the
+// validator found.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextError {
+    pub message: String,
+    pub invalid: Option<Invalid>,
+}
+
+impl From<String> for TextError {
+    fn from(message: String) -> Self {
+        TextError {
+            message,
+            invalid: None,
+        }
+    }
+}
+
+impl fmt::Display for TextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl ModuleText {
+    // The functions and sections assembled into a module, or the problems that prevent
+    // it.
+    pub fn assemble(&self) -> Result<Module, Vec<TextError>> {
+        let funcs: Vec<ModuleFunction> = self
+            .funcs
+            .iter()
+            .map(|func| func.module_function())
+            .collect();
+        let (sections, section_errors) = self.sections.resolve(&funcs);
+        let mut errors: Vec<TextError> = (section_errors.iter())
+            .map(|error| error.to_string().into())
+            .collect();
+        for (index, func) in self.funcs.iter().enumerate() {
+            for (field, error) in func.header.lines().1 {
+                let message = format!("function {}, {}: {error}", index + 1, field.placeholder());
+                errors.push(message.into());
+            }
+        }
+        match assemble_module(&funcs, &sections) {
+            Ok(module) if errors.is_empty() => return Ok(module),
+            Ok(_) => {}
+            Err(assemble_errors) => {
+                for error in assemble_errors {
+                    let header_len = self.funcs[error.func].header.lines().0.len();
+                    let message = match error.line.checked_sub(header_len) {
+                        Some(line) => format!(
+                            "function {}, line {}: {}",
+                            error.func + 1,
+                            line + 1,
+                            error.message
+                        ),
+                        None => format!("function {}: {}", error.func + 1, error.message),
+                    };
+                    errors.push(TextError {
+                        message,
+                        invalid: error.invalid,
+                    });
+                }
+            }
+        }
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_line;
+
+    fn function(name: Option<&str>, text: &str) -> ModuleFunction {
+        ModuleFunction {
+            name: name.map(str::to_string),
+            lines: text.lines().map(parse_line).collect(),
+        }
+    }
+
+    fn errors(sections: &Sections, funcs: &[ModuleFunction]) -> Vec<String> {
+        let (_, errors) = sections.resolve(funcs);
+        errors.iter().map(|error| error.to_string()).collect()
+    }
+
+    #[test]
+    fn entries() {
+        let mut sections = Sections::default();
+        sections.push(Section::Imports, &["env", "log", "$log", "$x i32, f64", ""]);
+        sections.push(Section::Imports, &["env", "now", "", "", "i64"]);
+        sections.push(Section::Table, &["4"]);
+        sections.push(Section::Memory, &["1", "0x10"]);
+        sections.push(Section::Globals, &["$count", "mut i32", "-1"]);
+        sections.push(Section::Globals, &["", "f32", "1.5"]);
+        sections.push(Section::Exports, &["mem", "memory", ""]);
+        sections.push(Section::Exports, &["count", "global", "$count"]);
+        sections.push(Section::Exports, &["run", "func", "$run"]);
+        sections.push(Section::Elements, &["1", "$run 5 $print_i32"]);
+        sections.push(Section::Data, &["8", "\"hi\\00\""]);
+        let funcs = [function(Some("run"), "(export \"main\")\nnop")];
+        let (resolved, errors) = sections.resolve(&funcs);
+        assert_eq!(errors, []);
+
+        let funcs = resolved.funcs(&funcs);
+        assert_eq!(funcs.len(), 3);
+        assert_eq!(funcs[0].name.as_deref(), Some("log"));
+        assert_eq!(funcs[0].ty.params, [ValType::I32, ValType::F64]);
+        assert_eq!(funcs[1].ty.results, [ValType::I64]);
+        assert_eq!(resolved.imports[1].name, "now");
+        assert_eq!(resolved.table, Some(Limits { min: 4, max: None }));
+        assert_eq!(
+            resolved.memory,
+            Some(Limits {
+                min: 1,
+                max: Some(16)
+            })
+        );
+        assert_eq!(
+            resolved.globals(),
+            [
+                validate::Global {
+                    name: Some("count".to_string()),
+                    ty: ValType::I32,
+                    mutable: true
+                },
+                validate::Global {
+                    name: None,
+                    ty: ValType::F32,
+                    mutable: false
+                }
+            ]
+        );
+        assert_eq!(resolved.globals[0].1.init, Value::I32(-1));
+        let exports: Vec<(&str, ExportKind, u32)> = (resolved.exports.iter())
+            .map(|export| (export.name.as_str(), export.kind, export.index))
+            .collect();
+        assert_eq!(
+            exports,
+            [
+                ("mem", ExportKind::Memory, 0),
+                ("count", ExportKind::Global, 0),
+                ("run", ExportKind::Func, 6)
+            ]
+        );
+        assert_eq!(
+            resolved.elems,
+            [Elem {
+                offset: 1,
+                funcs: vec![6, 5, 0]
+            }]
+        );
+        assert_eq!(
+            resolved.data,
+            [Data {
+                offset: 8,
+                bytes: b"hi\0".to_vec()
+            }]
+        );
+        assert!(Sections::default().resolve(&[]).0 == ModuleSections::default());
+    }
+
+    #[test]
+    fn problems() {
+        let mut sections = Sections::default();
+        sections.push(Section::Imports, &["", "f", "$run", "i33", ""]);
+        sections.push(Section::Memory, &["2", "1"]);
+        sections.push(Section::Globals, &["$g", "mut", "1"]);
+        sections.push(Section::Globals, &["$g", "i64", "1.5"]);
+        sections.push(Section::Exports, &["main", "func", "$main"]);
+        sections.push(Section::Exports, &["t", "table", ""]);
+        sections.push(Section::Exports, &["g", "globl", "0"]);
+        sections.push(Section::Exports, &["h", "global", "$h"]);
+        sections.push(Section::Elements, &["0", "$run"]);
+        sections.push(Section::Data, &["x", "hi"]);
+        let funcs = [function(Some("run"), "(export \"main\")\nnop")];
+        assert_eq!(
+            errors(&sections, &funcs),
+            [
+                "import 1, module: expected a module name",
+                "import 1, params: expected a value type (i32, i64, f32 or f64)",
+                "memory 1, max pages: the maximum is less than the minimum",
+                "global 1, type: expected a value type, or `mut` and one (e.g. `mut i32`)",
+                "global 2, $id: another global is named $g",
+                "global 2, value: expected an i64 constant",
+                "export 1, name: duplicate export \"main\"",
+                "export 1, index: unknown function $main",
+                "export 2, index: the module has no table",
+                "export 3, kind: expected func, table, memory or global",
+                "export 4, index: unknown global $h",
+                "element segment 1, offset: the module has no table",
+                "data segment 1, offset: expected an offset",
+                "data segment 1, \"string\": expected a string in quotes, e.g. \"hi\\0a\""
+            ]
+        );
+
+        // an import can't take a print function's name, and segments must fit
+        let mut sections = Sections::default();
+        sections.push(Section::Imports, &["env", "f", "$print_i32", "", ""]);
+        sections.push(Section::Table, &["2"]);
+        sections.push(Section::Elements, &["1", "4 4"]);
+        sections.push(Section::Data, &["65535", "\"ab\""]);
+        assert_eq!(
+            errors(&sections, &[]),
+            [
+                "import 1, $id: another function is named $print_i32",
+                "element segment 1, offset: the segment doesn't fit in the table's 2 entries",
+                "data segment 1, offset: the segment doesn't fit in the memory's 1 page"
+            ]
+        );
+
+        // an import with a problem still counts, and a memory isn't needed without data
+        // or memory instructions
+        let (resolved, _) = sections.resolve(&[]);
+        assert_eq!(resolved.imports.len(), 1);
+        assert_eq!(resolved.elems, []);
+        let mut sections = Sections::default();
+        sections.push(Section::Exports, &["m", "memory", "0"]);
+        assert_eq!(
+            errors(&sections, &[]),
+            ["export 1, index: the module has no memory"]
+        );
+        assert_eq!(
+            errors(&sections, &[function(None, "i32.const 0\ni32.load\ndrop")]),
+            Vec::<String>::new()
+        );
+    }
+}
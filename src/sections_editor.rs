@@ -0,0 +1,303 @@
+// The module editor's panels for a module's other sections (see sections.rs): for each
+// section, its title, a row for each entry (an input for each of the entry's fields,
+// and a button to remove it), and a button to add an entry (unless the section already
+// has as many as it can). A field with a problem is marked, with the problem as its
+// tooltip.
+//
+// The panels don't act on their own inputs and buttons: they report each event to
+// their handler (the module editor), which brings the whole module up to date.
+
+use crate::{
+    dom_struct::DomStruct,
+    dom_text::DomText,
+    dom_vec::DomVec,
+    sections::{Section, SectionError, Sections},
+    web_support::{AccessToken, Component, ElementFactory, WithElement, WithNode, audit_eq},
+};
+use anyhow::{Result, bail};
+use std::rc::Rc;
+use web_sys::{HtmlButtonElement, HtmlDivElement, HtmlInputElement};
+
+type Input = DomStruct<(), HtmlInputElement>;
+type Button = DomStruct<(DomText, ()), HtmlButtonElement>;
+type Title = DomStruct<(DomText, ()), HtmlDivElement>;
+type EntryDiv = DomStruct<(DomVec<Input, HtmlDivElement>, (Button, ())), HtmlDivElement>;
+type Panel = DomStruct<(Title, (DomVec<Entry, HtmlDivElement>, (Button, ()))), HtmlDivElement>;
+
+// Something the user did in one of the panels. Entries are identified by an id that
+// stays the same while others are added and removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SectionEvent {
+    Add(Section),
+    Input(Section, usize, usize), // the entry's id, and the field
+    Remove(Section, usize),
+}
+
+struct Entry {
+    id: usize,
+    errors: Vec<Option<String>>, // as shown, for each field
+    component: EntryDiv,
+}
+
+impl Entry {
+    fn input(&self, field: usize) -> &Input {
+        self.component.get().0.get(field).expect("field")
+    }
+
+    fn input_mut(&mut self, field: usize) -> &mut Input {
+        self.component.get_mut().0.get_mut(field).expect("field")
+    }
+
+    fn show_errors(&mut self, errors: Vec<Option<String>>) {
+        for (field, error) in errors.iter().enumerate() {
+            if *error == self.errors[field] {
+                continue;
+            }
+            let input = self.input_mut(field);
+            match error {
+                Some(error) => {
+                    input.set_attribute("class", "error");
+                    input.set_attribute("title", error);
+                }
+                None => {
+                    input.remove_attribute("class");
+                    input.remove_attribute("title");
+                }
+            }
+        }
+        self.errors = errors;
+    }
+}
+
+impl WithNode for Entry {
+    fn with_node(&self, f: impl FnMut(&web_sys::Node), g: AccessToken) {
+        self.component.with_node(f, g);
+    }
+}
+
+impl Component for Entry {
+    fn try_audit(&self) -> Result<(), String> {
+        self.component.try_audit()
+    }
+}
+
+pub struct SectionsEditor {
+    factory: ElementFactory,
+    handler: Rc<dyn Fn(SectionEvent)>,
+    next_id: usize,
+    sections: Sections,        // as typed
+    errors: Vec<SectionError>, // as shown
+    component: DomVec<Panel, HtmlDivElement>,
+}
+
+impl SectionsEditor {
+    // Empty panels, reporting their events to `handler`.
+    pub fn new(factory: &ElementFactory, handler: impl Fn(SectionEvent) + 'static) -> Self {
+        let handler: Rc<dyn Fn(SectionEvent)> = Rc::new(handler);
+        let mut component = DomVec::new(factory.div());
+        component.set_attribute("class", "sections");
+        for section in Section::ALL {
+            let title = Title::new((DomText::new(section.title()), ()), factory.div());
+            let mut add = Button::new((DomText::new("Add"), ()), factory.button());
+            let add_handler = handler.clone();
+            add.set_onclick(move |_| add_handler(SectionEvent::Add(section)));
+            let mut panel = Panel::new(
+                (title, (DomVec::new(factory.div()), (add, ()))),
+                factory.div(),
+            );
+            panel.set_attribute("class", "section");
+            component.push(panel);
+        }
+        SectionsEditor {
+            factory: factory.clone(),
+            handler,
+            next_id: 0,
+            sections: Sections::default(),
+            errors: Vec::new(),
+            component,
+        }
+    }
+
+    // Each section's entries, as typed.
+    pub fn sections(&self) -> &Sections {
+        &self.sections
+    }
+
+    // Add an empty entry at the end of a section, returning its index.
+    pub fn add_entry(&mut self, section: Section) -> Result<usize> {
+        self.push_entry(section, &[])
+    }
+
+    pub fn set_field(
+        &mut self,
+        section: Section,
+        index: usize,
+        field: usize,
+        text: &str,
+    ) -> Result<()> {
+        self.check_field(section, index, field)?;
+        let entry = self.entries_mut(section).get_mut(index).expect("entry");
+        entry.input_mut(field).set_value(text);
+        self.sections.entries_mut(section)[index][field] = text.to_string();
+        Ok(())
+    }
+
+    pub fn remove_entry(&mut self, section: Section, index: usize) -> Result<()> {
+        self.check_field(section, index, 0)?;
+        self.entries_mut(section).remove(index);
+        self.sections.entries_mut(section).remove(index);
+        self.update_add_button(section);
+        Ok(())
+    }
+
+    // Replace every section's entries.
+    pub fn load(&mut self, sections: &Sections) {
+        for section in Section::ALL {
+            self.entries_mut(section).clear();
+            self.sections.entries_mut(section).clear();
+            for fields in sections.entries(section) {
+                let fields: Vec<&str> = fields.iter().map(String::as_str).collect();
+                self.push_entry(section, &fields)
+                    .expect("no more entries than the section allows");
+            }
+            self.update_add_button(section);
+        }
+    }
+
+    // The index of the entry with the given id.
+    pub fn find(&self, section: Section, id: usize) -> Option<usize> {
+        (self.entries(section).iter()).position(|entry| entry.id == id)
+    }
+
+    // Take the text of a field from its input (after the user has typed in it).
+    pub fn update_field(&mut self, section: Section, index: usize, field: usize) {
+        let Some(entry) = self.entries(section).get(index) else {
+            return;
+        };
+        let text = entry.input(field).value();
+        self.sections.entries_mut(section)[index][field] = text;
+    }
+
+    // Mark the fields with problems (with the first problem of each as its tooltip),
+    // and unmark the others.
+    pub fn show_errors(&mut self, errors: Vec<SectionError>) {
+        for section in Section::ALL {
+            for (index, entry) in self.entries_mut(section).iter_mut().enumerate() {
+                let mut shown = vec![None; section.fields().len()];
+                for error in &errors {
+                    if error.section == section && error.entry == index {
+                        shown[error.field].get_or_insert_with(|| error.message.clone());
+                    }
+                }
+                entry.show_errors(shown);
+            }
+        }
+        self.errors = errors;
+    }
+
+    // The problems shown on the fields.
+    pub fn errors(&self) -> &[SectionError] {
+        &self.errors
+    }
+
+    fn panel(&self, section: Section) -> &Panel {
+        self.component.get(section as usize).expect("panel")
+    }
+
+    fn panel_mut(&mut self, section: Section) -> &mut Panel {
+        self.component.get_mut(section as usize).expect("panel")
+    }
+
+    fn entries(&self, section: Section) -> &DomVec<Entry, HtmlDivElement> {
+        &self.panel(section).get().1.0
+    }
+
+    fn entries_mut(&mut self, section: Section) -> &mut DomVec<Entry, HtmlDivElement> {
+        &mut self.panel_mut(section).get_mut().1.0
+    }
+
+    fn check_field(&self, section: Section, index: usize, field: usize) -> Result<()> {
+        if index >= self.entries(section).len() {
+            bail!("{} {index} out of range", section.entry_name());
+        }
+        if field >= section.fields().len() {
+            bail!("field {field} out of range");
+        }
+        Ok(())
+    }
+
+    fn push_entry(&mut self, section: Section, fields: &[&str]) -> Result<usize> {
+        if self.is_full(section) {
+            bail!("a module has only one {}", section.entry_name());
+        }
+        let id = self.next_id;
+        self.next_id += 1;
+        let mut inputs = DomVec::new(self.factory.div());
+        inputs.set_attribute("class", "fields");
+        for (field, placeholder) in section.fields().iter().enumerate() {
+            let mut input = Input::new((), self.factory.input());
+            input.set_attribute("placeholder", placeholder);
+            input.set_attribute("spellcheck", "false");
+            input.set_value(fields.get(field).copied().unwrap_or_default());
+            let handler = self.handler.clone();
+            input.set_oninput(move |_| handler(SectionEvent::Input(section, id, field)));
+            inputs.push(input);
+        }
+        let mut remove = Button::new((DomText::new("Remove"), ()), self.factory.button());
+        let handler = self.handler.clone();
+        remove.set_onclick(move |_| handler(SectionEvent::Remove(section, id)));
+        let mut component = EntryDiv::new((inputs, (remove, ())), self.factory.div());
+        component.set_attribute("class", "entry");
+        self.entries_mut(section).push(Entry {
+            id,
+            errors: vec![None; section.fields().len()],
+            component,
+        });
+        self.sections.push(section, fields);
+        self.update_add_button(section);
+        Ok(self.entries(section).len() - 1)
+    }
+
+    fn is_full(&self, section: Section) -> bool {
+        (section.max_entries()).is_some_and(|max| self.entries(section).len() >= max)
+    }
+
+    // A section that can't have more entries has its Add button disabled.
+    fn update_add_button(&mut self, section: Section) {
+        let full = self.is_full(section);
+        let add = &mut self.panel_mut(section).get_mut().1.1.0;
+        match full {
+            true => add.set_attribute("disabled", ""),
+            false => add.remove_attribute("disabled"),
+        }
+    }
+}
+
+impl WithNode for SectionsEditor {
+    fn with_node(&self, f: impl FnMut(&web_sys::Node), g: AccessToken) {
+        self.component.with_node(f, g);
+    }
+}
+
+impl WithElement<HtmlDivElement> for SectionsEditor {
+    fn with_element(&self, f: impl FnMut(&HtmlDivElement), g: AccessToken) {
+        self.component.with_element(f, g);
+    }
+}
+
+// To audit, audit the panels, then check that the entries' text matches their inputs.
+impl Component for SectionsEditor {
+    fn try_audit(&self) -> Result<(), String> {
+        self.component.try_audit()?;
+        for section in Section::ALL {
+            let entries = self.sections.entries(section);
+            audit_eq!(entries.len(), self.entries(section).len());
+            for (fields, entry) in entries.iter().zip(self.entries(section).iter()) {
+                for (field, text) in fields.iter().enumerate() {
+                    audit_eq!(*text, entry.input(field).value());
+                }
+            }
+        }
+        Ok(())
+    }
+}
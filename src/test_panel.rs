@@ -0,0 +1,279 @@
+// The Test panel, next to the Run panel: a script of spec-style assertions about the
+// module (see script.rs), a Test button, and how each assertion came out. Testing
+// assembles the module's functions and runs the script in the interpreter. Clicking a
+// failed assertion's result selects its line in the script.
+//
+// A script can also be opened from a file, such as one of the spec's .wast scripts; a
+// script that starts by defining a module tests its own modules rather than the
+// editor's.
+
+use crate::{
+    dom_struct::DomStruct,
+    dom_text::DomText,
+    dom_vec::DomVec,
+    module_editor::ModuleEditor,
+    script::{Outcome, TestResult, defines_module, run_script, run_wast},
+    web_support::{
+        AccessToken, Component, ElementFactory, FileHandle, WithElement, WithNode, audit_eq,
+        dom_op_scope,
+    },
+};
+use std::{cell::RefCell, rc::Rc};
+use web_sys::{HtmlButtonElement, HtmlDivElement, HtmlInputElement, HtmlTextAreaElement};
+
+type Button = DomStruct<(DomText, ()), HtmlButtonElement>;
+type Input = DomStruct<(), HtmlInputElement>;
+type Controls = DomStruct<(Button, (Input, ())), HtmlDivElement>;
+type Script = DomStruct<(), HtmlTextAreaElement>;
+type TextDiv = DomStruct<(DomText, ()), HtmlDivElement>;
+type Results = DomVec<TextDiv, HtmlDivElement>;
+type PanelContents = (Controls, (Script, (TextDiv, (Results, ()))));
+
+struct _TestPanel {
+    factory: ElementFactory,
+    module: ModuleEditor,
+    component: DomStruct<PanelContents, HtmlDivElement>,
+    summary: String,          // as shown
+    results: Vec<TestResult>, // of the last test
+    lines: Vec<String>,       // as shown: the results, or the module's errors
+}
+
+pub struct TestPanel(Rc<RefCell<_TestPanel>>);
+
+impl TestPanel {
+    pub fn new(factory: &ElementFactory, module: &ModuleEditor) -> Self {
+        let mut input = Input::new((), factory.input());
+        input.set_attribute("type", "file");
+        input.set_attribute("accept", ".wast");
+        input.set_attribute("title", "Open a .wast script");
+        let mut controls = Controls::new(
+            (
+                Button::new((DomText::new("Test"), ()), factory.button()),
+                (input, ()),
+            ),
+            factory.div(),
+        );
+        controls.set_attribute("class", "controls");
+        let mut script = Script::new((), factory.textarea());
+        script.set_attribute(
+            "placeholder",
+            "(assert_return (invoke \"add\" (i32.const 1) (i32.const 2)) (i32.const 3))",
+        );
+        script.set_attribute("spellcheck", "false");
+        let mut summary = TextDiv::new((DomText::new(""), ()), factory.div());
+        summary.set_attribute("class", "summary");
+        let mut results = Results::new(factory.div());
+        results.set_attribute("class", "output");
+        let mut component = DomStruct::new(
+            (controls, (script, (summary, (results, ())))),
+            factory.div(),
+        );
+        component.set_attribute("class", "testpanel");
+
+        let ret = TestPanel(Rc::new(RefCell::new(_TestPanel {
+            factory: factory.clone(),
+            module: module.clone(),
+            component,
+            summary: String::new(),
+            results: Vec::new(),
+            lines: Vec::new(),
+        })));
+
+        let panel_ref = Rc::clone(&ret.0);
+        (ret.0.borrow_mut().button_mut()).set_onclick(move |_| TestPanel(panel_ref.clone()).test());
+
+        let panel_ref = Rc::clone(&ret.0);
+        ret.0.borrow_mut().input_mut().set_onchange(move |_| {
+            let file = {
+                let mut inner = panel_ref.borrow_mut();
+                let file = inner.input().files().into_iter().next();
+                inner.input_mut().set_value(""); // so choosing the same file opens it again
+                file
+            };
+            if let Some(file) = file {
+                let panel = TestPanel(panel_ref.clone());
+                wasm_bindgen_futures::spawn_local(async move { panel.open(file).await });
+            }
+        });
+
+        ret
+    }
+
+    pub fn set_script(&mut self, text: &str) {
+        self.0.borrow_mut().script_mut().set_value(text);
+    }
+
+    // Put a script from a file into the panel (or say why it can't be read).
+    pub async fn open(&self, file: FileHandle) {
+        let bytes = file.bytes().await;
+        let mut inner = self.0.borrow_mut();
+        let _scope = dom_op_scope("test panel");
+        match bytes {
+            Ok(bytes) => (inner.script_mut()).set_value(&String::from_utf8_lossy(&bytes)),
+            Err(error) => inner.set_summary(error.to_string()),
+        }
+    }
+
+    // How many assertions passed and failed (or why there was nothing to test).
+    pub fn summary(&self) -> String {
+        self.0.borrow().summary.clone()
+    }
+
+    // How each of the script's commands came out in the last test.
+    pub fn results(&self) -> Vec<TestResult> {
+        self.0.borrow().results.clone()
+    }
+
+    // Assemble the module and run the script against it (or, if the script defines its
+    // own modules, just run the script).
+    pub fn test(&mut self) {
+        let mut inner = self.0.borrow_mut();
+        let script = inner.script().value();
+        let results = match defines_module(&script) {
+            true => Ok(run_wast(&script)),
+            false => (inner.module.assemble()).map(|module| run_script(module, &script)),
+        };
+        let (summary, results, lines) = match results {
+            Ok(results) => {
+                let count = |outcome: fn(&Outcome) -> bool| {
+                    (results.iter())
+                        .filter(|result| outcome(&result.outcome))
+                        .count()
+                };
+                let failed = count(Outcome::is_failed);
+                let skipped = count(|outcome| matches!(outcome, Outcome::Skipped(_)));
+                let passed = results.len() - failed - skipped;
+                let summary = match (results.len(), skipped) {
+                    (0, _) => "no assertions".to_string(),
+                    (_, 0) => format!("{passed} passed, {failed} failed"),
+                    _ => format!("{passed} passed, {failed} failed, {skipped} skipped"),
+                };
+                let lines: Vec<String> = results.iter().map(TestResult::to_string).collect();
+                (summary, results, lines)
+            }
+            Err(errors) => (
+                "the module has errors".to_string(),
+                Vec::new(),
+                errors.iter().map(|error| error.to_string()).collect(),
+            ),
+        };
+
+        let _scope = dom_op_scope("test panel");
+        inner.set_summary(summary);
+        let factory = inner.factory.clone();
+        let dom_lines = &mut inner.component.get_mut().1.1.1.0;
+        dom_lines.clear();
+        for (index, text) in lines.iter().enumerate() {
+            let mut div = TextDiv::new((DomText::new(text), ()), factory.div());
+            match results.get(index) {
+                Some(TestResult {
+                    outcome: Outcome::Passed,
+                    ..
+                }) => div.set_attribute("class", "pass"),
+                Some(TestResult {
+                    outcome: Outcome::Skipped(_),
+                    ..
+                }) => div.set_attribute("class", "skip"),
+                Some(result) => {
+                    div.set_attribute("class", "fail");
+                    let (panel_ref, line) = (Rc::clone(&self.0), result.line);
+                    div.set_onclick(move |_| TestPanel(panel_ref.clone()).select_line(line));
+                }
+                None => div.set_attribute("class", "fail"),
+            }
+            dom_lines.push(div);
+        }
+        inner.results = results;
+        inner.lines = lines;
+    }
+
+    // Select a line of the script (counting from 0).
+    pub fn select_line(&mut self, line: usize) {
+        let mut inner = self.0.borrow_mut();
+        let text = inner.script().value();
+        // (the textarea counts UTF-16 code units)
+        let mut start = 0;
+        for (index, text) in text.split('\n').enumerate() {
+            let len = text.encode_utf16().count();
+            if index == line {
+                let _scope = dom_op_scope("test panel");
+                inner.script_mut().select_range(start..start + len);
+                return;
+            }
+            start += len + 1;
+        }
+    }
+
+    // The line of the script where the selection starts.
+    pub fn selected_line(&self) -> usize {
+        let inner = self.0.borrow();
+        let script = inner.script();
+        let offset = script.selection_start();
+        let mut utf16 = 0;
+        (script.value().chars())
+            .take_while(|ch| {
+                utf16 += ch.len_utf16();
+                utf16 <= offset
+            })
+            .filter(|&ch| ch == '\n')
+            .count()
+    }
+}
+
+impl _TestPanel {
+    fn button_mut(&mut self) -> &mut Button {
+        &mut self.component.get_mut().0.get_mut().0
+    }
+
+    fn set_summary(&mut self, summary: String) {
+        if summary != self.summary {
+            (self.component.get_mut().1.1.0.get_mut().0).set_data(&summary);
+            self.summary = summary;
+        }
+    }
+
+    fn input(&self) -> &Input {
+        &self.component.get().0.get().1.0
+    }
+
+    fn input_mut(&mut self) -> &mut Input {
+        &mut self.component.get_mut().0.get_mut().1.0
+    }
+
+    fn script(&self) -> &Script {
+        &self.component.get().1.0
+    }
+
+    fn script_mut(&mut self) -> &mut Script {
+        &mut self.component.get_mut().1.0
+    }
+}
+
+impl WithNode for TestPanel {
+    fn with_node(&self, f: impl FnMut(&web_sys::Node), g: AccessToken) {
+        self.0.borrow().component.with_node(f, g);
+    }
+}
+
+impl WithElement<HtmlDivElement> for TestPanel {
+    fn with_element(&self, f: impl FnMut(&HtmlDivElement), g: AccessToken) {
+        self.0.borrow().component.with_element(f, g);
+    }
+}
+
+// To audit, audit the panel's elements, then check that the summary and the result
+// lines show what was recorded.
+impl Component for TestPanel {
+    fn try_audit(&self) -> Result<(), String> {
+        let inner = self.0.borrow();
+        inner.component.try_audit()?;
+        let contents = inner.component.get();
+        audit_eq!(contents.1.1.0.get().0.get(), &inner.summary);
+        let lines = &contents.1.1.1.0;
+        audit_eq!(lines.len(), inner.lines.len());
+        for (line, text) in lines.iter().zip(&inner.lines) {
+            audit_eq!(line.get().0.get(), text);
+        }
+        Ok(())
+    }
+}
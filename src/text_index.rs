@@ -0,0 +1,410 @@
+// A string with an incrementally-maintained index for converting between byte, char,
+// UTF-16 and grapheme-cluster offsets without rescanning the whole string.
+//
+// The string is divided into chunks of roughly CHUNK_BYTES bytes, each ending on a
+// grapheme-cluster boundary. For each chunk we keep its length in every unit, along
+// with where it starts (a prefix sum). A conversion finds the chunk by binary search
+// and then scans within that one chunk. An edit re-segments only the chunks around it
+// and shifts the starts of the chunks after it.
+
+use std::ops::Range;
+use unicode_segmentation::{GraphemeCursor, UnicodeSegmentation};
+
+const CHUNK_BYTES: usize = 64;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Unit {
+    Byte,
+    Char,
+    Utf16,
+    Grapheme,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Counts {
+    bytes: usize,
+    chars: usize,
+    utf16: usize,
+    graphemes: usize,
+}
+
+impl Counts {
+    fn get(&self, unit: Unit) -> usize {
+        match unit {
+            Unit::Byte => self.bytes,
+            Unit::Char => self.chars,
+            Unit::Utf16 => self.utf16,
+            Unit::Grapheme => self.graphemes,
+        }
+    }
+
+    fn add(&self, other: &Counts) -> Counts {
+        Counts {
+            bytes: self.bytes + other.bytes,
+            chars: self.chars + other.chars,
+            utf16: self.utf16 + other.utf16,
+            graphemes: self.graphemes + other.graphemes,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct IndexedText {
+    contents: String,
+    chunks: Vec<Counts>,
+    starts: Vec<Counts>,
+}
+
+impl IndexedText {
+    pub fn new(string: &str) -> Self {
+        let mut ret = Self::default();
+        ret.set(string);
+        ret
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.contents
+    }
+
+    pub fn len(&self, unit: Unit) -> usize {
+        self.total().get(unit)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.contents.is_empty()
+    }
+
+    fn total(&self) -> Counts {
+        match (self.starts.last(), self.chunks.last()) {
+            (Some(start), Some(chunk)) => start.add(chunk),
+            _ => Counts::default(),
+        }
+    }
+
+    pub fn set(&mut self, string: &str) {
+        self.contents = string.to_string();
+        self.chunks = segment(string);
+        self.recompute_starts(0);
+    }
+
+    pub fn push_str(&mut self, string: &str) {
+        self.replace_bytes(self.contents.len()..self.contents.len(), string);
+    }
+
+    // Replace a range of the string (given in bytes, on char boundaries).
+    pub fn replace_bytes(&mut self, range: Range<usize>, string: &str) {
+        if self.chunks.is_empty() {
+            self.set(string);
+            return;
+        }
+
+        // The affected chunks: those containing the edit, plus one on either side
+        // (an edit can change the grapheme boundaries next to it).
+        let first = self.chunk_at(Unit::Byte, range.start).saturating_sub(1);
+        let mut last = (self.chunk_at(Unit::Byte, range.end) + 2).min(self.chunks.len());
+        let region_start = self.starts[first].bytes;
+        let mut region_end = self.chunk_end(last - 1) + string.len() - range.len();
+
+        self.contents.replace_range(range, string);
+
+        // In rare cases (e.g. long runs of combining marks) the effect on grapheme
+        // boundaries reaches further, so keep going until the region ends on a boundary.
+        while last < self.chunks.len() && !self.is_boundary(region_end) {
+            region_end += self.chunks[last].bytes;
+            last += 1;
+        }
+
+        let new_chunks = segment(&self.contents[region_start..region_end]);
+        self.chunks.splice(first..last, new_chunks);
+        self.recompute_starts(first);
+    }
+
+    // Convert an offset from one unit to another. Offsets that fall inside a char (or
+    // grapheme cluster) are rounded down to its start; offsets past the end are clamped.
+    pub fn convert(&self, offset: usize, from: Unit, to: Unit) -> usize {
+        if from == to {
+            return offset.min(self.len(to));
+        }
+        if self.chunks.is_empty() {
+            return 0;
+        }
+        let index = self.chunk_at(from, offset);
+        let start = self.starts[index];
+        let chunk = &self.contents[start.bytes..start.bytes + self.chunks[index].bytes];
+        let local = offset - start.get(from);
+
+        let local_byte = match from {
+            Unit::Byte => local.min(chunk.len()),
+            Unit::Char => str_indices::chars::to_byte_idx(chunk, local),
+            Unit::Utf16 => str_indices::utf16::to_byte_idx(chunk, local),
+            Unit::Grapheme => chunk
+                .grapheme_indices(true)
+                .nth(local)
+                .map_or(chunk.len(), |(byte, _)| byte),
+        };
+        let local_to = match to {
+            Unit::Byte => local_byte,
+            Unit::Char => str_indices::chars::from_byte_idx(chunk, local_byte),
+            Unit::Utf16 => str_indices::utf16::from_byte_idx(chunk, local_byte),
+            Unit::Grapheme => chunk
+                .grapheme_indices(true)
+                .take_while(|(byte, grapheme)| byte + grapheme.len() <= local_byte)
+                .count(),
+        };
+        start.get(to) + local_to
+    }
+
+    pub fn char_to_byte(&self, char_idx: usize) -> usize {
+        self.convert(char_idx, Unit::Char, Unit::Byte)
+    }
+
+    pub fn byte_to_char(&self, byte_idx: usize) -> usize {
+        self.convert(byte_idx, Unit::Byte, Unit::Char)
+    }
+
+    pub fn char_to_utf16(&self, char_idx: usize) -> usize {
+        self.convert(char_idx, Unit::Char, Unit::Utf16)
+    }
+
+    pub fn utf16_to_char(&self, utf16_idx: usize) -> usize {
+        self.convert(utf16_idx, Unit::Utf16, Unit::Char)
+    }
+
+    pub fn char_to_grapheme(&self, char_idx: usize) -> usize {
+        self.convert(char_idx, Unit::Char, Unit::Grapheme)
+    }
+
+    pub fn grapheme_to_char(&self, grapheme_idx: usize) -> usize {
+        self.convert(grapheme_idx, Unit::Grapheme, Unit::Char)
+    }
+
+    // Grapheme-cluster boundaries, in char offsets. (A caret should only ever be placed
+    // at a boundary, and deletion should remove whole clusters.)
+    pub fn is_grapheme_boundary(&self, char_idx: usize) -> bool {
+        self.grapheme_to_char(self.char_to_grapheme(char_idx)) == char_idx
+    }
+
+    // The nearest boundary before `char_idx` (or 0).
+    pub fn prev_grapheme_boundary(&self, char_idx: usize) -> usize {
+        let grapheme = self.char_to_grapheme(char_idx);
+        let start = self.grapheme_to_char(grapheme);
+        if start < char_idx {
+            start
+        } else {
+            self.grapheme_to_char(grapheme.saturating_sub(1))
+        }
+    }
+
+    // The nearest boundary after `char_idx` (or the end of the string).
+    pub fn next_grapheme_boundary(&self, char_idx: usize) -> usize {
+        self.grapheme_to_char(self.char_to_grapheme(char_idx) + 1)
+    }
+
+    // The index of the chunk containing `offset` (the last chunk, if it is at the end).
+    fn chunk_at(&self, unit: Unit, offset: usize) -> usize {
+        self.starts
+            .partition_point(|start| start.get(unit) <= offset)
+            .saturating_sub(1)
+    }
+
+    fn chunk_end(&self, index: usize) -> usize {
+        self.starts[index].bytes + self.chunks[index].bytes
+    }
+
+    fn is_boundary(&self, byte_idx: usize) -> bool {
+        GraphemeCursor::new(byte_idx, self.contents.len(), true)
+            .is_boundary(&self.contents, 0)
+            .expect("whole string is provided")
+    }
+
+    fn recompute_starts(&mut self, from: usize) {
+        self.starts.truncate(from);
+        let mut next = match from {
+            0 => Counts::default(),
+            _ => self.starts[from - 1].add(&self.chunks[from - 1]),
+        };
+        for chunk in &self.chunks[from..] {
+            self.starts.push(next);
+            next = next.add(chunk);
+        }
+    }
+}
+
+// Divide a string (that starts on a grapheme boundary) into chunks.
+fn segment(string: &str) -> Vec<Counts> {
+    let mut ret = Vec::new();
+    let mut chunk = Counts::default();
+    for grapheme in string.graphemes(true) {
+        chunk = chunk.add(&Counts {
+            bytes: grapheme.len(),
+            chars: str_indices::chars::count(grapheme),
+            utf16: str_indices::utf16::count(grapheme),
+            graphemes: 1,
+        });
+        if chunk.bytes >= CHUNK_BYTES {
+            ret.push(chunk);
+            chunk = Counts::default();
+        }
+    }
+    if chunk.bytes > 0 {
+        ret.push(chunk);
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    // Reference conversions by direct scanning: the counts at every char boundary,
+    // and the byte offset of every grapheme boundary.
+    struct Reference {
+        chars: Vec<Counts>,
+        graphemes: Vec<usize>,
+    }
+
+    impl Reference {
+        fn new(string: &str) -> Self {
+            let mut chars = vec![Counts::default()];
+            for ch in string.chars() {
+                let last = *chars.last().unwrap();
+                chars.push(Counts {
+                    bytes: last.bytes + ch.len_utf8(),
+                    chars: last.chars + 1,
+                    utf16: last.utf16 + ch.len_utf16(),
+                    graphemes: 0,
+                });
+            }
+            let mut graphemes: Vec<usize> = string.grapheme_indices(true).map(|(b, _)| b).collect();
+            graphemes.push(string.len());
+            Self { chars, graphemes }
+        }
+
+        fn convert(&self, offset: usize, from: Unit, to: Unit) -> usize {
+            let byte = match from {
+                Unit::Grapheme => self.graphemes[offset.min(self.graphemes.len() - 1)],
+                _ => {
+                    self.chars
+                        .iter()
+                        .rfind(|counts| counts.get(from) <= offset)
+                        .unwrap()
+                        .bytes
+                }
+            };
+            match to {
+                Unit::Grapheme => self.graphemes[1..].iter().filter(|b| **b <= byte).count(),
+                _ => self.chars.iter().find(|c| c.bytes == byte).unwrap().get(to),
+            }
+        }
+    }
+
+    fn check_all(text: &IndexedText) {
+        let string = text.as_str();
+        let reference = Reference::new(string);
+        assert_eq!(text.len(Unit::Byte), string.len());
+        assert_eq!(text.len(Unit::Char), string.chars().count());
+        assert_eq!(text.len(Unit::Grapheme), string.graphemes(true).count());
+        let units = [Unit::Byte, Unit::Char, Unit::Utf16, Unit::Grapheme];
+        for from in units {
+            for to in units {
+                for offset in 0..=text.len(from) {
+                    // skip offsets inside a UTF-8 or UTF-16 sequence
+                    if from != Unit::Grapheme && reference.convert(offset, from, from) != offset {
+                        continue;
+                    }
+                    assert_eq!(
+                        text.convert(offset, from, to),
+                        reference.convert(offset, from, to),
+                        "{string:?}: {offset} {from:?} -> {to:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn conversions() {
+        for string in [
+            "",
+            "plain ascii",
+            "café",
+            "e\u{301}\u{302}x",
+            "👩🏽‍💻 and 👨‍👩‍👧‍👦",
+            "🇫🇷🇩🇪🇯🇵",
+            &"αβγ😀 i32.const 1\u{301} ".repeat(20),
+        ] {
+            check_all(&IndexedText::new(string));
+        }
+    }
+
+    fn boundaries(string: &str) -> (Vec<usize>, Vec<usize>) {
+        let text = IndexedText::new(string);
+        let len = text.len(Unit::Char);
+        let mut forward = vec![0];
+        while *forward.last().unwrap() < len {
+            forward.push(text.next_grapheme_boundary(*forward.last().unwrap()));
+        }
+        let mut backward = vec![len];
+        while *backward.last().unwrap() > 0 {
+            backward.push(text.prev_grapheme_boundary(*backward.last().unwrap()));
+        }
+        backward.reverse();
+        (forward, backward)
+    }
+
+    #[test]
+    fn grapheme_boundaries() {
+        // ZWJ sequences (with a skin-tone modifier), combining marks, and flags
+        // are each a single cluster.
+        for (string, expected) in [
+            ("a👩🏽\u{200d}💻b", vec![0, 1, 5, 6]),
+            ("👨\u{200d}👩\u{200d}👧\u{200d}👦", vec![0, 7]),
+            ("e\u{301}\u{323}x", vec![0, 3, 4]),
+            ("n\u{303}o\u{308}", vec![0, 2, 4]),
+            ("🇫🇷🇩🇪", vec![0, 2, 4]),
+            ("", vec![0]),
+        ] {
+            let (forward, backward) = boundaries(string);
+            assert_eq!(forward, expected, "{string:?}");
+            assert_eq!(backward, expected, "{string:?}");
+            let text = IndexedText::new(string);
+            for ch in 0..=text.len(Unit::Char) {
+                assert_eq!(text.is_grapheme_boundary(ch), expected.contains(&ch));
+            }
+        }
+
+        // from inside a cluster, move to its edges
+        let text = IndexedText::new("a👩🏽\u{200d}💻b");
+        assert_eq!(text.prev_grapheme_boundary(3), 1);
+        assert_eq!(text.next_grapheme_boundary(3), 5);
+    }
+
+    fn piece() -> impl Strategy<Value = String> {
+        prop::collection::vec(
+            prop::sample::select(vec![
+                "a", "é", "e\u{301}", "\u{301}", "中", "😀", "🏽", "\u{200d}", "👩", "🇫", " ",
+            ]),
+            0..12,
+        )
+        .prop_map(|pieces| pieces.concat())
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+        #[test]
+        fn index_survives_edits(
+            initial in piece(),
+            edits in prop::collection::vec((any::<usize>(), any::<usize>(), piece()), 0..20)
+        ) {
+            let mut text = IndexedText::new(&initial.repeat(8));
+            for (a, b, insert) in edits {
+                let len = text.len(Unit::Char);
+                let (a, b) = (a % (len + 1), b % (len + 1));
+                let range = text.char_to_byte(a.min(b))..text.char_to_byte(a.max(b));
+                text.replace_bytes(range, &insert);
+                check_all(&text);
+            }
+        }
+    }
+}
@@ -0,0 +1,605 @@
+// A reader for modules in WebAssembly's text format (the fields of a `(module ...)`,
+// as s-expressions; see script.rs), turning them into the module editor's functions
+// and section entries (see header.rs and sections.rs), which the assembler then
+// checks and assembles like any others. It's the counterpart of wat.rs for the
+// modules of test scripts.
+//
+// A function's folded instructions (e.g. `(i32.add (local.get 0) (i32.const 1))`) are
+// unfolded into the editor's lines, one instruction per line, and its inline exports
+// become `export` lines. The editor's modules import the print functions first (see
+// module.rs), so a function's number in the text format is 4 less than in the editor,
+// and the reader adds 4 to the numbers in calls, exports and element segments.
+//
+// It reads what the editor can express: functions (defined or imported), a table, a
+// memory, globals with a constant value, exports, and element and data segments with
+// constant offsets. A start function, and imports of anything other than functions,
+// are reported as not supported.
+
+use crate::{
+    header::{FunctionText, Header},
+    instruction::{ImmKind, ValType, lookup},
+    interpreter::PAGE_SIZE,
+    module::PRINT_FUNCS,
+    parse::parse_string_bytes,
+    script::{SExpr, SExprKind},
+    sections::{ModuleText, Section, Sections},
+    token::{quote, quote_bytes},
+};
+
+// A function type, as header fields: the params (e.g. "$x i32, i64 f32") and the
+// results (e.g. "i32 i64").
+#[derive(Clone, Debug, Default)]
+struct TypeUse {
+    params: String,
+    results: String,
+}
+
+struct Reader {
+    types: Vec<(Option<String>, TypeUse)>,
+    sections: Sections,
+    funcs: Vec<FunctionText>,
+    func_imports: usize,
+    globals: usize,
+}
+
+fn list(sexpr: &SExpr) -> &[SExpr] {
+    match &sexpr.kind {
+        SExprKind::List(items) => items,
+        SExprKind::Atom(_) => &[],
+    }
+}
+
+// The keyword a list starts with.
+fn keyword(sexpr: &SExpr) -> Option<&str> {
+    list(sexpr).first()?.atom()
+}
+
+fn is_id(sexpr: &SExpr) -> bool {
+    sexpr.atom().is_some_and(|atom| atom.starts_with('$'))
+}
+
+// A field's $id (or "" if it has none), and the items after it.
+fn split_id(items: &[SExpr]) -> (String, &[SExpr]) {
+    match items.split_first() {
+        Some((id, rest)) if is_id(id) => (id.to_string(), rest),
+        _ => (String::new(), items),
+    }
+}
+
+// A function index in the editor's numbering (a $id stays as it is).
+fn func_index(index: &str) -> String {
+    match index.parse::<u32>() {
+        Ok(index) => (index + PRINT_FUNCS.len() as u32).to_string(),
+        Err(_) => index.to_string(),
+    }
+}
+
+// The value of a constant expression such as `(i32.const 1)`.
+fn constant(sexpr: &SExpr) -> Result<(ValType, String), String> {
+    match list(sexpr) {
+        [op, value] => (op.atom())
+            .and_then(|op| op.strip_suffix(".const"))
+            .and_then(ValType::from_keyword)
+            .zip(value.atom())
+            .map(|(ty, value)| (ty, value.to_string())),
+        _ => None,
+    }
+    .ok_or_else(|| format!("only constant expressions are supported, not `{sexpr}`"))
+}
+
+// A segment's offset: `(i32.const n)`, or the same in `(offset ...)`.
+fn offset(sexpr: &SExpr) -> Result<String, String> {
+    let sexpr = match sexpr.form("offset") {
+        Some([expr]) => expr,
+        _ => sexpr,
+    };
+    match constant(sexpr)? {
+        (ValType::I32, value) => Ok(value),
+        _ => Err(format!("expected an i32 offset, not `{sexpr}`")),
+    }
+}
+
+fn string_bytes(sexpr: &SExpr) -> Result<Vec<u8>, String> {
+    (sexpr.atom())
+        .and_then(parse_string_bytes)
+        .ok_or_else(|| format!("expected a string, not `{sexpr}`"))
+}
+
+fn string(sexpr: &SExpr) -> Result<String, String> {
+    sexpr
+        .string()
+        .ok_or_else(|| format!("expected a string, not `{sexpr}`"))
+}
+
+// The words of a declaration after its keyword, e.g. "$x i32" for `(param $x i32)`.
+fn declaration(sexpr: &SExpr) -> String {
+    let words: Vec<String> = list(sexpr)[1..].iter().map(SExpr::to_string).collect();
+    words.join(" ")
+}
+
+fn add_part(field: &mut String, part: String, separator: &str) {
+    if part.is_empty() {
+        return;
+    }
+    if !field.is_empty() {
+        field.push_str(separator);
+    }
+    field.push_str(&part);
+}
+
+// Unfold instructions into lines: plain ones (an instruction and its immediates, which
+// are atoms or, for a block, its `(result ...)`) and folded ones.
+fn instructions(items: &[SExpr], lines: &mut Vec<String>) -> Result<(), String> {
+    let mut rest = items;
+    while let Some((first, after)) = rest.split_first() {
+        rest = after;
+        let Some(word) = first.atom() else {
+            folded(first, lines)?;
+            continue;
+        };
+        let Some(info) = lookup(word) else {
+            return Err(format!("unknown instruction `{word}`"));
+        };
+        let mut line = word.to_string();
+        while let Some((next, after)) = rest.split_first() {
+            let immediate = match next.atom() {
+                Some(atom) => lookup(atom).is_none(),
+                None => info.imm == ImmKind::BlockType && next.form("result").is_some(),
+            };
+            if !immediate {
+                break;
+            }
+            line.push(' ');
+            match (info.imm, next.atom()) {
+                (ImmKind::Func, Some(index)) => line.push_str(&func_index(index)),
+                _ => line.push_str(&next.to_string()),
+            }
+            rest = after;
+        }
+        lines.push(line);
+    }
+    Ok(())
+}
+
+// A folded instruction: its operands first, then the instruction (and for a block,
+// its body and `end`).
+fn folded(sexpr: &SExpr, lines: &mut Vec<String>) -> Result<(), String> {
+    let items = list(sexpr);
+    let Some(word) = items.first().and_then(SExpr::atom) else {
+        return Err(format!("expected an instruction, not `{sexpr}`"));
+    };
+    // the label and block type, and where the rest starts
+    let block_header = |items: &[SExpr]| {
+        let header = (items.iter().skip(1))
+            .take_while(|item| is_id(item) || item.form("result").is_some())
+            .count();
+        let words: Vec<String> = items[..=header].iter().map(SExpr::to_string).collect();
+        (words.join(" "), header + 1)
+    };
+    match word {
+        "block" | "loop" => {
+            let (header, start) = block_header(items);
+            lines.push(header);
+            instructions(&items[start..], lines)?;
+            lines.push("end".to_string());
+        }
+        "if" => {
+            let (header, start) = block_header(items);
+            let mut then = None;
+            let mut otherwise = None;
+            for item in &items[start..] {
+                if let Some(body) = item.form("then") {
+                    then = Some(body);
+                } else if let Some(body) = item.form("else") {
+                    otherwise = Some(body);
+                } else {
+                    folded(item, lines)?;
+                }
+            }
+            lines.push(header);
+            instructions(then.unwrap_or_default(), lines)?;
+            if let Some(body) = otherwise {
+                lines.push("else".to_string());
+                instructions(body, lines)?;
+            }
+            lines.push("end".to_string());
+        }
+        _ => {
+            let operands = (items.iter().skip(1)).position(|item| item.atom().is_none());
+            let end = operands.map_or(items.len(), |operands| operands + 1);
+            for operand in &items[end..] {
+                folded(operand, lines)?;
+            }
+            instructions(&items[..end], lines)?;
+        }
+    }
+    Ok(())
+}
+
+impl Reader {
+    // A function's type: `(type $t)` and/or its params and results, and the items
+    // after them.
+    fn type_use<'a>(&self, items: &'a [SExpr]) -> Result<(TypeUse, &'a [SExpr]), String> {
+        let mut ty = TypeUse::default();
+        let mut rest = items;
+        if let Some([index]) = rest.first().and_then(|item| item.form("type")) {
+            let index = index.atom().unwrap_or_default();
+            let found = match index.parse::<usize>() {
+                Ok(index) => self.types.get(index),
+                Err(_) => (self.types.iter()).find(|(id, _)| id.as_deref() == Some(index)),
+            };
+            let Some((_, found)) = found else {
+                return Err(format!("unknown type {index}"));
+            };
+            ty = found.clone();
+            rest = &rest[1..];
+        }
+        let (mut params, mut results) = (String::new(), String::new());
+        while let Some((first, after)) = rest.split_first() {
+            match keyword(first) {
+                Some("param") => add_part(&mut params, declaration(first), ", "),
+                Some("result") => add_part(&mut results, declaration(first), " "),
+                _ => break,
+            }
+            rest = after;
+        }
+        if !params.is_empty() || !results.is_empty() {
+            ty = TypeUse { params, results };
+        }
+        Ok((ty, rest))
+    }
+
+    // Inline exports, and the items after them.
+    fn exports<'a>(&mut self, items: &'a [SExpr], kind: &str, index: usize) -> &'a [SExpr] {
+        let count = (items.iter())
+            .take_while(|item| item.form("export").is_some())
+            .count();
+        for export in &items[..count] {
+            let name = (export.form("export").unwrap().first())
+                .and_then(SExpr::string)
+                .unwrap_or_default();
+            self.sections
+                .push(Section::Exports, &[&name, kind, &index.to_string()]);
+        }
+        &items[count..]
+    }
+
+    fn import_func(&mut self, module: &str, name: &str, id: &str, ty: &TypeUse) {
+        let fields = [module, name, id, &ty.params, &ty.results];
+        self.sections.push(Section::Imports, &fields);
+        self.func_imports += 1;
+    }
+
+    fn func(&mut self, items: &[SExpr]) -> Result<(), String> {
+        let (id, items) = split_id(items);
+        let exports: Vec<String> = (items.iter())
+            .map_while(|item| item.form("export"))
+            .map(|export| export.first().and_then(SExpr::string).unwrap_or_default())
+            .collect();
+        let items = &items[exports.len()..];
+        if let Some([module, name]) = items.first().and_then(|item| item.form("import")) {
+            let (ty, _) = self.type_use(&items[1..])?;
+            let index = PRINT_FUNCS.len() + self.func_imports;
+            self.import_func(&string(module)?, &string(name)?, &id, &ty);
+            for name in exports {
+                let index = index.to_string();
+                self.sections
+                    .push(Section::Exports, &[&name, "func", &index]);
+            }
+            return Ok(());
+        }
+        let (ty, mut rest) = self.type_use(items)?;
+        let mut locals = String::new();
+        while let Some(local) = rest.first().filter(|item| item.form("local").is_some()) {
+            add_part(&mut locals, declaration(local), ", ");
+            rest = &rest[1..];
+        }
+        let mut lines: Vec<String> = (exports.iter())
+            .map(|name| format!("(export {})", quote(name)))
+            .collect();
+        instructions(rest, &mut lines)?;
+        self.funcs.push(FunctionText {
+            header: Header {
+                fields: [id, ty.params, ty.results, locals],
+            },
+            lines,
+        });
+        Ok(())
+    }
+
+    fn field(&mut self, sexpr: &SExpr) -> Result<(), String> {
+        let items = &list(sexpr)[1..];
+        match keyword(sexpr).unwrap_or_default() {
+            "type" => Ok(()), // read before the other fields
+            "func" => self.func(items),
+            "import" => {
+                let [module, name, desc] = items else {
+                    return Err(format!(
+                        "expected `(import \"module\" \"name\" (func ...))`, not `{sexpr}`"
+                    ));
+                };
+                let Some(desc_items) = desc.form("func") else {
+                    let kind = keyword(desc).unwrap_or_default();
+                    return Err(format!("imports of a {kind} are not supported"));
+                };
+                let (id, desc_items) = split_id(desc_items);
+                let (ty, _) = self.type_use(desc_items)?;
+                self.import_func(&string(module)?, &string(name)?, &id, &ty);
+                Ok(())
+            }
+            "table" => {
+                let (_, items) = split_id(items);
+                let items = self.exports(items, "table", 0);
+                let elem = items.iter().find_map(|item| item.form("elem"));
+                let (min, max) = match (elem, items) {
+                    (Some(funcs), _) => {
+                        let funcs: Vec<String> = (funcs.iter())
+                            .map(|func| func_index(func.atom().unwrap_or_default()))
+                            .collect();
+                        self.sections
+                            .push(Section::Elements, &["0", &funcs.join(" ")]);
+                        (funcs.len().to_string(), funcs.len().to_string())
+                    }
+                    (None, [min, max, _]) => (min.to_string(), max.to_string()),
+                    (None, [min, _]) => (min.to_string(), String::new()),
+                    _ => return Err(format!("expected the table's size, in `{sexpr}`")),
+                };
+                self.sections.push(Section::Table, &[&min, &max]);
+                Ok(())
+            }
+            "memory" => {
+                let (_, items) = split_id(items);
+                let items = self.exports(items, "memory", 0);
+                let (min, max) = match items {
+                    [data] if data.form("data").is_some() => {
+                        let mut bytes = Vec::new();
+                        for string in data.form("data").unwrap() {
+                            bytes.extend(string_bytes(string)?);
+                        }
+                        let pages = bytes.len().div_ceil(PAGE_SIZE).to_string();
+                        self.sections
+                            .push(Section::Data, &["0", &quote_bytes(&bytes)]);
+                        (pages.clone(), pages)
+                    }
+                    [min, max] => (min.to_string(), max.to_string()),
+                    [min] => (min.to_string(), String::new()),
+                    _ => return Err(format!("expected the memory's size, in `{sexpr}`")),
+                };
+                self.sections.push(Section::Memory, &[&min, &max]);
+                Ok(())
+            }
+            "global" => {
+                let (id, items) = split_id(items);
+                let items = self.exports(items, "global", self.globals);
+                let [ty, init] = items else {
+                    return Err(format!(
+                        "expected the global's type and value, in `{sexpr}`"
+                    ));
+                };
+                let ty = match ty.form("mut") {
+                    Some([ty]) => format!("mut {ty}"),
+                    _ => ty.to_string(),
+                };
+                let (_, value) = constant(init)?;
+                self.sections.push(Section::Globals, &[&id, &ty, &value]);
+                self.globals += 1;
+                Ok(())
+            }
+            "export" => {
+                let [name, target] = items else {
+                    return Err(format!(
+                        "expected `(export \"name\" (func 0))`, not `{sexpr}`"
+                    ));
+                };
+                let (kind, index) = match list(target) {
+                    [kind, index] => (kind.to_string(), index.to_string()),
+                    _ => return Err(format!("expected what `{sexpr}` exports")),
+                };
+                let index = match kind.as_str() {
+                    "func" => func_index(&index),
+                    _ => index,
+                };
+                self.sections
+                    .push(Section::Exports, &[&string(name)?, &kind, &index]);
+                Ok(())
+            }
+            "elem" => {
+                let items = match items.first() {
+                    Some(table) if table.atom().is_some() => &items[1..],
+                    _ => items,
+                };
+                let Some((offset_expr, funcs)) = items.split_first() else {
+                    return Err(format!("expected the segment's offset, in `{sexpr}`"));
+                };
+                let funcs: Vec<String> = (funcs.iter())
+                    .filter_map(SExpr::atom)
+                    .filter(|func| *func != "func")
+                    .map(func_index)
+                    .collect();
+                self.sections.push(
+                    Section::Elements,
+                    &[&offset(offset_expr)?, &funcs.join(" ")],
+                );
+                Ok(())
+            }
+            "data" => {
+                let items = match items.first() {
+                    Some(memory) if memory.atom().is_some_and(|atom| !atom.starts_with('"')) => {
+                        &items[1..]
+                    }
+                    _ => items,
+                };
+                let Some((offset_expr, strings)) = items.split_first() else {
+                    return Err(format!("expected the segment's offset, in `{sexpr}`"));
+                };
+                let mut bytes = Vec::new();
+                for string in strings {
+                    bytes.extend(string_bytes(string)?);
+                }
+                self.sections.push(
+                    Section::Data,
+                    &[&offset(offset_expr)?, &quote_bytes(&bytes)],
+                );
+                Ok(())
+            }
+            "start" => Err("start functions are not supported".to_string()),
+            word => Err(format!("unknown module field `{word}`")),
+        }
+    }
+}
+
+// The module made of the fields (the items of `(module ...)` after its $id, if any).
+pub fn text_module(fields: &[SExpr]) -> Result<ModuleText, String> {
+    let mut reader = Reader {
+        types: Vec::new(),
+        sections: Sections::default(),
+        funcs: Vec::new(),
+        func_imports: 0,
+        globals: 0,
+    };
+    for field in fields.iter().filter(|field| keyword(field) == Some("type")) {
+        let (id, func) = match &list(field)[1..] {
+            [id, func] if is_id(id) => (Some(id.to_string()), func),
+            [func] => (None, func),
+            _ => return Err(format!("expected `(type (func ...))`, not `{field}`")),
+        };
+        let Some(func) = func.form("func") else {
+            return Err(format!("expected `(type (func ...))`, not `{field}`"));
+        };
+        let (ty, _) = reader.type_use(func)?;
+        reader.types.push((id, ty));
+    }
+    for field in fields {
+        if keyword(field).is_none() {
+            return Err(format!("expected a module field, not `{field}`"));
+        }
+        reader.field(field)?;
+    }
+    Ok(ModuleText {
+        sections: reader.sections,
+        funcs: reader.funcs,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{header::HeaderField, script::read_sexprs};
+
+    fn read(text: &str) -> Result<ModuleText, String> {
+        let (fields, error) = read_sexprs(text);
+        assert_eq!(error, None);
+        text_module(&fields)
+    }
+
+    #[test]
+    fn functions() {
+        let module = read(
+            r#"(type $binary (func (param i32 i32) (result i32)))
+(import "env" "log" (func $log (param $x i32)))
+(func $f (export "f") (export "g") (type $binary) (local $t i64) (local f32 f64)
+  (block $out (result i32)
+    (if (result i32) (i32.eqz (local.get 0))
+      (then (i32.const 1))
+      (else (call 0 (local.get 1)) (i32.const 2)))
+    br 0)
+  loop $l (result i32) i32.const 3 end
+  i32.store offset=4 align=1
+  (call $f (i32.const 1)))"#,
+        )
+        .unwrap();
+        assert_eq!(
+            module.sections.entries(Section::Imports),
+            [["env", "log", "$log", "$x i32", ""]]
+        );
+        let func = &module.funcs[0];
+        assert_eq!(func.header.field(HeaderField::Name), "$f");
+        assert_eq!(func.header.field(HeaderField::Params), "i32 i32");
+        assert_eq!(func.header.field(HeaderField::Results), "i32");
+        assert_eq!(func.header.field(HeaderField::Locals), "$t i64, f32 f64");
+        assert_eq!(
+            func.lines,
+            [
+                "(export \"f\")",
+                "(export \"g\")",
+                "block $out (result i32)",
+                "local.get 0",
+                "i32.eqz",
+                "if (result i32)",
+                "i32.const 1",
+                "else",
+                "local.get 1",
+                "call 4",
+                "i32.const 2",
+                "end",
+                "br 0",
+                "end",
+                "loop $l (result i32)",
+                "i32.const 3",
+                "end",
+                "i32.store offset=4 align=1",
+                "i32.const 1",
+                "call $f",
+            ]
+        );
+    }
+
+    #[test]
+    fn other_fields() {
+        let module = read(
+            r#"(func $f) (func)
+(table 2 funcref)
+(memory (export "mem") 1 2)
+(global $g (export "g") (mut i32) (i32.const -1))
+(global f64 (f64.const 0.5))
+(export "second" (func 1))
+(export "h" (global $g))
+(elem (i32.const 0) $f 1)
+(data (offset (i32.const 8)) "a" "\62\00")"#,
+        )
+        .unwrap();
+        let sections = &module.sections;
+        assert_eq!(sections.entries(Section::Table), [["2", ""]]);
+        assert_eq!(sections.entries(Section::Memory), [["1", "2"]]);
+        assert_eq!(
+            sections.entries(Section::Globals),
+            [["$g", "mut i32", "-1"], ["", "f64", "0.5"]]
+        );
+        assert_eq!(
+            sections.entries(Section::Exports),
+            [
+                ["mem", "memory", "0"],
+                ["g", "global", "0"],
+                ["second", "func", "5"],
+                ["h", "global", "$g"]
+            ]
+        );
+        assert_eq!(sections.entries(Section::Elements), [["0", "$f 5"]]);
+        assert_eq!(sections.entries(Section::Data), [["8", "\"ab\\00\""]]);
+        assert!(module.assemble().is_ok());
+
+        // a memory or table can be sized by its inline segment
+        let module = read(r#"(memory (data "abc")) (table funcref (elem 0 0)) (func)"#).unwrap();
+        assert_eq!(module.sections.entries(Section::Memory), [["1", "1"]]);
+        assert_eq!(module.sections.entries(Section::Data), [["0", "\"abc\""]]);
+        assert_eq!(module.sections.entries(Section::Table), [["2", "2"]]);
+        assert_eq!(module.sections.entries(Section::Elements), [["0", "4 4"]]);
+
+        assert_eq!(
+            read("(start 0)").unwrap_err(),
+            "start functions are not supported"
+        );
+        assert_eq!(
+            read(r#"(import "spectest" "memory" (memory 1))"#).unwrap_err(),
+            "imports of a memory are not supported"
+        );
+        assert_eq!(
+            read("(global i32 (global.get 0))").unwrap_err(),
+            "only constant expressions are supported, not `(global.get 0)`"
+        );
+        assert_eq!(
+            read("(func (i32.frob))").unwrap_err(),
+            "unknown instruction `i32.frob`"
+        );
+    }
+}
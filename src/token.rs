@@ -0,0 +1,297 @@
+// A tokenizer for one line of WebAssembly text. Every byte of the line belongs to
+// exactly one token (including whitespace), so the tokens' text concatenates back
+// to the line. A block comment or string that isn't closed on the line runs to its end.
+
+use crate::instruction::lookup;
+use std::ops::Range;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenKind {
+    Keyword,
+    Instruction,
+    Id,
+    Number,
+    String,
+    Comment,
+    Paren,
+    Space,
+    Other,
+}
+
+impl TokenKind {
+    // The CSS class of a token's <span>.
+    pub fn class(&self) -> &'static str {
+        match self {
+            TokenKind::Keyword => "tok-keyword",
+            TokenKind::Instruction => "tok-instr",
+            TokenKind::Id => "tok-id",
+            TokenKind::Number => "tok-number",
+            TokenKind::String => "tok-string",
+            TokenKind::Comment => "tok-comment",
+            TokenKind::Paren => "tok-paren",
+            TokenKind::Space => "tok-space",
+            TokenKind::Other => "tok-other",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub range: Range<usize>, // in bytes
+}
+
+const KEYWORDS: &[&str] = &[
+    "module",
+    "func",
+    "param",
+    "result",
+    "local",
+    "type",
+    "import",
+    "export",
+    "memory",
+    "data",
+    "table",
+    "elem",
+    "global",
+    "mut",
+    "start",
+    "then",
+    "offset",
+    "item",
+    "declare",
+    "i32",
+    "i64",
+    "f32",
+    "f64",
+    "funcref",
+    "externref",
+];
+
+// Whether a name can be written as a $name: the text format's identifier characters.
+pub fn is_id(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || "!#$%&'*+-./:<=>?@\\^_`|~".contains(ch))
+}
+
+// A string literal, escaping what can't appear in one as is.
+pub fn quote(string: &str) -> String {
+    let mut ret = String::from('"');
+    for ch in string.chars() {
+        match ch {
+            '"' | '\\' => {
+                ret.push('\\');
+                ret.push(ch);
+            }
+            ch if ch.is_control() => {
+                let mut bytes = [0; 4];
+                for byte in ch.encode_utf8(&mut bytes).bytes() {
+                    ret.push_str(&format!("\\{byte:02x}"));
+                }
+            }
+            ch => ret.push(ch),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+// A string literal of bytes (e.g. a data segment's), escaping those that aren't
+// printable ASCII.
+pub fn quote_bytes(bytes: &[u8]) -> String {
+    let mut ret = String::from('"');
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => {
+                ret.push('\\');
+                ret.push(byte as char);
+            }
+            b' '..=b'~' => ret.push(byte as char),
+            _ => ret.push_str(&format!("\\{byte:02x}")),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+// Characters that end a word (other than whitespace).
+fn is_delimiter(ch: char) -> bool {
+    ch.is_whitespace() || matches!(ch, '(' | ')' | '"' | ';')
+}
+
+fn classify_word(word: &str) -> TokenKind {
+    let unsigned = word.strip_prefix(['+', '-']).unwrap_or(word);
+    if word.starts_with('$') && word.len() > 1 {
+        TokenKind::Id
+    } else if unsigned.starts_with(|ch: char| ch.is_ascii_digit())
+        || unsigned == "inf"
+        || unsigned == "nan"
+        || unsigned.starts_with("nan:0x")
+    {
+        TokenKind::Number
+    } else if lookup(word).is_some() {
+        TokenKind::Instruction
+    } else if KEYWORDS.contains(&word) || word.starts_with("offset=") || word.starts_with("align=")
+    {
+        TokenKind::Keyword
+    } else {
+        TokenKind::Other
+    }
+}
+
+pub fn tokenize(line: &str) -> Vec<Token> {
+    let mut ret = Vec::new();
+    let mut pos = 0;
+    while pos < line.len() {
+        let rest = &line[pos..];
+        let ch = rest.chars().next().expect("non-empty");
+        let (kind, len) = if ch.is_whitespace() {
+            let len = rest
+                .find(|c: char| !c.is_whitespace())
+                .unwrap_or(rest.len());
+            (TokenKind::Space, len)
+        } else if rest.starts_with(";;") {
+            (TokenKind::Comment, rest.len())
+        } else if rest.starts_with("(;") {
+            let len = rest.find(";)").map_or(rest.len(), |end| end + 2);
+            (TokenKind::Comment, len)
+        } else if ch == '(' || ch == ')' {
+            (TokenKind::Paren, 1)
+        } else if ch == '"' {
+            // find the closing quote, skipping escaped characters
+            let mut end = rest.len();
+            let mut escaped = false;
+            for (index, c) in rest.char_indices().skip(1) {
+                match (escaped, c) {
+                    (false, '\\') => escaped = true,
+                    (false, '"') => {
+                        end = index + 1;
+                        break;
+                    }
+                    _ => escaped = false,
+                }
+            }
+            (TokenKind::String, end)
+        } else {
+            let len = rest
+                .char_indices()
+                .skip(1)
+                .find(|(_, c)| is_delimiter(*c))
+                .map_or(rest.len(), |(index, _)| index);
+            (classify_word(&rest[..len]), len)
+        };
+        ret.push(Token {
+            kind,
+            range: pos..pos + len,
+        });
+        pos += len;
+    }
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(line: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(line)
+            .into_iter()
+            .map(|token| (token.kind, &line[token.range]))
+            .collect()
+    }
+
+    #[test]
+    fn instructions_and_immediates() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds("  i32.const -42 ;; answer"),
+            vec![
+                (Space, "  "),
+                (Instruction, "i32.const"),
+                (Space, " "),
+                (Number, "-42"),
+                (Space, " "),
+                (Comment, ";; answer"),
+            ]
+        );
+        assert_eq!(
+            kinds("block $outer (result i32)"),
+            vec![
+                (Instruction, "block"),
+                (Space, " "),
+                (Id, "$outer"),
+                (Space, " "),
+                (Paren, "("),
+                (Keyword, "result"),
+                (Space, " "),
+                (Keyword, "i32"),
+                (Paren, ")"),
+            ]
+        );
+        assert_eq!(
+            kinds("i64.load offset=8 (; note ;)x"),
+            vec![
+                (Instruction, "i64.load"),
+                (Space, " "),
+                (Keyword, "offset=8"),
+                (Space, " "),
+                (Comment, "(; note ;)"),
+                (Other, "x"),
+            ]
+        );
+    }
+
+    #[test]
+    fn strings_and_unterminated_tokens() {
+        use TokenKind::*;
+        assert_eq!(
+            kinds(r#"(export "a \"b\"") "open"#),
+            vec![
+                (Paren, "("),
+                (Keyword, "export"),
+                (Space, " "),
+                (String, r#""a \"b\"""#),
+                (Paren, ")"),
+                (Space, " "),
+                (String, r#""open"#),
+            ]
+        );
+        assert_eq!(
+            kinds("nop (; open"),
+            vec![(Instruction, "nop"), (Space, " "), (Comment, "(; open")]
+        );
+        assert_eq!(
+            kinds("f32.const nan:0x200000 +inf"),
+            vec![
+                (Instruction, "f32.const"),
+                (Space, " "),
+                (Number, "nan:0x200000"),
+                (Space, " "),
+                (Number, "+inf"),
+            ]
+        );
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("tab\there \u{e9}"), "\"tab\\09here \u{e9}\"");
+        assert_eq!(quote_bytes(b"a\"\\\0\xe9"), r#""a\"\\\00\e9""#);
+    }
+
+    #[test]
+    fn tokens_cover_the_line() {
+        for line in ["", "  ", "é😀 $x\"y", "(;;)", "a;b", "i32.add)"] {
+            let tokens = tokenize(line);
+            let mut pos = 0;
+            for token in &tokens {
+                assert_eq!(token.range.start, pos);
+                assert!(token.range.end > pos);
+                pos = token.range.end;
+            }
+            assert_eq!(pos, line.len());
+        }
+    }
+}
@@ -0,0 +1,1111 @@
+// Validation of a function body, following the algorithm in the appendix of the
+// WebAssembly spec: a stack of operand types and a stack of control frames. Each line
+// gets the operand stack after its instruction (for display), the first type error
+// it caused, if any, and a warning if it can never run. Validation continues past an
+// error, so one mistake doesn't hide the rest of the function's stack annotations.
+
+use std::ops::Range;
+
+use crate::{
+    instruction::{BlockType, ImmKind, Immediate, Index, Instruction, ValType},
+    module::{PRINT_FUNCS, print_func_type},
+    parse::{DeclKind, ParsedLine},
+};
+
+// An operand type, or None for an unknown type (popped from the polymorphic stack
+// of unreachable code).
+pub type StackType = Option<ValType>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Local {
+    pub name: Option<String>,
+    pub ty: ValType,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FuncType {
+    pub params: Vec<ValType>,
+    pub results: Vec<ValType>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Func {
+    pub name: Option<String>,
+    pub ty: FuncType,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Global {
+    pub name: Option<String>,
+    pub ty: ValType,
+    pub mutable: bool,
+}
+
+// Everything an instruction may refer to.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Context {
+    pub locals: Vec<Local>, // the params, then the declared locals
+    pub results: Vec<ValType>,
+    pub funcs: Vec<Func>,
+    pub globals: Vec<Global>,
+    pub has_memory: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LineCheck {
+    pub stack: Option<Vec<StackType>>, // for lines with an instruction
+    pub error: Option<String>,
+    pub invalid: Option<Invalid>, // the kind of error (None for a declaration's)
+    pub warning: Option<String>,
+}
+
+// The kinds of error the validator finds, so that they can be matched with the
+// messages of the spec's scripts (the errors themselves say more).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Invalid {
+    TypeMismatch,
+    UnknownLabel,
+    UnknownLocal,
+    UnknownGlobal,
+    UnknownFunction,
+    UnknownMemory,
+    Alignment,
+    ImmutableGlobal,
+    Unmatched, // an `else` or `end` without its block
+}
+
+impl Invalid {
+    pub fn spec_message(self) -> &'static str {
+        match self {
+            Invalid::TypeMismatch => "type mismatch",
+            Invalid::UnknownLabel => "unknown label",
+            Invalid::UnknownLocal => "unknown local",
+            Invalid::UnknownGlobal => "unknown global",
+            Invalid::UnknownFunction => "unknown function",
+            Invalid::UnknownMemory => "unknown memory",
+            Invalid::Alignment => "alignment must not be larger than natural",
+            Invalid::ImmutableGlobal => "global is immutable",
+            Invalid::Unmatched => "unexpected token",
+        }
+    }
+}
+
+fn format_type(ty: StackType) -> String {
+    ty.map_or("any".to_string(), |ty| ty.to_string())
+}
+
+pub fn format_stack(stack: &[StackType]) -> String {
+    let types: Vec<String> = stack.iter().map(|ty| format_type(*ty)).collect();
+    format!("[{}]", types.join(" "))
+}
+
+fn format_types(types: &[ValType]) -> String {
+    format_stack(&types.iter().map(|ty| Some(*ty)).collect::<Vec<_>>())
+}
+
+// The function described by the declaration lines (which must come before the
+// instructions), and the errors of declarations that come too late. The function can
+// call the host's print functions (as functions 0 to 3, or by name, e.g.
+// `call $print_i32`) and itself (as function 4), and has a memory.
+pub fn function_context<'a>(
+    lines: impl IntoIterator<Item = &'a ParsedLine>,
+) -> (Context, Vec<(usize, String)>) {
+    let mut ctx = Context {
+        has_memory: true,
+        ..Context::default()
+    };
+    let mut ty = FuncType::default();
+    let mut declared_locals = Vec::new();
+    let mut errors = Vec::new();
+    let mut seen_instruction = false;
+    for (index, line) in lines.into_iter().enumerate() {
+        match line {
+            ParsedLine::Instr(_) => seen_instruction = true,
+            ParsedLine::Decl(decl) if seen_instruction => errors.push((
+                index,
+                format!(
+                    "`{}` must come before the function's instructions",
+                    decl.kind.keyword()
+                ),
+            )),
+            ParsedLine::Decl(decl)
+                if decl.kind == DeclKind::Param && !declared_locals.is_empty() =>
+            {
+                errors.push((index, "`param` must come before `local`".to_string()))
+            }
+            ParsedLine::Decl(decl) => {
+                let locals = decl.types.iter().map(|&ty| Local {
+                    name: decl.name.clone(),
+                    ty,
+                });
+                match decl.kind {
+                    DeclKind::Param => {
+                        ty.params.extend(&decl.types);
+                        ctx.locals.extend(locals);
+                    }
+                    DeclKind::Result => ty.results.extend(&decl.types),
+                    DeclKind::Local => declared_locals.extend(locals),
+                    DeclKind::Export => {}
+                }
+            }
+            ParsedLine::Empty | ParsedLine::Error(_) => {}
+        }
+    }
+    ctx.locals.extend(declared_locals);
+    ctx.results = ty.results.clone();
+    ctx.funcs = (PRINT_FUNCS.iter())
+        .map(|(name, param)| Func {
+            name: Some(name.to_string()),
+            ty: print_func_type(*param),
+        })
+        .collect();
+    ctx.funcs.push(Func { name: None, ty });
+    (ctx, errors)
+}
+
+// Like function_context, for one of a module's functions: instead of itself, it can
+// call the module's functions `funcs` (which come after the print functions: its other
+// imports, then the functions it defines), and use its globals.
+pub fn module_function_context<'a>(
+    lines: impl IntoIterator<Item = &'a ParsedLine>,
+    funcs: &[Func],
+    globals: &[Global],
+) -> (Context, Vec<(usize, String)>) {
+    let (mut ctx, errors) = function_context(lines);
+    ctx.funcs.truncate(PRINT_FUNCS.len());
+    ctx.funcs.extend_from_slice(funcs);
+    ctx.globals = globals.to_vec();
+    (ctx, errors)
+}
+
+// The type the declaration lines give a function.
+pub fn function_type<'a>(lines: impl IntoIterator<Item = &'a ParsedLine>) -> FuncType {
+    let (mut ctx, _) = function_context(lines);
+    ctx.funcs.pop().expect("the function itself").ty
+}
+
+// Check each line of a function body (lines without an instruction are skipped). At
+// the end, the operand stack must hold the function's results; if it doesn't, the
+// error goes on the last instruction.
+pub fn validate_function<'a>(
+    lines: impl IntoIterator<Item = &'a ParsedLine, IntoIter: Clone>,
+) -> Vec<LineCheck> {
+    let lines = lines.into_iter();
+    check_lines(lines.clone(), function_context(lines))
+}
+
+// The same, for one of a module's functions (see module_function_context).
+pub fn validate_module_function<'a>(
+    lines: impl IntoIterator<Item = &'a ParsedLine, IntoIter: Clone>,
+    funcs: &[Func],
+    globals: &[Global],
+) -> Vec<LineCheck> {
+    let lines = lines.into_iter();
+    check_lines(
+        lines.clone(),
+        module_function_context(lines, funcs, globals),
+    )
+}
+
+fn check_lines<'a>(
+    lines: impl IntoIterator<Item = &'a ParsedLine>,
+    (ctx, decl_errors): (Context, Vec<(usize, String)>),
+) -> Vec<LineCheck> {
+    let mut ret = check_body(&ctx, lines.into_iter().map(|line| line.instruction()));
+    for (index, error) in decl_errors {
+        ret[index].error = Some(error);
+        ret[index].invalid = None;
+    }
+    ret
+}
+
+pub fn check_body<'a>(
+    ctx: &Context,
+    lines: impl IntoIterator<Item = Option<&'a Instruction>>,
+) -> Vec<LineCheck> {
+    let mut validator = Validator::new(ctx);
+    let mut ret: Vec<LineCheck> = (lines.into_iter())
+        .map(|instr| validator.check_line(instr))
+        .collect();
+    if let (Some(error), Some(index)) = (validator.end_error(), end_line(ret.iter())) {
+        add_end_error(&mut ret[index], &error);
+    }
+    ret
+}
+
+// The line that gets the error of the function's implicit `end`: its last instruction
+// (or its last line, if it has none).
+fn end_line<'a>(
+    mut checks: impl ExactSizeIterator<Item = &'a LineCheck> + DoubleEndedIterator,
+) -> Option<usize> {
+    let len = checks.len();
+    (checks.rposition(|check| check.stack.is_some())).or(len.checked_sub(1))
+}
+
+fn add_end_error(check: &mut LineCheck, (invalid, error): &(Invalid, String)) {
+    if check.error.is_none() {
+        check.error = Some(format!("at the end of the function: {error}"));
+        check.invalid = Some(*invalid);
+    }
+}
+
+// The checks of a function body's lines, kept up to date as they are edited: after an
+// edit, the lines are checked from the first edited line only until the validator's
+// state after a line is the same as before the edit (so an edit within a block is
+// checked up to the block's `end`, at most), since the lines after it then check as
+// they did. An edit that changes the function's context (e.g. its declarations) checks
+// all of the lines again.
+#[derive(Default)]
+pub struct IncrementalCheck {
+    context: Option<(Context, Vec<(usize, String)>)>,
+    lines: Vec<CheckedLine>,
+    end_error: Option<(usize, (Invalid, String))>,
+}
+
+#[derive(Default)]
+struct CheckedLine {
+    check: LineCheck,    // from the line's instruction alone
+    state: CheckedState, // the validator's state after the line
+}
+
+#[derive(Clone, Default, PartialEq)]
+struct CheckedState {
+    vals: Vec<StackType>,
+    ctrls: Vec<Frame>,
+}
+
+impl IncrementalCheck {
+    // The check of a line, with the errors of its declaration or of the function's
+    // implicit `end` (as validate_function gives it).
+    pub fn get(&self, index: usize) -> Option<LineCheck> {
+        let (_, decl_errors) = self.context.as_ref()?;
+        let mut check = self.lines.get(index)?.check.clone();
+        if let Some((_, error)) = (self.end_error.as_ref()).filter(|(line, _)| *line == index) {
+            add_end_error(&mut check, error);
+        }
+        if let Some((_, error)) = decl_errors.iter().find(|(line, _)| *line == index) {
+            check.error = Some(error.clone());
+            check.invalid = None;
+        }
+        Some(check)
+    }
+
+    // Replace the lines in `range` with `count` lines, then check them and the lines
+    // after them (as far as needed), given the function's context (see
+    // function_context) and each line's instruction after the edit. Returns the lines
+    // whose check may have changed (including every new line).
+    pub fn splice<'a>(
+        &mut self,
+        context: (Context, Vec<(usize, String)>),
+        range: Range<usize>,
+        count: usize,
+        instruction: impl Fn(usize) -> Option<&'a Instruction>,
+    ) -> Vec<usize> {
+        let (range, count) = match self.context.as_ref() == Some(&context) {
+            true => (range, count),
+            false => (0..self.lines.len(), self.lines.len() - range.len() + count),
+        };
+        let ctx = &self.context.insert(context).0;
+
+        // (the line that had the error of the implicit `end`, after the edit)
+        let old_end = self.end_error.take().and_then(|(index, _)| {
+            if index < range.start {
+                Some(index)
+            } else if index >= range.end {
+                Some(index - range.len() + count)
+            } else {
+                None
+            }
+        });
+        self.lines
+            .splice(range.clone(), (0..count).map(|_| CheckedLine::default()));
+        let mut changed: Vec<usize> = (range.start..range.start + count).chain(old_end).collect();
+
+        let mut validator = match range.start {
+            0 => Validator::new(ctx),
+            start => Validator::resume(ctx, self.lines[start - 1].state.clone()),
+        };
+        for index in range.start..self.lines.len() {
+            let check = validator.check_line(instruction(index));
+            let line = &mut self.lines[index];
+            if line.check != check {
+                line.check = check;
+                changed.push(index);
+            }
+            if index >= range.start + count && validator.has_state(&line.state) {
+                break;
+            }
+            line.state = validator.state();
+        }
+
+        let mut validator = match self.lines.last() {
+            None => Validator::new(ctx),
+            Some(line) => Validator::resume(ctx, line.state.clone()),
+        };
+        let end = end_line(self.lines.iter().map(|line| &line.check));
+        self.end_error = end.zip(validator.end_error());
+        changed.extend(self.end_error.as_ref().map(|(index, _)| *index));
+        changed.sort_unstable();
+        changed.dedup();
+        changed
+    }
+}
+
+#[derive(Clone, PartialEq)]
+struct Frame {
+    mnemonic: &'static str,
+    label: Option<String>,
+    start: Vec<ValType>,
+    end: Vec<ValType>,
+    height: usize,
+    unreachable: bool,
+}
+
+impl Frame {
+    // The types a branch to this frame carries.
+    fn label_types(&self) -> &[ValType] {
+        if self.mnemonic == "loop" {
+            &self.start
+        } else {
+            &self.end
+        }
+    }
+}
+
+struct Validator<'a> {
+    ctx: &'a Context,
+    vals: Vec<StackType>,
+    ctrls: Vec<Frame>,
+    error: Option<(Invalid, String)>, // the first error of the current instruction
+}
+
+impl<'a> Validator<'a> {
+    fn new(ctx: &'a Context) -> Self {
+        let func = Frame {
+            mnemonic: "func",
+            label: None,
+            start: Vec::new(),
+            end: ctx.results.clone(),
+            height: 0,
+            unreachable: false,
+        };
+        Self::resume(
+            ctx,
+            CheckedState {
+                vals: Vec::new(),
+                ctrls: vec![func],
+            },
+        )
+    }
+
+    // Continue validating from the state after some line.
+    fn resume(ctx: &'a Context, state: CheckedState) -> Self {
+        Validator {
+            ctx,
+            vals: state.vals,
+            ctrls: state.ctrls,
+            error: None,
+        }
+    }
+
+    fn state(&self) -> CheckedState {
+        CheckedState {
+            vals: self.vals.clone(),
+            ctrls: self.ctrls.clone(),
+        }
+    }
+
+    fn has_state(&self, state: &CheckedState) -> bool {
+        self.vals == state.vals && self.ctrls == state.ctrls
+    }
+
+    // Check the next line (which may have no instruction).
+    fn check_line(&mut self, instr: Option<&Instruction>) -> LineCheck {
+        let Some(instr) = instr else {
+            return LineCheck::default();
+        };
+        let dead = self.top().unreachable && !matches!(instr.mnemonic(), "else" | "end");
+        self.instruction(instr);
+        let (invalid, error) = self.error.take().unzip();
+        LineCheck {
+            stack: Some(self.vals.clone()),
+            error,
+            invalid,
+            warning: dead.then(|| "unreachable code".to_string()),
+        }
+    }
+
+    // The error of the function's implicit `end` (unless a block is left open).
+    fn end_error(&mut self) -> Option<(Invalid, String)> {
+        if self.ctrls.len() != 1 {
+            return None;
+        }
+        self.pop_ctrl();
+        self.error.take()
+    }
+
+    fn fail(&mut self, invalid: Invalid, message: impl Into<String>) {
+        if self.error.is_none() {
+            self.error = Some((invalid, message.into()));
+        }
+    }
+
+    fn top(&self) -> &Frame {
+        self.ctrls.last().expect("function frame")
+    }
+
+    fn push(&mut self, ty: StackType) {
+        self.vals.push(ty);
+    }
+
+    fn push_vals(&mut self, types: &[ValType]) {
+        self.vals.extend(types.iter().map(|ty| Some(*ty)));
+    }
+
+    // Pop one operand (of any type).
+    fn pop(&mut self, what: &str) -> StackType {
+        if self.vals.len() == self.top().height {
+            if !self.top().unreachable {
+                self.fail(
+                    Invalid::TypeMismatch,
+                    format!("{what} expects an operand but the stack is empty"),
+                );
+            }
+            return None;
+        }
+        self.vals.pop().expect("operand")
+    }
+
+    // Pop operands of the given types (the last one from the top of the stack). If
+    // they don't match, the error shows what was expected and what was there.
+    fn pop_vals(&mut self, expected: &[ValType], what: &str) {
+        let frame = self.top();
+        let available = self.vals.len() - frame.height;
+        let count = expected.len().min(available);
+        let actual = &self.vals[self.vals.len() - count..];
+        let missing = available < expected.len() && !frame.unreachable;
+        let mismatched = actual
+            .iter()
+            .zip(&expected[expected.len() - count..])
+            .any(|(actual, expected)| actual.is_some_and(|actual| actual != *expected));
+        if missing || mismatched {
+            let message = format!(
+                "{what} expects {} but the stack has {}",
+                format_types(expected),
+                format_stack(actual)
+            );
+            self.fail(Invalid::TypeMismatch, message);
+        }
+        self.vals.truncate(self.vals.len() - count);
+    }
+
+    fn push_ctrl(
+        &mut self,
+        mnemonic: &'static str,
+        label: Option<String>,
+        start: Vec<ValType>,
+        end: Vec<ValType>,
+    ) {
+        self.push_vals(&start);
+        self.ctrls.push(Frame {
+            mnemonic,
+            label,
+            start,
+            end,
+            height: self.vals.len(),
+            unreachable: false,
+        });
+        let frame = self.ctrls.last_mut().expect("frame");
+        frame.height -= frame.start.len();
+    }
+
+    // Leave a block: its results must be exactly what is left on its part of the stack.
+    fn pop_ctrl(&mut self) -> Frame {
+        let end = self.top().end.clone();
+        let what = format!("`end` of {}", self.top().mnemonic);
+        self.pop_vals(&end, &what);
+        let frame = self.ctrls.pop().expect("frame");
+        if self.vals.len() > frame.height {
+            let extra = format_stack(&self.vals[frame.height..]);
+            self.fail(
+                Invalid::TypeMismatch,
+                format!(
+                    "{what} leaves {extra} on the stack (the block's results are {})",
+                    format_types(&end)
+                ),
+            );
+            self.vals.truncate(frame.height);
+        }
+        frame
+    }
+
+    fn set_unreachable(&mut self) {
+        let height = self.top().height;
+        self.vals.truncate(height);
+        self.ctrls.last_mut().expect("frame").unreachable = true;
+    }
+
+    // The depth of the frame a label refers to (0 is the innermost).
+    fn label(&mut self, index: &Index) -> Option<usize> {
+        let depth = match index {
+            Index::Num(num) => Some(*num as usize).filter(|&num| num < self.ctrls.len()),
+            Index::Id(id) => self
+                .ctrls
+                .iter()
+                .rev()
+                .position(|frame| frame.label.as_ref() == Some(id)),
+        };
+        if depth.is_none() {
+            self.fail(Invalid::UnknownLabel, format!("unknown label {index}"));
+        }
+        depth
+    }
+
+    fn label_types(&mut self, index: &Index) -> Option<Vec<ValType>> {
+        let depth = self.label(index)?;
+        Some(
+            self.ctrls[self.ctrls.len() - 1 - depth]
+                .label_types()
+                .to_vec(),
+        )
+    }
+
+    fn lookup<T>(
+        &mut self,
+        items: &[T],
+        name: impl Fn(&T) -> Option<&String>,
+        index: &Index,
+        what: &str,
+        invalid: Invalid,
+    ) -> Option<usize> {
+        let found = match index {
+            Index::Num(num) => Some(*num as usize).filter(|&num| num < items.len()),
+            Index::Id(id) => items.iter().position(|item| name(item) == Some(id)),
+        };
+        if found.is_none() {
+            self.fail(invalid, format!("unknown {what} {index}"));
+        }
+        found
+    }
+
+    fn local(&mut self, index: &Index) -> Option<ValType> {
+        let ctx = self.ctx;
+        let found = self.lookup(
+            &ctx.locals,
+            |local| local.name.as_ref(),
+            index,
+            "local",
+            Invalid::UnknownLocal,
+        );
+        found.map(|found| ctx.locals[found].ty)
+    }
+
+    fn block_results(ty: &BlockType) -> Vec<ValType> {
+        match ty {
+            BlockType::Empty => Vec::new(),
+            BlockType::Value(ty) => vec![*ty],
+        }
+    }
+
+    fn instruction(&mut self, instr: &Instruction) {
+        let mnemonic = instr.mnemonic();
+        if let ImmKind::MemArg(natural) = instr.info.imm {
+            if !self.ctx.has_memory {
+                self.fail(Invalid::UnknownMemory, format!("{mnemonic} needs a memory"));
+            }
+            if let Immediate::MemArg(memarg) = &instr.imm
+                && memarg.align > natural
+            {
+                self.fail(
+                    Invalid::Alignment,
+                    format!(
+                        "alignment {} is larger than the natural alignment of {mnemonic} ({})",
+                        1u64 << memarg.align,
+                        1u64 << natural
+                    ),
+                );
+            }
+        }
+        if let Some(sig) = &instr.info.sig {
+            if mnemonic.starts_with("memory.") && !self.ctx.has_memory {
+                self.fail(Invalid::UnknownMemory, format!("{mnemonic} needs a memory"));
+            }
+            self.pop_vals(sig.params, mnemonic);
+            self.push_vals(sig.results);
+            return;
+        }
+
+        match (mnemonic, &instr.imm) {
+            ("unreachable", _) => self.set_unreachable(),
+            ("block" | "loop" | "if", Immediate::Block { label, ty }) => {
+                if mnemonic == "if" {
+                    self.pop_vals(&[ValType::I32], "if");
+                }
+                self.push_ctrl(mnemonic, label.clone(), Vec::new(), Self::block_results(ty));
+            }
+            ("else", _) => {
+                if self.top().mnemonic != "if" {
+                    self.fail(Invalid::Unmatched, "`else` outside of an `if`");
+                    return;
+                }
+                let frame = self.pop_ctrl();
+                self.push_ctrl("else", frame.label, frame.start, frame.end);
+            }
+            ("end", _) => {
+                if self.ctrls.len() == 1 {
+                    self.fail(Invalid::Unmatched, "`end` without a matching block");
+                    return;
+                }
+                let frame = self.pop_ctrl();
+                if frame.mnemonic == "if" && frame.start != frame.end {
+                    self.fail(
+                        Invalid::TypeMismatch,
+                        format!(
+                            "an `if` with results {} needs an `else`",
+                            format_types(&frame.end)
+                        ),
+                    );
+                }
+                self.push_vals(&frame.end);
+            }
+            ("br", Immediate::Index(index)) => {
+                if let Some(types) = self.label_types(index) {
+                    self.pop_vals(&types, &format!("br {index}"));
+                }
+                self.set_unreachable();
+            }
+            ("br_if", Immediate::Index(index)) => {
+                self.pop_vals(&[ValType::I32], "br_if");
+                if let Some(types) = self.label_types(index) {
+                    self.pop_vals(&types, &format!("br_if {index}"));
+                    self.push_vals(&types);
+                }
+            }
+            ("br_table", Immediate::BrTable { labels, default }) => {
+                self.pop_vals(&[ValType::I32], "br_table");
+                if let Some(types) = self.label_types(default) {
+                    // Each target's values must be on the stack too; check them without
+                    // consuming the operands (they may be of unknown type when unreachable).
+                    for label in labels {
+                        let Some(label_types) = self.label_types(label) else {
+                            continue;
+                        };
+                        if label_types.len() != types.len() {
+                            self.fail(Invalid::TypeMismatch, format!(
+                                "br_table targets carry different values: {label} has {}, {default} has {}",
+                                format_types(&label_types),
+                                format_types(&types)
+                            ));
+                            continue;
+                        }
+                        let vals = self.vals.clone();
+                        self.pop_vals(&label_types, &format!("br_table {label}"));
+                        self.vals = vals;
+                    }
+                    self.pop_vals(&types, "br_table");
+                }
+                self.set_unreachable();
+            }
+            ("return", _) => {
+                let results = self.ctx.results.clone();
+                self.pop_vals(&results, "return");
+                self.set_unreachable();
+            }
+            ("call", Immediate::Index(index)) => {
+                let ctx = self.ctx;
+                if let Some(found) = self.lookup(
+                    &ctx.funcs,
+                    |func| func.name.as_ref(),
+                    index,
+                    "function",
+                    Invalid::UnknownFunction,
+                ) {
+                    let ty = &ctx.funcs[found].ty;
+                    self.pop_vals(&ty.params, &format!("call {index}"));
+                    self.push_vals(&ty.results);
+                }
+            }
+            ("drop", _) => {
+                self.pop("drop");
+            }
+            ("select", _) => {
+                self.pop_vals(&[ValType::I32], "select");
+                let first = self.pop("select");
+                let second = self.pop("select");
+                if let (Some(first), Some(second)) = (first, second)
+                    && first != second
+                {
+                    self.fail(Invalid::TypeMismatch, format!(
+                        "select expects two operands of the same type but the stack has [{second} {first}]"
+                    ));
+                }
+                self.push(first.or(second));
+            }
+            ("local.get", Immediate::Index(index)) => {
+                let ty = self.local(index);
+                self.push(ty);
+            }
+            ("local.set" | "local.tee", Immediate::Index(index)) => {
+                if let Some(ty) = self.local(index) {
+                    self.pop_vals(&[ty], &format!("{mnemonic} {index}"));
+                    if mnemonic == "local.tee" {
+                        self.push(Some(ty));
+                    }
+                } else {
+                    self.pop(mnemonic);
+                }
+            }
+            ("global.get" | "global.set", Immediate::Index(index)) => {
+                let ctx = self.ctx;
+                let found = self.lookup(
+                    &ctx.globals,
+                    |global| global.name.as_ref(),
+                    index,
+                    "global",
+                    Invalid::UnknownGlobal,
+                );
+                let global = found.map(|found| &ctx.globals[found]);
+                match (mnemonic, global) {
+                    ("global.get", global) => self.push(global.map(|global| global.ty)),
+                    (_, Some(global)) => {
+                        if !global.mutable {
+                            self.fail(
+                                Invalid::ImmutableGlobal,
+                                format!("global {index} is immutable"),
+                            );
+                        }
+                        self.pop_vals(&[global.ty], &format!("global.set {index}"));
+                    }
+                    (_, None) => {
+                        self.pop(mnemonic);
+                    }
+                }
+            }
+            _ => unreachable!("{mnemonic} has the wrong kind of immediate"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse::parse_line;
+    use proptest::prelude::*;
+
+    fn check(text: &str) -> Vec<LineCheck> {
+        let parsed: Vec<_> = text.lines().map(parse_line).collect();
+        validate_function(&parsed)
+    }
+
+    fn stacks(text: &str) -> Vec<String> {
+        check(text)
+            .iter()
+            .map(|line| {
+                line.stack
+                    .as_ref()
+                    .map_or(String::new(), |stack| format_stack(stack))
+            })
+            .collect()
+    }
+
+    fn errors(text: &str) -> Vec<Option<String>> {
+        check(text).into_iter().map(|line| line.error).collect()
+    }
+
+    fn kinds(text: &str) -> Vec<Option<Invalid>> {
+        check(text).into_iter().map(|line| line.invalid).collect()
+    }
+
+    #[test]
+    fn stack_annotations() {
+        let text = "(param $n i32)\n(result i32)\n(local $acc i64)\nlocal.get $n\ni32.const 2\n\ni32.mul\ni64.const 1\nlocal.set $acc\nblock (result i32)\ni32.const 7\nend\ni32.add";
+        assert_eq!(
+            stacks(text),
+            vec![
+                "",
+                "",
+                "",
+                "[i32]",
+                "[i32 i32]",
+                "",
+                "[i32]",
+                "[i32 i64]",
+                "[i32]",
+                "[i32]",
+                "[i32 i32]",
+                "[i32 i32]",
+                "[i32]"
+            ]
+        );
+        assert!(errors(text).iter().all(Option::is_none));
+    }
+
+    #[test]
+    fn type_errors() {
+        assert_eq!(
+            errors("i32.const 1\ni64.const 2\ni32.add\ndrop")[2],
+            Some("i32.add expects [i32 i32] but the stack has [i32 i64]".to_string())
+        );
+        assert_eq!(
+            errors("(result i32)\nnop"),
+            vec![
+                None,
+                Some(
+                    "at the end of the function: `end` of func expects [i32] but the stack has []"
+                        .to_string()
+                )
+            ]
+        );
+        // (with no instructions, on the last line)
+        assert_eq!(
+            errors("(result i32)\n;; nothing")[1],
+            Some(
+                "at the end of the function: `end` of func expects [i32] but the stack has []"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            errors("block\ni32.const 1\nend")[2],
+            Some(
+                "`end` of block leaves [i32] on the stack (the block's results are [])".to_string()
+            )
+        );
+        assert_eq!(
+            errors("i32.const 0\nif (result i32)\ni32.const 1\nend\ndrop")[3],
+            Some("an `if` with results [i32] needs an `else`".to_string())
+        );
+        // (each error has a kind, as the spec's scripts name it)
+        assert_eq!(
+            kinds("i32.const 0\nif (result i32)\ni32.const 1\nend\ndrop"),
+            vec![None, None, None, Some(Invalid::TypeMismatch), None]
+        );
+        assert_eq!(
+            kinds("local.get 0\nbr 1"),
+            vec![Some(Invalid::UnknownLocal), Some(Invalid::UnknownLabel)]
+        );
+        assert_eq!(kinds("(result i32)\nnop")[1], Some(Invalid::TypeMismatch));
+        assert_eq!(kinds("nop\n(local i32)")[1], None);
+        assert_eq!(
+            errors("local.get 0")[0],
+            Some("unknown local 0".to_string())
+        );
+        assert_eq!(
+            errors("i32.const 0\ni32.load align=8\ndrop")[1],
+            Some("alignment 8 is larger than the natural alignment of i32.load (4)".to_string())
+        );
+        assert_eq!(
+            errors("nop\n(local i32)")[1],
+            Some("`local` must come before the function's instructions".to_string())
+        );
+    }
+
+    #[test]
+    fn branches_and_unreachable_code() {
+        // after a branch the stack is polymorphic, so anything goes until the `end`
+        let text = "(result i32)\nblock $b (result i32)\ni32.const 1\nbr $b\ni32.add\nend";
+        assert_eq!(
+            stacks(text),
+            vec!["", "[]", "[i32]", "[]", "[i32]", "[i32]"]
+        );
+        assert!(errors(text).iter().all(Option::is_none));
+        let warnings: Vec<bool> = check(text)
+            .iter()
+            .map(|line| line.warning.is_some())
+            .collect();
+        assert_eq!(warnings, vec![false, false, false, false, true, false]);
+
+        // a loop's label carries its params (none), not its results
+        let text = "loop (result i32)\ni32.const 1\nbr_if 0\ni32.const 2\nend\ndrop";
+        assert!(errors(text).iter().all(Option::is_none));
+        assert_eq!(
+            errors("block (result i64)\ni32.const 0\nbr 0\nend\ndrop")[2],
+            Some("br 0 expects [i64] but the stack has [i32]".to_string())
+        );
+        assert_eq!(errors("br 1")[0], Some("unknown label 1".to_string()));
+        // br_table's targets must agree on their types, not just on how many there are
+        let text = "block (result f32)\nblock (result i32)\ni32.const 1\ni32.const 0\nbr_table 1 0";
+        assert_eq!(
+            errors(text)[4],
+            Some("br_table 1 expects [f32] but the stack has [i32]".to_string())
+        );
+        let text = "block (result f32)\nblock (result i32)\nunreachable\nbr_table 1 0";
+        assert_eq!(errors(text)[3], None);
+        assert_eq!(
+            stacks("unreachable\nselect\ni64.const 1\ni32.const 2\nselect"),
+            vec!["[]", "[any]", "[any i64]", "[any i64 i32]", "[i64]"]
+        );
+        // calls to the function itself, and to the host
+        assert_eq!(
+            errors("(param i32)\n(result i32)\ni32.const 3\ncall 4"),
+            vec![None, None, None, None]
+        );
+        assert_eq!(
+            errors("f32.const 1\ncall $print_f32\ni32.const 2\ncall 0"),
+            vec![None, None, None, None]
+        );
+        assert_eq!(
+            errors("i32.const 1\ncall $print_i64")[1],
+            Some("call $print_i64 expects [i64] but the stack has [i32]".to_string())
+        );
+    }
+
+    #[test]
+    fn module_functions() {
+        let parsed: Vec<_> = ["(param i64)", "(result i32)", "i64.const 1", "call $twice"]
+            .map(parse_line)
+            .to_vec();
+        assert_eq!(
+            function_type(&parsed),
+            FuncType {
+                params: vec![ValType::I64],
+                results: vec![ValType::I32]
+            }
+        );
+
+        // functions are called by name or number, counting from after the print
+        // functions
+        let funcs = [
+            Func {
+                name: None,
+                ty: function_type(&parsed),
+            },
+            Func {
+                name: Some("twice".to_string()),
+                ty: FuncType {
+                    params: vec![ValType::I64],
+                    results: vec![ValType::I32],
+                },
+            },
+        ];
+        let checks = validate_module_function(&parsed, &funcs, &[]);
+        assert!(checks.iter().all(|check| check.error.is_none()));
+        let (ctx, _) = module_function_context(&parsed, &funcs, &[]);
+        assert_eq!(ctx.funcs.len(), PRINT_FUNCS.len() + 2);
+        let parsed: Vec<_> = ["i32.const 1", "call 5"].map(parse_line).to_vec();
+        assert_eq!(
+            validate_module_function(&parsed, &funcs, &[])[1].error,
+            Some("call 5 expects [i64] but the stack has [i32]".to_string())
+        );
+        assert_eq!(
+            validate_module_function(&parsed, &funcs[..1], &[])[1].error,
+            Some("unknown function 5".to_string())
+        );
+
+        // and the module's globals by name or number
+        let globals = [
+            Global {
+                name: Some("count".to_string()),
+                ty: ValType::I32,
+                mutable: true,
+            },
+            Global {
+                name: None,
+                ty: ValType::F64,
+                mutable: false,
+            },
+        ];
+        let parsed: Vec<_> = [
+            "global.get $count",
+            "i32.const 1",
+            "i32.add",
+            "global.set 0",
+        ]
+        .map(parse_line)
+        .to_vec();
+        let checks = validate_module_function(&parsed, &[], &globals);
+        assert!(checks.iter().all(|check| check.error.is_none()));
+        let parsed: Vec<_> = ["f64.const 1", "global.set 1"].map(parse_line).to_vec();
+        assert_eq!(
+            validate_module_function(&parsed, &[], &globals)[1].error,
+            Some("global 1 is immutable".to_string())
+        );
+        assert_eq!(
+            validate_module_function(&parsed, &[], &globals[..1])[1].error,
+            Some("unknown global 1".to_string())
+        );
+    }
+
+    fn line() -> impl Strategy<Value = &'static str> {
+        prop::sample::select(vec![
+            "(param i32)",
+            "(result i32)",
+            "(local i64)",
+            "i32.const 1",
+            "local.get 0",
+            "i32.add",
+            "drop",
+            "block (result i32)",
+            "loop",
+            "if",
+            "else",
+            "end",
+            "br 0",
+            "unreachable",
+            "",
+        ])
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+        #[test]
+        fn incremental_checks_match(
+            edits in prop::collection::vec(
+                (any::<usize>(), 0..4usize, prop::collection::vec(line(), 0..6)),
+                1..20,
+            )
+        ) {
+            let mut lines: Vec<ParsedLine> = Vec::new();
+            let mut incremental = IncrementalCheck::default();
+            for (start, removed, inserted) in edits {
+                let start = start % (lines.len() + 1);
+                let range = start..(start + removed).min(lines.len());
+                let count = inserted.len();
+                lines.splice(range.clone(), inserted.into_iter().map(parse_line));
+                let changed = incremental.splice(
+                    function_context(&lines),
+                    range,
+                    count,
+                    |index| lines[index].instruction(),
+                );
+
+                for (index, check) in validate_function(&lines).into_iter().enumerate() {
+                    prop_assert_eq!(incremental.get(index), Some(check), "line {}", index);
+                }
+                prop_assert_eq!(incremental.get(lines.len()), None);
+                prop_assert!(changed.iter().all(|&index| index < lines.len()));
+            }
+        }
+    }
+
+    #[test]
+    fn incremental_checks_stop_at_the_end_of_the_block() {
+        let text = "(result i32)\ni32.const 1\nblock\ni32.const 2\ndrop\nend\nnop\nnop";
+        let lines: Vec<_> = text.lines().map(parse_line).collect();
+        let mut incremental = IncrementalCheck::default();
+        let instruction = |index: usize| lines[index].instruction();
+        incremental.splice(function_context(&lines), 0..0, lines.len(), instruction);
+
+        // removing the `drop` changes the stacks up to the `end` (which now has an
+        // error), and no further
+        let edited: Vec<_> = (lines.iter().enumerate())
+            .filter(|&(index, _)| index != 4)
+            .map(|(_, line)| line.clone())
+            .collect();
+        let instruction = |index: usize| edited[index].instruction();
+        let changed = incremental.splice(function_context(&edited), 4..5, 0, instruction);
+        assert_eq!(changed, [4]);
+        assert_eq!(
+            incremental.get(4).and_then(|check| check.error),
+            Some(
+                "`end` of block leaves [i32] on the stack (the block's results are [])".to_string()
+            )
+        );
+
+        // declaring a local changes the context, so every line is checked again
+        let mut declared = edited.clone();
+        declared.insert(1, parse_line("(local i32)"));
+        let instruction = |index: usize| declared[index].instruction();
+        let changed = incremental.splice(function_context(&declared), 1..1, 1, instruction);
+        assert_eq!(changed, (0..declared.len()).collect::<Vec<_>>());
+    }
+}
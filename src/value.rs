@@ -0,0 +1,115 @@
+// A WebAssembly value: an argument or result of a function, or an operand. Values
+// print (and parse) in the text format's syntax for constants.
+
+use crate::{
+    instruction::{ValType, format_f32, format_f64},
+    parse::{FloatFormat, parse_float, parse_int},
+};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Value {
+    I32(i32),
+    I64(i64),
+    F32(f32),
+    F64(f64),
+}
+
+impl Value {
+    pub fn ty(&self) -> ValType {
+        match self {
+            Value::I32(_) => ValType::I32,
+            Value::I64(_) => ValType::I64,
+            Value::F32(_) => ValType::F32,
+            Value::F64(_) => ValType::F64,
+        }
+    }
+
+    // A constant of the given type (e.g. `-1`, `0xff`, `1.5e3`, `nan`).
+    pub fn parse(text: &str, ty: ValType) -> Option<Self> {
+        Some(match ty {
+            ValType::I32 => Value::I32(parse_int(text, 32)? as i32),
+            ValType::I64 => Value::I64(parse_int(text, 64)? as i64),
+            ValType::F32 => Value::F32(f32::from_bits(parse_float(text, FloatFormat::F32)? as u32)),
+            ValType::F64 => Value::F64(f64::from_bits(parse_float(text, FloatFormat::F64)?)),
+        })
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::I32(value) => write!(f, "{value}"),
+            Value::I64(value) => write!(f, "{value}"),
+            Value::F32(value) => f.write_str(&format_f32(value.to_bits())),
+            Value::F64(value) => f.write_str(&format_f64(value.to_bits())),
+        }
+    }
+}
+
+// Values with their types, e.g. `1 : i32, 2.5 : f64` (or `no results`).
+pub fn format_results(values: &[Value]) -> String {
+    if values.is_empty() {
+        return "no results".to_string();
+    }
+    let values: Vec<String> = (values.iter())
+        .map(|value| format!("{value} : {}", value.ty()))
+        .collect();
+    values.join(", ")
+}
+
+// Arguments for a function with the given param types, separated by spaces or commas.
+pub fn parse_args(text: &str, params: &[ValType]) -> Result<Vec<Value>, String> {
+    let words: Vec<&str> = text
+        .split(|ch: char| ch.is_whitespace() || ch == ',')
+        .filter(|word| !word.is_empty())
+        .collect();
+    if words.len() != params.len() {
+        return Err(format!(
+            "expected {} argument{}, got {}",
+            params.len(),
+            if params.len() == 1 { "" } else { "s" },
+            words.len()
+        ));
+    }
+    (words.iter().zip(params))
+        .map(|(word, ty)| Value::parse(word, *ty).ok_or_else(|| format!("invalid {ty} `{word}`")))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn args_parse_by_type() {
+        assert_eq!(
+            parse_args(
+                "-1, 0xff 2.5 inf",
+                &[ValType::I32, ValType::I64, ValType::F32, ValType::F64]
+            ),
+            Ok(vec![
+                Value::I32(-1),
+                Value::I64(255),
+                Value::F32(2.5),
+                Value::F64(f64::INFINITY)
+            ])
+        );
+        assert_eq!(
+            parse_args("4294967295", &[ValType::I32]),
+            Ok(vec![Value::I32(-1)])
+        );
+        assert_eq!(
+            parse_args("1 2", &[ValType::I32]),
+            Err("expected 1 argument, got 2".to_string())
+        );
+        assert_eq!(
+            parse_args("1.5", &[ValType::I32]),
+            Err("invalid i32 `1.5`".to_string())
+        );
+        let values = [Value::I64(-7), Value::F32(0.1), Value::F64(-0.0)];
+        let printed: Vec<String> = values.iter().map(|value| value.to_string()).collect();
+        assert_eq!(printed, vec!["-7", "0.1", "-0.0"]);
+        assert_eq!(Value::F32(f32::NAN).to_string(), "nan");
+    }
+}
@@ -0,0 +1,250 @@
+// The text format: write a module (or the editor's function) as a whole module in
+// WebAssembly's text format (a .wat file), with the same imports and memory that the
+// assembler gives it (see assemble.rs). The lines are kept as written, comments and
+// all, and indented by their block nesting. A function's header (see header.rs)
+// becomes its first declarations, and its `export` lines move to the front of the
+// function, where the text format requires them. The entries of the other sections
+// (see sections.rs) come first, each as one field of the module, written from their
+// text (leaving out a $id that isn't valid).
+
+use crate::{
+    header::{FunctionText, Header},
+    module::{HOST_MODULE, PRINT_FUNCS},
+    nesting::nesting,
+    parse::{DeclKind, ParsedLine, parse_line},
+    sections::{ModuleText, Section, Sections},
+    token::{is_id, quote},
+};
+use std::fmt::Write;
+
+const INDENT: &str = "  ";
+
+pub fn module_text(module: &ModuleText) -> String {
+    let parsed: Vec<Vec<ParsedLine>> = (module.funcs.iter())
+        .map(|func| func.lines.iter().map(|line| parse_line(line)).collect())
+        .collect();
+    let mut out = String::from("(module\n");
+    for (name, ty) in PRINT_FUNCS {
+        writeln!(
+            out,
+            "{INDENT}(import \"{HOST_MODULE}\" \"{name}\" (func ${name} (param {ty})))"
+        )
+        .expect("write to String");
+    }
+    let sections = &module.sections;
+    let uses_memory =
+        (parsed.iter().flatten().filter_map(ParsedLine::instruction)).any(|i| i.uses_memory());
+    for section in Section::ALL {
+        if section == Section::Memory
+            && sections.entries(section).is_empty()
+            && (uses_memory || !sections.entries(Section::Data).is_empty())
+        {
+            writeln!(out, "{INDENT}(memory 1)").expect("write to String");
+        }
+        for line in entry_lines(sections, section) {
+            writeln!(out, "{INDENT}{line}").expect("write to String");
+        }
+    }
+    for (func, parsed) in module.funcs.iter().zip(&parsed) {
+        function_text(&mut out, func, parsed);
+    }
+    out.push_str(")\n");
+    out
+}
+
+// A $id, if the field has a valid one (its `$` is optional).
+fn id(field: &str) -> Option<String> {
+    let name = field.strip_prefix('$').unwrap_or(field);
+    is_id(name).then(|| format!("${name}"))
+}
+
+// Each entry of a section, as a module field (e.g. `(global $n (mut i32) (i32.const 0))`).
+fn entry_lines(sections: &Sections, section: Section) -> Vec<String> {
+    (sections.entries(section).iter())
+        .map(|fields| {
+            let field = |index: usize| {
+                fields
+                    .get(index)
+                    .map_or("", |field| field.trim())
+                    .to_string()
+            };
+            let parts: Vec<String> = match section {
+                Section::Imports => {
+                    let header = Header {
+                        fields: [String::new(), field(3), field(4), String::new()],
+                    };
+                    let func = (id(&field(2)).into_iter())
+                        .chain(header.text_lines())
+                        .fold("func".to_string(), |func, part| format!("{func} {part}"));
+                    vec![
+                        "import".to_string(),
+                        quote(&field(0)),
+                        quote(&field(1)),
+                        format!("({func})"),
+                    ]
+                }
+                Section::Table => vec!["table".into(), field(0), field(1), "funcref".into()],
+                Section::Memory => vec!["memory".into(), field(0), field(1)],
+                Section::Globals => {
+                    let ty = field(1);
+                    let (ty, value_ty) = match ty.strip_prefix("mut ") {
+                        Some(value_ty) => (format!("(mut {})", value_ty.trim()), value_ty.trim()),
+                        None => (ty.clone(), ty.as_str()),
+                    };
+                    let init = format!("({value_ty}.const {})", field(2));
+                    (["global".to_string()].into_iter())
+                        .chain(id(&field(0)))
+                        .chain([ty, init])
+                        .collect()
+                }
+                Section::Exports => {
+                    let index = match field(2).as_str() {
+                        "" => "0".to_string(),
+                        index => index.to_string(),
+                    };
+                    let target = format!("({} {index})", field(1));
+                    vec!["export".into(), quote(&field(0)), target]
+                }
+                Section::Elements => {
+                    vec!["elem".into(), format!("(i32.const {})", field(0)), field(1)]
+                }
+                Section::Data => {
+                    vec!["data".into(), format!("(i32.const {})", field(0)), field(1)]
+                }
+            };
+            let parts: Vec<String> = parts.into_iter().filter(|part| !part.is_empty()).collect();
+            format!("({})", parts.join(" "))
+        })
+        .collect()
+}
+
+fn function_text(out: &mut String, func: &FunctionText, parsed: &[ParsedLine]) {
+    match func.header.name() {
+        Ok(Some(name)) => writeln!(out, "{INDENT}(func ${name}"),
+        _ => writeln!(out, "{INDENT}(func"),
+    }
+    .expect("write to String");
+    let nesting = nesting(parsed.iter().map(|line| line.instruction()));
+    let is_export = |index: &usize| {
+        parsed[*index]
+            .declaration()
+            .is_some_and(|decl| decl.kind == DeclKind::Export)
+    };
+    let (exports, others): (Vec<usize>, Vec<usize>) = (0..func.lines.len()).partition(is_export);
+    let body = INDENT.repeat(2);
+    for index in &exports {
+        writeln!(out, "{body}{}", func.lines[*index].trim()).expect("write to String");
+    }
+    for line in func.header.text_lines() {
+        writeln!(out, "{body}{line}").expect("write to String");
+    }
+    for index in others {
+        let line = func.lines[index].trim();
+        if line.is_empty() {
+            out.push('\n');
+        } else {
+            let indent = INDENT.repeat(2 + nesting[index].depth);
+            writeln!(out, "{indent}{line}").expect("write to String");
+        }
+    }
+    writeln!(out, "{INDENT})").expect("write to String");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::header::HeaderField;
+
+    fn function(header: [&str; 4], text: &str) -> FunctionText {
+        FunctionText {
+            header: Header {
+                fields: header.map(str::to_string),
+            },
+            lines: text.lines().map(str::to_string).collect(),
+        }
+    }
+
+    fn module(funcs: Vec<FunctionText>) -> ModuleText {
+        ModuleText {
+            sections: Sections::default(),
+            funcs,
+        }
+    }
+
+    fn wat(text: &str) -> String {
+        module_text(&module(vec![function(Default::default(), text)]))
+    }
+
+    const IMPORTS: &str = r#"(module
+  (import "host" "print_i32" (func $print_i32 (param i32)))
+  (import "host" "print_i64" (func $print_i64 (param i64)))
+  (import "host" "print_f32" (func $print_f32 (param f32)))
+  (import "host" "print_f64" (func $print_f64 (param f64)))
+"#;
+
+    #[test]
+    fn whole_modules() {
+        assert_eq!(
+            wat(
+                "(param $n i32)\n(export \"f\")\n  block ;; out\n\nlocal.get $n\ncall $print_i32\nend"
+            ),
+            format!(
+                "{IMPORTS}  (func\n    (export \"f\")\n    (param $n i32)\n    block ;; out\n\n      local.get $n\n      call $print_i32\n    end\n  )\n)\n"
+            )
+        );
+
+        // with a memory, if an instruction uses one; lines that don't parse are kept
+        assert_eq!(
+            wat("i32.const 0\ni32.load\ndrop\ni32.frob"),
+            format!(
+                "{IMPORTS}  (memory 1)\n  (func\n    i32.const 0\n    i32.load\n    drop\n    i32.frob\n  )\n)\n"
+            )
+        );
+    }
+
+    #[test]
+    fn functions_with_headers() {
+        let mut header = Header::default();
+        header.set_field(HeaderField::Params, "$x i32");
+        assert_eq!(
+            module_text(&module(vec![
+                function(
+                    ["$twice", "$x i32", "i32", "$t i32"],
+                    "(export \"twice\")\nlocal.get $x\nlocal.get $x\ni32.add"
+                ),
+                FunctionText {
+                    header,
+                    lines: vec!["local.get $x".to_string(), "drop".to_string()]
+                },
+            ])),
+            format!(
+                "{IMPORTS}  (func $twice\n    (export \"twice\")\n    (param $x i32)\n    (result i32)\n    (local $t i32)\n    local.get $x\n    local.get $x\n    i32.add\n  )\n  (func\n    (param $x i32)\n    local.get $x\n    drop\n  )\n)\n"
+            )
+        );
+        assert_eq!(module_text(&module(Vec::new())), format!("{IMPORTS})\n"));
+    }
+
+    #[test]
+    fn sections() {
+        let mut sections = Sections::default();
+        sections.push(Section::Imports, &["env", "log", "log", "$x i32, f64", ""]);
+        sections.push(Section::Imports, &["env", "now", "", "", "i64"]);
+        sections.push(Section::Table, &["2"]);
+        sections.push(Section::Globals, &["$n", "mut i32", "-1"]);
+        sections.push(Section::Globals, &["two words", "f32", "1.5"]);
+        sections.push(Section::Exports, &["mem", "memory", ""]);
+        sections.push(Section::Exports, &["n", "global", "$n"]);
+        sections.push(Section::Elements, &["0", "$f 4"]);
+        sections.push(Section::Data, &["8", "\"hi\\00\""]);
+        let text = module_text(&ModuleText {
+            sections,
+            funcs: vec![function(["$f", "", "", ""], "nop")],
+        });
+        assert_eq!(
+            text,
+            format!(
+                "{IMPORTS}  (import \"env\" \"log\" (func $log (param $x i32) (param f64)))\n  (import \"env\" \"now\" (func (result i64)))\n  (table 2 funcref)\n  (memory 1)\n  (global $n (mut i32) (i32.const -1))\n  (global f32 (f32.const 1.5))\n  (export \"mem\" (memory 0))\n  (export \"n\" (global $n))\n  (elem (i32.const 0) $f 4)\n  (data (i32.const 8) \"hi\\00\")\n  (func $f\n    nop\n  )\n)\n"
+            )
+        );
+    }
+}
@@ -4,14 +4,49 @@
 // from modifying a DOM object belonging to another. This means that Components
 // cannot directly access the children or parents of a DOM node.
 
-use delegate::delegate;
+use anyhow::{Result, anyhow};
 use std::{
-    collections::HashMap,
-    ops::{Deref, DerefMut},
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    ops::{Deref, DerefMut, Range},
 };
 use wasm_bindgen::closure::Closure;
+use wasm_bindgen_futures::JsFuture;
 use web_sys::wasm_bindgen::JsCast;
 
+// Checks for `try_audit`: return the discrepancy (as an Err) if a condition fails, or
+// if two values differ.
+macro_rules! audit_assert {
+    ($cond:expr) => {
+        audit_assert!($cond, "{} failed", stringify!($cond))
+    };
+    ($cond:expr, $($arg:tt)+) => {
+        if !$cond {
+            return Err(format!($($arg)+));
+        }
+    };
+}
+
+macro_rules! audit_eq {
+    ($left:expr, $right:expr) => {
+        match (&$left, &$right) {
+            (left, right) => {
+                if left != right {
+                    return Err(format!(
+                        "{} != {} ({:?} vs {:?})",
+                        stringify!($left),
+                        stringify!($right),
+                        left,
+                        right
+                    ));
+                }
+            }
+        }
+    };
+}
+
+pub(crate) use {audit_assert, audit_eq};
+
 // Traits that give "raw" access to an underlying node or element,
 // only usable from the web_support module.
 struct _Private();
@@ -63,6 +98,7 @@ impl<T: AsRef<web_sys::Node>> DerefMut for AutoRemove<T> {
 impl<T: AsRef<web_sys::Node>> Drop for AutoRemove<T> {
     fn drop(&mut self) {
         if let Some(parent) = self.0.as_ref().parent_node() {
+            count_dom_op(DomOp::RemoveChild);
             parent.remove_child(self.0.as_ref()).expect("remove_child");
         }
     }
@@ -85,22 +121,98 @@ impl WithNode for TextHandle {
 }
 
 impl TextHandle {
-    delegate! {
-    to self.0 {
-        pub fn data(&self) -> String;
-    pub fn set_data(&self, value: &str);
-    #[unwrap] // no return value anyway
-    pub fn append_data(&self, data: &str);
-    #[unwrap] // no return value anyway
-    pub fn insert_data(&self, offset: u32, data: &str);
+    pub fn data(&self) -> String {
+        self.0.data()
     }
+
+    pub fn set_data(&self, value: &str) {
+        count_dom_op(DomOp::SetData);
+        self.0.set_data(value);
+    }
+
+    pub fn append_data(&self, data: &str) {
+        count_dom_op(DomOp::AppendData);
+        self.0.append_data(data).unwrap(); // no return value anyway
+    }
+
+    pub fn insert_data(&self, offset: u32, data: &str) {
+        count_dom_op(DomOp::InsertData);
+        self.0.insert_data(offset, data).unwrap(); // no return value anyway
+    }
+
+    pub fn delete_data(&self, offset: u32, count: u32) {
+        count_dom_op(DomOp::DeleteData);
+        self.0.delete_data(offset, count).unwrap(); // no return value anyway
     }
 }
 
-// Event handlers on an element (currently only `beforeinput` is represented).
-#[derive(Default)]
-struct Handlers {
-    beforeinput: Option<Closure<dyn Fn(web_sys::InputEvent)>>,
+// Event handlers on an element. For each kind of event, this generates a field of
+// `Handlers` (keeping the closure alive), a setter on ElementHandle, and an audit
+// that the DOM element's handler is the one we installed.
+macro_rules! event_handlers {
+    ($($name:ident: $event:ty => $setter:ident, $getter:ident;)*) => {
+        #[derive(Default)]
+        struct Handlers {
+            $($name: Option<Closure<dyn Fn($event)>>,)*
+        }
+
+        impl<T: AnyElement> ElementHandle<T> {
+            $(
+                pub fn $setter<F: Fn($event) + 'static>(&mut self, handler: F) {
+                    let closure = Closure::new(handler);
+                    count_dom_op(DomOp::SetEventHandler);
+                    self.elem
+                        .element()
+                        .$setter(Some(closure.as_ref().unchecked_ref()));
+                    self.event_handlers.$name = Some(closure);
+                }
+            )*
+
+            fn audit_event_handlers(&self) -> Result<(), String> {
+                $(
+                    audit_event_handler(
+                        stringify!($name),
+                        self.event_handlers.$name.as_ref().map(|c| c.as_ref()),
+                        self.elem.element().$getter(),
+                    )?;
+                )*
+                Ok(())
+            }
+        }
+    };
+}
+
+event_handlers! {
+    beforeinput: web_sys::InputEvent => set_onbeforeinput, onbeforeinput;
+    scroll: web_sys::Event => set_onscroll, onscroll;
+    keydown: web_sys::KeyboardEvent => set_onkeydown, onkeydown;
+    click: web_sys::MouseEvent => set_onclick, onclick;
+    mousedown: web_sys::MouseEvent => set_onmousedown, onmousedown;
+    mouseover: web_sys::MouseEvent => set_onmouseover, onmouseover;
+    mouseleave: web_sys::MouseEvent => set_onmouseleave, onmouseleave;
+    input: web_sys::Event => set_oninput, oninput;
+    change: web_sys::Event => set_onchange, onchange;
+    dragover: web_sys::DragEvent => set_ondragover, ondragover;
+    drop: web_sys::DragEvent => set_ondrop, ondrop;
+}
+
+fn audit_event_handler(
+    name: &str,
+    expect: Option<&wasm_bindgen::JsValue>,
+    actual: Option<js_sys::Function>,
+) -> Result<(), String> {
+    match (expect, actual) {
+        (Some(expect), Some(actual)) => {
+            audit_assert!(
+                actual == *expect.unchecked_ref(),
+                "wrong {name} event handler"
+            )
+        }
+        (Some(_), None) => return Err(format!("missing {name} event handler")),
+        (None, Some(_)) => return Err(format!("unexpected {name} event handler")),
+        (None, None) => (),
+    }
+    Ok(())
 }
 
 // Wrapper for a DOM Element, allowing access to and modification of its attributes
@@ -133,13 +245,46 @@ impl<T: AnyElement> ElementHandle<T> {
     }
 
     pub fn append_node(&self, child: &impl WithNode) {
+        count_dom_op(DomOp::AppendChild);
         child.with_node(
             |node| self.elem.element().append_with_node_1(node).unwrap(), // no return value anyway
             TOKEN,
         )
     }
 
+    pub fn insert_node(&self, index: usize, child: &impl WithNode) {
+        let reference = self
+            .elem
+            .element()
+            .child_nodes()
+            .item(index.try_into().expect("index -> u32"));
+        count_dom_op(DomOp::InsertBefore);
+        child.with_node(
+            |node| {
+                self.elem
+                    .element()
+                    .insert_before(node, reference.as_ref())
+                    .expect("insert_before");
+            },
+            TOKEN,
+        )
+    }
+
+    pub fn remove_node(&self, child: &impl WithNode) {
+        count_dom_op(DomOp::RemoveChild);
+        child.with_node(
+            |node| {
+                self.elem
+                    .element()
+                    .remove_child(node)
+                    .expect("remove_child");
+            },
+            TOKEN,
+        )
+    }
+
     pub fn attach_node(&self, child: &impl WithNode) {
+        count_dom_op(DomOp::ReplaceChildren);
         child.with_node(
             |node| self.elem.element().replace_children_with_node_1(node),
             TOKEN,
@@ -147,52 +292,158 @@ impl<T: AnyElement> ElementHandle<T> {
     }
 
     pub fn attach_nodes(&self, children: ArrayHandle) {
+        count_dom_op(DomOp::ReplaceChildren);
         self.elem.element().replace_children_with_node(&children.0);
     }
 
     pub fn set_attribute(&mut self, name: &str, value: &str) {
         self.attributes.insert(name.to_string(), value.to_string());
+        count_dom_op(DomOp::SetAttribute);
         self.elem.element().set_attribute(name, value).unwrap();
     }
 
-    pub fn audit(&self) {
+    pub fn remove_attribute(&mut self, name: &str) {
+        self.attributes.remove(name);
+        count_dom_op(DomOp::RemoveAttribute);
+        self.elem.element().remove_attribute(name).unwrap();
+    }
+
+    pub fn try_audit(&self) -> Result<(), String> {
         for (key, value) in &self.attributes {
-            if let Some(dom_value) = self.elem.element().get_attribute(key) {
-                assert_eq!(dom_value, *value);
-            } else {
-                panic!("missing {key} (expected value {value})");
+            match self.elem.element().get_attribute(key) {
+                Some(dom_value) => audit_eq!(dom_value, *value),
+                None => return Err(format!("missing {key} (expected value {value})")),
             }
         }
 
         for dom_key in self.elem.element().get_attribute_names() {
-            assert!(self.attributes.contains_key(&dom_key.as_string().unwrap()));
+            let dom_key = dom_key.as_string().unwrap();
+            audit_assert!(
+                self.attributes.contains_key(&dom_key),
+                "unexpected attribute {dom_key}"
+            );
         }
 
-        match (
-            &self.event_handlers.beforeinput,
-            self.elem.element().onbeforeinput(),
-        ) {
-            (Some(expect), Some(actual)) => assert_eq!(actual, *expect.as_ref().unchecked_ref()),
-            (Some(_), None) => panic!("missing event handler"),
-            (None, Some(_)) => panic!("unexpected event handler"),
-            (None, None) => (),
-        }
+        self.audit_event_handlers()
+    }
+
+    // Scroll position and visible height (in CSS pixels), for elements that scroll.
+    pub fn scroll_top(&self) -> f64 {
+        self.elem.element().scroll_top() as f64
+    }
+
+    pub fn client_height(&self) -> f64 {
+        self.elem.element().client_height() as f64
+    }
+
+    pub fn set_scroll_top(&mut self, top: f64) {
+        count_dom_op(DomOp::SetScrollTop);
+        self.elem.element().set_scroll_top(top as i32);
     }
 
     pub fn get_child_node_list(&self) -> NodeListHandle {
         NodeListHandle(self.elem.element().child_nodes())
     }
+}
+
+// The text in an input element (which the user can change at any time, so it isn't
+// audited).
+impl ElementHandle<web_sys::HtmlInputElement> {
+    pub fn value(&self) -> String {
+        self.elem.value()
+    }
+
+    pub fn set_value(&mut self, value: &str) {
+        count_dom_op(DomOp::SetValue);
+        self.elem.set_value(value);
+    }
+
+    // The files chosen in a file input.
+    pub fn files(&self) -> Vec<FileHandle> {
+        file_list(self.elem.files())
+    }
+}
+
+// Likewise the text in a textarea, where offsets (as in the DOM) count UTF-16 code
+// units.
+impl ElementHandle<web_sys::HtmlTextAreaElement> {
+    pub fn value(&self) -> String {
+        self.elem.value()
+    }
 
-    pub fn set_onbeforeinput<F: Fn(web_sys::InputEvent) + 'static>(&mut self, handler: F) {
-        self.event_handlers.beforeinput = Some(Closure::new(handler));
-        self.elem.element().set_onbeforeinput(Some(
-            self.event_handlers
-                .beforeinput
-                .as_ref()
-                .unwrap()
-                .as_ref()
-                .unchecked_ref(),
-        ));
+    pub fn set_value(&mut self, value: &str) {
+        count_dom_op(DomOp::SetValue);
+        self.elem.set_value(value);
+    }
+
+    pub fn selection_start(&self) -> usize {
+        self.elem.selection_start().ok().flatten().unwrap_or(0) as usize
+    }
+
+    // Select some of the text, and focus the textarea to show it.
+    pub fn select_range(&mut self, range: Range<usize>) {
+        count_dom_op(DomOp::SetSelection);
+        let _ = (self.elem).set_selection_range(range.start as u32, range.end as u32);
+        let _ = self.elem.focus();
+    }
+}
+
+// Wrapper for a File the user chose or dropped, which can be read (asynchronously).
+pub struct FileHandle(web_sys::File);
+
+impl FileHandle {
+    pub fn name(&self) -> String {
+        self.0.name()
+    }
+
+    pub async fn bytes(&self) -> Result<Vec<u8>> {
+        let buffer = JsFuture::from(self.0.array_buffer())
+            .await
+            .map_err(|error| anyhow!("can't read {}: {error:?}", self.name()))?;
+        Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+    }
+}
+
+fn file_list(list: Option<web_sys::FileList>) -> Vec<FileHandle> {
+    let Some(list) = list else {
+        return Vec::new();
+    };
+    (0..list.length())
+        .filter_map(|index| list.item(index))
+        .map(FileHandle)
+        .collect()
+}
+
+// Whether something being dragged includes files, and the files that were dropped.
+pub fn drags_files(ev: &web_sys::DragEvent) -> bool {
+    ev.data_transfer()
+        .is_some_and(|transfer| transfer.types().includes(&"Files".into(), 0))
+}
+
+pub fn dropped_files(ev: &web_sys::DragEvent) -> Vec<FileHandle> {
+    file_list(ev.data_transfer().and_then(|transfer| transfer.files()))
+}
+
+// Wrapper for an object URL of a Blob holding some bytes (to download them). The URL
+// is revoked when this is dropped.
+pub struct BlobUrl(String);
+
+impl BlobUrl {
+    pub fn new(bytes: &[u8], mime_type: &str) -> Result<Self> {
+        let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+        let options = web_sys::BlobPropertyBag::new();
+        options.set_type(mime_type);
+        let blob = web_sys::Blob::new_with_u8_array_sequence_and_options(&parts, &options)
+            .map_err(|error| anyhow!("can't make a Blob: {error:?}"))?;
+        let url = web_sys::Url::create_object_url_with_blob(&blob)
+            .map_err(|error| anyhow!("can't make a URL for a Blob: {error:?}"))?;
+        Ok(Self(url))
+    }
+}
+
+impl Drop for BlobUrl {
+    fn drop(&mut self) {
+        let _ = web_sys::Url::revoke_object_url(&self.0);
     }
 }
 
@@ -222,6 +473,7 @@ impl<BodyType: ElementComponent<web_sys::HtmlBodyElement>> Default for DocumentH
     }
 }
 
+#[derive(Clone)]
 pub struct ElementFactory(web_sys::Document);
 
 impl<BodyType: ElementComponent<web_sys::HtmlBodyElement>> DocumentHandle<BodyType> {
@@ -234,6 +486,7 @@ impl<BodyType: ElementComponent<web_sys::HtmlBodyElement>> DocumentHandle<BodyTy
     }
 
     pub fn set_body(&mut self, body: BodyType) {
+        count_dom_op(DomOp::SetBody);
         body.with_element(|elem| self.document.set_body(Some(elem)), TOKEN);
         self.body = Some(body);
     }
@@ -280,9 +533,30 @@ impl ElementFactory {
         ElementHandle::new(self.create_element("br"))
     }
 
+    pub fn input(&self) -> ElementHandle<web_sys::HtmlInputElement> {
+        ElementHandle::new(self.create_element("input"))
+    }
+
+    pub fn button(&self) -> ElementHandle<web_sys::HtmlButtonElement> {
+        ElementHandle::new(self.create_element("button"))
+    }
+
+    pub fn textarea(&self) -> ElementHandle<web_sys::HtmlTextAreaElement> {
+        ElementHandle::new(self.create_element("textarea"))
+    }
+
     pub fn body(&self) -> ElementHandle<web_sys::HtmlBodyElement> {
         ElementHandle::new(self.create_element("body"))
     }
+
+    // Download the contents of a BlobUrl as `filename`, by clicking a link (which is
+    // never attached to the document).
+    pub fn download(&self, url: &BlobUrl, filename: &str) {
+        let link: web_sys::HtmlAnchorElement = self.create_element("a");
+        link.set_href(&url.0);
+        link.set_download(filename);
+        link.click();
+    }
 }
 
 // Wrapper for a DOM NodeList, allowing audit that each entry matches an expected node.
@@ -293,21 +567,131 @@ impl NodeListHandle {
         self.0.length() as usize
     }
 
-    pub fn audit_node(&self, index: usize, child: &impl WithNode) {
+    pub fn audit_node(&self, index: usize, child: &impl WithNode) -> Result<(), String> {
+        let mut same = false;
         child.with_node(
             |node| {
-                if let Some(actual) = self.0.item(index.try_into().expect("index -> u32"))
-                    && actual.is_same_node(Some(node))
-                {
-                    return;
+                let actual = self.0.item(index.try_into().expect("index -> u32"));
+                same = actual.is_some_and(|actual| actual.is_same_node(Some(node)));
+            },
+            TOKEN,
+        );
+        match same {
+            true => Ok(()),
+            false => Err(format!(
+                "node {} mismatch (#{}/{})",
+                index,
+                index + 1,
+                self.length()
+            )),
+        }
+    }
+}
+
+// Wrapper for the document's Selection. Components can ask whether the anchor or focus
+// lies in one of their own nodes (and at what offset), and can place a collapsed caret
+// inside one of their own nodes, but cannot otherwise reach the selected nodes.
+pub struct SelectionHandle(web_sys::Selection);
+
+// One end (anchor or focus) of a Selection.
+pub struct SelectionPoint {
+    node: Option<web_sys::Node>,
+    offset: usize,
+}
+
+impl SelectionHandle {
+    pub fn get() -> Option<Self> {
+        web_sys::window()?.get_selection().ok()?.map(Self)
+    }
+
+    pub fn anchor(&self) -> SelectionPoint {
+        SelectionPoint {
+            node: self.0.anchor_node(),
+            offset: self.0.anchor_offset() as usize,
+        }
+    }
+
+    pub fn focus(&self) -> SelectionPoint {
+        SelectionPoint {
+            node: self.0.focus_node(),
+            offset: self.0.focus_offset() as usize,
+        }
+    }
+
+    // Where the selection's focus is in the viewport: the left and bottom edges of the
+    // caret. A caret between elements has no box of its own, so it gets the left and
+    // bottom of the element it is in.
+    pub fn caret_position(&self) -> Option<(f64, f64)> {
+        if self.0.range_count() == 0 {
+            return None;
+        }
+        // (a copy, since collapsing the selection's own range would move the selection)
+        let range = self.0.get_range_at(0).ok()?.clone_range();
+        range.collapse_with_to_start(false);
+        let rect = range.get_bounding_client_rect();
+        if rect.height() > 0.0 {
+            return Some((rect.left(), rect.bottom()));
+        }
+        let node = self.0.focus_node()?;
+        let element = match node.dyn_into::<web_sys::Element>() {
+            Ok(element) => element,
+            Err(node) => node.parent_element()?,
+        };
+        let rect = element.get_bounding_client_rect();
+        Some((rect.left(), rect.bottom()))
+    }
+
+    pub fn set_caret(&self, target: &impl WithNode, offset: usize) {
+        target.with_node(
+            |node| {
+                self.0
+                    .collapse_with_offset(Some(node), offset.try_into().expect("offset -> u32"))
+                    .expect("collapse")
+            },
+            TOKEN,
+        )
+    }
+}
+
+impl SelectionPoint {
+    // The offset of this point, if it lies in `target`'s own node. (For a Text node
+    // this is a UTF-16 offset; for an Element it is an index into its child nodes.)
+    pub fn offset_in(&self, target: &impl WithNode) -> Option<usize> {
+        let mut ret = None;
+        target.with_node(
+            |node| {
+                if node.is_same_node(self.node.as_ref()) {
+                    ret = Some(self.offset);
                 }
-                panic!("node {} mismatch (#{}/{})", index, index + 1, self.length())
             },
             TOKEN,
         );
+        ret
     }
 }
 
+// Whether an event happened on `target`'s own node (e.g. the element under the mouse),
+// so that a component can tell which of its children an event it hears is for.
+pub fn is_event_target(ev: &web_sys::Event, target: &impl WithNode) -> bool {
+    let mut ret = false;
+    target.with_node(
+        |node| {
+            let event_node = ev.target().and_then(|t| t.dyn_into::<web_sys::Node>().ok());
+            ret = node.is_same_node(event_node.as_ref());
+        },
+        TOKEN,
+    );
+    ret
+}
+
+// Audit that a Component's node is (intentionally) not attached to any parent.
+pub fn audit_detached(child: &impl WithNode) -> Result<(), String> {
+    let mut detached = false;
+    child.with_node(|node| detached = node.parent_node().is_none(), TOKEN);
+    audit_assert!(detached, "detached node has a parent");
+    Ok(())
+}
+
 // Wrapper for a DOM Array, allowing modification of its entries.
 pub struct ArrayHandle(js_sys::Array);
 
@@ -334,11 +718,127 @@ impl ArrayHandle {
 }
 
 // A trait for a safe "Component", allowing wrapped access to its root Node and audit
-// that the DOM subtree matches the Component's expectations.
+// that the DOM subtree matches the Component's expectations. `try_audit` reports the
+// first discrepancy (so that a property test can shrink the case that caused it, since
+// panics can't be caught on wasm32), and `audit` panics with it.
 pub trait Component: WithNode {
-    fn audit(&self);
+    fn try_audit(&self) -> Result<(), String>;
+
+    fn audit(&self) {
+        if let Err(e) = self.try_audit() {
+            panic!("audit failed: {e}");
+        }
+    }
 }
 
 // ElementComponent is a trait for a "Component" that is also an HTML Element (e.g. not Text).
 pub trait ElementComponent<T: AnyElement>: Component + WithElement<T> {}
 impl<T: AnyElement, U: Component + WithElement<T>> ElementComponent<T> for U {}
+
+// DOM mutation counters. Every mutation issued through one of the handles above is
+// counted by kind, and also by the innermost active "component scope" (if any), so
+// that tests and benchmarks can check how much DOM work an operation really does.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DomOp {
+    AppendChild,
+    InsertBefore,
+    RemoveChild,
+    ReplaceChildren,
+    SetAttribute,
+    RemoveAttribute,
+    SetEventHandler,
+    SetValue,
+    SetData,
+    AppendData,
+    InsertData,
+    DeleteData,
+    SetBody,
+    SetScrollTop,
+    SetSelection,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct DomOpCounts {
+    by_op: BTreeMap<DomOp, usize>,
+    by_component: BTreeMap<(&'static str, DomOp), usize>,
+}
+
+impl DomOpCounts {
+    pub fn total(&self) -> usize {
+        self.by_op.values().sum()
+    }
+
+    pub fn get(&self, op: DomOp) -> usize {
+        self.by_op.get(&op).copied().unwrap_or_default()
+    }
+
+    pub fn component_total(&self, component: &str) -> usize {
+        self.by_component
+            .iter()
+            .filter(|((name, _), _)| *name == component)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    pub fn component_get(&self, component: &str, op: DomOp) -> usize {
+        self.by_component
+            .iter()
+            .find(|((name, kind), _)| *name == component && *kind == op)
+            .map_or(0, |(_, count)| *count)
+    }
+}
+
+#[derive(Default)]
+struct DomOpStats {
+    counts: DomOpCounts,
+    scopes: Vec<&'static str>,
+    tracing: bool,
+}
+
+thread_local! {
+    static DOM_OP_STATS: RefCell<DomOpStats> = RefCell::default();
+}
+
+fn count_dom_op(op: DomOp) {
+    DOM_OP_STATS.with_borrow_mut(|stats| {
+        *stats.counts.by_op.entry(op).or_default() += 1;
+        let scope = stats.scopes.last().copied();
+        if let Some(component) = scope {
+            *stats
+                .counts
+                .by_component
+                .entry((component, op))
+                .or_default() += 1;
+        }
+        if stats.tracing {
+            web_sys::console::debug_1(&format!("DOM {op:?} [{}]", scope.unwrap_or("-")).into());
+        }
+    })
+}
+
+pub fn dom_op_counts() -> DomOpCounts {
+    DOM_OP_STATS.with_borrow(|stats| stats.counts.clone())
+}
+
+pub fn reset_dom_op_counts() {
+    DOM_OP_STATS.with_borrow_mut(|stats| stats.counts = DomOpCounts::default())
+}
+
+// Log every DOM mutation to the console (as it happens).
+pub fn set_dom_op_tracing(enabled: bool) {
+    DOM_OP_STATS.with_borrow_mut(|stats| stats.tracing = enabled)
+}
+
+// While a DomOpScope is alive, mutations are also attributed to its component.
+pub struct DomOpScope(());
+
+pub fn dom_op_scope(component: &'static str) -> DomOpScope {
+    DOM_OP_STATS.with_borrow_mut(|stats| stats.scopes.push(component));
+    DomOpScope(())
+}
+
+impl Drop for DomOpScope {
+    fn drop(&mut self) {
+        DOM_OP_STATS.with_borrow_mut(|stats| stats.scopes.pop());
+    }
+}
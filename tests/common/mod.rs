@@ -0,0 +1,39 @@
+// Helpers and programs shared by the browser tests (each uses only some of them). The
+// browser tests run with `wasm-pack test --headless --firefox`.
+
+#![allow(dead_code)]
+
+use trunkfun::{
+    dom_struct::DomStruct,
+    module_editor::ModuleEditor,
+    web_support::{DocumentHandle, ElementFactory},
+};
+
+type Body = DomStruct<(), web_sys::HtmlBodyElement>;
+
+// A factory for elements of the test page's document.
+pub fn factory() -> ElementFactory {
+    DocumentHandle::<Body>::default().element_factory()
+}
+
+// A module editor whose only function has `text`.
+pub fn module(factory: &ElementFactory, text: &str) -> ModuleEditor {
+    let module = ModuleEditor::new(factory);
+    module.editor(0).unwrap().set_text(text).unwrap();
+    module
+}
+
+// A module editor whose only function has `text`, and a panel of it (made with `new`,
+// e.g. RunPanel::new).
+pub fn panel<T>(
+    text: &str,
+    new: impl FnOnce(&ElementFactory, &ModuleEditor) -> T,
+) -> (ModuleEditor, T) {
+    let factory = factory();
+    let module = module(&factory, text);
+    let panel = new(&factory, &module);
+    (module, panel)
+}
+
+// Sums the numbers from $n down to 1, in a loop.
+pub const SUM: &str = "(param $n i32)\n(result i32)\n(export \"sum\")\n(local $total i32)\nloop $top\nlocal.get $total\nlocal.get $n\ni32.add\nlocal.set $total\nlocal.get $n\ni32.const 1\ni32.sub\nlocal.tee $n\nbr_if $top\nend\nlocal.get $total";
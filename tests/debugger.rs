@@ -0,0 +1,166 @@
+// Debugging a module's only function: breakpoints in the gutter that follow their lines,
+// stepping, and the panel's views of the machine.
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::{
+    debugger::{Debugger, View},
+    editor::Position,
+    web_support::Component,
+};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[wasm_bindgen_test]
+fn breakpoints_follow_their_lines() {
+    let (module, _) = common::panel("nop\ndrop\ni32.const 1", Debugger::new);
+    let mut editor = module.editor(0).unwrap();
+    editor.toggle_breakpoint(1).unwrap();
+    editor.toggle_breakpoint(2).unwrap();
+    editor.toggle_breakpoint(2).unwrap();
+    assert_eq!(editor.breakpoints(), vec![1]);
+
+    // lines inserted above, and a line split at its start
+    editor.insert(Position::new(0, 0), "nop\n").unwrap();
+    assert_eq!(editor.breakpoints(), vec![2]);
+    editor.split_line(Position::new(2, 0)).unwrap();
+    assert_eq!(editor.breakpoints(), vec![3]);
+    editor.audit();
+
+    // undoing joins the lines again; a split elsewhere keeps the first line's
+    editor.undo().unwrap();
+    assert_eq!(editor.breakpoints(), vec![2]);
+    editor.split_line(Position::new(2, 2)).unwrap();
+    assert_eq!(editor.breakpoints(), vec![2]);
+
+    // deleting the line deletes its breakpoint
+    editor
+        .delete(Position::new(1, 3), Position::new(3, 2))
+        .unwrap();
+    assert!(editor.breakpoints().is_empty());
+    editor.audit();
+}
+
+#[wasm_bindgen_test]
+fn stepping_shows_the_machine() {
+    let (module, mut debugger) = common::panel(common::SUM, Debugger::new);
+    let mut editor = module.editor(0).unwrap();
+    debugger.set_args("3");
+    debugger.start();
+    debugger.audit();
+    assert_eq!(debugger.status(), "paused at line 5");
+    assert_eq!(editor.current_line(), Some(4));
+    assert_eq!(
+        debugger.view(View::Locals),
+        vec!["$n = 3 : i32", "$total = 0 : i32"]
+    );
+
+    for _ in 0..3 {
+        debugger.step();
+    }
+    assert_eq!(debugger.status(), "paused at line 8");
+    assert_eq!(editor.current_line(), Some(7));
+    assert_eq!(debugger.view(View::Stack), vec!["3 : i32", "0 : i32"]);
+    assert_eq!(debugger.view(View::Labels), vec!["loop $top (line 5)"]);
+    debugger.audit();
+
+    // Continue stops at the breakpoint, in the loop's next iteration
+    editor.toggle_breakpoint(6).unwrap();
+    debugger.resume();
+    assert_eq!(debugger.status(), "paused at line 7");
+    assert_eq!(
+        debugger.view(View::Locals),
+        vec!["$n = 2 : i32", "$total = 3 : i32"]
+    );
+    editor.toggle_breakpoint(6).unwrap();
+    debugger.step_over();
+    assert_eq!(debugger.view(View::Stack), vec!["2 : i32", "3 : i32"]);
+    debugger.resume();
+    assert_eq!(debugger.status(), "sum(3) returned 6 : i32");
+    assert_eq!(editor.current_line(), None);
+    assert!(debugger.view(View::Stack).is_empty());
+    debugger.audit();
+    editor.audit();
+}
+
+#[wasm_bindgen_test]
+fn traps_problems_and_edits() {
+    let (module, mut debugger) = common::panel(
+        "(export \"half\")\n(param i32)\nlocal.get 0\ncall $print_i32\ni32.const 7\nlocal.get 0\ni32.div_u\ndrop",
+        Debugger::new,
+    );
+    let mut editor = module.editor(0).unwrap();
+    debugger.set_args("0");
+    debugger.resume();
+    assert_eq!(debugger.status(), "paused at line 3");
+    debugger.resume();
+    assert_eq!(debugger.status(), "trap at line 7: integer divide by zero");
+    assert_eq!(editor.current_line(), Some(6));
+    assert_eq!(debugger.view(View::Printed), vec!["0"]);
+    debugger.audit();
+
+    // editing ends the session
+    debugger.start();
+    editor.insert(Position::new(7, 4), "\nnop").unwrap();
+    debugger.step();
+    assert_eq!(debugger.status(), "the program has changed: Start again");
+    assert_eq!(editor.current_line(), None);
+
+    debugger.set_export("whole");
+    debugger.start();
+    assert_eq!(debugger.status(), "no function is exported as \"whole\"");
+    debugger.set_export("");
+    debugger.set_args("");
+    debugger.start();
+    assert_eq!(debugger.status(), "expected 1 argument, got 0");
+    debugger.audit();
+    editor.audit();
+}
+
+#[wasm_bindgen_test]
+fn stepping_back_and_scrubbing() {
+    let (module, mut debugger) = common::panel(common::SUM, Debugger::new);
+    let editor = module.editor(0).unwrap();
+    debugger.set_args("3");
+    debugger.resume();
+    debugger.resume();
+    assert_eq!(debugger.status(), "sum(3) returned 6 : i32");
+    let (earliest, end, furthest) = debugger.timeline();
+    assert_eq!((earliest, end), (0, furthest));
+
+    // back through the function's final `end`, and the instruction before it
+    debugger.step_back();
+    assert_eq!(debugger.status(), "paused at the end of the function");
+    assert_eq!(debugger.view(View::Stack), vec!["6 : i32"]);
+    debugger.step_back();
+    assert_eq!(debugger.status(), "paused at line 16");
+    assert_eq!(editor.current_line(), Some(15));
+    assert!(debugger.view(View::Stack).is_empty());
+    assert_eq!(debugger.timeline(), (0, end - 2, end));
+    debugger.audit();
+
+    // the scrubber restores any step, forwards or back
+    debugger.seek(0);
+    assert_eq!(debugger.status(), "paused at line 5");
+    assert_eq!(
+        debugger.view(View::Locals),
+        vec!["$n = 3 : i32", "$total = 0 : i32"]
+    );
+    debugger.seek(8);
+    assert_eq!(debugger.status(), "paused at line 13");
+    assert_eq!(editor.current_line(), Some(12));
+    assert_eq!(debugger.view(View::Stack), vec!["2 : i32"]);
+    assert_eq!(
+        debugger.view(View::Locals),
+        vec!["$n = 3 : i32", "$total = 3 : i32"]
+    );
+    assert_eq!(debugger.view(View::Labels), vec!["loop $top (line 5)"]);
+    assert_eq!(debugger.timeline(), (0, 8, end));
+    debugger.audit();
+    debugger.seek(end);
+    assert_eq!(debugger.status(), "sum(3) returned 6 : i32");
+    debugger.audit();
+}
@@ -0,0 +1,118 @@
+// Checks that editor operations make a constant number of DOM mutations, however
+// long the document, and that only a window of its lines is attached.
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::{
+    editor::{Editor, Position},
+    web_support::{Component, DomOp, DomOpCounts, dom_op_counts, reset_dom_op_counts},
+};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+// Pairs of lines that push and pop a value, so that each line's stack annotation
+// depends only on the lines near it.
+fn editor_with_lines(count: usize) -> Editor {
+    let factory = common::factory();
+    let mut editor = Editor::new(&factory);
+    editor
+        .paste(
+            Position::new(0, 0),
+            Position::new(0, 0),
+            &"i32.const 1\ndrop\n".repeat(count / 2),
+        )
+        .unwrap();
+    editor
+}
+
+fn count_ops(editor: &mut Editor, op: impl FnOnce(&mut Editor)) -> DomOpCounts {
+    reset_dom_op_counts();
+    op(editor);
+    dom_op_counts()
+}
+
+#[wasm_bindgen_test]
+fn typing_in_a_long_document_is_constant_work() {
+    let mut editor = editor_with_lines(6000);
+
+    // "i32.const 1" -> "i32.const 12": the number token is edited in place
+    let counts = count_ops(&mut editor, |e| {
+        e.insert(Position::new(5000, 11), "2").unwrap();
+    });
+    assert_eq!(counts.total(), 1);
+    assert_eq!(counts.get(DomOp::InsertData), 1);
+    assert_eq!(counts.component_get("editor", DomOp::InsertData), 1);
+
+    let counts = count_ops(&mut editor, |e| {
+        e.delete(Position::new(5000, 11), Position::new(5000, 12))
+            .unwrap();
+    });
+    assert_eq!(counts.total(), 1);
+    assert_eq!(counts.get(DomOp::DeleteData), 1);
+
+    // "i32.const" -> "i32x.const": the token is edited in place and reclassed, the
+    // line loses its stack annotation, and it and the `drop` after it get diagnostics
+    // (a badge, plus an overlay of two segments and of one segment)
+    let counts = count_ops(&mut editor, |e| {
+        e.insert(Position::new(5000, 3), "x").unwrap();
+    });
+    assert_eq!(counts.total(), 18);
+    assert_eq!(counts.get(DomOp::SetAttribute), 7);
+    assert_eq!(counts.get(DomOp::RemoveAttribute), 1);
+    assert_eq!(counts.get(DomOp::SetData), 3);
+}
+
+#[wasm_bindgen_test]
+fn line_operations_cost_the_same_in_short_and_long_documents() {
+    let mut short = editor_with_lines(10);
+    let mut long = editor_with_lines(6000);
+
+    let mut totals = Vec::new();
+    for (line, editor) in [(4, &mut short), (5000, &mut long)] {
+        let split = count_ops(editor, |e| {
+            e.split_line(Position::new(line, 3)).unwrap();
+        });
+        let join = count_ops(editor, |e| {
+            e.join_lines(line).unwrap();
+        });
+        let undo = count_ops(editor, |e| {
+            e.undo().unwrap();
+        });
+        assert_eq!(split.component_total("editor"), split.total());
+        // joining retokenizes the first line, removes the second, and updates the
+        // annotations and diagnostics of the lines around them
+        assert!(join.total() <= 40, "join made {} DOM ops", join.total());
+        assert!(join.get(DomOp::RemoveChild) <= 8);
+        totals.push((split.total(), join.total(), undo.total()));
+    }
+    assert_eq!(totals[0], totals[1]);
+}
+
+#[wasm_bindgen_test]
+fn long_edits_attach_only_a_window_of_lines() {
+    let mut editor = editor_with_lines(6000);
+    assert_eq!(editor.lines().len(), 6001);
+    assert!(editor.attached_lines().len() <= 100);
+
+    editor
+        .paste(
+            Position::new(10, 0),
+            Position::new(10, 0),
+            &"nop\n".repeat(6000),
+        )
+        .unwrap();
+    assert!(editor.attached_lines().len() <= 100);
+    editor.audit();
+
+    editor.set_text(&"nop\n".repeat(6000)).unwrap();
+    assert!(editor.attached_lines().len() <= 100);
+    editor.audit();
+
+    // the text an editor starts with is windowed too
+    let factory = common::factory();
+    let editor = Editor::with_text(&factory, &"nop\n".repeat(6000));
+    assert!(editor.attached_lines().len() <= 100);
+}
@@ -0,0 +1,137 @@
+// Tests for DomVirtualVec: only the window of children is attached, the rest are
+// intentionally detached (as are hidden children), and audit() checks both.
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::{
+    dom_text::DomText,
+    dom_virtual_vec::DomVirtualVec,
+    web_support::{Component, DomOp, dom_op_counts, reset_dom_op_counts},
+};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+type Vector = DomVirtualVec<DomText, web_sys::HtmlDivElement>;
+
+fn vector(len: usize) -> Vector {
+    let factory = common::factory();
+    let mut ret = Vector::new(factory.div(), &factory, 20.0);
+    for index in 0..len {
+        ret.push(DomText::new(&index.to_string()));
+    }
+    ret
+}
+
+fn texts(vector: &Vector) -> Vec<String> {
+    vector.iter().map(|text| text.get().to_string()).collect()
+}
+
+#[wasm_bindgen_test]
+fn window_moves_and_audits() {
+    let mut vec = vector(1000);
+    assert_eq!(vec.window(), 0..1000);
+    vec.audit();
+
+    vec.set_window(100..140);
+    assert_eq!(vec.window(), 100..140);
+    vec.audit();
+
+    // overlapping and disjoint moves
+    vec.set_window(120..160);
+    vec.audit();
+    vec.set_window(900..2000);
+    assert_eq!(vec.window(), 900..1000);
+    vec.audit();
+    vec.set_window(0..10);
+    vec.audit();
+}
+
+#[wasm_bindgen_test]
+fn edits_outside_the_window_only_resize_spacers() {
+    let mut vec = vector(1000);
+    vec.set_window(500..520);
+
+    reset_dom_op_counts();
+    vec.insert(10, DomText::new("early"));
+    vec.insert(900, DomText::new("late"));
+    vec.remove(20);
+    let counts = dom_op_counts();
+    assert_eq!(counts.get(DomOp::InsertBefore), 0);
+    assert_eq!(counts.get(DomOp::SetAttribute), 3);
+    assert_eq!(vec.window(), 500..520);
+    vec.audit();
+
+    vec.insert(510, DomText::new("visible"));
+    assert_eq!(vec.window(), 500..521);
+    vec.remove(505);
+    assert_eq!(vec.window(), 500..520);
+    vec.audit();
+
+    vec.ensure_attached(700, 5);
+    assert_eq!(vec.window(), 695..706);
+    vec.audit();
+
+    let mut expected: Vec<String> = (0..1000).map(|i| i.to_string()).collect();
+    expected.insert(10, "early".into());
+    expected.insert(900, "late".into());
+    expected.remove(20);
+    expected.insert(510, "visible".into());
+    expected.remove(505);
+    assert_eq!(texts(&vec), expected);
+}
+
+#[wasm_bindgen_test]
+fn hidden_children_stay_detached() {
+    let mut vec = vector(100);
+    vec.set_window(10..30);
+    vec.set_hidden(15..50);
+    assert_eq!(vec.hidden(), 15..50);
+    assert!(vec.is_attached(14));
+    assert!(!vec.is_attached(15));
+    vec.audit();
+
+    // the window can move across the hidden range
+    vec.set_window(40..60);
+    vec.audit();
+    vec.ensure_attached(45, 5);
+    assert_eq!(vec.window(), 40..60);
+    assert!(!vec.is_attached(45));
+
+    // children inserted inside the range are hidden, and removing shrinks it
+    vec.insert(20, DomText::new("inside"));
+    vec.insert(15, DomText::new("before"));
+    assert_eq!(vec.hidden(), 16..52);
+    vec.remove(30);
+    vec.remove(0);
+    assert_eq!(vec.hidden(), 15..50);
+    vec.audit();
+
+    // showing the children again attaches those in the window
+    vec.set_window(0..100);
+    vec.set_hidden(0..0);
+    assert_eq!(vec.hidden(), 0..0);
+    assert!(vec.is_attached(30));
+    vec.audit();
+    vec.set_hidden(95..200);
+    assert_eq!(vec.hidden(), 95..100);
+    vec.audit();
+}
+
+#[wasm_bindgen_test]
+fn removing_every_hidden_child_shows_later_insertions() {
+    let mut vec = vector(10);
+    vec.set_hidden(5..6);
+    vec.remove(5);
+    assert_eq!(vec.hidden(), 0..0);
+    vec.audit();
+
+    // (a child inserted where the hidden range was is visible)
+    vec.insert(3, DomText::new("new"));
+    assert_eq!(vec.hidden(), 0..0);
+    assert!(vec.is_attached(3));
+    assert!(vec.is_attached(5));
+    vec.audit();
+}
@@ -0,0 +1,29 @@
+// Assembling the editor's lines into a binary module, and reporting the lines that
+// prevent it.
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::editor::{Editor, Position};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[wasm_bindgen_test]
+fn lines_assemble_to_wasm() {
+    let mut editor = Editor::with_text(
+        &common::factory(),
+        "(result i32)\n(export \"two\")\ni32.const 2",
+    );
+    let wasm = editor.to_wasm().unwrap();
+    assert_eq!(&wasm[..8], b"\0asm\x01\0\0\0");
+    assert_eq!(wasm[wasm.len() - 3..], [0x41, 0x02, 0x0b][..]);
+
+    // an error names the line it's on
+    editor.insert(Position::new(2, 11), "x").unwrap();
+    let errors = editor.to_wasm().unwrap_err();
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].line, 2);
+    assert_eq!(editor.lines()[errors[0].line], "i32.const 2x");
+}
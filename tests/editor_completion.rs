@@ -0,0 +1,115 @@
+// The editor's completion popup: mnemonics ranked by the operand stack, the locals,
+// labels and functions an instruction can refer to, choosing and accepting a
+// completion, and hiding the popup.
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::{
+    editor::{Editor, Position},
+    header::HeaderField,
+    module_editor::ModuleEditor,
+    web_support::Component,
+};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn shown(editor: &Editor) -> Vec<String> {
+    let popup = editor.completion_popup();
+    popup.items().into_iter().map(|item| item.text).collect()
+}
+
+#[wasm_bindgen_test]
+fn mnemonics_that_fit_the_stack_come_first() {
+    let mut editor = Editor::with_text(&common::factory(), "i32.const 1\ni64.const 2\ni32.");
+    let items = editor.completions(Position::new(2, 4)).unwrap().unwrap();
+    assert_eq!(items.range, 0..4);
+    let position = |text: &str| (items.items.iter()).position(|item| item.text == text);
+    assert!(position("i32.wrap_i64") < position("i32.add"));
+    assert_eq!(
+        items.items[position("i32.wrap_i64").unwrap()].detail,
+        "[i64] -> [i32]"
+    );
+
+    // accepting replaces the word
+    editor.insert(Position::new(2, 4), "ad").unwrap();
+    assert!(editor.show_completions(Position::new(2, 6)).unwrap());
+    assert_eq!(shown(&editor), ["i32.add"]);
+    editor.audit();
+    editor.completion_popup().audit();
+    assert_eq!(
+        editor.accept_completion().unwrap(),
+        Some(Position::new(2, 7))
+    );
+    assert_eq!(editor.lines()[2], "i32.add");
+    assert!(!editor.completion_popup().is_shown());
+    assert_eq!(editor.accept_completion().unwrap(), None);
+    editor.audit();
+    editor.completion_popup().audit();
+}
+
+#[wasm_bindgen_test]
+fn locals_and_labels() {
+    let mut editor = Editor::with_text(&common::factory(), "(param $n i32)\n(local f64)\nloc");
+    assert!(editor.show_completions(Position::new(2, 3)).unwrap());
+    assert_eq!(shown(&editor), ["local.get", "local.set", "local.tee"]);
+
+    // choosing a mnemonic goes on to the locals it can refer to
+    let mut popup = editor.completion_popup();
+    popup.select_next(false);
+    assert_eq!(popup.selected().unwrap().text, "local.tee");
+    popup.select_next(true);
+    assert_eq!(
+        editor.accept_completion().unwrap(),
+        Some(Position::new(2, 10))
+    );
+    assert_eq!(shown(&editor), ["$n", "1"]);
+    assert_eq!(popup.selected().unwrap().detail, "i32");
+    editor.accept_completion().unwrap();
+    assert_eq!(editor.lines()[2], "local.get $n");
+    editor.audit();
+    popup.audit();
+
+    // labels, innermost first, then the function's
+    editor
+        .set_text("block $outer\nloop\nbr \nend\nend")
+        .unwrap();
+    assert!(editor.show_completions(Position::new(2, 3)).unwrap());
+    assert_eq!(shown(&editor), ["0", "$outer", "2"]);
+    editor.hide_completions();
+    assert!(!popup.is_shown());
+    assert!(popup.items().is_empty());
+
+    // there is nothing to complete in a comment
+    editor.set_text(";; br ").unwrap();
+    assert!(!editor.show_completions(Position::new(0, 6)).unwrap());
+    editor.audit();
+    popup.audit();
+}
+
+#[wasm_bindgen_test]
+fn functions_of_the_module() {
+    let factory = common::factory();
+    let mut module = ModuleEditor::new(&factory);
+    let index = module.add_function();
+    module
+        .set_header_field(index, HeaderField::Name, "$square")
+        .unwrap();
+    module
+        .set_header_field(index, HeaderField::Params, "i32")
+        .unwrap();
+    module
+        .set_header_field(index, HeaderField::Results, "i32")
+        .unwrap();
+
+    let mut editor = module.editor(0).unwrap();
+    editor.set_text("i32.const 3\ncall $s").unwrap();
+    assert!(editor.show_completions(Position::new(1, 7)).unwrap());
+    let items = editor.completion_popup().items();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0].text, "$square");
+    assert_eq!(items[0].detail, "[i32] -> [i32]");
+    module.audit();
+}
@@ -0,0 +1,135 @@
+// Diagnostics come from the editor's analysis of each line, or are added from
+// outside; either way they follow the text as it is edited.
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::{
+    diagnostic::{Diagnostic, Severity},
+    editor::{Editor, Position},
+    web_support::Component,
+};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn summary(editor: &Editor) -> Vec<(usize, Severity, std::ops::Range<usize>)> {
+    (editor.diagnostics().into_iter())
+        .map(|(line, diagnostic)| (line, diagnostic.severity, diagnostic.range))
+        .collect()
+}
+
+#[wasm_bindgen_test]
+fn analysis_problems_become_diagnostics() {
+    let mut editor = Editor::with_text(
+        &common::factory(),
+        "i32.const 1\n  i64.const 2 ;; oops\ni32.add\ndrop",
+    );
+    editor.audit();
+    assert_eq!(summary(&editor), vec![(2, Severity::Error, 0..7)]);
+
+    // a parse error underlines just the bad token
+    editor.insert(Position::new(1, 5), "x").unwrap();
+    editor.audit();
+    assert_eq!(
+        summary(&editor),
+        vec![(1, Severity::Error, 2..12), (2, Severity::Error, 0..7)]
+    );
+
+    // code after a `return` is unreachable
+    editor.undo().unwrap();
+    editor.insert(Position::new(0, 0), "return\n").unwrap();
+    editor.audit();
+    let warnings: Vec<usize> = (editor.diagnostics().into_iter())
+        .filter(|(_, diagnostic)| diagnostic.severity == Severity::Warning)
+        .map(|(line, _)| line)
+        .collect();
+    assert_eq!(warnings, vec![1, 2, 3, 4]);
+}
+
+#[wasm_bindgen_test]
+fn added_diagnostics_move_with_the_text() {
+    let mut editor = Editor::with_text(&common::factory(), "nop\nlocal.get $x\nnop");
+    editor
+        .add_diagnostic(1, Diagnostic::new(Severity::Note, 10..12, "declared below"))
+        .unwrap();
+    editor.audit();
+    let notes = |editor: &Editor| -> Vec<(usize, std::ops::Range<usize>)> {
+        (editor.diagnostics().into_iter())
+            .filter(|(_, diagnostic)| diagnostic.severity == Severity::Note)
+            .map(|(line, diagnostic)| (line, diagnostic.range))
+            .collect()
+    };
+    assert_eq!(notes(&editor), vec![(1, 10..12)]);
+
+    // typing before it on its line, and adding a line above it, move it
+    editor.insert(Position::new(1, 0), "  ").unwrap();
+    editor.insert(Position::new(0, 0), "nop\n").unwrap();
+    editor.audit();
+    assert_eq!(notes(&editor), vec![(2, 12..14)]);
+
+    // deleting its text removes it
+    editor
+        .delete(Position::new(2, 11), Position::new(2, 14))
+        .unwrap();
+    editor.audit();
+    assert_eq!(notes(&editor), vec![]);
+
+    assert!(
+        editor
+            .add_diagnostic(0, Diagnostic::new(Severity::Note, 2..9, "too long"))
+            .is_err()
+    );
+    editor
+        .add_diagnostic(0, Diagnostic::new(Severity::Note, 0..3, "again"))
+        .unwrap();
+    editor.clear_diagnostics();
+    editor.audit();
+    assert_eq!(notes(&editor), vec![]);
+}
+
+#[wasm_bindgen_test]
+fn added_diagnostics_move_with_split_and_joined_lines() {
+    let mut editor = Editor::with_text(&common::factory(), "nop\nlocal.get $x\nnop");
+    editor
+        .add_diagnostic(1, Diagnostic::new(Severity::Note, 10..12, "declared below"))
+        .unwrap();
+    let notes = |editor: &Editor| -> Vec<(usize, std::ops::Range<usize>)> {
+        (editor.diagnostics().into_iter())
+            .filter(|(_, diagnostic)| diagnostic.severity == Severity::Note)
+            .map(|(line, diagnostic)| (line, diagnostic.range))
+            .collect()
+    };
+
+    // splitting its line before it moves it to the new line, and joining the lines
+    // moves it back
+    editor.split_line(Position::new(1, 9)).unwrap();
+    editor.audit();
+    assert_eq!(notes(&editor), vec![(2, 1..3)]);
+    editor.join_lines(1).unwrap();
+    editor.audit();
+    assert_eq!(notes(&editor), vec![(1, 10..12)]);
+
+    // splitting its line after it leaves it
+    editor.split_line(Position::new(1, 12)).unwrap();
+    editor.audit();
+    assert_eq!(notes(&editor), vec![(1, 10..12)]);
+
+    // joining its line to the line above moves it past that line's text
+    editor.join_lines(0).unwrap();
+    editor.audit();
+    assert_eq!(notes(&editor), vec![(0, 13..15)]);
+
+    // inserting lines before it on its line moves it to the last of them
+    editor
+        .insert(Position::new(0, 3), "\ni32.const 1\nlocal.set $y\n")
+        .unwrap();
+    editor.audit();
+    assert_eq!(notes(&editor), vec![(3, 10..12)]);
+
+    // splitting it removes it
+    editor.split_line(Position::new(3, 11)).unwrap();
+    editor.audit();
+    assert_eq!(notes(&editor), vec![]);
+}
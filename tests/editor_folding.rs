@@ -0,0 +1,104 @@
+// Folding the editor's lines: a block folds up to its `end`, or the whole body after
+// its first line, while the model keeps every line. Edits away from the folded lines
+// keep them folded; edits that touch them, or the caret or debugger moving into them,
+// unfold them.
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::{
+    editor::{Editor, Position},
+    module_editor::ModuleEditor,
+    web_support::Component,
+};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const TEXT: &str = "i32.const 1\nblock $a\nnop\nloop\nbr 0\nend\nend $a\ndrop";
+
+#[wasm_bindgen_test]
+fn blocks_fold_to_their_end() {
+    let mut editor = Editor::with_text(&common::factory(), TEXT);
+    assert_eq!(editor.folded(), None);
+
+    editor.fold(1).unwrap();
+    assert_eq!(editor.folded(), Some(2..7));
+    assert_eq!(editor.lines().len(), 8);
+    editor.audit();
+
+    // one range is folded at a time
+    editor.fold(3).unwrap();
+    assert_eq!(editor.folded(), Some(4..6));
+    editor.audit();
+    editor.toggle_fold(3).unwrap();
+    assert_eq!(editor.folded(), None);
+    editor.toggle_fold(1).unwrap();
+    assert_eq!(editor.folded(), Some(2..7));
+    editor.audit();
+
+    // only a line that opens a block can be folded
+    assert!(editor.fold(0).is_err());
+    assert!(editor.fold(2).is_err());
+    assert!(editor.fold(20).is_err());
+    assert_eq!(editor.folded(), Some(2..7));
+
+    editor.fold_body().unwrap();
+    assert_eq!(editor.folded(), Some(1..8));
+    editor.audit();
+    editor.unfold();
+    assert_eq!(editor.folded(), None);
+    editor.audit();
+}
+
+#[wasm_bindgen_test]
+fn edits_move_or_unfold_the_folded_lines() {
+    let mut editor = Editor::with_text(&common::factory(), TEXT);
+    editor.fold(1).unwrap();
+
+    // edits before and after the folded lines move them or leave them be
+    editor.insert(Position::new(0, 0), "nop\n").unwrap();
+    assert_eq!(editor.folded(), Some(3..8));
+    editor.insert(Position::new(8, 4), " ;; x").unwrap();
+    assert_eq!(editor.folded(), Some(3..8));
+    editor.audit();
+
+    // an edit on the line standing in for them unfolds them
+    editor.insert(Position::new(2, 0), " ").unwrap();
+    assert_eq!(editor.folded(), None);
+    editor.audit();
+    editor.undo().unwrap();
+    assert_eq!(editor.lines()[2], "block $a");
+
+    // as does the debugger stopping in them
+    editor.fold(2).unwrap();
+    editor.set_current_line(Some(5)).unwrap();
+    assert_eq!(editor.folded(), None);
+    assert_eq!(editor.current_line(), Some(5));
+    editor.audit();
+
+    // or replacing all of the text
+    editor.fold(2).unwrap();
+    editor.set_text("block\nend").unwrap();
+    assert_eq!(editor.folded(), None);
+    editor.fold(0).unwrap();
+    assert_eq!(editor.folded(), Some(1..2));
+    editor.audit();
+}
+
+#[wasm_bindgen_test]
+fn functions_fold_their_bodies() {
+    let factory = common::factory();
+    let mut module = ModuleEditor::new(&factory);
+    let mut editor = module.editor(0).unwrap();
+    editor.set_text("block\nnop\nend").unwrap();
+
+    module.toggle_fold(0).unwrap();
+    assert_eq!(editor.folded(), Some(1..3));
+    module.audit();
+    module.toggle_fold(0).unwrap();
+    assert_eq!(editor.folded(), None);
+    module.audit();
+    assert!(module.toggle_fold(5).is_err());
+}
@@ -0,0 +1,74 @@
+// Caret movement and deletion in the editor work on whole grapheme clusters.
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::{
+    editor::{Editor, Position},
+    web_support::Component,
+};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+// "x", a ZWJ sequence with a skin-tone modifier (4 chars), "e" with two combining
+// marks (3 chars), and "y".
+const LINE: &str = "x👩🏽\u{200d}💻e\u{301}\u{323}y";
+
+fn editor() -> Editor {
+    Editor::with_text(&common::factory(), &format!("{LINE}\nsecond"))
+}
+
+#[wasm_bindgen_test]
+fn caret_moves_by_cluster() {
+    let editor = editor();
+    let mut pos = Position::new(0, 0);
+    let mut stops = vec![pos];
+    while pos != editor.caret_right(pos) {
+        pos = editor.caret_right(pos);
+        stops.push(pos);
+    }
+    let expected: Vec<_> = [0, 1, 5, 8, 9]
+        .map(|ch| Position::new(0, ch))
+        .into_iter()
+        .chain((0..=6).map(|ch| Position::new(1, ch)))
+        .collect();
+    assert_eq!(stops, expected);
+
+    let mut back = vec![pos];
+    while pos != editor.caret_left(pos) {
+        pos = editor.caret_left(pos);
+        back.push(pos);
+    }
+    back.reverse();
+    assert_eq!(back, expected);
+
+    // from inside a cluster, the caret moves to its edges
+    assert_eq!(editor.caret_left(Position::new(0, 3)), Position::new(0, 1));
+    assert_eq!(editor.caret_right(Position::new(0, 3)), Position::new(0, 5));
+}
+
+#[wasm_bindgen_test]
+fn deletion_removes_whole_clusters() {
+    let mut editor = editor();
+
+    // backspace over the combining marks, then the ZWJ sequence
+    let pos = editor.delete_backward(Position::new(0, 8)).unwrap();
+    assert_eq!(pos, Position::new(0, 5));
+    assert_eq!(editor.lines()[0], "x👩🏽\u{200d}💻y");
+    let pos = editor.delete_backward(pos).unwrap();
+    assert_eq!(pos, Position::new(0, 1));
+    assert_eq!(editor.lines()[0], "xy");
+
+    // forward-delete at the end of a line joins the next one
+    editor.delete_forward(Position::new(0, 2)).unwrap();
+    assert_eq!(editor.lines(), vec!["xysecond"]);
+
+    // undo restores the clusters intact
+    editor.undo().unwrap();
+    editor.undo().unwrap();
+    editor.undo().unwrap();
+    assert_eq!(editor.lines(), vec![LINE, "second"]);
+    editor.audit();
+}
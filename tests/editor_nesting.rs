@@ -0,0 +1,53 @@
+// The editor indents lines by their block nesting as the text is edited, and flags
+// lines that break the block structure.
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::{
+    editor::{Editor, Position},
+    web_support::Component,
+};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn depths(editor: &Editor) -> Vec<usize> {
+    editor.nesting().iter().map(|line| line.depth).collect()
+}
+
+fn errors(editor: &Editor) -> Vec<usize> {
+    (editor.nesting().iter().enumerate())
+        .filter(|(_, line)| line.error.is_some())
+        .map(|(index, _)| index)
+        .collect()
+}
+
+#[wasm_bindgen_test]
+fn typing_blocks_indents_and_end_dedents() {
+    let mut editor = Editor::with_text(&common::factory(), "nop\nnop");
+    assert_eq!(depths(&editor), vec![0, 0]);
+
+    // opening a block indents the following lines (and flags the open block)
+    editor.insert(Position::new(0, 0), "block\n").unwrap();
+    editor.audit();
+    assert_eq!(depths(&editor), vec![0, 1, 1]);
+    assert_eq!(errors(&editor), vec![0]);
+
+    // typing `end` dedents it, and closes the block
+    let pos = editor.insert(Position::new(2, 3), "\nen").unwrap();
+    assert_eq!(depths(&editor), vec![0, 1, 1, 1]);
+    editor.insert(pos, "d").unwrap();
+    editor.audit();
+    assert_eq!(depths(&editor), vec![0, 1, 1, 0]);
+    assert_eq!(errors(&editor), Vec::<usize>::new());
+
+    // a stray `else` is flagged, and fixing it clears the flag
+    editor.insert(Position::new(1, 0), "else\n").unwrap();
+    editor.audit();
+    assert_eq!(errors(&editor), vec![1]);
+    editor.undo().unwrap();
+    editor.audit();
+    assert_eq!(errors(&editor), Vec::<usize>::new());
+}
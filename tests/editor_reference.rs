@@ -0,0 +1,105 @@
+// Hovering over an instruction's mnemonic shows its reference (signature, summary and
+// traps) in a tooltip, which other tokens hide.
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::{
+    dom_tooltip::DomTooltip,
+    editor::{Editor, Position},
+    module_editor::ModuleEditor,
+    web_support::Component,
+};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+#[wasm_bindgen_test]
+fn references_of_mnemonics() {
+    let editor = Editor::with_text(
+        &common::factory(),
+        "  i32.const 7\ni32.div_u ;; i32.add\nfoo",
+    );
+    assert_eq!(
+        editor.reference(Position::new(1, 4)).unwrap().unwrap(),
+        [
+            "i32.div_u : [i32 i32] -> [i32]",
+            "Divide the first operand by the second (as unsigned), rounding down.",
+            "Traps if the divisor is 0 (integer divide by zero).",
+        ]
+    );
+    assert_eq!(
+        editor.reference(Position::new(0, 2)).unwrap().unwrap()[0],
+        "i32.const : [] -> [i32]"
+    );
+    // not in the indentation, an immediate, a comment, or an unknown word
+    for pos in [(0, 0), (0, 12), (1, 16), (2, 1)] {
+        assert_eq!(editor.reference(Position::new(pos.0, pos.1)).unwrap(), None);
+    }
+    assert!(editor.reference(Position::new(3, 0)).is_err());
+}
+
+#[wasm_bindgen_test]
+fn the_tooltip_shows_and_hides() {
+    let mut editor = Editor::with_text(&common::factory(), "local.get 0\ndrop");
+    let tooltip: DomTooltip = editor.tooltip();
+    assert!(!tooltip.is_shown());
+
+    assert!(
+        editor
+            .show_reference(Position::new(0, 3), 10.0, 30.0)
+            .unwrap()
+    );
+    assert_eq!(
+        tooltip.lines(),
+        ["local.get : [] -> [t]", "Push the value of a local."]
+    );
+    assert_eq!(tooltip.position(), Some((10.0, 30.0)));
+    tooltip.audit();
+
+    assert!(
+        editor
+            .show_reference(Position::new(1, 0), 10.0, 62.0)
+            .unwrap()
+    );
+    assert_eq!(tooltip.lines()[0], "drop : [t] -> []");
+    assert_eq!(tooltip.position(), Some((10.0, 62.0)));
+    tooltip.audit();
+
+    // the immediate has no reference
+    assert!(
+        !editor
+            .show_reference(Position::new(0, 10), 0.0, 0.0)
+            .unwrap()
+    );
+    assert!(tooltip.lines().is_empty());
+    tooltip.audit();
+
+    editor
+        .show_reference(Position::new(1, 2), 0.0, 0.0)
+        .unwrap();
+    editor.hide_reference();
+    assert!(!tooltip.is_shown());
+    tooltip.audit();
+}
+
+#[wasm_bindgen_test]
+fn the_module_editor_holds_the_tooltips() {
+    let factory = common::factory();
+    let module = ModuleEditor::new(&factory);
+    let mut editor = module.editor(0).unwrap();
+    editor.set_text("unreachable").unwrap();
+    editor
+        .show_reference(Position::new(0, 0), 0.0, 0.0)
+        .unwrap();
+    assert_eq!(
+        editor.tooltip().lines(),
+        [
+            "unreachable : [t1*] -> [t2*]",
+            "Trap at once.",
+            "Traps always (unreachable).",
+        ]
+    );
+    module.audit();
+}
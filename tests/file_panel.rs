@@ -0,0 +1,121 @@
+// Opening a binary .wasm module from the File panel: its functions replace the
+// module editor's, with names restored and blocks indented. And the names downloads
+// get, and what stops one.
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::{
+    editor::Position,
+    file_panel::{FilePanel, Format},
+    header::HeaderField,
+    web_support::Component,
+};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+// A "name" section naming function 4's locals and its first label, as a toolchain
+// would write it.
+fn with_names(mut wasm: Vec<u8>) -> Vec<u8> {
+    let locals = b"\x02\x0d\x01\x04\x02\x00\x01n\x01\x05total";
+    let labels = b"\x03\x08\x01\x04\x01\x00\x03top";
+    wasm.push(0x00);
+    wasm.push((5 + locals.len() + labels.len()) as u8);
+    wasm.extend_from_slice(b"\x04name");
+    wasm.extend_from_slice(locals);
+    wasm.extend_from_slice(labels);
+    wasm
+}
+
+#[wasm_bindgen_test]
+fn opening_a_module() {
+    let factory = common::factory();
+    let wasm = common::module(&factory, common::SUM).to_wasm().unwrap();
+    let target = common::module(&factory, "nop\ndrop");
+    target.editor(0).unwrap().toggle_breakpoint(1).unwrap();
+    let mut panel = FilePanel::new(&factory, &target);
+
+    // without a name section, locals and labels are numbered
+    panel.open_wasm("sum.wasm", &wasm);
+    assert_eq!(panel.status(), "opened sum.wasm");
+    assert_eq!(target.len(), 1);
+    assert_eq!(target.header(0).unwrap().fields, ["", "i32", "i32", "i32"]);
+    let editor = target.editor(0).unwrap();
+    assert_eq!(editor.lines()[..2], ["(export \"sum\")", "loop"]);
+    assert!(target.breakpoints().is_empty());
+    panel.audit();
+    target.audit();
+
+    // with one, the names come back, and the lines are as written
+    panel.open_wasm("sum.wasm", &with_names(wasm.clone()));
+    let header = target.header(0).unwrap();
+    assert_eq!(header.fields, ["", "$n i32", "i32", "$total i32"]);
+    let editor = target.editor(0).unwrap();
+    let lines: Vec<&str> = common::SUM.lines().collect();
+    assert_eq!(editor.lines()[0], lines[2]);
+    assert_eq!(editor.lines()[1..], lines[4..]);
+    let depths: Vec<usize> = editor.nesting().iter().map(|line| line.depth).collect();
+    assert_eq!(depths, [0, 0, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0]);
+    assert_eq!(target.to_wasm().unwrap(), wasm);
+    target.audit();
+
+    // every function of the module is opened
+    let mut source = common::module(
+        &factory,
+        "(export \"main\")\ni32.const 4\ncall $square\ndrop",
+    );
+    let index = source.add_function();
+    source
+        .set_header_field(index, HeaderField::Name, "square")
+        .unwrap();
+    source
+        .set_header_field(index, HeaderField::Params, "$x i32")
+        .unwrap();
+    source
+        .set_header_field(index, HeaderField::Results, "i32")
+        .unwrap();
+    source
+        .editor(index)
+        .unwrap()
+        .insert(Position::new(0, 0), "local.get $x\nlocal.get $x\ni32.mul")
+        .unwrap();
+    panel.open_wasm("two.wasm", &source.to_wasm().unwrap());
+    assert_eq!(target.len(), 2);
+    assert_eq!(target.editor(0).unwrap().lines()[2], "call 5");
+    assert_eq!(target.to_wasm().unwrap(), source.to_wasm().unwrap());
+    target.audit();
+
+    // a file that isn't a module leaves the module editor alone
+    panel.open_wasm("notes.txt", b"hello");
+    assert_eq!(
+        panel.status(),
+        "can't open notes.txt: not a WebAssembly module"
+    );
+    assert_eq!(target.len(), 2);
+    panel.audit();
+    target.audit();
+}
+
+#[wasm_bindgen_test]
+fn download_names_and_problems() {
+    let factory = common::factory();
+    let module = common::module(&factory, "i32.const 1\ni64.const 2\ni32.add");
+    let mut panel = FilePanel::new(&factory, &module);
+    assert_eq!(panel.filename(Format::Wat), "module.wat");
+    panel.set_filename(" sum.wat ");
+    assert_eq!(panel.filename(Format::Wasm), "sum.wasm");
+    panel.set_filename("lab 3.wasm.wasm");
+    assert_eq!(panel.filename(Format::Wat), "lab 3.wasm.wat");
+    panel.set_filename("");
+    assert_eq!(panel.filename(Format::Wasm), "module.wasm");
+
+    // a binary needs the function to assemble
+    panel.download(Format::Wasm);
+    assert_eq!(
+        panel.status(),
+        "can't download module.wasm: line 3: i32.add expects [i32 i32] but the stack has [i32 i64]"
+    );
+    panel.audit();
+}
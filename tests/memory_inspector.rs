@@ -0,0 +1,126 @@
+// The debugger's memory inspector: the rows of memory, the bytes the last step changed,
+// the typed values at an address, and following loads and stores (scrolling the grid
+// to them).
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::{
+    debugger::Debugger, module_editor::ModuleEditor, sections::Section, web_support::Component,
+};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+// Stores 0x41424344 at 20 and -1 (as an i64) at 0x8000, then loads from 20.
+const STORES: &str = "(export \"main\")\n(result i32)\ni32.const 20\ni32.const 0x41424344\ni32.store\ni32.const 0x8000\ni64.const -1\ni64.store\ni32.const 20\ni32.load";
+
+fn steps(debugger: &mut Debugger, count: usize) {
+    for _ in 0..count {
+        debugger.step();
+    }
+}
+
+#[wasm_bindgen_test]
+fn changed_bytes_are_highlighted() {
+    let (_module, mut debugger) = common::panel(STORES, Debugger::new);
+    assert_eq!(debugger.memory_rows(), (0..0, Vec::new()));
+    debugger.start();
+    let (rows, texts) = debugger.memory_rows();
+    assert_eq!(rows.start, 0);
+    assert_eq!(texts.len(), rows.len());
+    assert_eq!(
+        texts[0],
+        "00000000  00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00  ................"
+    );
+    assert!(debugger.memory_values().is_empty());
+    debugger.audit();
+
+    steps(&mut debugger, 3);
+    assert_eq!(debugger.changed_bytes(), [20, 21, 22, 23]);
+    assert_eq!(
+        debugger.memory_rows().1[1],
+        "00000010  00 00 00 00 44 43 42 41 00 00 00 00 00 00 00 00  ....DCBA........"
+    );
+    assert_eq!(debugger.memory_address(), "0x14");
+    assert_eq!(
+        debugger.memory_values()[..2],
+        ["i32: 1094861636", "i64: 1094861636"]
+    );
+    debugger.audit();
+
+    // a step that doesn't write leaves nothing highlighted
+    debugger.step();
+    assert!(debugger.changed_bytes().is_empty());
+    debugger.step_back();
+    assert_eq!(debugger.changed_bytes(), [20, 21, 22, 23]);
+    debugger.audit();
+}
+
+#[wasm_bindgen_test]
+fn the_address_follows_loads_and_stores() {
+    let (_module, mut debugger) = common::panel(STORES, Debugger::new);
+    steps(&mut debugger, 6);
+    assert_eq!(debugger.memory_address(), "0x8000");
+    assert_eq!(
+        debugger.changed_bytes(),
+        (0x8000..0x8008).collect::<Vec<_>>()
+    );
+    let (rows, texts) = debugger.memory_rows();
+    assert!(rows.contains(&0x800));
+    assert!(rows.len() < 100);
+    assert_eq!(
+        texts[0x800 - rows.start],
+        "00008000  ff ff ff ff ff ff ff ff 00 00 00 00 00 00 00 00  ................"
+    );
+    assert_eq!(debugger.memory_values()[1], "i64: -1");
+    debugger.audit();
+
+    // typing an address stops following
+    debugger.set_memory_address("16");
+    assert_eq!(debugger.memory_rows().0.start, 0);
+    assert_eq!(
+        debugger.memory_values()[..2],
+        ["i32: 0", "i64: 4702394920265056256"]
+    );
+    steps(&mut debugger, 2);
+    assert_eq!(debugger.memory_address(), "16");
+    assert!(debugger.changed_bytes().is_empty());
+    debugger.audit();
+
+    // following again goes back to the latest load
+    debugger.set_memory_follow(true);
+    assert_eq!(debugger.memory_address(), "0x14");
+    assert_eq!(debugger.memory_values()[0], "i32: 1094861636");
+    debugger.audit();
+}
+
+#[wasm_bindgen_test]
+fn a_memory_too_big_to_scroll_row_by_row() {
+    // 1024 pages have 4M rows, too many for the grid to give each its height
+    let factory = common::factory();
+    let mut module = ModuleEditor::new(&factory);
+    let index = module.add_entry(Section::Memory).unwrap();
+    module
+        .set_entry_field(Section::Memory, index, 0, "1024")
+        .unwrap();
+    let text = "(export \"main\")\ni32.const 0x3fffff8\ni64.const -1\ni64.store";
+    module.editor(0).unwrap().set_text(text).unwrap();
+    let mut debugger = Debugger::new(&factory, &module);
+    steps(&mut debugger, 3);
+    assert_eq!(debugger.memory_address(), "0x3fffff8");
+    let (rows, texts) = debugger.memory_rows();
+    assert!(rows.contains(&0x3fffff));
+    assert!(rows.len() < 100);
+    assert_eq!(
+        texts[0x3fffff - rows.start],
+        "03fffff0  00 00 00 00 00 00 00 00 ff ff ff ff ff ff ff ff  ................"
+    );
+    debugger.audit();
+
+    // and back to the start
+    debugger.set_memory_address("0");
+    assert_eq!(debugger.memory_rows().0.start, 0);
+    debugger.audit();
+}
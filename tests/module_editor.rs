@@ -0,0 +1,189 @@
+// Editing a module of several functions: adding, removing and moving them, their
+// headers and the problems with them, calls between them, and debugging across them.
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::{
+    debugger::{Debugger, View},
+    editor::Position,
+    header::HeaderField,
+    module_editor::ModuleEditor,
+    web_support::{Component, ElementFactory},
+};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+// A module whose `main` calls a second function, `$square`.
+fn square(factory: &ElementFactory) -> ModuleEditor {
+    let mut module = ModuleEditor::new(factory);
+    let mut editor = module.editor(0).unwrap();
+    editor
+        .set_text("(export \"main\")\ni32.const 4\ncall $square")
+        .unwrap();
+    module
+        .set_header_field(0, HeaderField::Results, "i32")
+        .unwrap();
+
+    let index = module.add_function();
+    module
+        .set_header_field(index, HeaderField::Name, "$square")
+        .unwrap();
+    module
+        .set_header_field(index, HeaderField::Params, "$x i32")
+        .unwrap();
+    module
+        .set_header_field(index, HeaderField::Results, "i32")
+        .unwrap();
+    let mut editor = module.editor(index).unwrap();
+    editor
+        .insert(Position::new(0, 0), "local.get $x\nlocal.get $x\ni32.mul")
+        .unwrap();
+    module
+}
+
+#[wasm_bindgen_test]
+fn adding_removing_and_moving() {
+    let mut module = ModuleEditor::new(&common::factory());
+    assert_eq!(module.len(), 1);
+    assert_eq!(module.editor(0).unwrap().lines(), ["Hello, world."]);
+
+    // new functions are empty, and go at the end
+    assert_eq!(module.add_function(), 1);
+    assert_eq!(module.add_function(), 2);
+    assert_eq!(module.editor(1).unwrap().lines(), [""]);
+    module.editor(2).unwrap().set_text("nop").unwrap();
+    module.set_header_field(2, HeaderField::Name, "c").unwrap();
+    module.audit();
+
+    module.move_function(2, 0).unwrap();
+    assert_eq!(module.header(0).unwrap().field(HeaderField::Name), "c");
+    assert_eq!(module.editor(0).unwrap().lines(), ["nop"]);
+    assert_eq!(module.editor(1).unwrap().lines(), ["Hello, world."]);
+    assert!(module.move_function(0, 3).is_err());
+
+    module.remove_function(1).unwrap();
+    assert_eq!(module.len(), 2);
+    assert!(module.remove_function(2).is_err());
+    assert!(module.editor(2).is_none());
+    module.audit();
+}
+
+#[wasm_bindgen_test]
+fn header_problems() {
+    let mut module = square(&common::factory());
+    assert!(module.header_errors(0).is_empty());
+    assert!(module.header_errors(1).is_empty());
+
+    module
+        .set_header_field(0, HeaderField::Name, "square")
+        .unwrap();
+    module
+        .set_header_field(1, HeaderField::Locals, "$y i32 i64")
+        .unwrap();
+    assert!(module.header_errors(0).is_empty());
+    assert_eq!(
+        module.header_errors(1),
+        [
+            (
+                HeaderField::Name,
+                "another function is named $square".to_string()
+            ),
+            (
+                HeaderField::Locals,
+                "a named local has only one type".to_string()
+            )
+        ]
+    );
+    let errors: Vec<String> = (module.assemble().unwrap_err().iter())
+        .map(|error| error.to_string())
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            "the header of $square: name: another function is named $square",
+            "the header of $square: locals: a named local has only one type"
+        ]
+    );
+    module.audit();
+
+    // fixing the fields fixes the module
+    module.set_header_field(0, HeaderField::Name, "").unwrap();
+    module.set_header_field(1, HeaderField::Locals, "").unwrap();
+    assert!(module.header_errors(1).is_empty());
+    assert!(module.assemble().is_ok());
+    module.audit();
+}
+
+#[wasm_bindgen_test]
+fn calls_between_functions() {
+    let mut module = square(&common::factory());
+    assert!(module.assemble().is_ok());
+    let checks = module.editor(0).unwrap().checks();
+    assert!(checks.iter().all(|check| check.error.is_none()));
+
+    // a change to the callee's header is checked at the call
+    module
+        .set_header_field(1, HeaderField::Params, "$x i64")
+        .unwrap();
+    let checks = module.editor(0).unwrap().checks();
+    assert!(checks[2].error.is_some());
+    let errors = module.assemble().unwrap_err();
+    assert_eq!(errors[0].func, Some(0));
+    assert_eq!(errors[0].line, Some(2));
+    assert!(errors[0].to_string().starts_with("line 3 of function 4: "));
+    module
+        .set_header_field(1, HeaderField::Params, "$x i32")
+        .unwrap();
+
+    // and so is a call to a function that isn't there
+    module.editor(1).unwrap().set_text("call $cube").unwrap();
+    let errors = module.assemble().unwrap_err();
+    assert_eq!(errors[0].func, Some(1));
+    assert!(errors[0].to_string().starts_with("line 1 of $square: "));
+    module.audit();
+}
+
+#[wasm_bindgen_test]
+fn text_format() {
+    let module = square(&common::factory());
+    assert!(module.to_wat().ends_with(
+        "  (func\n    (export \"main\")\n    (result i32)\n    i32.const 4\n    call $square\n  )\n  (func $square\n    (param $x i32)\n    (result i32)\n    local.get $x\n    local.get $x\n    i32.mul\n  )\n)\n"
+    ));
+}
+
+#[wasm_bindgen_test]
+fn debugging_across_functions() {
+    let factory = common::factory();
+    let mut module = square(&factory);
+    let mut debugger = Debugger::new(&factory, &module);
+    debugger.set_export("main");
+    debugger.start();
+    assert_eq!(debugger.status(), "paused at line 2 of function 4");
+    assert_eq!(module.current_line(), Some((0, 1)));
+
+    // stepping into the call
+    debugger.step();
+    debugger.step();
+    assert_eq!(debugger.status(), "paused at line 1 of $square");
+    assert_eq!(module.current_line(), Some((1, 0)));
+    assert_eq!(debugger.view(View::Locals), ["$x = 4 : i32"]);
+    debugger.audit();
+    module.audit();
+
+    // a breakpoint in the second function
+    module.editor(1).unwrap().toggle_breakpoint(2).unwrap();
+    assert_eq!(module.breakpoints(), [(1, 2)]);
+    debugger.start();
+    debugger.resume();
+    assert_eq!(debugger.status(), "paused at line 3 of $square");
+    debugger.resume();
+    assert_eq!(debugger.status(), "main() returned 16 : i32");
+    assert_eq!(module.current_line(), None);
+    module.set_current_line(Some((1, 0))).unwrap();
+    assert!(module.set_current_line(Some((2, 0))).is_err());
+    debugger.audit();
+    module.audit();
+}
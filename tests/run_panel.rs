@@ -0,0 +1,79 @@
+// Running a module's only function from the Run panel: printed values, results, traps,
+// and problems found before the run.
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::{
+    editor::Position,
+    run_panel::{OutputKind, RunPanel},
+    web_support::Component,
+};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+fn lines(expected: &[(OutputKind, &str)]) -> Vec<(OutputKind, String)> {
+    (expected.iter())
+        .map(|(kind, text)| (*kind, text.to_string()))
+        .collect()
+}
+
+#[wasm_bindgen_test]
+async fn prints_and_results() {
+    let (_module, mut panel) = common::panel(
+        "(param $x i64)\n(result i64 f32)\n(export \"next\")\nlocal.get $x\ncall $print_i64\nf64.const 0.5\ncall $print_f64\nlocal.get $x\ni64.const 1\ni64.add\nf32.const 2.5",
+        RunPanel::new,
+    );
+    panel.set_args("-9007199254740993");
+    panel.run().await;
+    panel.audit();
+    assert_eq!(
+        panel.output(),
+        lines(&[
+            (OutputKind::Print, "-9007199254740993"),
+            (OutputKind::Print, "0.5"),
+            (
+                OutputKind::Result,
+                "next(-9007199254740993) returned -9007199254740992 : i64, 2.5 : f32"
+            ),
+        ])
+    );
+}
+
+#[wasm_bindgen_test]
+async fn traps_and_errors() {
+    let (module, mut panel) = common::panel("(export \"boom\")\nunreachable", RunPanel::new);
+    let mut editor = module.editor(0).unwrap();
+    panel.run().await;
+    let out = panel.output();
+    assert_eq!(out.len(), 1);
+    assert_eq!(out[0].0, OutputKind::Error);
+    assert!(out[0].1.starts_with("RuntimeError"), "{}", out[0].1);
+
+    // problems found before running, with the line they're on
+    panel.set_export("bang");
+    panel.run().await;
+    assert_eq!(
+        panel.output(),
+        lines(&[(OutputKind::Error, "no function is exported as \"bang\"")])
+    );
+    panel.set_export("");
+    panel.set_args("1");
+    panel.run().await;
+    assert_eq!(
+        panel.output(),
+        lines(&[(OutputKind::Error, "expected 0 arguments, got 1")])
+    );
+    editor.insert(Position::new(1, 0), "i32.add\n").unwrap();
+    panel.run().await;
+    panel.audit();
+    assert_eq!(
+        panel.output(),
+        lines(&[(
+            OutputKind::Error,
+            "line 2: i32.add expects [i32 i32] but the stack has []"
+        )])
+    );
+}
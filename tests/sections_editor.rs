@@ -0,0 +1,170 @@
+// The module editor's panels for imports, the table, the memory, globals, exports and
+// segments: adding and removing entries, the problems marked on their fields, and how
+// the functions and the assembled module see them.
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::{
+    disassemble::disassemble_wasm,
+    header::HeaderField,
+    module::{ExportKind, Limits},
+    module_editor::ModuleEditor,
+    sections::Section,
+    value::Value,
+    web_support::Component,
+};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+// Add an entry with the given fields.
+fn add(module: &mut ModuleEditor, section: Section, fields: &[&str]) -> usize {
+    let index = module.add_entry(section).unwrap();
+    for (field, text) in fields.iter().enumerate() {
+        module.set_entry_field(section, index, field, text).unwrap();
+    }
+    index
+}
+
+#[wasm_bindgen_test]
+fn adding_and_removing_entries() {
+    let mut module = ModuleEditor::new(&common::factory());
+    assert!(module.sections().is_empty());
+
+    // a module has only one table and one memory
+    assert_eq!(add(&mut module, Section::Table, &["2"]), 0);
+    assert!(module.add_entry(Section::Table).is_err());
+    assert_eq!(add(&mut module, Section::Globals, &["$a", "i32", "1"]), 0);
+    assert_eq!(add(&mut module, Section::Globals, &["$b", "i64", "2"]), 1);
+    assert_eq!(
+        module.sections().entries(Section::Globals)[1],
+        ["$b", "i64", "2"]
+    );
+    module.audit();
+
+    module.remove_entry(Section::Globals, 0).unwrap();
+    assert_eq!(module.sections().entries(Section::Globals).len(), 1);
+    assert!(module.remove_entry(Section::Globals, 1).is_err());
+    assert!(module.set_entry_field(Section::Globals, 0, 3, "").is_err());
+    module.remove_entry(Section::Table, 0).unwrap();
+    assert_eq!(add(&mut module, Section::Table, &["1"]), 0);
+    module.audit();
+}
+
+#[wasm_bindgen_test]
+fn problems_are_marked_inline() {
+    let mut module = ModuleEditor::new(&common::factory());
+
+    // an export naming a function that isn't there
+    add(&mut module, Section::Exports, &["run", "func", "$run"]);
+    let errors: Vec<String> = (module.section_errors().iter())
+        .map(|error| error.to_string())
+        .collect();
+    assert_eq!(errors, ["export 1, index: unknown function $run"]);
+    let errors: Vec<String> = (module.assemble().unwrap_err().iter())
+        .map(|error| error.to_string())
+        .collect();
+    assert_eq!(errors, ["export 1, index: unknown function $run"]);
+    assert_eq!(module.assemble().unwrap_err()[0].func, None);
+    module.audit();
+
+    // naming the function fixes it
+    module
+        .set_header_field(0, HeaderField::Name, "$run")
+        .unwrap();
+    assert!(module.section_errors().is_empty());
+    let assembled = module.assemble().unwrap();
+    let export = &assembled.exports[0];
+    assert_eq!(
+        (export.name.as_str(), export.kind, export.index),
+        ("run", ExportKind::Func, 4)
+    );
+    module.audit();
+
+    // a function can't take an import's name
+    add(
+        &mut module,
+        Section::Imports,
+        &["env", "run", "$run", "", ""],
+    );
+    assert_eq!(
+        module.header_errors(0),
+        [(
+            HeaderField::Name,
+            "another function is named $run".to_string()
+        )]
+    );
+    module.audit();
+}
+
+#[wasm_bindgen_test]
+fn functions_see_the_sections() {
+    let mut module = ModuleEditor::new(&common::factory());
+    add(
+        &mut module,
+        Section::Imports,
+        &["env", "log", "", "i32", ""],
+    );
+    add(&mut module, Section::Globals, &["$total", "mut i32", "5"]);
+    add(&mut module, Section::Memory, &["1", "1"]);
+    add(&mut module, Section::Data, &["0", "\"\\07\""]);
+    let mut editor = module.editor(0).unwrap();
+    let text =
+        "(export \"main\")\n(result i32)\nglobal.get $total\ni32.const 0\ni32.load8_u\ni32.add";
+    editor.set_text(text).unwrap();
+    let checks = editor.checks();
+    assert!(checks.iter().all(|check| check.error.is_none()));
+
+    // function 4 is the import, which takes an i32
+    editor
+        .set_text(&format!("{text}\ni64.const 1\ncall 4"))
+        .unwrap();
+    assert!(editor.checks()[7].error.is_some());
+    let errors = module.assemble().unwrap_err();
+    assert_eq!(errors[0].func, Some(0));
+    assert!(errors[0].to_string().starts_with("line 8: "));
+
+    editor.set_text(text).unwrap();
+    let assembled = module.assemble().unwrap();
+    assert_eq!(
+        assembled.memory,
+        Some(Limits {
+            min: 1,
+            max: Some(1)
+        })
+    );
+    assert_eq!(assembled.globals[0].init, Value::I32(5));
+    module.audit();
+
+    // a function without a name is numbered after the imports
+    module.add_function();
+    module
+        .editor(1)
+        .unwrap()
+        .set_text("global.set $total")
+        .unwrap();
+    let errors = module.assemble().unwrap_err();
+    assert!(errors[0].to_string().starts_with("line 1 of function 6: "));
+}
+
+#[wasm_bindgen_test]
+fn opening_and_writing_modules() {
+    let mut module = common::module(&common::factory(), "nop");
+    add(&mut module, Section::Table, &["2", "4"]);
+    add(&mut module, Section::Globals, &["", "mut f64", "0.5"]);
+    add(&mut module, Section::Exports, &["table", "table", "0"]);
+    add(&mut module, Section::Elements, &["1", "$print_i32"]);
+    let wasm = module.to_wasm().unwrap();
+    assert!(module.to_wat().contains(
+        "  (table 2 4 funcref)\n  (global (mut f64) (f64.const 0.5))\n  (export \"table\" (table 0))\n  (elem (i32.const 1) $print_i32)\n"
+    ));
+
+    // opening the module brings its sections back
+    let mut opened = ModuleEditor::new(&common::factory());
+    opened.load(disassemble_wasm(&wasm).unwrap());
+    assert_eq!(opened.sections(), module.sections());
+    assert_eq!(opened.to_wasm().unwrap(), wasm);
+    opened.audit();
+}
@@ -0,0 +1,89 @@
+// Testing a module from the Test panel: assertions that pass and fail, selecting a
+// failed assertion's line, a module that doesn't assemble, and a script that defines
+// its own modules.
+
+#![cfg(target_arch = "wasm32")]
+
+mod common;
+
+use trunkfun::{test_panel::TestPanel, web_support::Component};
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_browser);
+
+const ADD: &str =
+    "(export \"add\")\n(param i32 i32)\n(result i32)\nlocal.get 0\nlocal.get 1\ni32.add";
+
+#[wasm_bindgen_test]
+fn assertions_pass_and_fail() {
+    let (_module, mut panel) = common::panel(ADD, TestPanel::new);
+    panel.test();
+    assert_eq!(panel.summary(), "no assertions");
+    panel.audit();
+
+    panel.set_script(
+        ";; adding\n(assert_return (invoke \"add\" (i32.const 1) (i32.const 2)) (i32.const 3))\n(assert_return (invoke \"add\" (i32.const -1) (i32.const 1))\n  (i32.const 1))\n(assert_trap (invoke \"add\" (i32.const 0) (i32.const 0)) \"unreachable\")",
+    );
+    panel.test();
+    assert_eq!(panel.summary(), "1 passed, 2 failed");
+    let results: Vec<String> = (panel.results().iter())
+        .map(|result| result.to_string())
+        .collect();
+    assert_eq!(
+        results,
+        [
+            "line 2: passed",
+            "line 3: failed: expected 1 : i32, got 0 : i32",
+            "line 5: failed: expected a trap (unreachable), got 0 : i32",
+        ]
+    );
+    panel.audit();
+
+    // a failure's line can be selected
+    panel.select_line(panel.results()[2].line);
+    assert_eq!(panel.selected_line(), 4);
+    panel.select_line(2);
+    assert_eq!(panel.selected_line(), 2);
+}
+
+#[wasm_bindgen_test]
+fn the_module_must_assemble() {
+    let (module, mut panel) = common::panel(ADD, TestPanel::new);
+    module.editor(0).unwrap().set_text("i32.add").unwrap();
+    panel.set_script("(assert_return (invoke \"add\"))");
+    panel.test();
+    assert_eq!(panel.summary(), "the module has errors");
+    assert!(panel.results().is_empty());
+    panel.audit();
+
+    module.editor(0).unwrap().set_text(ADD).unwrap();
+    panel
+        .set_script("(assert_return (invoke \"add\" (i32.const 1)) (i32.const 1))\n(assert_return");
+    panel.test();
+    let results: Vec<String> = (panel.results().iter())
+        .map(|result| result.to_string())
+        .collect();
+    assert_eq!(
+        results,
+        [
+            "line 1: failed: \"add\" takes (i32, i32)",
+            "line 2: failed: unclosed parenthesis",
+        ]
+    );
+    module.audit();
+    panel.audit();
+}
+
+#[wasm_bindgen_test]
+fn scripts_can_define_modules() {
+    let (module, mut panel) = common::panel(ADD, TestPanel::new);
+    module.editor(0).unwrap().set_text("i32.add").unwrap();
+    panel.set_script(
+        "(module (func (export \"add\") (param i32 i32) (result i32)\n  (i32.add (local.get 0) (local.get 1))))\n(assert_return (invoke \"add\" (i32.const 1) (i32.const 2)) (i32.const 3))\n(assert_invalid (module (func (result i32))) \"type mismatch\")",
+    );
+    panel.test();
+    assert_eq!(panel.summary(), "3 passed, 0 failed");
+    let lines: Vec<usize> = panel.results().iter().map(|result| result.line).collect();
+    assert_eq!(lines, [0, 2, 3]);
+    panel.audit();
+}
@@ -0,0 +1,39 @@
+// Runs the test scripts in tests/wast, in the format of the spec's .wast scripts (see
+// script.rs), and checks that no command fails. Those in tests/wast/spec are the
+// spec's own (i32, br, block and memory), cut down to what the interpreter supports;
+// commands the runner doesn't support are skipped rather than failed.
+
+#![cfg(not(target_arch = "wasm32"))]
+
+use std::{fs, path::Path};
+use trunkfun::script::run_wast;
+
+#[test]
+fn scripts_pass() {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/wast");
+    let mut paths: Vec<_> = [dir.clone(), dir.join("spec")]
+        .iter()
+        .flat_map(|dir| fs::read_dir(dir).expect("tests/wast"))
+        .map(|entry| entry.expect("directory entry").path())
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "wast")
+        })
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    let mut failures = Vec::new();
+    for path in paths {
+        let text = fs::read_to_string(&path).expect("a script");
+        let name = path.strip_prefix(&dir).unwrap().display().to_string();
+        let results = run_wast(&text);
+        assert!(!results.is_empty(), "{name} has no commands");
+        failures.extend(
+            (results.iter())
+                .filter(|result| result.outcome.is_failed())
+                .map(|result| format!("{name}, {result}")),
+        );
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
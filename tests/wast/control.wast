@@ -0,0 +1,56 @@
+;; Blocks, loops, branches, calls and recursion, in both the folded and the flat
+;; forms of instructions. Written for this repository in the format of the spec's test
+;; scripts.
+
+(module
+  (func $fac (export "fac") (param $n i64) (result i64)
+    (if (result i64) (i64.eqz (local.get $n))
+      (then (i64.const 1))
+      (else (i64.mul (local.get $n) (call $fac (i64.sub (local.get $n) (i64.const 1)))))))
+
+  (func (export "fac-loop") (param $n i64) (result i64) (local $acc i64)
+    (local.set $acc (i64.const 1))
+    (block $done
+      (loop $next
+        (br_if $done (i64.eqz (local.get $n)))
+        (local.set $acc (i64.mul (local.get $acc) (local.get $n)))
+        (local.set $n (i64.sub (local.get $n) (i64.const 1)))
+        (br $next)))
+    (local.get $acc))
+
+  (func (export "switch") (param i32) (result i32)
+    block
+      block
+        block
+          local.get 0
+          br_table 0 1 2
+        end
+        i32.const 10
+        return
+      end
+      i32.const 11
+      return
+    end
+    i32.const 12)
+
+  (func (export "select") (param i32) (result i32)
+    (select (i32.const 1) (i32.const 2) (local.get 0)))
+
+  (func $forever (export "forever") (call $forever))
+  (func (export "unreachable") (result i32) unreachable)
+  (func (export "nothing"))
+)
+
+(assert_return (invoke "fac" (i64.const 0)) (i64.const 1))
+(assert_return (invoke "fac" (i64.const 20)) (i64.const 2432902008176640000))
+(assert_return (invoke "fac-loop" (i64.const 5)) (i64.const 120))
+(assert_return (invoke "switch" (i32.const 0)) (i32.const 10))
+(assert_return (invoke "switch" (i32.const 1)) (i32.const 11))
+(assert_return (invoke "switch" (i32.const 2)) (i32.const 12))
+(assert_return (invoke "switch" (i32.const -1)) (i32.const 12))
+(assert_return (invoke "select" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "select" (i32.const 0)) (i32.const 2))
+(assert_trap (invoke "forever") "call stack exhausted")
+(assert_trap (invoke "unreachable") "unreachable")
+(invoke "nothing")
+(assert_return (invoke "nothing"))
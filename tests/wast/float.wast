@@ -0,0 +1,51 @@
+;; f32 and f64 arithmetic: signed zeros, NaNs, rounding and conversions. Written for
+;; this repository in the format of the spec's test scripts.
+
+(module
+  (func (export "f32.add") (param f32 f32) (result f32)
+    (f32.add (local.get 0) (local.get 1)))
+  (func (export "f32.div") (param f32 f32) (result f32)
+    (f32.div (local.get 0) (local.get 1)))
+  (func (export "f32.min") (param f32 f32) (result f32)
+    (f32.min (local.get 0) (local.get 1)))
+  (func (export "f64.sqrt") (param f64) (result f64) (f64.sqrt (local.get 0)))
+  (func (export "f64.nearest") (param f64) (result f64) (f64.nearest (local.get 0)))
+  (func (export "f64.neg") (param f64) (result f64) (f64.neg (local.get 0)))
+  (func (export "i32.trunc_f64_s") (param f64) (result i32)
+    (i32.trunc_f64_s (local.get 0)))
+  (func (export "i32.trunc_f32_u") (param f32) (result i32)
+    (i32.trunc_f32_u (local.get 0)))
+  (func (export "i32.trunc_f64_u") (param f64) (result i32)
+    (i32.trunc_f64_u (local.get 0)))
+  (func (export "i64.trunc_f64_u") (param f64) (result i64)
+    (i64.trunc_f64_u (local.get 0)))
+  (func (export "f32.demote_f64") (param f64) (result f32)
+    (f32.demote_f64 (local.get 0)))
+)
+
+(assert_return (invoke "f32.add" (f32.const 0.5) (f32.const 0.25)) (f32.const 0.75))
+(assert_return (invoke "f32.add" (f32.const -0.0) (f32.const -0.0)) (f32.const -0.0))
+(assert_return (invoke "f32.add" (f32.const -0.0) (f32.const 0.0)) (f32.const 0.0))
+(assert_return (invoke "f32.add" (f32.const inf) (f32.const -inf)) (f32.const nan:canonical))
+(assert_return (invoke "f32.div" (f32.const 1) (f32.const 0)) (f32.const inf))
+(assert_return (invoke "f32.div" (f32.const 0) (f32.const 0)) (f32.const nan:canonical))
+(assert_return (invoke "f32.min" (f32.const 0.0) (f32.const -0.0)) (f32.const -0.0))
+(assert_return (invoke "f32.min" (f32.const nan) (f32.const 1)) (f32.const nan:canonical))
+
+(assert_return (invoke "f64.sqrt" (f64.const 0x1p-2)) (f64.const 0.5))
+(assert_return (invoke "f64.sqrt" (f64.const -1)) (f64.const nan:canonical))
+(assert_return (invoke "f64.nearest" (f64.const 2.5)) (f64.const 2))
+(assert_return (invoke "f64.nearest" (f64.const -3.5)) (f64.const -4))
+(assert_return (invoke "f64.neg" (f64.const nan:0x1)) (f64.const -nan:0x1))
+
+(assert_return (invoke "i32.trunc_f64_s" (f64.const -1.9)) (i32.const -1))
+(assert_trap (invoke "i32.trunc_f64_s" (f64.const 2147483648)) "integer overflow")
+(assert_trap (invoke "i32.trunc_f64_s" (f64.const nan)) "invalid conversion to integer")
+(assert_return (invoke "i32.trunc_f32_u" (f32.const -0.9)) (i32.const 0))
+(assert_trap (invoke "i32.trunc_f32_u" (f32.const -1)) "integer overflow")
+(assert_return (invoke "i32.trunc_f64_u" (f64.const 4294967295.9)) (i32.const -1))
+(assert_trap (invoke "i32.trunc_f64_u" (f64.const -1)) "integer overflow")
+(assert_return (invoke "i64.trunc_f64_u" (f64.const -0x1.ccccccccccccdp-1)) (i64.const 0))
+(assert_trap (invoke "i64.trunc_f64_u" (f64.const -1)) "integer overflow")
+(assert_return (invoke "f32.demote_f64" (f64.const 0x1.fffffffp+127)) (f32.const inf))
+(assert_return (invoke "f32.demote_f64" (f64.const 1e-50)) (f32.const 0))
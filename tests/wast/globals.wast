@@ -0,0 +1,35 @@
+;; Globals, immutable and mutable, and modules that are referred to by $id. Written for
+;; this repository in the format of the spec's test scripts.
+
+(module $counter
+  (global $step i32 (i32.const 2))
+  (global $count (mut i32) (i32.const 40))
+  (global $total (export "total") (mut i64) (i64.const 0))
+  (func (export "next") (result i32)
+    (global.set $count (i32.add (global.get $count) (global.get $step)))
+    (global.set $total (i64.add (global.get $total) (i64.extend_i32_s (global.get $count))))
+    (global.get $count))
+  (func (export "get-total") (result i64) (global.get $total))
+)
+
+(assert_return (invoke "next") (i32.const 42))
+(assert_return (invoke "next") (i32.const 44))
+(assert_return (invoke "get-total") (i64.const 86))
+
+;; a new module has its own globals, and the earlier one can still be invoked
+(module
+  (global $g (mut f32) (f32.const 1.5))
+  (func (export "next") (result f32)
+    (global.set $g (f32.mul (global.get $g) (f32.const 2)))
+    (global.get $g))
+)
+(assert_return (invoke "next") (f32.const 3))
+(assert_return (invoke $counter "next") (i32.const 46))
+(assert_return (invoke $counter "get-total") (i64.const 132))
+
+(assert_invalid
+  (module (global i32 (i32.const 0)) (func (global.set 0 (i32.const 1))))
+  "global is immutable")
+(assert_invalid
+  (module (global i32 (i32.const 0)) (func (result i64) (global.get 0)))
+  "type mismatch")
@@ -0,0 +1,36 @@
+;; Modules that must be rejected: those that don't validate (assert_invalid), and
+;; those that can't be read, in the text or the binary format (assert_malformed). The
+;; messages are the spec's, which the runner compares with the assembler's reasons.
+;; Written for this repository in the format of the spec's test scripts.
+
+(assert_invalid (module (func (result i32))) "type mismatch")
+(assert_invalid (module (func (result i32) (i64.const 1))) "type mismatch")
+(assert_invalid (module (func (i32.add (i32.const 1) (f32.const 1)))) "type mismatch")
+(assert_invalid (module (func (local.get 0))) "unknown local")
+(assert_invalid (module (func (call 1))) "unknown function")
+(assert_invalid (module (func (br 1))) "unknown label")
+(assert_invalid (module (func (global.get 0) (drop))) "unknown global")
+(assert_invalid
+  (module (func (export "f")) (func (export "f")))
+  "duplicate export name")
+(assert_invalid
+  (module (func (param i32) (result i32) (if (local.get 0) (then (i32.const 1)))))
+  "type mismatch")
+(assert_invalid
+  (module (func (result i32) (if (result i32) (i32.const 1) (then (i32.const 1)))))
+  "type mismatch")
+(assert_invalid (module (global i32 (i32.const 0)) (func (global.set 0 (i32.const 1))))
+  "global is immutable")
+
+(assert_malformed (module quote "(func i32.frob)") "unknown operator")
+(assert_malformed (module quote "(func (i32.const nan))") "unknown operator")
+(assert_malformed (module quote "(func (i32.const 0x100000000) drop)") "constant out of range")
+(assert_malformed (module quote "(func (param i33))") "unexpected token")
+(assert_malformed (module binary "") "unexpected end")
+(assert_malformed (module binary "\00asm") "unexpected end")
+(assert_malformed (module binary "\00asm" "\02\00\00\00") "unknown binary version")
+(assert_malformed (module binary "\00msa" "\01\00\00\00") "magic header not detected")
+
+;; and a module that's fine
+(module (func (export "f") (result i32) (i32.const 1)))
+(assert_return (invoke "f") (i32.const 1))
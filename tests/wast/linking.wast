@@ -0,0 +1,40 @@
+;; Modules that import the functions of modules registered before them, and the
+;; spectest module's print functions. Written for this repository in the format of the
+;; spec's test scripts.
+
+(module $math
+  (func (export "square") (param i64) (result i64)
+    (i64.mul (local.get 0) (local.get 0)))
+  (func (export "div") (param i32 i32) (result i32)
+    (i32.div_u (local.get 0) (local.get 1)))
+)
+(register "math" $math)
+
+(module
+  (import "spectest" "print_i64" (func $print (param i64)))
+  (import "math" "square" (func $square (param i64) (result i64)))
+  (import "math" "div" (func $div (param i32 i32) (result i32)))
+  (func (export "fourth-power") (param i64) (result i64)
+    (call $print (local.get 0))
+    (call $square (call $square (local.get 0))))
+  (func (export "div") (param i32 i32) (result i32)
+    (call $div (local.get 0) (local.get 1)))
+)
+
+(assert_return (invoke "fourth-power" (i64.const 3)) (i64.const 81))
+(assert_return (invoke "div" (i32.const 7) (i32.const 2)) (i32.const 3))
+;; a trap in the imported function is a trap in the caller
+(assert_trap (invoke "div" (i32.const 7) (i32.const 0)) "integer divide by zero")
+
+;; an import can be re-exported, and imported again under another name
+(module $again
+  (func $square (import "math" "square") (param i64) (result i64))
+  (export "sq" (func $square))
+)
+(register "again" $again)
+(module
+  (import "again" "sq" (func $sq (param i64) (result i64)))
+  (func (export "cube-ish") (param i64) (result i64)
+    (i64.add (call $sq (local.get 0)) (local.get 0)))
+)
+(assert_return (invoke "cube-ish" (i64.const 4)) (i64.const 20))
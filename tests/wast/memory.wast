@@ -0,0 +1,40 @@
+;; Loads and stores, data segments, growing the memory, and accesses out of bounds.
+;; Written for this repository in the format of the spec's test scripts.
+
+(module
+  (memory 1 2)
+  (data (i32.const 0) "ABC\a7D")
+  (data (i32.const 0xfffd) "xyz")
+
+  (func (export "load8_s") (param i32) (result i32) (i32.load8_s (local.get 0)))
+  (func (export "load8_u") (param i32) (result i32) (i32.load8_u (local.get 0)))
+  (func (export "load") (param i32) (result i32) (i32.load (local.get 0)))
+  (func (export "load16_offset") (param i32) (result i32)
+    (i32.load16_u offset=2 (local.get 0)))
+  (func (export "store64") (param i32 i64) (i64.store (local.get 0) (local.get 1)))
+  (func (export "load64") (param i32) (result i64) (i64.load (local.get 0)))
+  (func (export "size") (result i32) (memory.size))
+  (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+)
+
+(assert_return (invoke "load8_s" (i32.const 0)) (i32.const 65))
+(assert_return (invoke "load8_s" (i32.const 3)) (i32.const -89))
+(assert_return (invoke "load8_u" (i32.const 3)) (i32.const 167))
+(assert_return (invoke "load" (i32.const 0)) (i32.const 0xa7434241))
+(assert_return (invoke "load16_offset" (i32.const 0)) (i32.const 0xa743))
+(assert_return (invoke "load8_u" (i32.const 0xffff)) (i32.const 122))
+(assert_trap (invoke "load" (i32.const 0xfffd)) "out of bounds memory access")
+(assert_trap (invoke "load8_u" (i32.const -1)) "out of bounds memory access")
+
+;; stores are seen by later invocations
+(invoke "store64" (i32.const 8) (i64.const -2))
+(assert_return (invoke "load64" (i32.const 8)) (i64.const -2))
+(assert_return (invoke "load" (i32.const 12)) (i32.const -1))
+
+;; the memory grows up to its maximum
+(assert_return (invoke "size") (i32.const 1))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "size") (i32.const 2))
+(assert_return (invoke "load8_u" (i32.const 0x1ffff)) (i32.const 0))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const -1))
+(assert_return (invoke "grow" (i32.const 0)) (i32.const 2))